@@ -0,0 +1,98 @@
+//! The window, surface, and event loop shared by the examples, each of which implements `Example`
+//! and calls `run`.
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    window::WindowBuilder,
+};
+
+pub trait Example: 'static + Sized {
+    fn init(
+        config: &wgpu::SurfaceConfiguration,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self;
+    fn resize(
+        &mut self,
+        config: &wgpu::SurfaceConfiguration,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    );
+    fn update(&mut self, event: WindowEvent<'_>);
+    fn render(&mut self, view: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue);
+}
+
+/// Open a window titled `title`, and draw `E` into it every frame until it is closed.
+pub fn run<E: Example>(title: &str) -> ! {
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(title)
+        .build(&event_loop)
+        .expect("couldn't create a window");
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    // SAFETY: the window is moved into the event loop along with the surface, and outlives it.
+    let surface = unsafe { instance.create_surface(&window) }.expect("couldn't create a surface");
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        compatible_surface: Some(&surface),
+        ..Default::default()
+    }))
+    .expect("no adapter can draw to the window");
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("example_device"),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+        },
+        None,
+    ))
+    .expect("couldn't create a device");
+    let size = window.inner_size();
+    let mut config = surface
+        .get_default_config(&adapter, size.width.max(1), size.height.max(1))
+        .expect("the surface isn't supported by the adapter");
+    // Draw in sRGB, as the tilesets are.
+    let capabilities = surface.get_capabilities(&adapter);
+    if let Some(&format) = capabilities.formats.iter().find(|f| f.is_srgb()) {
+        config.format = format;
+    }
+    surface.configure(&device, &config);
+    let mut example = E::init(&config, &adapter, &device, &queue);
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                config.width = size.width.max(1);
+                config.height = size.height.max(1);
+                surface.configure(&device, &config);
+                example.resize(&config, &device, &queue);
+            }
+            Event::WindowEvent { event, .. } => example.update(event),
+            Event::MainEventsCleared => window.request_redraw(),
+            Event::RedrawRequested(_) => {
+                let frame = match surface.get_current_texture() {
+                    Ok(frame) => frame,
+                    // The surface is lost or outdated, e.g. after the window was minimized.
+                    Err(_) => {
+                        surface.configure(&device, &config);
+                        return;
+                    }
+                };
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                example.render(&view, &device, &queue);
+                frame.present();
+            }
+            _ => {}
+        }
+    })
+}
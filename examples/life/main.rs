@@ -0,0 +1,122 @@
+#[path = "../framework/mod.rs"]
+mod framework;
+
+use std::time::{Duration, Instant};
+use vek::Vec2;
+use wgpu_tilemap::{
+    AlphaMode, DoubleBuffered, EdgeMode, FixedTimestep, Tilemap, TilemapDrawData, TilemapPipeline,
+    TilesetRef,
+};
+
+const STEP_TIME: Duration = Duration::from_millis(16);
+const SIDELENGTH: u32 = 600;
+
+struct Example {
+    state: DoubleBuffered<Tilemap>,
+    last_frame: Instant,
+    timestep: FixedTimestep,
+    tilemap_pipeline: TilemapPipeline,
+}
+
+impl Example {
+    fn step(&mut self) {
+        self.state.step(|prev, next| {
+            for y in 0..SIDELENGTH {
+                for x in 0..SIDELENGTH {
+                    let center = prev.get_tile(x, y) != 0;
+                    let count = prev
+                        .neighbors8(x, y, EdgeMode::Wrap)
+                        .filter(|&(_, tile)| tile != 0)
+                        .count();
+                    if (center && [2, 3].contains(&count)) || (!center && [3].contains(&count)) {
+                        next.put_tile(x, y, 1);
+                    } else {
+                        next.put_tile(x, y, 0);
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl framework::Example for Example {
+    fn init(
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let mut state = Tilemap::new_zeroed(Vec2::broadcast(SIDELENGTH));
+        let mut tilemap_pipeline = TilemapPipeline::new(device, config.format, None);
+        tilemap_pipeline.set_camera(queue, wgpu_tilemap::FULLSCREEN_QUAD_CAMERA);
+        let mut tileset = TilesetRef::new(
+            Vec2::new(1, 2),
+            Vec2::new(1, 1),
+            &[0xffffffff, 0x000000ff][..],
+        );
+        tileset.alpha_mode = AlphaMode::Premultiplied;
+        tilemap_pipeline
+            .upload_tilesets(device, queue, &[tileset])
+            .unwrap();
+        // block
+        state.put_tile(25, 25, 1);
+        state.put_tile(25, 26, 1);
+        state.put_tile(26, 25, 1);
+        state.put_tile(26, 26, 1);
+        // glider
+        state.put_tile(50, 50, 1);
+        state.put_tile(49, 51, 1);
+        state.put_tile(49, 52, 1);
+        state.put_tile(50, 52, 1);
+        state.put_tile(51, 52, 1);
+        // R-pentomino
+        state.put_tile(301, 300, 1);
+        state.put_tile(302, 300, 1);
+        state.put_tile(300, 301, 1);
+        state.put_tile(301, 301, 1);
+        state.put_tile(301, 302, 1);
+        Example {
+            state: DoubleBuffered::new(state),
+            last_frame: Instant::now(),
+            timestep: FixedTimestep::new(STEP_TIME),
+            tilemap_pipeline,
+        }
+    }
+    fn resize(&mut self, _: &wgpu::SurfaceConfiguration, _: &wgpu::Device, _: &wgpu::Queue) {}
+    fn update(&mut self, _: winit::event::WindowEvent<'_>) {}
+    fn render(&mut self, surface: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let now = Instant::now();
+        for _ in 0..self.timestep.advance(now - self.last_frame) {
+            self.step();
+        }
+        self.last_frame = now;
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_encoder"),
+        });
+        self.tilemap_pipeline.upload_tilemaps(
+            device,
+            queue,
+            &[TilemapDrawData::new(self.state.current(), 0)],
+        );
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("surface_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.tilemap_pipeline.render(device, &mut rpass);
+        }
+        queue.submit(vec![encoder.finish()]);
+    }
+}
+
+fn main() {
+    framework::run::<Example>("life")
+}
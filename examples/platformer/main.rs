@@ -0,0 +1,384 @@
+//! A side-scrolling platformer: a level with animated water and hills drawn behind it with
+//! parallax, and a player who runs and jumps (with the arrow keys and space) and collides with the
+//! solid tiles of the level.
+#[path = "../framework/mod.rs"]
+mod framework;
+
+use std::time::{Duration, Instant};
+use vek::{Mat4, Vec2};
+use wgpu_tilemap::{
+    AnimationFrame, FixedTimestep, FrameContext, TileAnimation, Tilemap, TilemapDrawData,
+    TilemapPipeline, TilesetRef,
+};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+const STEP_TIME: Duration = Duration::from_millis(10);
+const SIZE_OF_TILE: u32 = 16;
+const LEVEL_SIZE: Vec2<u32> = Vec2::new(120, 20);
+/// The number of pixels of the window covered by each pixel of the tileset.
+const ZOOM: f32 = 3.0;
+
+const SKY: u8 = 0;
+const GROUND: u8 = 1;
+const GRASS: u8 = 2;
+const BRICK: u8 = 3;
+/// The first frame of the water animation, whose other frames are the tiles after it.
+const WATER: u8 = 4;
+const WATER_FRAMES: u8 = 4;
+const HILL: u8 = 8;
+const PLAYER: u8 = 9;
+/// Water below the surface, which isn't animated.
+const DEEP_WATER: u8 = 10;
+const NUM_TILES: u32 = 11;
+
+/// The size of the player, in tiles.
+const PLAYER_SIZE: Vec2<f32> = Vec2::new(0.75, 0.9);
+/// Speeds are in tiles per second, with y pointing down like the rows of the level.
+const GRAVITY: f32 = 50.0;
+const MAX_FALL_SPEED: f32 = 20.0;
+const RUN_SPEED: f32 = 8.0;
+const JUMP_SPEED: f32 = 17.0;
+
+/// The color of a pixel of a tile, drawn procedurally so that the example needs no image files.
+fn texel(tile: u8, x: u32, y: u32) -> [u8; 4] {
+    match tile {
+        GROUND if (x * 7 + y * 3) % 11 == 0 => [90, 60, 30, 255],
+        GROUND => [130, 90, 50, 255],
+        GRASS if y < 3 + x % 3 / 2 => [70, 170, 60, 255],
+        GRASS => texel(GROUND, x, y),
+        BRICK if y % 8 == 0 || (x + y / 8 * 8) % 16 == 0 => [80, 40, 30, 255],
+        BRICK => [170, 80, 50, 255],
+        WATER..=7 => {
+            // A wave along the top of the tile, moving by a quarter of a tile each frame.
+            let phase = (x + (tile - WATER) as u32 * SIZE_OF_TILE / 4) % SIZE_OF_TILE;
+            let surface = if phase < SIZE_OF_TILE / 2 { 1 } else { 3 };
+            match y {
+                y if y < surface => [0; 4],
+                y if y < surface + 2 => [200, 230, 255, 220],
+                _ => texel(DEEP_WATER, x, y),
+            }
+        }
+        DEEP_WATER => [40, 100, 200, 180],
+        HILL if (x * 5 + y * 9) % 13 == 0 => [50, 100, 80, 255],
+        HILL => [70, 130, 100, 255],
+        PLAYER => match (x, y) {
+            (5 | 10, 4..=6) => [20, 20, 20, 255],
+            (1..=14, 1..=15) => [230, 200, 60, 255],
+            _ => [0; 4],
+        },
+        _ => [0; 4],
+    }
+}
+
+fn tileset() -> TilesetRef<'static> {
+    let dimensions = Vec2::new(NUM_TILES * SIZE_OF_TILE, SIZE_OF_TILE);
+    let mut pixels = Vec::new();
+    for y in 0..dimensions.y {
+        for x in 0..dimensions.x {
+            let tile = (x / SIZE_OF_TILE) as u8;
+            pixels.extend(texel(tile, x % SIZE_OF_TILE, y));
+        }
+    }
+    TilesetRef::from_rgba8(
+        &pixels,
+        dimensions,
+        Vec2::broadcast(SIZE_OF_TILE),
+        Vec2::zero(),
+    )
+}
+
+/// The level, along with the cells that hold water (which are animated). The ground rises and
+/// falls in steps, with a pool of water and a brick platform every so often.
+fn level() -> (Tilemap, Vec<Vec2<u32>>) {
+    let mut level = Tilemap::new_filled(LEVEL_SIZE, SKY);
+    let mut water = Vec::new();
+    for x in 0..LEVEL_SIZE.x {
+        let top = LEVEL_SIZE.y - 3 - (x / 8 * 5 + 1) % 4;
+        // Pools are inside a step, so that the player can climb out of them.
+        let pool = x / 8 % 3 == 1 && (2..6).contains(&(x % 8));
+        for y in top..LEVEL_SIZE.y {
+            let tile = match y - top {
+                0 if pool => WATER,
+                1 if pool => DEEP_WATER,
+                0 => GRASS,
+                2 if pool => GRASS,
+                _ => GROUND,
+            };
+            if tile == WATER {
+                water.push(Vec2::new(x, y));
+            }
+            level.put_tile(x, y, tile);
+        }
+        if x % 17 >= 8 && x % 17 < 12 {
+            level.put_tile(x, top - 4, BRICK);
+        }
+    }
+    (level, water)
+}
+
+/// Hills behind the level, which are drawn with parallax so that they seem far away. They are twice
+/// as wide as the level, since they move half as fast.
+fn hills() -> Tilemap {
+    let size = LEVEL_SIZE * Vec2::new(2, 1);
+    let mut hills = Tilemap::new_filled(size, SKY);
+    for x in 0..size.x {
+        let height = 7.0 + 3.0 * (x as f32 * 0.15).sin() + 2.0 * (x as f32 * 0.4).cos();
+        for y in LEVEL_SIZE.y - height as u32..LEVEL_SIZE.y {
+            hills.put_tile(x, y, HILL);
+        }
+    }
+    hills
+}
+
+/// Whether the player can't move through `cell`. The sides and bottom of the level are walls, and
+/// the sky above it is open.
+fn is_solid(level: &Tilemap, cell: Vec2<i32>) -> bool {
+    let size = LEVEL_SIZE.as_::<i32>();
+    if cell.x < 0 || cell.x >= size.x || cell.y >= size.y {
+        return true;
+    }
+    cell.y >= 0
+        && matches!(
+            level.get_tile(cell.x as u32, cell.y as u32),
+            GROUND | GRASS | BRICK
+        )
+}
+
+#[derive(Copy, Clone, Default)]
+struct Input {
+    left: bool,
+    right: bool,
+    jump: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Player {
+    /// The top left corner of the player, in tiles.
+    position: Vec2<f32>,
+    velocity: Vec2<f32>,
+    grounded: bool,
+}
+
+impl Player {
+    fn step(&mut self, level: &Tilemap, input: Input, dt: f32) {
+        self.velocity.x = (input.right as i32 - input.left as i32) as f32 * RUN_SPEED;
+        if input.jump && self.grounded {
+            self.velocity.y = -JUMP_SPEED;
+        }
+        self.velocity.y = (self.velocity.y + GRAVITY * dt).min(MAX_FALL_SPEED);
+        self.move_axis(level, 0, self.velocity.x * dt);
+        self.grounded = false;
+        if self.move_axis(level, 1, self.velocity.y * dt) {
+            self.grounded = self.velocity.y > 0.0;
+            self.velocity.y = 0.0;
+        }
+    }
+
+    /// Move along `axis` by `delta` (less than a tile), stopping against the first solid tile in
+    /// the way. Returns whether a tile was hit.
+    fn move_axis(&mut self, level: &Tilemap, axis: usize, delta: f32) -> bool {
+        self.position[axis] += delta;
+        let min = self.position.map(|x| x.floor() as i32);
+        let max = (self.position + PLAYER_SIZE).map(|x| x.ceil() as i32);
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                let cell = Vec2::new(x, y);
+                if is_solid(level, cell) {
+                    self.position[axis] = if delta > 0.0 {
+                        cell[axis] as f32 - PLAYER_SIZE[axis]
+                    } else {
+                        cell[axis] as f32 + 1.0
+                    };
+                    return true;
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Where a position in tiles (with y pointing down) is in the world, in pixels of the tileset
+/// (with y pointing up, like the camera).
+fn world_position(position: Vec2<f32>) -> Vec2<f32> {
+    Vec2::new(position.x, LEVEL_SIZE.y as f32 - position.y) * SIZE_OF_TILE as f32
+}
+
+fn draw(transform: Mat4<f32>, tilemap: &Tilemap, parallax: Vec2<f32>) -> TilemapDrawData<'_> {
+    TilemapDrawData::new(tilemap, 0)
+        .with_transform(transform)
+        .with_parallax(parallax)
+        .with_empty_tile(SKY as u32)
+        .with_pixel_snap(true)
+}
+
+struct Example {
+    level: Tilemap,
+    /// The level as drawn, with the current frame of the water animation.
+    drawn: Tilemap,
+    hills: Tilemap,
+    sprite: Tilemap,
+    water_cells: Vec<Vec2<u32>>,
+    water: TileAnimation,
+    player: Player,
+    /// The player before the latest step, to interpolate between.
+    previous: Player,
+    input: Input,
+    start: Instant,
+    last_frame: Instant,
+    frame: u32,
+    timestep: FixedTimestep,
+    viewport_size: Vec2<u32>,
+    tilemap_pipeline: TilemapPipeline,
+}
+
+impl framework::Example for Example {
+    fn init(
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let mut tilemap_pipeline = TilemapPipeline::new(device, config.format, None);
+        tilemap_pipeline
+            .upload_tilesets(device, queue, &[tileset()])
+            .unwrap();
+        let (level, water_cells) = level();
+        let water = TileAnimation {
+            frames: (WATER..WATER + WATER_FRAMES)
+                .map(|tile| AnimationFrame {
+                    tile: tile as u32,
+                    duration: Duration::from_millis(200),
+                })
+                .collect(),
+        };
+        let player = Player {
+            position: Vec2::new(2.0, 0.0),
+            velocity: Vec2::zero(),
+            grounded: false,
+        };
+        Example {
+            drawn: level.clone(),
+            level,
+            hills: hills(),
+            sprite: Tilemap::new_filled(Vec2::one(), PLAYER),
+            water_cells,
+            water,
+            player,
+            previous: player,
+            input: Input::default(),
+            start: Instant::now(),
+            last_frame: Instant::now(),
+            frame: 0,
+            timestep: FixedTimestep::new(STEP_TIME),
+            viewport_size: Vec2::new(config.width, config.height),
+            tilemap_pipeline,
+        }
+    }
+    fn resize(&mut self, config: &wgpu::SurfaceConfiguration, _: &wgpu::Device, _: &wgpu::Queue) {
+        self.viewport_size = Vec2::new(config.width, config.height);
+    }
+    fn update(&mut self, event: WindowEvent<'_>) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state,
+                    ..
+                },
+            ..
+        } = event
+        {
+            let pressed = state == ElementState::Pressed;
+            match key {
+                VirtualKeyCode::Left | VirtualKeyCode::A => self.input.left = pressed,
+                VirtualKeyCode::Right | VirtualKeyCode::D => self.input.right = pressed,
+                VirtualKeyCode::Space | VirtualKeyCode::Up => self.input.jump = pressed,
+                _ => {}
+            }
+        }
+    }
+    fn render(&mut self, surface: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let now = Instant::now();
+        let delta_time = now - self.last_frame;
+        for _ in 0..self.timestep.advance(delta_time) {
+            self.previous = self.player;
+            let dt = STEP_TIME.as_secs_f32();
+            self.player.step(&self.level, self.input, dt);
+        }
+        self.last_frame = now;
+
+        let elapsed = now - self.start;
+        if let Some(tile) = self.water.tile_at(elapsed) {
+            for cell in &self.water_cells {
+                self.drawn.put_tile(cell.x, cell.y, tile as u8);
+            }
+        }
+        self.tilemap_pipeline.set_frame_context(
+            queue,
+            &FrameContext {
+                time: elapsed.as_secs_f32(),
+                delta_time: delta_time.as_secs_f32(),
+                index: self.frame,
+                viewport_size: Some(self.viewport_size),
+            },
+        );
+        self.frame += 1;
+
+        // Follow the player, without showing past the ends of the level.
+        let position = self
+            .timestep
+            .interpolate(self.previous.position, self.player.position);
+        let view = self.viewport_size.as_::<f32>() / ZOOM;
+        let level_pixels = (LEVEL_SIZE * SIZE_OF_TILE).as_::<f32>();
+        let center = world_position(position + PLAYER_SIZE / 2.0);
+        let offset = (center - view / 2.0).map2(level_pixels - view, |x, max| x.min(max).max(0.0));
+        self.tilemap_pipeline
+            .set_camera_pixels(queue, self.viewport_size, offset, ZOOM);
+
+        let level_transform = Mat4::scaling_3d(level_pixels.with_z(1.0));
+        let hills_transform = Mat4::<f32>::translation_2d(-level_pixels * Vec2::new(0.5, 0.0))
+            * Mat4::scaling_3d((level_pixels * Vec2::new(2.0, 1.0)).with_z(1.0));
+        let sprite_size = PLAYER_SIZE * SIZE_OF_TILE as f32;
+        let sprite_transform =
+            Mat4::<f32>::translation_2d(world_position(position) - Vec2::unit_y() * sprite_size)
+                * Mat4::scaling_3d(sprite_size.with_z(1.0));
+        self.tilemap_pipeline.upload_tilemaps(
+            device,
+            queue,
+            &[
+                draw(hills_transform, &self.hills, Vec2::new(0.5, 1.0)),
+                draw(level_transform, &self.drawn, Vec2::one()),
+                draw(sprite_transform, &self.sprite, Vec2::one()),
+            ],
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("surface_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color {
+                            r: 0.45,
+                            g: 0.7,
+                            b: 0.95,
+                            a: 1.0,
+                        }),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.tilemap_pipeline.render(device, &mut rpass);
+        }
+        queue.submit(vec![encoder.finish()]);
+    }
+}
+
+fn main() {
+    framework::run::<Example>("platformer")
+}
@@ -1,10 +1,9 @@
-use std::{
-    borrow::Cow,
-    fs::File,
-};
-use vek::{Mat4, Vec2};
-use wgpu_example::framework::Spawner;
-use wgpu_tilemap::{TilemapDrawData, TilemapNoise, TilemapPipeline, TilemapRef, TilesetRef};
+#[path = "../framework/mod.rs"]
+mod framework;
+
+use std::fs::File;
+use vek::Vec2;
+use wgpu_tilemap::{TilemapDrawData, TilemapPipeline, TilemapRef, TilesetRef};
 
 const SIDELENGTH: u32 = 30;
 
@@ -13,7 +12,7 @@ struct Example {
     tilemap_pipeline: TilemapPipeline,
 }
 
-impl wgpu_example::framework::Example for Example {
+impl framework::Example for Example {
     fn init(
         config: &wgpu::SurfaceConfiguration,
         _: &wgpu::Adapter,
@@ -22,15 +21,25 @@ impl wgpu_example::framework::Example for Example {
     ) -> Self {
         let mut tilemap_pipeline = TilemapPipeline::new(device, config.format, None);
         use image::io::Reader as ImageReader;
-        let image = ImageReader::open("tiles_spritesheet.png")
-            .unwrap()
-            .decode()
-            .unwrap();
+        let image = ImageReader::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/png_tileset/tiles_spritesheet.png"
+        ))
+        .unwrap()
+        .decode()
+        .unwrap();
         tilemap_pipeline.set_camera(queue, wgpu_tilemap::FULLSCREEN_QUAD_CAMERA);
-        let tileset = TilesetRef::from_image_with_spacing(&image, Vec2::broadcast(70), Vec2::broadcast(2));
-        tilemap_pipeline.upload_tilesets(device, queue, &[tileset]);
-        let csv = File::open("example_tilemap.csv").unwrap();
-        let tilemap = TilemapRef::from_csv(Vec2::broadcast(SIDELENGTH), csv).unwrap();
+        let tileset =
+            TilesetRef::from_image_with_spacing(&image, Vec2::broadcast(70), Vec2::broadcast(2));
+        tilemap_pipeline
+            .upload_tilesets(device, queue, &[tileset])
+            .unwrap();
+        let csv = File::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/examples/png_tileset/example_tilemap.csv"
+        ))
+        .unwrap();
+        let tilemap = TilemapRef::from_csv(Some(Vec2::broadcast(SIDELENGTH)), csv).unwrap();
         Example {
             state: tilemap,
             tilemap_pipeline,
@@ -38,25 +47,14 @@ impl wgpu_example::framework::Example for Example {
     }
     fn resize(&mut self, _: &wgpu::SurfaceConfiguration, _: &wgpu::Device, _: &wgpu::Queue) {}
     fn update(&mut self, _: winit::event::WindowEvent<'_>) {}
-    fn render(
-        &mut self,
-        surface: &wgpu::TextureView,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-        _: &Spawner<'_>,
-    ) {
+    fn render(&mut self, surface: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue) {
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
             label: Some("frame_encoder"),
         });
         self.tilemap_pipeline.upload_tilemaps(
             device,
             queue,
-            &[TilemapDrawData {
-                transform: Mat4::identity(),
-                tilemap: Cow::Borrowed(&self.state),
-                tileset: 0,
-                noise: TilemapNoise::default(),
-            }],
+            &[TilemapDrawData::new(&self.state, 0)],
         );
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
@@ -66,18 +64,17 @@ impl wgpu_example::framework::Example for Example {
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
-                        store: wgpu::StoreOp::Store,
+                        store: true,
                     },
                 })],
                 depth_stencil_attachment: None,
-                ..Default::default()
             });
-            self.tilemap_pipeline.render(&device, &mut rpass);
+            self.tilemap_pipeline.render(device, &mut rpass);
         }
         queue.submit(vec![encoder.finish()]);
     }
 }
 
 fn main() {
-    wgpu_example::framework::run::<Example>("png_tileset")
+    framework::run::<Example>("png_tileset")
 }
@@ -0,0 +1,471 @@
+//! A turn-based dungeon crawler: rooms and corridors that are revealed by the player's field of
+//! view, with tiles that have been seen before kept dimly in a fog of war, and the player, goblins,
+//! potions, and stairs drawn as a layer of ASCII glyphs. Move with the arrow keys, attack goblins
+//! by walking into them, and take the stairs (`>`) down to a new level.
+#[path = "../framework/mod.rs"]
+mod framework;
+
+use std::ops::Range;
+use vek::{Mat4, Vec2};
+use wgpu_tilemap::{
+    raycast, PresentParams, PresentScaling, TileSpatialIndex, Tilemap, TilemapDrawData,
+    TilemapPipeline, TilesetRef,
+};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+const MAP_SIZE: Vec2<u32> = Vec2::new(48, 27);
+const SIZE_OF_TILE: u32 = 8;
+const FOV_RADIUS: i32 = 8;
+
+const VOID: u8 = 0;
+const FLOOR: u8 = 1;
+const WALL: u8 = 2;
+const NUM_TERRAIN_TILES: u32 = 3;
+
+/// The glyphs of the glyph tileset as 5x7 bitmaps, in tile order. The first one is blank.
+const GLYPHS: [(char, [&str; 7]); 5] = [
+    (' ', ["     "; 7]),
+    (
+        '@',
+        [
+            " ### ", "#   #", "# ###", "# # #", "# ###", "#    ", " ####",
+        ],
+    ),
+    (
+        'g',
+        [
+            "     ", "     ", " ####", "#   #", " ####", "    #", " ### ",
+        ],
+    ),
+    (
+        '!',
+        [
+            "  #  ", "  #  ", "  #  ", "  #  ", "  #  ", "     ", "  #  ",
+        ],
+    ),
+    (
+        '>',
+        [
+            "#    ", " #   ", "  #  ", "   # ", "  #  ", " #   ", "#    ",
+        ],
+    ),
+];
+
+/// The tint of terrain that is in view, and of terrain that was seen before but isn't now.
+const LIT: [u8; 4] = [255, 255, 255, 255];
+const REMEMBERED: [u8; 4] = [70, 70, 100, 255];
+
+/// The glyph tile of `glyph`.
+fn glyph(glyph: char) -> u8 {
+    GLYPHS.iter().position(|(c, _)| *c == glyph).unwrap() as u8
+}
+
+fn rgba(color: [u8; 4]) -> u32 {
+    u32::from_le_bytes(color)
+}
+
+/// A row of tiles drawn by `texel`.
+fn tileset(tiles: u32, texel: impl Fn(u32, u32, u32) -> [u8; 4]) -> TilesetRef<'static> {
+    let dimensions = Vec2::new(tiles * SIZE_OF_TILE, SIZE_OF_TILE);
+    let mut pixels = Vec::new();
+    for y in 0..dimensions.y {
+        for x in 0..dimensions.x {
+            pixels.extend(texel(x / SIZE_OF_TILE, x % SIZE_OF_TILE, y));
+        }
+    }
+    TilesetRef::from_rgba8(
+        &pixels,
+        dimensions,
+        Vec2::broadcast(SIZE_OF_TILE),
+        Vec2::zero(),
+    )
+}
+
+fn terrain_texel(tile: u32, x: u32, y: u32) -> [u8; 4] {
+    match tile as u8 {
+        FLOOR if x == 3 && y == 3 => [90, 90, 100, 255],
+        FLOOR => [35, 35, 45, 255],
+        WALL if y % 4 == 3 || (x + y / 4 * 4) % 8 == 0 => [70, 60, 55, 255],
+        WALL => [140, 120, 100, 255],
+        _ => [0; 4],
+    }
+}
+
+/// The glyphs are white, so that they can be colored with the tint of the glyph layer.
+fn glyph_texel(tile: u32, x: u32, y: u32) -> [u8; 4] {
+    let rows = GLYPHS[tile as usize].1;
+    let lit = (1..6).contains(&x) && y < 7 && rows[y as usize].as_bytes()[x as usize - 1] == b'#';
+    if lit {
+        [255; 4]
+    } else {
+        [0; 4]
+    }
+}
+
+/// A small deterministic random number generator (a 64-bit LCG), so that the example needs no
+/// dependencies.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+
+    fn range(&mut self, range: Range<u32>) -> u32 {
+        range.start + self.next() % (range.end - range.start)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Item {
+    Goblin,
+    Potion,
+    Stairs,
+}
+
+impl Item {
+    fn glyph(self) -> (char, [u8; 4]) {
+        match self {
+            Item::Goblin => ('g', [90, 220, 90, 255]),
+            Item::Potion => ('!', [230, 80, 220, 255]),
+            Item::Stairs => ('>', [255, 255, 255, 255]),
+        }
+    }
+}
+
+struct Level {
+    terrain: Tilemap,
+    /// What is on each tile besides the player, by index into `items` (`None` once removed).
+    items: Vec<Option<(Item, Vec2<i32>)>>,
+    index: TileSpatialIndex<usize>,
+    player: Vec2<i32>,
+    /// Which tiles are in view, and which have ever been, one row after another.
+    visible: Vec<bool>,
+    explored: Vec<bool>,
+}
+
+impl Level {
+    /// Carve rooms out of solid rock and join each one to the one before with a corridor.
+    fn generate(rng: &mut Rng) -> Level {
+        let mut terrain = Tilemap::new_filled(MAP_SIZE, WALL);
+        let mut rooms: Vec<(Vec2<u32>, Vec2<u32>)> = Vec::new();
+        for _ in 0..60 {
+            let size = Vec2::new(rng.range(4..11), rng.range(4..8));
+            let origin = Vec2::new(
+                rng.range(1..MAP_SIZE.x - size.x - 1),
+                rng.range(1..MAP_SIZE.y - size.y - 1),
+            );
+            // Keep a wall between rooms.
+            let overlaps = rooms.iter().any(|&(other, other_size)| {
+                origin.x <= other.x + other_size.x
+                    && other.x <= origin.x + size.x
+                    && origin.y <= other.y + other_size.y
+                    && other.y <= origin.y + size.y
+            });
+            if !overlaps {
+                rooms.push((origin, size));
+            }
+        }
+        for &(origin, size) in &rooms {
+            for y in origin.y..origin.y + size.y {
+                for x in origin.x..origin.x + size.x {
+                    terrain.put_tile(x, y, FLOOR);
+                }
+            }
+        }
+        let center = |(origin, size): (Vec2<u32>, Vec2<u32>)| origin + size / 2;
+        for pair in rooms.windows(2) {
+            let (from, to) = (center(pair[0]), center(pair[1]));
+            for x in from.x.min(to.x)..=from.x.max(to.x) {
+                terrain.put_tile(x, from.y, FLOOR);
+            }
+            for y in from.y.min(to.y)..=from.y.max(to.y) {
+                terrain.put_tile(to.x, y, FLOOR);
+            }
+        }
+
+        let mut level = Level {
+            terrain,
+            items: Vec::new(),
+            index: TileSpatialIndex::new(),
+            player: center(rooms[0]).as_(),
+            visible: Vec::new(),
+            explored: vec![false; MAP_SIZE.product() as usize],
+        };
+        level.add(Item::Stairs, center(*rooms.last().unwrap()).as_());
+        for &(origin, size) in &rooms[1..] {
+            let spot = |rng: &mut Rng| {
+                Vec2::new(
+                    rng.range(origin.x..origin.x + size.x),
+                    rng.range(origin.y..origin.y + size.y),
+                )
+                .as_()
+            };
+            level.add(Item::Goblin, spot(rng));
+            if rng.range(0..3) == 0 {
+                level.add(Item::Potion, spot(rng));
+            }
+        }
+        level.update_view();
+        level
+    }
+
+    fn add(&mut self, item: Item, cell: Vec2<i32>) {
+        self.index.insert(self.items.len(), cell.as_());
+        self.items.push(Some((item, cell)));
+    }
+
+    fn remove(&mut self, key: usize) {
+        self.index.remove(key);
+        self.items[key] = None;
+    }
+
+    /// The item at `cell` (the one added last if there are several), and its key.
+    fn item_at(&self, cell: Vec2<i32>) -> Option<(usize, Item)> {
+        let key = *self.index.query_cell(cell).last()?;
+        self.items[key].map(|(item, _)| (key, item))
+    }
+
+    fn is_floor(&self, cell: Vec2<i32>) -> bool {
+        let inside =
+            cell.x >= 0 && cell.y >= 0 && cell.x < MAP_SIZE.x as i32 && cell.y < MAP_SIZE.y as i32;
+        inside && self.terrain.get_tile(cell.x as u32, cell.y as u32) == FLOOR
+    }
+
+    /// Recompute the tiles in view of the player: those within `FOV_RADIUS` whose centers can be
+    /// reached from the player's without passing through a wall. Walls themselves are seen when
+    /// they are the first wall on the way.
+    fn update_view(&mut self) {
+        self.visible = vec![false; MAP_SIZE.product() as usize];
+        let from = self.player.as_::<f32>() + 0.5;
+        for y in -FOV_RADIUS..=FOV_RADIUS {
+            for x in -FOV_RADIUS..=FOV_RADIUS {
+                let cell = self.player + Vec2::new(x, y);
+                let inside = cell.x >= 0
+                    && cell.y >= 0
+                    && cell.x < MAP_SIZE.x as i32
+                    && cell.y < MAP_SIZE.y as i32;
+                if !inside || x * x + y * y > FOV_RADIUS * FOV_RADIUS {
+                    continue;
+                }
+                let to = cell.as_::<f32>() + 0.5;
+                let hit = raycast(&self.terrain.as_ref(), from, to, |tile| tile == WALL);
+                if hit.map_or(true, |hit| hit.cell == cell.as_()) {
+                    let i = (cell.y as u32 * MAP_SIZE.x + cell.x as u32) as usize;
+                    self.visible[i] = true;
+                    self.explored[i] = true;
+                }
+            }
+        }
+    }
+
+    /// Move the player by `step` (attacking a goblin in the way), then let the goblins that can
+    /// see the player close in. Returns whether the player took the stairs.
+    fn take_turn(&mut self, step: Vec2<i32>) -> bool {
+        let target = self.player + step;
+        if !self.is_floor(target) {
+            return false;
+        }
+        match self.item_at(target) {
+            Some((key, Item::Goblin)) => self.remove(key),
+            Some((_, Item::Stairs)) => return true,
+            Some((key, Item::Potion)) => {
+                self.remove(key);
+                self.player = target;
+            }
+            None => self.player = target,
+        }
+        for key in 0..self.items.len() {
+            let Some((Item::Goblin, cell)) = self.items[key] else {
+                continue;
+            };
+            let i = (cell.y as u32 * MAP_SIZE.x + cell.x as u32) as usize;
+            if !self.visible[i] {
+                continue;
+            }
+            let offset = self.player - cell;
+            let step = if offset.x.abs() > offset.y.abs() {
+                Vec2::new(offset.x.signum(), 0)
+            } else {
+                Vec2::new(0, offset.y.signum())
+            };
+            let next = cell + step;
+            if next != self.player && self.is_floor(next) && self.item_at(next).is_none() {
+                self.index.move_to(key, next.as_());
+                self.items[key] = Some((Item::Goblin, next));
+            }
+        }
+        self.update_view();
+        false
+    }
+
+    /// The terrain as drawn, with tiles that were never seen left out, and the tint of each tile.
+    fn terrain_layer(&self) -> (Tilemap, Vec<u32>) {
+        let mut shown = self.terrain.clone();
+        let mut tint = Vec::with_capacity(shown.data.len());
+        for (i, tile) in shown.data.iter_mut().enumerate() {
+            if !self.explored[i] {
+                *tile = VOID;
+            }
+            tint.push(rgba(if self.visible[i] { LIT } else { REMEMBERED }));
+        }
+        (shown, tint)
+    }
+
+    /// The glyphs of the player and of the items in view, and the color of each glyph.
+    fn glyph_layer(&self) -> (Tilemap, Vec<u32>) {
+        let mut glyphs = Tilemap::new_filled(MAP_SIZE, glyph(' '));
+        let mut tint = vec![0; MAP_SIZE.product() as usize];
+        let mut put = |cell: Vec2<i32>, (c, color): (char, [u8; 4])| {
+            glyphs.put_tile(cell.x as u32, cell.y as u32, glyph(c));
+            tint[(cell.y as u32 * MAP_SIZE.x + cell.x as u32) as usize] = rgba(color);
+        };
+        for (item, cell) in self.items.iter().flatten() {
+            if self.visible[(cell.y as u32 * MAP_SIZE.x + cell.x as u32) as usize] {
+                put(*cell, item.glyph());
+            }
+        }
+        put(self.player, ('@', [255, 230, 120, 255]));
+        (glyphs, tint)
+    }
+}
+
+fn draw(
+    tilemap: Tilemap,
+    tileset: u32,
+    empty_tile: u8,
+    tint: Vec<u32>,
+) -> TilemapDrawData<'static> {
+    TilemapDrawData::new(tilemap, tileset)
+        .with_transform(Mat4::scaling_3d(
+            (MAP_SIZE * SIZE_OF_TILE).as_::<f32>().with_z(1.0),
+        ))
+        .with_empty_tile(empty_tile as u32)
+        .with_tint(tint)
+}
+
+const PRESENT_PARAMS: PresentParams = PresentParams {
+    virtual_resolution: Vec2::new(MAP_SIZE.x * SIZE_OF_TILE, MAP_SIZE.y * SIZE_OF_TILE),
+    scaling: PresentScaling::Integer,
+};
+
+struct Example {
+    rng: Rng,
+    level: Level,
+    /// Whether the level has changed since the tilemaps were last uploaded.
+    changed: bool,
+    tilemap_pipeline: TilemapPipeline,
+}
+
+impl framework::Example for Example {
+    fn init(
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let mut tilemap_pipeline = TilemapPipeline::new(device, config.format, None);
+        tilemap_pipeline.set_present_params(
+            queue,
+            &PRESENT_PARAMS,
+            Vec2::new(config.width, config.height),
+        );
+        tilemap_pipeline
+            .upload_tilesets(
+                device,
+                queue,
+                &[
+                    tileset(NUM_TERRAIN_TILES, terrain_texel),
+                    tileset(GLYPHS.len() as u32, glyph_texel),
+                ],
+            )
+            .unwrap();
+        let mut rng = Rng(0x5eed);
+        Example {
+            level: Level::generate(&mut rng),
+            rng,
+            changed: true,
+            tilemap_pipeline,
+        }
+    }
+    fn resize(
+        &mut self,
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        self.tilemap_pipeline.set_present_params(
+            queue,
+            &PRESENT_PARAMS,
+            Vec2::new(config.width, config.height),
+        );
+    }
+    fn update(&mut self, event: WindowEvent<'_>) {
+        let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        else {
+            return;
+        };
+        let step = match key {
+            VirtualKeyCode::Left | VirtualKeyCode::H => Vec2::new(-1, 0),
+            VirtualKeyCode::Right | VirtualKeyCode::L => Vec2::new(1, 0),
+            VirtualKeyCode::Up | VirtualKeyCode::K => Vec2::new(0, -1),
+            VirtualKeyCode::Down | VirtualKeyCode::J => Vec2::new(0, 1),
+            _ => return,
+        };
+        if self.level.take_turn(step) {
+            self.level = Level::generate(&mut self.rng);
+        }
+        self.changed = true;
+    }
+    fn render(&mut self, surface: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue) {
+        // The dungeon only changes when the player moves.
+        if self.changed {
+            let (terrain, terrain_tint) = self.level.terrain_layer();
+            let (glyphs, glyph_tint) = self.level.glyph_layer();
+            self.tilemap_pipeline.upload_tilemaps(
+                device,
+                queue,
+                &[
+                    draw(terrain, 0, VOID, terrain_tint),
+                    draw(glyphs, 1, glyph(' '), glyph_tint),
+                ],
+            );
+            self.changed = false;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("surface_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.tilemap_pipeline.render(device, &mut rpass);
+        }
+        queue.submit(vec![encoder.finish()]);
+    }
+}
+
+fn main() {
+    framework::run::<Example>("roguelike")
+}
@@ -0,0 +1,407 @@
+//! A strategy map: terrain with the territory of three factions, each claiming the land it can
+//! reach most cheaply from its capital, shown with a tint and a border along its edges. A heatmap
+//! overlay shows the influence of one faction across the map: press `H` to toggle it and `Tab` to
+//! switch factions. The map is a hex grid, drawn as rows of tiles with every other row offset by
+//! half a tile (see `TileStagger`), so that each tile borders six others: two above, two below,
+//! and one on each side.
+#[path = "../framework/mod.rs"]
+mod framework;
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+use vek::{Mat4, Vec2};
+use wgpu_tilemap::{
+    AnyTilemapRef, HeatmapRef, PresentParams, PresentScaling, TileStagger, Tilemap,
+    TilemapDrawData, TilemapPipeline, TilesetRef,
+};
+use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+const MAP_SIZE: Vec2<u32> = Vec2::new(40, 22);
+const SIZE_OF_TILE: u32 = 12;
+const STAGGER: TileStagger = TileStagger::OddRows;
+
+const WATER: u8 = 0;
+const GRASS: u8 = 1;
+const FOREST: u8 = 2;
+const HILLS: u8 = 3;
+const MOUNTAINS: u8 = 4;
+const CAPITAL: u8 = 5;
+const SAND: u8 = 6;
+const NUM_TERRAIN_TILES: u32 = 7;
+
+/// The capital and color of each faction.
+const FACTIONS: [(Vec2<u32>, [u8; 4]); 3] = [
+    (Vec2::new(7, 6), [230, 70, 60, 255]),
+    (Vec2::new(31, 5), [70, 120, 240, 255]),
+    (Vec2::new(20, 17), [240, 210, 60, 255]),
+];
+/// How far a faction's influence reaches, in the cost of moving over terrain.
+const MAX_INFLUENCE: u32 = 24;
+
+/// The cost of moving onto a tile, or `None` if it can't be crossed.
+fn cost(tile: u8) -> Option<u32> {
+    match tile {
+        WATER => None,
+        GRASS | SAND | CAPITAL => Some(1),
+        FOREST => Some(2),
+        HILLS => Some(3),
+        _ => Some(5),
+    }
+}
+
+/// A row of tiles drawn by `texel`.
+fn tileset(tiles: u32, texel: impl Fn(u32, u32, u32) -> [u8; 4]) -> TilesetRef<'static> {
+    let dimensions = Vec2::new(tiles * SIZE_OF_TILE, SIZE_OF_TILE);
+    let mut pixels = Vec::new();
+    for y in 0..dimensions.y {
+        for x in 0..dimensions.x {
+            pixels.extend(texel(x / SIZE_OF_TILE, x % SIZE_OF_TILE, y));
+        }
+    }
+    TilesetRef::from_rgba8(
+        &pixels,
+        dimensions,
+        Vec2::broadcast(SIZE_OF_TILE),
+        Vec2::zero(),
+    )
+}
+
+fn terrain_texel(tile: u32, x: u32, y: u32) -> [u8; 4] {
+    let (cx, cy) = (x as i32 - 6, y as i32 - 6);
+    // Shade the top and left edges of each tile, so that the offset rows show.
+    let shade = |[r, g, b, a]: [u8; 4]| match x == 0 || y == 0 {
+        true => [r - r / 8, g - g / 8, b - b / 8, a],
+        false => [r, g, b, a],
+    };
+    shade(match tile as u8 {
+        WATER if (x + 2 * y) % 7 == 0 => [80, 130, 210, 255],
+        WATER => [50, 100, 190, 255],
+        FOREST if (cx.abs() + (cy + 2).abs()) < 5 && cy < 3 => [30, 100, 40, 255],
+        FOREST => [90, 160, 70, 255],
+        HILLS if cy > -3 && cy < 2 && cx.abs() < 5 - cy.abs() => [150, 140, 80, 255],
+        HILLS => [110, 170, 80, 255],
+        MOUNTAINS if cy + 4 > 2 * cx.abs() => [220, 220, 230, 255],
+        MOUNTAINS if cy + 6 > 2 * cx.abs() => [120, 110, 110, 255],
+        MOUNTAINS => [110, 170, 80, 255],
+        SAND if (x * 5 + y * 3) % 11 == 0 => [190, 170, 110, 255],
+        SAND => [220, 200, 140, 255],
+        CAPITAL if (2..10).contains(&x) && (4..10).contains(&y) => [200, 190, 170, 255],
+        CAPITAL if (4..8).contains(&x) && (1..4).contains(&y) => [150, 60, 50, 255],
+        _ => [110, 170, 80, 255],
+    })
+}
+
+/// The six neighbors of `cell` that are on the map, with the edge of `cell` that each is across:
+/// 0 is the top left, then clockwise through the top right, right, bottom right, bottom left, and
+/// left. The tiles above and below an offset row are half a tile to the left of it, so those of
+/// other rows are half a tile to the right.
+fn hex_neighbors(cell: Vec2<u32>) -> impl Iterator<Item = (u32, Vec2<u32>)> {
+    let (x, y) = (cell.x as i32, cell.y as i32);
+    let (left, right) = match STAGGER.is_offset(cell.y) {
+        true => (x, x + 1),
+        false => (x - 1, x),
+    };
+    let neighbors = [
+        (left, y - 1),
+        (right, y - 1),
+        (x + 1, y),
+        (right, y + 1),
+        (left, y + 1),
+        (x - 1, y),
+    ];
+    (0..).zip(neighbors).filter_map(|(edge, (x, y))| {
+        let on_map = (0..MAP_SIZE.x as i32).contains(&x) && (0..MAP_SIZE.y as i32).contains(&y);
+        on_map.then(|| (edge, Vec2::new(x as u32, y as u32)))
+    })
+}
+
+/// Border tiles, one per combination of the edges (numbered as by `hex_neighbors`) that a tile
+/// has on the border of its territory. The top and bottom edges of a tile are split in half
+/// between the two tiles across them. The lines are white, so that they can be colored by the
+/// tint of the border layer.
+fn border_texel(mask: u32, x: u32, y: u32) -> [u8; 4] {
+    let (last, half) = (SIZE_OF_TILE - 2, SIZE_OF_TILE / 2);
+    let edges = [
+        y < 2 && x < half,
+        y < 2 && x >= half,
+        x >= last,
+        y >= last && x >= half,
+        y >= last && x < half,
+        x < 2,
+    ];
+    let on_border = (0..6).any(|edge| mask & (1 << edge) != 0 && edges[edge]);
+    if on_border {
+        [255; 4]
+    } else {
+        [0; 4]
+    }
+}
+
+/// Smooth pseudo-random values from 0 to 1, interpolated between a grid of random values `scale`
+/// tiles apart.
+fn value_noise(position: Vec2<u32>, scale: u32, seed: u32) -> f32 {
+    let hash = |cell: Vec2<u32>| {
+        let mut h = cell.x.wrapping_mul(374761393) ^ cell.y.wrapping_mul(668265263) ^ seed;
+        h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+        (h ^ (h >> 16)) as f32 / u32::MAX as f32
+    };
+    let cell = position / scale;
+    let t = (position % scale).as_::<f32>() / scale as f32;
+    let t = t * t * (Vec2::broadcast(3.0) - t * 2.0);
+    let top = hash(cell) * (1.0 - t.x) + hash(cell + Vec2::unit_x()) * t.x;
+    let bottom = hash(cell + Vec2::unit_y()) * (1.0 - t.x) + hash(cell + 1) * t.x;
+    top * (1.0 - t.y) + bottom * t.y
+}
+
+fn terrain() -> Tilemap {
+    let mut terrain = Tilemap::new_zeroed(MAP_SIZE);
+    for y in 0..MAP_SIZE.y {
+        for x in 0..MAP_SIZE.x {
+            let position = Vec2::new(x, y);
+            let height = value_noise(position, 6, 1) * 0.7 + value_noise(position, 3, 2) * 0.3;
+            let tile = match height {
+                h if h < 0.3 => WATER,
+                h if h < 0.34 => SAND,
+                h if h > 0.75 => MOUNTAINS,
+                h if h > 0.62 => HILLS,
+                _ if value_noise(position, 4, 3) > 0.6 => FOREST,
+                _ => GRASS,
+            };
+            terrain.put_tile(x, y, tile);
+        }
+    }
+    for (capital, _) in FACTIONS {
+        terrain.put_tile(capital.x, capital.y, CAPITAL);
+    }
+    terrain
+}
+
+/// The cheapest cost of reaching each tile from `capital` (Dijkstra's algorithm over the terrain),
+/// or `None` for tiles that can't be reached within `MAX_INFLUENCE`.
+fn influence(terrain: &Tilemap, capital: Vec2<u32>) -> Vec<Option<u32>> {
+    let index = |cell: Vec2<u32>| (cell.y * MAP_SIZE.x + cell.x) as usize;
+    let mut costs = vec![None; terrain.data.len()];
+    let mut queue = BinaryHeap::new();
+    costs[index(capital)] = Some(0);
+    queue.push(Reverse((0, capital.x, capital.y)));
+    while let Some(Reverse((cost_so_far, x, y))) = queue.pop() {
+        if costs[index(Vec2::new(x, y))] != Some(cost_so_far) {
+            continue;
+        }
+        for (_, cell) in hex_neighbors(Vec2::new(x, y)) {
+            let Some(step) = cost(terrain.get_tile(cell.x, cell.y)) else {
+                continue;
+            };
+            let next = cost_so_far + step;
+            let known = costs[index(cell)];
+            if next <= MAX_INFLUENCE && known.map_or(true, |known| next < known) {
+                costs[index(cell)] = Some(next);
+                queue.push(Reverse((next, cell.x, cell.y)));
+            }
+        }
+    }
+    costs
+}
+
+/// The owner of each tile (as 1 + the faction, or 0 if no faction reaches it), which is the
+/// faction that reaches it most cheaply.
+fn territory(influences: &[Vec<Option<u32>>]) -> Tilemap {
+    let mut owners = Tilemap::new_zeroed(MAP_SIZE);
+    for (i, owner) in owners.data.iter_mut().enumerate() {
+        let cheapest = influences
+            .iter()
+            .enumerate()
+            .filter_map(|(faction, costs)| Some((costs[i]?, faction)))
+            .min();
+        *owner = cheapest.map_or(0, |(_, faction)| faction as u8 + 1);
+    }
+    owners
+}
+
+/// The border tile of each tile of a territory: which of its edges face another territory. The
+/// edges of the map aren't borders.
+fn borders(owners: &Tilemap) -> Tilemap {
+    let mut borders = Tilemap::new_zeroed(MAP_SIZE);
+    for (x, y, owner) in owners.iter_coords() {
+        if owner == 0 {
+            continue;
+        }
+        let mask = hex_neighbors(Vec2::new(x, y))
+            .filter(|&(_, cell)| owners.get_tile(cell.x, cell.y) != owner)
+            .fold(0, |mask, (edge, _)| mask | 1 << edge);
+        borders.put_tile(x, y, mask);
+    }
+    borders
+}
+
+/// The color of each tile of a territory, from the colors of the factions: light for the land
+/// and full strength for its border.
+fn territory_tint(owners: &Tilemap, strength: f32) -> Vec<u32> {
+    owners
+        .data
+        .iter()
+        .map(|&owner| match owner {
+            0 => u32::MAX,
+            _ => {
+                let color = FACTIONS[owner as usize - 1].1;
+                let mix = |c: u8| (255.0 + (c as f32 - 255.0) * strength) as u8;
+                u32::from_le_bytes([mix(color[0]), mix(color[1]), mix(color[2]), 255])
+            }
+        })
+        .collect()
+}
+
+fn influence_heatmap(costs: &[Option<u32>]) -> HeatmapRef<'static> {
+    let mut heatmap = HeatmapRef::new(MAP_SIZE, Vec2::new(0.0, MAX_INFLUENCE as f32));
+    for (value, cost) in heatmap.data.to_mut().iter_mut().zip(costs) {
+        *value = cost.map_or(0.0, |cost| (MAX_INFLUENCE - cost) as f32);
+    }
+    heatmap
+}
+
+/// Draw a layer of the map over its grid, which is half a tile wider than the map for the offset
+/// rows.
+fn draw(tilemap: impl Into<AnyTilemapRef<'static>>, tileset: u32) -> TilemapDrawData<'static> {
+    let grid_size = STAGGER.grid_size(MAP_SIZE) * SIZE_OF_TILE as f32;
+    TilemapDrawData::new(tilemap, tileset)
+        .with_transform(Mat4::scaling_3d(grid_size.with_z(1.0)))
+        .with_stagger(STAGGER)
+}
+
+const PRESENT_PARAMS: PresentParams = PresentParams {
+    virtual_resolution: Vec2::new(
+        MAP_SIZE.x * SIZE_OF_TILE + SIZE_OF_TILE / 2,
+        MAP_SIZE.y * SIZE_OF_TILE,
+    ),
+    scaling: PresentScaling::Integer,
+};
+
+const TERRAIN_TILESET: u32 = 0;
+const BORDER_TILESET: u32 = 1;
+const RAMP_TILESET: u32 = 2;
+
+struct Example {
+    /// The map never changes, so its layers are only uploaded when the overlay changes.
+    layers: Vec<TilemapDrawData<'static>>,
+    influences: Vec<Vec<Option<u32>>>,
+    show_heatmap: bool,
+    /// The faction whose influence the heatmap shows.
+    selected: usize,
+    changed: bool,
+    tilemap_pipeline: TilemapPipeline,
+}
+
+impl framework::Example for Example {
+    fn init(
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Adapter,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let mut tilemap_pipeline = TilemapPipeline::new(device, config.format, None);
+        tilemap_pipeline.set_present_params(
+            queue,
+            &PRESENT_PARAMS,
+            Vec2::new(config.width, config.height),
+        );
+        let ramp = [
+            [40, 40, 120, 255],
+            [60, 200, 120, 255],
+            [250, 240, 120, 255],
+        ];
+        tilemap_pipeline
+            .upload_tilesets(
+                device,
+                queue,
+                &[
+                    tileset(NUM_TERRAIN_TILES, terrain_texel),
+                    tileset(64, border_texel),
+                    TilesetRef::color_ramp(&ramp.map(Into::into)),
+                ],
+            )
+            .unwrap();
+
+        let terrain = terrain();
+        let influences: Vec<_> = FACTIONS
+            .iter()
+            .map(|&(capital, _)| influence(&terrain, capital))
+            .collect();
+        let owners = territory(&influences);
+        let land = draw(terrain, TERRAIN_TILESET).with_tint(territory_tint(&owners, 0.3));
+        let borders = draw(borders(&owners), BORDER_TILESET)
+            .with_empty_tile(0)
+            .with_tint(territory_tint(&owners, 1.0));
+        Example {
+            layers: vec![land, borders],
+            influences,
+            show_heatmap: false,
+            selected: 0,
+            changed: true,
+            tilemap_pipeline,
+        }
+    }
+    fn resize(
+        &mut self,
+        config: &wgpu::SurfaceConfiguration,
+        _: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        self.tilemap_pipeline.set_present_params(
+            queue,
+            &PRESENT_PARAMS,
+            Vec2::new(config.width, config.height),
+        );
+    }
+    fn update(&mut self, event: WindowEvent<'_>) {
+        if let WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            match key {
+                VirtualKeyCode::H => self.show_heatmap = !self.show_heatmap,
+                VirtualKeyCode::Tab => self.selected = (self.selected + 1) % FACTIONS.len(),
+                _ => return,
+            }
+            self.changed = true;
+        }
+    }
+    fn render(&mut self, surface: &wgpu::TextureView, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if self.changed {
+            let mut draws = self.layers.clone();
+            if self.show_heatmap {
+                let heatmap = influence_heatmap(&self.influences[self.selected]);
+                draws.push(draw(heatmap, RAMP_TILESET).with_opacity(0.6));
+            }
+            self.tilemap_pipeline.upload_tilemaps(device, queue, &draws);
+            self.changed = false;
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame_encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("surface_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: surface,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.tilemap_pipeline.render(device, &mut rpass);
+        }
+        queue.submit(vec![encoder.finish()]);
+    }
+}
+
+fn main() {
+    framework::run::<Example>("strategy")
+}
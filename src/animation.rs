@@ -0,0 +1,70 @@
+use crate::TileId;
+use alloc::{borrow::Cow, collections::BTreeMap, vec::Vec};
+use core::time::Duration;
+
+/// One frame of a `TileAnimation`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnimationFrame {
+    /// The tile shown during this frame, as an index into the same tileset as the animated tile.
+    pub tile: u32,
+    /// How long this frame is shown for.
+    pub duration: Duration,
+}
+
+/// A tile that cycles through other tiles of its tileset (e.g. for water or torches), as defined
+/// by map editors. The animation loops once its last frame has been shown.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileAnimation {
+    pub frames: Vec<AnimationFrame>,
+}
+
+impl TileAnimation {
+    /// The length of one loop of this animation.
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /// The tile shown when `elapsed` time has passed since the animation started, or `None` if it
+    /// has no frames.
+    pub fn tile_at(&self, elapsed: Duration) -> Option<u32> {
+        let total = self.duration().as_nanos();
+        if total == 0 {
+            return self.frames.first().map(|frame| frame.tile);
+        }
+        let mut remaining = elapsed.as_nanos() % total;
+        for frame in self.frames.iter() {
+            if remaining < frame.duration.as_nanos() {
+                return Some(frame.tile);
+            }
+            remaining -= frame.duration.as_nanos();
+        }
+        self.frames.last().map(|frame| frame.tile)
+    }
+}
+
+/// Replace each tile of `tiles` that has an animation in `animations` (by the index of the tile,
+/// e.g. `TilemapWorld::animations`) with the tile its animation shows when `elapsed` time has
+/// passed. The tiles are only copied if one of them is animated.
+pub fn animate_tiles<T: TileId>(
+    tiles: &mut Cow<'_, [T]>,
+    animations: &BTreeMap<u32, TileAnimation>,
+    elapsed: Duration,
+) {
+    if animations.is_empty() {
+        return;
+    }
+    let frame = |tile: T| {
+        let animation = animations.get(&tile.index())?;
+        animation.tile_at(elapsed).map(T::from_index)
+    };
+    let Some(first) = tiles.iter().position(|&tile| frame(tile).is_some()) else {
+        return;
+    };
+    for tile in tiles.to_mut()[first..].iter_mut() {
+        if let Some(shown) = frame(*tile) {
+            *tile = shown;
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{TileGrid, Tilemap, TilemapRef};
+use alloc::borrow::Cow;
+use vek::Vec2;
+
+/// A tilemap of a size known at compile time, with its tiles stored inline instead of on the heap,
+/// for small grids (e.g. inventories, menus, and tests) built without allocating.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TilemapArray<const W: usize, const H: usize> {
+    /// The tiles of this tilemap, indexed with `[y][x]`.
+    pub tiles: [[u8; W]; H],
+}
+
+impl<const W: usize, const H: usize> TilemapArray<W, H> {
+    /// Size of this tilemap, in tiles. Sizes that don't fit in a `u32` fail to compile.
+    pub const TILE_SIZE: Vec2<u32> = {
+        assert!(W <= u32::MAX as usize && H <= u32::MAX as usize);
+        Vec2::new(W as u32, H as u32)
+    };
+
+    pub const fn new_zeroed() -> Self {
+        Self::new_filled(0)
+    }
+
+    /// Create a tilemap with every position set to `tile`.
+    pub const fn new_filled(tile: u8) -> Self {
+        TilemapArray {
+            tiles: [[tile; W]; H],
+        }
+    }
+
+    /// Get the tile at the specified position.
+    #[inline(always)]
+    pub fn get_tile(&self, x: u32, y: u32) -> u8 {
+        self.tiles[y as usize][x as usize]
+    }
+
+    /// Put a tile at the specified position.
+    #[inline(always)]
+    pub fn put_tile(&mut self, x: u32, y: u32, val: u8) {
+        self.tiles[y as usize][x as usize] = val;
+    }
+
+    /// The tiles of this tilemap, one row after another.
+    pub fn data(&self) -> &[u8] {
+        self.tiles.as_flattened()
+    }
+
+    /// View the tiles as rows, indexed with `[y][x]`.
+    pub fn as_2d(&self) -> TileGrid<'_> {
+        TileGrid::new(self.data(), Self::TILE_SIZE)
+    }
+
+    /// Borrow the tiles of this tilemap, to be drawn.
+    pub fn as_ref(&self) -> TilemapRef<'_> {
+        TilemapRef {
+            tile_size: Self::TILE_SIZE,
+            data: Cow::Borrowed(self.data()),
+        }
+    }
+}
+
+impl<const W: usize, const H: usize> Default for TilemapArray<W, H> {
+    fn default() -> Self {
+        Self::new_zeroed()
+    }
+}
+
+impl<const W: usize, const H: usize> From<TilemapArray<W, H>> for Tilemap {
+    fn from(tilemap: TilemapArray<W, H>) -> Self {
+        Tilemap {
+            tile_size: TilemapArray::<W, H>::TILE_SIZE,
+            data: tilemap.data().to_vec(),
+        }
+    }
+}
+
+impl<'a, const W: usize, const H: usize> From<&'a TilemapArray<W, H>> for TilemapRef<'a> {
+    fn from(tilemap: &'a TilemapArray<W, H>) -> Self {
+        tilemap.as_ref()
+    }
+}
@@ -0,0 +1,108 @@
+//! Packing separate tile images (e.g. a PNG per tile) into a tileset.
+use crate::{AlphaMode, TilesetRef};
+use std::{borrow::Cow, collections::HashMap, fmt};
+use vek::Vec2;
+
+/// An error encountered while packing tile images into a tileset with `TilesetRef::from_images`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TileImagesError {
+    /// There are no images, so the size of the tiles is unknown.
+    Empty,
+    /// The images have no pixels.
+    EmptyImage,
+    /// An image isn't the same size as the first one.
+    SizeMismatch {
+        index: usize,
+        expected: Vec2<u32>,
+        found: Vec2<u32>,
+    },
+    /// The tileset would have more tiles (counting the empty ones that fill its last row) than
+    /// `max_tiles`, so it couldn't be uploaded.
+    TooManyTiles { tiles: u32, max: u32 },
+}
+
+impl fmt::Display for TileImagesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TileImagesError::Empty => write!(f, "there are no tile images"),
+            TileImagesError::EmptyImage => write!(f, "the tile images have no pixels"),
+            TileImagesError::SizeMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "tile image {index} is {}x{}, but the tiles are {}x{}",
+                found.x, found.y, expected.x, expected.y
+            ),
+            TileImagesError::TooManyTiles { tiles, max } => write!(
+                f,
+                "the images need a tileset of {tiles} tiles, but at most {max} are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TileImagesError {}
+
+impl TilesetRef<'static> {
+    /// Pack a list of images of the same size into a tileset with `columns` tiles in each row (see
+    /// `to_image`), one tile per image. Identical images share a tile. Returns the tileset along
+    /// with the tile of each image, in the order of `images`. Each tile is a layer of the tileset
+    /// texture, so tilesets that would have more than `max_tiles` tiles (e.g.
+    /// `device.limits().max_texture_array_layers`) are rejected.
+    pub fn from_images<I: image::GenericImageView<Pixel = image::Rgba<u8>>>(
+        images: &[I],
+        columns: u32,
+        max_tiles: u32,
+    ) -> Result<(TilesetRef<'static>, Vec<u32>), TileImagesError> {
+        let size_of_tile = Vec2::from(images.first().ok_or(TileImagesError::Empty)?.dimensions());
+        if size_of_tile.x == 0 || size_of_tile.y == 0 {
+            return Err(TileImagesError::EmptyImage);
+        }
+        let mut tiles: HashMap<Vec<u32>, u32> = HashMap::new();
+        let mut data = Vec::new();
+        let mut mapping = Vec::with_capacity(images.len());
+        for (index, image) in images.iter().enumerate() {
+            let found = Vec2::from(image.dimensions());
+            if found != size_of_tile {
+                return Err(TileImagesError::SizeMismatch {
+                    index,
+                    expected: size_of_tile,
+                    found,
+                });
+            }
+            let pixels: Vec<u32> = image
+                .pixels()
+                .map(|(_, _, pixel)| u32::from_le_bytes(pixel.0))
+                .collect();
+            let next = tiles.len() as u32;
+            let tile = *tiles.entry(pixels).or_insert_with_key(|pixels| {
+                data.extend_from_slice(pixels);
+                next
+            });
+            mapping.push(tile);
+        }
+        let columns = columns.max(1);
+        let rows = (tiles.len() as u32).div_ceil(columns);
+        if columns as u64 * rows as u64 > max_tiles as u64 {
+            return Err(TileImagesError::TooManyTiles {
+                tiles: columns.saturating_mul(rows),
+                max: max_tiles,
+            });
+        }
+        let pixel_size = Vec2::new(columns, rows) * size_of_tile;
+        // The rest of the last row is left transparent.
+        data.resize(pixel_size.product() as usize, 0);
+        let tileset = TilesetRef {
+            pixel_size,
+            size_of_tile,
+            data: Cow::Owned(data),
+            alpha_mode: AlphaMode::Straight,
+            uv_inset: 0.0,
+            checkerboard: Cow::Borrowed(&[]),
+            compressed: None,
+        };
+        Ok((tileset, mapping))
+    }
+}
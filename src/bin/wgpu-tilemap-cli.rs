@@ -0,0 +1,371 @@
+//! Convert maps and tilesets with the importers and exporters of wgpu-tilemap, e.g. as part of an
+//! asset build step. Run without arguments for usage.
+use std::{error::Error, ffi::OsStr, fs::File, io::BufWriter, path::Path, process::ExitCode};
+use vek::{Mat4, Rect, Vec2};
+use wgpu_tilemap::{
+    godot, ogmo, pyxel, tiled, DownscaleFilter, ImportWarning, MapCompression, TilemapPipeline,
+    TilemapStack, TilemapWorld, TilesetRef, WorldLayer, FULLSCREEN_QUAD_CAMERA,
+};
+
+const USAGE: &str = "\
+usage:
+    wgpu-tilemap-cli convert <map> <output> [--format <tmx|rle|raw>]
+        Export a map as a Tiled map (tmx, the default), or as a binary tilemap with the tile
+        layers of the map stacked from bottom to top, either run-length encoded (rle) or not
+        (raw). Its tilesets are saved next to it as tileset<N>.tsx and tileset<N>.png.
+    wgpu-tilemap-cli pack <output.png> <tile.png>... [--columns <n>]
+        Lay out images of the same size as the tiles of a tileset, in rows of <n> tiles (16 by
+        default), and save a Tiled tileset for it next to <output.png>.
+    wgpu-tilemap-cli thumbnail <map> <output.png> [--size <pixels>]
+        Render a map on the GPU, shrunk to fit in <pixels> by <pixels> (512 by default) with a
+        Kaiser filter on linear colors.
+
+Maps are read from Tiled (.tmx), Pyxel Edit (.pyxel), Godot (.tscn), and Ogmo Editor (.json) files.
+Godot resources are read relative to the directory of the scene, and Ogmo levels are read with the
+first .ogmo project found in the directory of the level or above it.";
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["convert", map, output, rest @ ..] => {
+            string_option(rest, "--format", "tmx").and_then(|(rest, format)| match rest[..] {
+                [] => convert(Path::new(map), Path::new(output), format),
+                _ => Err(USAGE.into()),
+            })
+        }
+        ["pack", output, rest @ ..] => {
+            option(rest, "--columns", 16).and_then(|(tiles, columns)| match tiles[..] {
+                [] => Err(USAGE.into()),
+                _ => pack(Path::new(output), &tiles, columns),
+            })
+        }
+        ["thumbnail", map, output, rest @ ..] => {
+            option(rest, "--size", 512).and_then(|(rest, size)| match rest[..] {
+                [] => thumbnail(Path::new(map), Path::new(output), size),
+                _ => Err(USAGE.into()),
+            })
+        }
+        _ => Err(USAGE.into()),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Remove `--name <value>` from `args`, returning the remaining arguments and the value, which
+/// has to be a positive number, or `default` if the option isn't given.
+fn option<'a>(args: &[&'a str], name: &str, default: u32) -> Result<(Vec<&'a str>, u32)> {
+    let (rest, value) = take_option(args, name)?;
+    match value.map(|value| (value, value.parse())) {
+        None => Ok((rest, default)),
+        Some((_, Ok(value))) if value > 0 => Ok((rest, value)),
+        Some((value, _)) => Err(format!("{name} must be a positive number, not {value:?}").into()),
+    }
+}
+
+/// Remove `--name <value>` from `args` like `option`, for options whose value is a string.
+fn string_option<'a>(
+    args: &[&'a str],
+    name: &str,
+    default: &'a str,
+) -> Result<(Vec<&'a str>, &'a str)> {
+    let (rest, value) = take_option(args, name)?;
+    Ok((rest, value.unwrap_or(default)))
+}
+
+/// Remove `--name <value>` from `args`, returning the remaining arguments and the last value given.
+fn take_option<'a>(args: &[&'a str], name: &str) -> Result<(Vec<&'a str>, Option<&'a str>)> {
+    let mut rest = Vec::with_capacity(args.len());
+    let mut value = None;
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        if arg == name {
+            let next = args.next().ok_or_else(|| format!("{name} needs a value"))?;
+            value = Some(*next);
+        } else {
+            rest.push(arg);
+        }
+    }
+    Ok((rest, value))
+}
+
+/// Import a map with the importer for its extension, printing what couldn't be imported.
+fn load_map(path: &Path) -> Result<TilemapWorld> {
+    let (world, warnings): (TilemapWorld, Vec<ImportWarning>) =
+        match path.extension().and_then(OsStr::to_str) {
+            Some("tmx") => {
+                let map = tiled::load_tmx(path)?;
+                (map.world, map.warnings)
+            }
+            Some("pyxel") => {
+                let document = pyxel::load_pyxel(path)?;
+                (document.world, document.warnings)
+            }
+            Some("tscn") => {
+                let scene = godot::load_tscn(path, path.parent().unwrap_or(Path::new("")))?;
+                (scene.world, scene.warnings)
+            }
+            Some("json") => {
+                let project = ogmo::load_ogmo_project(find_ogmo_project(path)?)?;
+                let level = ogmo::load_ogmo_level(path, &project)?;
+                let warnings = project.warnings.into_iter().chain(level.warnings);
+                (level.world, warnings.collect())
+            }
+            _ => {
+                return Err(format!(
+                    "{}: unsupported map format, expected .tmx, .pyxel, .tscn, or .json",
+                    path.display()
+                )
+                .into())
+            }
+        };
+    for warning in warnings {
+        eprintln!("{}: {warning}", path.display());
+    }
+    Ok(world)
+}
+
+/// The first Ogmo project (.ogmo file) in the directory of `level` or one of its ancestors.
+fn find_ogmo_project(level: &Path) -> Result<std::path::PathBuf> {
+    for dir in level.ancestors().skip(1) {
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let mut projects: Vec<_> = std::fs::read_dir(dir)?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension() == Some(OsStr::new("ogmo")))
+            .collect();
+        projects.sort();
+        if let Some(project) = projects.into_iter().next() {
+            return Ok(project);
+        }
+    }
+    Err(format!("{}: no .ogmo project found for this level", level.display()).into())
+}
+
+fn convert(map: &Path, output: &Path, format: &str) -> Result<()> {
+    let compression = match format {
+        "tmx" => None,
+        "rle" => Some(MapCompression::RunLength),
+        "raw" => Some(MapCompression::None),
+        _ => {
+            return Err(
+                format!("unknown output format {format:?}, expected tmx, rle, or raw").into(),
+            )
+        }
+    };
+    let world = load_map(map)?;
+    let dir = output.parent().unwrap_or(Path::new(""));
+    for (i, tileset) in world.tilesets.iter().enumerate() {
+        save_tileset(tileset, &dir.join(format!("tileset{i}.png")))?;
+    }
+    match compression {
+        None => std::fs::write(output, tiled::export_tmx(&world))?,
+        Some(compression) => {
+            let stack = tile_layers(&world, map)?;
+            stack
+                .as_ref()
+                .write_binary(BufWriter::new(File::create(output)?), compression)?;
+        }
+    }
+    Ok(())
+}
+
+/// The tile layers of `world` (imported from `map`), stacked from bottom to top to be written as
+/// a binary tilemap. Image layers are left out, and chunked layers (of infinite maps) and layers
+/// of other sizes than the map can't be written.
+fn tile_layers(world: &TilemapWorld, map: &Path) -> Result<TilemapStack> {
+    let mut stack = TilemapStack::new_zeroed(world.tile_size, 0);
+    for layer in world.layers.iter() {
+        match layer {
+            WorldLayer::Tiles(layer) if layer.tilemap.tile_size == world.tile_size => {
+                stack.push_layer(&(&layer.tilemap).into());
+            }
+            WorldLayer::Tiles(layer) => {
+                let message = format!(
+                    "{}: layer {:?} isn't the size of the map, which binary tilemaps need",
+                    map.display(),
+                    layer.name
+                );
+                return Err(message.into());
+            }
+            WorldLayer::Chunks(layer) => {
+                let message = format!(
+                    "{}: layer {:?} is chunked, which binary tilemaps can't hold",
+                    map.display(),
+                    layer.name
+                );
+                return Err(message.into());
+            }
+            WorldLayer::Image(layer) => {
+                eprintln!("{}: image layer {:?} left out", map.display(), layer.name);
+            }
+        }
+    }
+    Ok(stack)
+}
+
+/// Save the image of `tileset` at `path`, and a Tiled tileset using it next to it.
+fn save_tileset(tileset: &TilesetRef, path: &Path) -> Result<()> {
+    tileset.to_image().save(path)?;
+    let name = path
+        .file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or("tileset");
+    let image_source = path.file_name().and_then(OsStr::to_str).unwrap_or_default();
+    let tsx = tiled::export_tsx(tileset, name, image_source);
+    std::fs::write(path.with_extension("tsx"), tsx)?;
+    Ok(())
+}
+
+fn pack(output: &Path, tiles: &[&str], columns: u32) -> Result<()> {
+    let mut size_of_tile = None;
+    let mut data = Vec::new();
+    for path in tiles {
+        let image = image::open(path)?.to_rgba8();
+        let size = Vec2::from(image.dimensions());
+        if *size_of_tile.get_or_insert(size) != size {
+            return Err(format!("{path}: every tile must be the same size as the first").into());
+        }
+        data.extend(image.pixels().map(|pixel| u32::from_le_bytes(pixel.0)));
+    }
+    let size_of_tile = size_of_tile.unwrap_or_default();
+    let count = tiles.len() as u32;
+    let columns = columns.clamp(1, count);
+    let pixel_size = size_of_tile * Vec2::new(columns, count.div_ceil(columns));
+    let tileset = TilesetRef::new(pixel_size, size_of_tile, data);
+    save_tileset(&tileset, output)
+}
+
+fn thumbnail(map: &Path, output: &Path, max_size: u32) -> Result<()> {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let world = load_map(map)?;
+    let pixel_size = world.pixel_size();
+    if pixel_size.product() == 0 {
+        return Err(format!("{}: the map is empty", map.display()).into());
+    }
+    let tilesets = world.upload_tilesets();
+    let draws = world.draw_list(Mat4::identity());
+    if draws
+        .iter()
+        .any(|draw| draw.tileset as usize >= tilesets.len())
+    {
+        return Err(format!(
+            "{}: the images of the map's tilesets weren't loaded",
+            map.display()
+        )
+        .into());
+    }
+    let scale = (max_size as f32 / pixel_size.reduce_max() as f32).min(1.0);
+    let size = (pixel_size.as_::<f32>() * scale).map(|x| (x.round() as u32).max(1));
+
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapter =
+        pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+            .ok_or("no GPU adapter is available")?;
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("thumbnail_device"),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits {
+                // Each tile is a layer of its tileset's texture, so allow as many as possible.
+                max_texture_array_layers: adapter.limits().max_texture_array_layers,
+                ..wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+            },
+        },
+        None,
+    ))?;
+    let mut pipeline = TilemapPipeline::new(&device, FORMAT, None);
+    pipeline.set_camera(&queue, FULLSCREEN_QUAD_CAMERA);
+    pipeline.upload_tilesets(&device, &queue, &tilesets)?;
+    pipeline.upload_tilemaps(&device, &queue, &draws);
+    // Bake the map at about its own size and filter it down, rather than sampling a single point
+    // of the tiles for each pixel of the thumbnail.
+    pipeline.set_downscale_filter(DownscaleFilter::Kaiser((1.0 / scale).ceil() as u32));
+    let area = Rect::new(0.0, 0.0, 1.0, 1.0);
+    let baked = pipeline.bake(&device, &queue, 0..draws.len(), area, size);
+
+    let extent = wgpu::Extent3d {
+        width: size.x,
+        height: size.y,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("thumbnail_target"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let row_len = 4 * size.x;
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("thumbnail_readback"),
+        size: (padded_row_len * size.y) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("thumbnail_encoder"),
+    });
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("thumbnail_rpass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pipeline.render_baked(&mut rpass, &baked);
+    }
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: &target,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: Some(size.y),
+            },
+        },
+        extent,
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = readback.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let pixels = slice
+        .get_mapped_range()
+        .chunks_exact(padded_row_len as usize)
+        .flat_map(|row| &row[..row_len as usize])
+        .copied()
+        .collect();
+    let image = image::RgbaImage::from_raw(size.x, size.y, pixels)
+        .ok_or("the rendered thumbnail has the wrong size")?;
+    image.save(output)?;
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! A compact binary format for tilemaps, which is much smaller and faster to load than CSV for
+//! large maps.
+use crate::{TileFormat, TileId, Tilemap, TilemapRef};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Read, Write},
+};
+use vek::Vec2;
+
+/// The first bytes of every binary tilemap.
+const MAGIC: &[u8; 4] = b"WTMB";
+/// The version of the format written by `TilemapRef::write_binary`.
+const VERSION: u16 = 1;
+
+/// How the tiles of a binary tilemap are stored.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MapCompression {
+    /// Every tile, one after another.
+    None,
+    /// Runs of identical tiles, each stored as its length and the tile. This is much smaller for
+    /// typical maps, which have large areas of the same tile.
+    #[default]
+    RunLength,
+}
+
+/// An error encountered while reading a binary tilemap.
+#[derive(Debug)]
+pub enum BinaryMapError {
+    /// The tilemap could not be read.
+    Io(io::Error),
+    /// The data doesn't start with the magic bytes of a binary tilemap.
+    NotATilemap,
+    /// The tilemap has a version of the format that can't be read: one written by a newer version
+    /// of this crate, or 0, which no version has written.
+    UnsupportedVersion(u16),
+    /// The tiles are stored as a different `TileFormat` than the one being read.
+    FormatMismatch {
+        expected: TileFormat,
+        found: TileFormat,
+    },
+    /// The header or the tiles are invalid, e.g. runs of tiles extend past the end of the map.
+    InvalidData(String),
+}
+
+impl fmt::Display for BinaryMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BinaryMapError::Io(e) => write!(f, "couldn't read the tilemap: {e}"),
+            BinaryMapError::NotATilemap => write!(f, "not a binary tilemap"),
+            BinaryMapError::UnsupportedVersion(version) => {
+                write!(f, "unsupported binary tilemap version {version}")
+            }
+            BinaryMapError::FormatMismatch { expected, found } => write!(
+                f,
+                "expected a tilemap of {expected:?} tiles, but it has {found:?} tiles"
+            ),
+            BinaryMapError::InvalidData(what) => write!(f, "invalid binary tilemap: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for BinaryMapError {}
+
+impl From<io::Error> for BinaryMapError {
+    fn from(e: io::Error) -> Self {
+        BinaryMapError::Io(e)
+    }
+}
+
+fn format_code(format: TileFormat) -> u8 {
+    match format {
+        TileFormat::R8 => 0,
+        TileFormat::R16 => 1,
+        TileFormat::R32 => 2,
+        TileFormat::Rg8 => 3,
+        TileFormat::R32Float => 4,
+    }
+}
+
+fn format_from_code(code: u8) -> Option<TileFormat> {
+    Some(match code {
+        0 => TileFormat::R8,
+        1 => TileFormat::R16,
+        2 => TileFormat::R32,
+        3 => TileFormat::Rg8,
+        4 => TileFormat::R32Float,
+        _ => return None,
+    })
+}
+
+impl<T: TileId> TilemapRef<'_, T> {
+    /// Write this tilemap (and all of its layers) in a compact binary format, which can be read
+    /// back with `TilemapRef::read_binary`. The header holds a magic number, the version of the
+    /// format, the `TileFormat`, the compression, the size, and the number of layers, and is
+    /// followed by the tiles. The tiles are written as they are compressed, so `writer` should
+    /// be buffered (e.g. a `std::io::BufWriter`).
+    pub fn write_binary<W: Write>(
+        &self,
+        mut writer: W,
+        compression: MapCompression,
+    ) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[
+            format_code(T::FORMAT),
+            match compression {
+                MapCompression::None => 0,
+                MapCompression::RunLength => 1,
+            },
+        ])?;
+        writer.write_all(&self.tile_size.x.to_le_bytes())?;
+        writer.write_all(&self.tile_size.y.to_le_bytes())?;
+        writer.write_all(&self.layers().to_le_bytes())?;
+        let bytes_per_tile = T::FORMAT.bytes_per_tile();
+        let tile_bytes = |tile: T| tile.index().to_le_bytes();
+        match compression {
+            MapCompression::None => {
+                for &tile in self.data.iter() {
+                    writer.write_all(&tile_bytes(tile)[..bytes_per_tile])?;
+                }
+            }
+            MapCompression::RunLength => {
+                let mut tiles = self.data.iter().copied().peekable();
+                while let Some(tile) = tiles.next() {
+                    let mut run = 1u32;
+                    while run < u32::MAX && tiles.next_if_eq(&tile).is_some() {
+                        run += 1;
+                    }
+                    writer.write_all(&run.to_le_bytes())?;
+                    writer.write_all(&tile_bytes(tile)[..bytes_per_tile])?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
+
+impl<T: TileId> TilemapRef<'static, T> {
+    /// Read a tilemap written by `TilemapRef::write_binary`, with the same `TileId`. The tiles are
+    /// decompressed as they are read, so `reader` should be buffered (e.g. a
+    /// `std::io::BufReader`).
+    pub fn read_binary<R: Read>(mut reader: R) -> Result<Self, BinaryMapError> {
+        let mut header = [0; 20];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(BinaryMapError::NotATilemap);
+        }
+        let word =
+            |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if !(1..=VERSION).contains(&version) {
+            return Err(BinaryMapError::UnsupportedVersion(version));
+        }
+        let found = format_from_code(header[6])
+            .ok_or_else(|| invalid(&format!("unknown tile format {}", header[6])))?;
+        if found != T::FORMAT {
+            return Err(BinaryMapError::FormatMismatch {
+                expected: T::FORMAT,
+                found,
+            });
+        }
+        let tile_size = Vec2::new(word(8), word(12));
+        let len = (tile_size.x as u64 * tile_size.y as u64)
+            .checked_mul(word(16) as u64)
+            .and_then(|len| usize::try_from(len).ok())
+            .ok_or_else(|| invalid("the tilemap is too large"))?;
+        let bytes_per_tile = T::FORMAT.bytes_per_tile();
+        let read_tile = |reader: &mut R| -> io::Result<T> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes[..bytes_per_tile])?;
+            Ok(T::from_index(u32::from_le_bytes(bytes)))
+        };
+        // Grow the tiles as they are read rather than trusting the size in the header up front, so
+        // that truncated data fails before its tiles are allocated.
+        let mut data = Vec::new();
+        match header[7] {
+            0 => {
+                while data.len() < len {
+                    data.push(read_tile(&mut reader)?);
+                }
+            }
+            1 => {
+                while data.len() < len {
+                    let mut run = [0; 4];
+                    reader.read_exact(&mut run)?;
+                    let run = u32::from_le_bytes(run) as usize;
+                    let tile = read_tile(&mut reader)?;
+                    if run > len - data.len() {
+                        return Err(invalid("a run of tiles extends past the end of the map"));
+                    }
+                    data.resize(data.len() + run, tile);
+                }
+            }
+            compression => return Err(invalid(&format!("unknown compression {compression}"))),
+        }
+        Ok(TilemapRef {
+            tile_size,
+            data: Cow::Owned(data),
+        })
+    }
+}
+
+impl<T: TileId> Tilemap<T> {
+    /// Read a tilemap written by `TilemapRef::write_binary`, with the same `TileId`. See
+    /// `TilemapRef::read_binary`.
+    pub fn read_binary<R: Read>(reader: R) -> Result<Self, BinaryMapError> {
+        TilemapRef::read_binary(reader).map(Into::into)
+    }
+}
+
+fn invalid(what: &str) -> BinaryMapError {
+    BinaryMapError::InvalidData(what.to_owned())
+}
@@ -0,0 +1,150 @@
+// The structs and bindings of the camera (group 0), tileset (group 1), and tilemap (group 2) that
+// every tilemap shader starts with, also returned by TilemapPipeline::wgsl_bindings for passes that
+// read the resources of a TilemapPipeline.
+
+struct Tiledata {
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    checkerboard_len: u32,
+    // how far the texels drawn for each tile are kept from the edges of the tile
+    uv_inset: f32,
+    // the finest mip level of the tileset that has been uploaded, which is drawn
+    mip_level: u32,
+    // the number of mip levels of the tileset, whose minified tiles are filtered if there are more
+    // than one
+    mip_levels: u32,
+    // 1 if tile_normal_map holds the normal map of the tileset, 0 otherwise
+    has_normal_map: u32,
+    // 1 if tile_emissive holds the emissive texture of the tileset, 0 otherwise
+    has_emissive: u32,
+    // 1 if tile_materials holds the material table of the tileset, 0 otherwise
+    has_materials: u32,
+    pad0: u32,
+    // each entry is (tile, alternate, unused, unused)
+    checkerboard: array<vec4<u32>, 8>,
+}
+
+struct Tilemap {
+    // transform maps from [0, 1]x[0,1] to world coordinates
+    transform: mat4x4<f32>,
+    // the size of each layer in tilemap_indices, in tiles
+    width: u32,
+    height: u32,
+    noise_data: u32,
+    shadow_samples: u32,
+    // shadow_offset is the full extent of the shadow, in tiles
+    shadow_offset: vec2<f32>,
+    shadow_opacity: f32,
+    opacity: f32,
+    // how much this tilemap moves with the camera, 1.0 moves with the world and 0.0 stays on screen
+    parallax: vec2<f32>,
+    // the part of the tilemap that is drawn, in tiles
+    source_origin: vec2<u32>,
+    source_size: vec2<u32>,
+    // added to every tile index before it is looked up in the tileset
+    tile_index_offset: u32,
+    // the number of bits of each tile when its 3 highest bits are flip flags, or 0 without flags
+    flag_bits: u32,
+    // 1 if the second channel of each tile selects a row of the tileset (TileVariant), 0 otherwise
+    variant_rows: u32,
+    // the number of layers of the tilemap, which are drawn from bottom to top (see TilemapStack)
+    layers: u32,
+    // 1 if tiles equal to empty_tile (ignoring flip flags) aren't drawn, 0 otherwise
+    has_empty_tile: u32,
+    empty_tile: u32,
+    // the values of a heatmap drawn as the first and last stops of its color ramp (see HeatmapRef)
+    heatmap_range: vec2<f32>,
+    // 1 if the translation of the tilemap is rounded to whole pixels of the viewport, 0 otherwise
+    pixel_snap: u32,
+    // the position of this tilemap in the list given to TilemapPipeline::upload_tilemaps
+    draw_index: u32,
+    // the tile at the first texel of tilemap_indices, which holds width by height tiles of each
+    // layer (a part of the tilemap, if it was split to fit the maximum texture size)
+    texture_origin: vec2<u32>,
+    // the part of the tilemap covered by this draw, within source_origin and source_size
+    quad_origin: vec2<u32>,
+    quad_size: vec2<u32>,
+    // how brightly the emissive parts of the tiles glow, 0.0 for shadows and heatmaps
+    emissive_intensity: f32,
+    // the number of heights searched for walls, or 0 if the tilemap has no walls (see TilemapWalls)
+    wall_samples: u32,
+    // how far the tops of tiles with a height of 1 are offset from the center of the camera, as a
+    // fraction of their distance from it
+    wall_parallax: f32,
+    // the height of the tallest walls
+    wall_max_height: f32,
+    // the z of the quad before transform (see TilemapDrawData::depth)
+    depth: f32,
+    // 1 if odd rows of the tilemap are offset by half a tile, 2 if even rows are, and 0 otherwise
+    // (see TileStagger)
+    stagger: u32,
+    // what is drawn for tiles past the end of the tileset: 0 looks them up anyway, 1 draws the last
+    // tile, 2 draws invalid_tile, and 3 draws the error tile (see InvalidTiles)
+    invalid_tiles: u32,
+    invalid_tile: u32,
+    _pad: vec2<u32>,
+    // (normal, -distance, 0) of each clip plane, keeping the world positions p where
+    // dot(plane.xy, p) + plane.z >= 0, so unused planes (all zeros) keep everything
+    clip_planes: array<vec4<f32>, 4>,
+    // the linear team color that the team mask of the tileset is multiplied with, with an alpha of
+    // 1.0 if there is one and 0.0 otherwise
+    team_color: vec4<f32>,
+    // parameters for tilemap_hook, see TilemapDrawData::user_data
+    user_data: array<vec4<u32>, 4>,
+}
+
+struct Camera {
+    // maps from world coordinates to NDC
+    view_proj: mat4x4<f32>,
+    // the world coordinates at the center of the screen
+    center: vec2<f32>,
+    // the number of entries along each axis of color_lut, or 0 if colors aren't graded
+    color_lut_size: u32,
+    pad0: u32,
+    // the size of the viewport in pixels, or 0 if it hasn't been set
+    viewport_size: vec2<f32>,
+    pad1: u32,
+    pad2: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+// sRGB to sRGB color grading LUT, see TilemapPipeline::set_color_lut
+@group(0) @binding(1) var color_lut: texture_3d<f32>;
+@group(0) @binding(2) var color_lut_sampler: sampler;
+
+struct FrameContext {
+    // seconds since an arbitrary start, see TilemapPipeline::set_frame_context
+    time: f32,
+    // seconds since the previous frame
+    delta_time: f32,
+    // the number of this frame
+    index: u32,
+    pad0: u32,
+}
+
+@group(0) @binding(3) var<uniform> frame: FrameContext;
+
+@group(1) @binding(0) var<uniform> tiledata: Tiledata;
+@group(1) @binding(1) var tilemap_data: texture_2d_array<f32>;
+// (emissive, sway, reflectivity, height) of each tile of the tileset in rows of MATERIALS_ROW tiles, or
+// a single zero texel if it has no materials, see TilemapPipeline::set_tileset_materials
+@group(1) @binding(2) var tile_materials: texture_2d<f32>;
+// filters the tiles drawn by tile_color, see TilemapPipeline::set_tile_filter
+@group(1) @binding(3) var tile_sampler: sampler;
+// how much of each pixel of each tile of the tileset is recolored by tilemap.team_color, or a
+// single zero texel if it has no mask, see TilemapPipeline::set_tileset_team_mask
+@group(1) @binding(4) var tile_team_mask: texture_2d_array<f32>;
+// the normals of the pixels of each tile of the tileset, encoded in [0, 1], or an unused
+// placeholder if tiledata.has_normal_map is 0, see TilemapPipeline::set_tileset_normal_map
+@group(1) @binding(5) var tile_normal_map: texture_2d_array<f32>;
+// the light of the pixels of each tile of the tileset (sRGB, with how strongly it glows in alpha),
+// or an unused placeholder if tiledata.has_emissive is 0, see
+// TilemapPipeline::set_tileset_emissive
+@group(1) @binding(6) var tile_emissive: texture_2d_array<f32>;
+
+@group(2) @binding(0) var<uniform> tilemap: Tilemap;
+// R8Uint, R16Uint, R32Uint, or Rg8Uint, depending on the TileFormat of the tilemap, with the layers
+// of the tilemap one below another (heatmaps store the bits of their f32 values as R32Uint)
+@group(2) @binding(1) var tilemap_indices: texture_2d<u32>;
@@ -0,0 +1,59 @@
+/// Two copies of a value (usually a `Tilemap`), for simulations that compute each state from the
+/// previous one (e.g. cellular automata). Each step writes into the copy holding the state before
+/// the previous one, so no copies are made while the simulation runs.
+#[derive(Clone, Debug, Default)]
+pub struct DoubleBuffered<T> {
+    buffers: [T; 2],
+    current: usize,
+}
+
+impl<T: Clone> DoubleBuffered<T> {
+    /// Start a simulation from `initial`, which is cloned for the second buffer.
+    pub fn new(initial: T) -> Self {
+        Self::from_buffers(initial.clone(), initial)
+    }
+}
+
+impl<T> DoubleBuffered<T> {
+    /// Start a simulation from `current`, where `next` is the buffer the first step writes into.
+    pub fn from_buffers(current: T, next: T) -> Self {
+        DoubleBuffered {
+            buffers: [current, next],
+            current: 0,
+        }
+    }
+
+    /// The current state.
+    pub fn current(&self) -> &T {
+        &self.buffers[self.current]
+    }
+
+    /// The current state, for changes made outside of `step` (e.g. from user input).
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.buffers[self.current]
+    }
+
+    /// Advance the simulation, where `f(prev, next)` writes the new state into `next` based on
+    /// `prev` (the current state). `next` initially holds the state before `prev`, so every part
+    /// of it should be written. The new state becomes current once `f` returns.
+    pub fn step<R>(&mut self, f: impl FnOnce(&T, &mut T) -> R) -> R {
+        let [first, second] = &mut self.buffers;
+        let result = if self.current == 0 {
+            f(first, second)
+        } else {
+            f(second, first)
+        };
+        self.current ^= 1;
+        result
+    }
+
+    /// Take the current state, dropping the other buffer.
+    pub fn into_current(self) -> T {
+        let [first, second] = self.buffers;
+        if self.current == 0 {
+            first
+        } else {
+            second
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::{Tilemap, TilemapDrawData};
+use std::collections::HashMap;
+use vek::{Mat4, Rect, Vec2, Vec3};
+
+/// A sparse tilemap made of equally sized chunks, for maps that are unbounded (e.g. Tiled's
+/// infinite maps) or too large to keep resident all at once. Chunks that were never written to are
+/// not stored, and only the chunks in view need to be uploaded each frame.
+#[derive(Clone, Debug)]
+pub struct ChunkedTilemap {
+    /// Size of each chunk, in tiles.
+    pub chunk_size: Vec2<u32>,
+    /// The chunks of this tilemap, keyed by their position in chunks (so the chunk at `(1, 0)`
+    /// starts at the tile at `(chunk_size.x, 0)`).
+    pub chunks: HashMap<Vec2<i32>, Tilemap>,
+    /// The tile read from positions not covered by any chunk, and used to fill new chunks.
+    pub fill: u8,
+}
+
+impl ChunkedTilemap {
+    /// Create an empty chunked tilemap, whose chunks are each `chunk_size` tiles.
+    pub fn new(chunk_size: Vec2<u32>, fill: u8) -> Self {
+        ChunkedTilemap {
+            chunk_size,
+            chunks: HashMap::new(),
+            fill,
+        }
+    }
+
+    /// The chunk containing the tile at the specified position, and the position within it.
+    pub fn chunk_position(&self, x: i32, y: i32) -> (Vec2<i32>, Vec2<u32>) {
+        let size = self.chunk_size.as_::<i32>();
+        let chunk = Vec2::new(x.div_euclid(size.x), y.div_euclid(size.y));
+        let offset = Vec2::new(x.rem_euclid(size.x), y.rem_euclid(size.y));
+        (chunk, offset.as_::<u32>())
+    }
+
+    /// Get the tile at the specified position.
+    pub fn get_tile(&self, x: i32, y: i32) -> u8 {
+        let (chunk, offset) = self.chunk_position(x, y);
+        self.chunks
+            .get(&chunk)
+            .map_or(self.fill, |chunk| chunk.get_tile(offset.x, offset.y))
+    }
+
+    /// Put a tile at the specified position, creating its chunk if necessary.
+    pub fn put_tile(&mut self, x: i32, y: i32, val: u8) {
+        let (chunk, offset) = self.chunk_position(x, y);
+        let (chunk_size, fill) = (self.chunk_size, self.fill);
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| Tilemap::new_filled(chunk_size, fill))
+            .put_tile(offset.x, offset.y, val);
+    }
+
+    /// The smallest rectangle of tiles containing every chunk, or `None` if there are no chunks.
+    pub fn bounds(&self) -> Option<Rect<i32, i32>> {
+        let size = self.chunk_size.as_::<i32>();
+        let mut chunks = self.chunks.keys();
+        let first = *chunks.next()?;
+        let (min, max) = chunks.fold((first, first), |(min, max), &chunk| {
+            (Vec2::partial_min(min, chunk), Vec2::partial_max(max, chunk))
+        });
+        let extent = (max - min + Vec2::one()) * size;
+        Some(Rect::new(
+            min.x * size.x,
+            min.y * size.y,
+            extent.x,
+            extent.y,
+        ))
+    }
+
+    /// The positions of the chunks overlapping `visible`, a rectangle of tiles.
+    pub fn visible_chunks(&self, visible: Rect<i32, i32>) -> impl Iterator<Item = Vec2<i32>> + '_ {
+        let (min, _) = self.chunk_position(visible.x, visible.y);
+        let (max, _) = self.chunk_position(
+            visible.x + visible.w.max(1) - 1,
+            visible.y + visible.h.max(1) - 1,
+        );
+        (min.y..=max.y)
+            .flat_map(move |y| (min.x..=max.x).map(move |x| Vec2::new(x, y)))
+            .filter(|chunk| self.chunks.contains_key(chunk))
+    }
+
+    /// The position of `chunk` in `draw_list` when it's given no `visible` rectangle, or `None` if
+    /// there is no such chunk. Upload that list once, and pass the positions of the chunks that
+    /// an external visibility system finds in view to `TilemapPipeline::render_visible`.
+    pub fn draw_index(&self, chunk: Vec2<i32>) -> Option<usize> {
+        self.chunks.contains_key(&chunk).then(|| {
+            self.chunks
+                .keys()
+                .filter(|other| (other.y, other.x) < (chunk.y, chunk.x))
+                .count()
+        })
+    }
+
+    /// The instructions to draw the chunks of this tilemap overlapping `visible` (or every chunk,
+    /// if `visible` is `None`) with `tileset`, where `tile_transform` maps from [0, 1]x[0, 1] to
+    /// world coordinates for the tile at `(0, 0)`. Subsequent rows of tiles are drawn below it,
+    /// as with `TilemapDrawData`.
+    pub fn draw_list(
+        &self,
+        tile_transform: Mat4<f32>,
+        tileset: u32,
+        visible: Option<Rect<i32, i32>>,
+    ) -> Vec<TilemapDrawData<'_>> {
+        let chunks: Vec<Vec2<i32>> = match visible {
+            Some(visible) => self.visible_chunks(visible).collect(),
+            None => {
+                let mut chunks: Vec<Vec2<i32>> = self.chunks.keys().copied().collect();
+                chunks.sort_by_key(|chunk| (chunk.y, chunk.x));
+                chunks
+            }
+        };
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                let size = self.chunk_size.as_::<f32>();
+                let origin = chunk.as_::<f32>() * size;
+                let transform = tile_transform
+                    * Mat4::<f32>::translation_3d(Vec3::new(
+                        origin.x,
+                        1.0 - origin.y - size.y,
+                        0.0,
+                    ))
+                    * Mat4::<f32>::scaling_3d(Vec3::new(size.x, size.y, 1.0));
+                TilemapDrawData::new(&self.chunks[&chunk], tileset).with_transform(transform)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,449 @@
+//! Loading tilesets from KTX2 and DDS textures, along with their mip levels.
+use crate::{AlphaMode, CompressedTiles, TilesetRef};
+use std::fmt;
+use vek::Vec2;
+
+/// A tileset along with smaller copies of its tiles for its mip levels, e.g. as loaded from a KTX2
+/// or DDS file with `parse_ktx2` or `parse_dds`. Upload it with
+/// `TilemapPipeline::upload_mipped_tilesets_streamed`.
+#[derive(Clone, Debug)]
+pub struct MippedTileset {
+    /// The full size tiles (mip level 0).
+    pub tileset: TilesetRef<'static>,
+    /// The pixels of each further mip level, in the same order and alpha mode as
+    /// `tileset.data`, where the tiles of each level are half the size of the level before (and
+    /// at least 1x1).
+    pub mips: Vec<Vec<u32>>,
+}
+
+/// An error encountered while loading a tileset from a KTX2 or DDS file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextureContainerError {
+    /// The file is truncated or its header is invalid.
+    InvalidData(String),
+    /// The file uses a feature that isn't supported, e.g. a format without color channels,
+    /// supercompression, or a cube map.
+    Unsupported(String),
+    /// The size of the texture isn't a whole number of tiles.
+    TileSize {
+        dimensions: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+    },
+}
+
+impl fmt::Display for TextureContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureContainerError::InvalidData(what) => write!(f, "invalid texture: {what}"),
+            TextureContainerError::Unsupported(what) => write!(f, "unsupported texture: {what}"),
+            TextureContainerError::TileSize {
+                dimensions,
+                size_of_tile,
+            } => write!(
+                f,
+                "a {}x{} texture isn't made of whole {}x{} tiles",
+                dimensions.x, dimensions.y, size_of_tile.x, size_of_tile.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureContainerError {}
+
+/// How the pixels of a texture are stored, among the formats that can be loaded.
+#[derive(Copy, Clone)]
+enum PixelFormat {
+    Rgba,
+    Bgra,
+    /// Blocks of a compressed format, which are uploaded as they are.
+    Compressed(wgpu::TextureFormat),
+}
+
+impl PixelFormat {
+    /// The number of bytes of an image of `size` pixels.
+    fn image_len(self, size: Vec2<u32>) -> usize {
+        match self {
+            PixelFormat::Rgba | PixelFormat::Bgra => size.as_::<usize>().product() * 4,
+            PixelFormat::Compressed(format) => {
+                let (width, height) = format.block_dimensions();
+                let blocks = size.map2(Vec2::new(width, height), u32::div_ceil);
+                let block_len = format.block_size(None).unwrap_or(0) as usize;
+                blocks.as_::<usize>().product() * block_len
+            }
+        }
+    }
+}
+
+/// A texture read from a container, before it is cut into tiles.
+struct Texture<'a> {
+    dimensions: Vec2<u32>,
+    layers: u32,
+    format: PixelFormat,
+    alpha_mode: AlphaMode,
+    /// The bytes of every layer of each mip level, from the largest level.
+    levels: Vec<Vec<&'a [u8]>>,
+}
+
+/// Load a tileset from the contents of a KTX2 file whose images are cut into tiles of
+/// `size_of_tile`, keeping its mip levels. Array textures are read one layer after another, e.g.
+/// with a layer per tile. 8-bit RGBA and BGRA formats are supported, and UNORM formats are
+/// treated as holding sRGB colors like SRGB ones, as tilesets are drawn. The blocks of BC1, BC2,
+/// BC3, BC7, ETC2, and ASTC textures are kept as `TilesetRef::compressed` tiles, without mip
+/// levels, which needs tiles that are a whole number of blocks.
+pub fn parse_ktx2(
+    bytes: &[u8],
+    size_of_tile: Vec2<u32>,
+) -> Result<MippedTileset, TextureContainerError> {
+    const IDENTIFIER: &[u8] = b"\xabKTX 20\xbb\r\n\x1a\n";
+    if !bytes.starts_with(IDENTIFIER) {
+        return Err(invalid("not a KTX2 file"));
+    }
+    let format = read_u32(bytes, 12)?;
+    let dimensions = Vec2::new(read_u32(bytes, 20)?, read_u32(bytes, 24)?);
+    let depth = read_u32(bytes, 28)?;
+    let layers = read_u32(bytes, 32)?.max(1);
+    let faces = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?.max(1);
+    let supercompression = read_u32(bytes, 44)?;
+    let pixel_format = match format {
+        // VK_FORMAT_R8G8B8A8_UNORM and VK_FORMAT_R8G8B8A8_SRGB
+        37 | 43 => PixelFormat::Rgba,
+        // VK_FORMAT_B8G8R8A8_UNORM and VK_FORMAT_B8G8R8A8_SRGB
+        44 | 50 => PixelFormat::Bgra,
+        0 => return Err(unsupported("Basis Universal textures")),
+        _ => match vk_compressed_format(format) {
+            Some(format) => PixelFormat::Compressed(format),
+            None => return Err(unsupported(&format!("Vulkan format {format}"))),
+        },
+    };
+    if supercompression != 0 {
+        return Err(unsupported("supercompression"));
+    }
+    if faces > 1 {
+        return Err(unsupported("cube maps"));
+    }
+    if depth > 1 {
+        return Err(unsupported("3D textures"));
+    }
+    // The first byte of the flags of the basic data format descriptor block.
+    let dfd_offset = read_u32(bytes, 48)? as usize;
+    let flags = *bytes
+        .get(dfd_offset + 15)
+        .ok_or_else(|| invalid("truncated data format descriptor"))?;
+    let alpha_mode = if flags & 1 != 0 {
+        AlphaMode::Premultiplied
+    } else {
+        AlphaMode::Straight
+    };
+    let mut levels = Vec::new();
+    for level in 0..level_count {
+        let index = 80 + 24 * level as usize;
+        let offset = read_u64(bytes, index)?;
+        let layer_len = pixel_format.image_len(mip_dimensions(dimensions, level));
+        let len = layer_len
+            .checked_mul(layers as usize)
+            .ok_or_else(|| invalid("the levels are too large"))?;
+        let data = slice(bytes, offset, len)?;
+        levels.push(data.chunks_exact(layer_len.max(1)).collect());
+    }
+    cut_tiles(
+        Texture {
+            dimensions,
+            layers,
+            format: pixel_format,
+            alpha_mode,
+            levels,
+        },
+        size_of_tile,
+    )
+}
+
+/// Load a tileset from the contents of a DDS file whose images are cut into tiles of
+/// `size_of_tile`, keeping its mip levels. Array textures are read one layer after another, e.g.
+/// with a layer per tile. 8-bit RGBA and BGRA formats are supported, and UNORM formats are
+/// treated as holding sRGB colors like SRGB ones, as tilesets are drawn. The blocks of BC1, BC2,
+/// BC3, and BC7 textures are kept as `TilesetRef::compressed` tiles, without mip levels, which
+/// needs tiles that are a whole number of blocks.
+pub fn parse_dds(
+    bytes: &[u8],
+    size_of_tile: Vec2<u32>,
+) -> Result<MippedTileset, TextureContainerError> {
+    const DDSD_MIPMAPCOUNT: u32 = 0x2_0000;
+    const DDPF_FOURCC: u32 = 0x4;
+    const DDPF_RGB: u32 = 0x40;
+    const DDSCAPS2_CUBEMAP: u32 = 0x200;
+    const DDSCAPS2_VOLUME: u32 = 0x20_0000;
+    if !bytes.starts_with(b"DDS ") || read_u32(bytes, 4)? != 124 {
+        return Err(invalid("not a DDS file"));
+    }
+    let flags = read_u32(bytes, 8)?;
+    let dimensions = Vec2::new(read_u32(bytes, 16)?, read_u32(bytes, 12)?);
+    let level_count = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    let pixel_flags = read_u32(bytes, 80)?;
+    let four_cc = bytes
+        .get(84..88)
+        .ok_or_else(|| invalid("truncated header"))?;
+    if read_u32(bytes, 112)? & (DDSCAPS2_CUBEMAP | DDSCAPS2_VOLUME) != 0 {
+        return Err(unsupported("cube maps and 3D textures"));
+    }
+    let (pixel_format, alpha_mode, layers, data_offset) =
+        if pixel_flags & DDPF_FOURCC != 0 && four_cc == b"DX10" {
+            use wgpu::TextureFormat;
+            let dxgi_format = read_u32(bytes, 128)?;
+            let pixel_format = match dxgi_format {
+                // DXGI_FORMAT_R8G8B8A8_UNORM and DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+                28 | 29 => PixelFormat::Rgba,
+                // DXGI_FORMAT_B8G8R8A8_UNORM and DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+                87 | 91 => PixelFormat::Bgra,
+                // DXGI_FORMAT_BC1_UNORM and DXGI_FORMAT_BC1_UNORM_SRGB
+                71 | 72 => PixelFormat::Compressed(TextureFormat::Bc1RgbaUnormSrgb),
+                // DXGI_FORMAT_BC2_UNORM and DXGI_FORMAT_BC2_UNORM_SRGB
+                74 | 75 => PixelFormat::Compressed(TextureFormat::Bc2RgbaUnormSrgb),
+                // DXGI_FORMAT_BC3_UNORM and DXGI_FORMAT_BC3_UNORM_SRGB
+                77 | 78 => PixelFormat::Compressed(TextureFormat::Bc3RgbaUnormSrgb),
+                // DXGI_FORMAT_BC7_UNORM and DXGI_FORMAT_BC7_UNORM_SRGB
+                98 | 99 => PixelFormat::Compressed(TextureFormat::Bc7RgbaUnormSrgb),
+                _ => return Err(unsupported(&format!("DXGI format {dxgi_format}"))),
+            };
+            if read_u32(bytes, 136)? & 0x4 != 0 {
+                return Err(unsupported("cube maps"));
+            }
+            // DDS_ALPHA_MODE_PREMULTIPLIED
+            let alpha_mode = if read_u32(bytes, 144)? & 0x7 == 2 {
+                AlphaMode::Premultiplied
+            } else {
+                AlphaMode::Straight
+            };
+            (pixel_format, alpha_mode, read_u32(bytes, 140)?.max(1), 148)
+        } else if pixel_flags & DDPF_FOURCC != 0 {
+            use wgpu::TextureFormat;
+            // DXT2 and DXT4 are premultiplied versions of DXT3 and DXT5.
+            let (format, alpha_mode) = match four_cc {
+                b"DXT1" => (TextureFormat::Bc1RgbaUnormSrgb, AlphaMode::Straight),
+                b"DXT2" => (TextureFormat::Bc2RgbaUnormSrgb, AlphaMode::Premultiplied),
+                b"DXT3" => (TextureFormat::Bc2RgbaUnormSrgb, AlphaMode::Straight),
+                b"DXT4" => (TextureFormat::Bc3RgbaUnormSrgb, AlphaMode::Premultiplied),
+                b"DXT5" => (TextureFormat::Bc3RgbaUnormSrgb, AlphaMode::Straight),
+                _ => {
+                    let name = String::from_utf8_lossy(four_cc);
+                    return Err(unsupported(&format!("compressed format {name:?}")));
+                }
+            };
+            (PixelFormat::Compressed(format), alpha_mode, 1, 128)
+        } else {
+            let masks = [92, 96, 100, 104].map(|offset| read_u32(bytes, offset));
+            let pixel_format = match (pixel_flags & DDPF_RGB != 0, read_u32(bytes, 88)?, masks) {
+                (true, 32, [Ok(0xff), Ok(0xff00), Ok(0xff_0000), _]) => PixelFormat::Rgba,
+                (true, 32, [Ok(0xff_0000), Ok(0xff00), Ok(0xff), _]) => PixelFormat::Bgra,
+                _ => return Err(unsupported("pixel formats besides 8-bit RGBA and BGRA")),
+            };
+            (pixel_format, AlphaMode::Straight, 1, 128)
+        };
+    // Unlike KTX2, DDS stores every level of a layer before the next layer.
+    let mut levels = vec![Vec::new(); level_count as usize];
+    let mut offset = data_offset as u64;
+    for _ in 0..layers {
+        for (level, layers) in levels.iter_mut().enumerate() {
+            let len = pixel_format.image_len(mip_dimensions(dimensions, level as u32));
+            layers.push(slice(bytes, offset, len)?);
+            offset += len as u64;
+        }
+    }
+    cut_tiles(
+        Texture {
+            dimensions,
+            layers,
+            format: pixel_format,
+            alpha_mode,
+            levels,
+        },
+        size_of_tile,
+    )
+}
+
+/// The Vulkan formats of KTX2 files whose blocks can be uploaded as they are, mapped to the
+/// formats they are uploaded in.
+fn vk_compressed_format(format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat};
+    const ASTC_BLOCKS: [AstcBlock; 14] = [
+        AstcBlock::B4x4,
+        AstcBlock::B5x4,
+        AstcBlock::B5x5,
+        AstcBlock::B6x5,
+        AstcBlock::B6x6,
+        AstcBlock::B8x5,
+        AstcBlock::B8x6,
+        AstcBlock::B8x8,
+        AstcBlock::B10x5,
+        AstcBlock::B10x6,
+        AstcBlock::B10x8,
+        AstcBlock::B10x10,
+        AstcBlock::B12x10,
+        AstcBlock::B12x12,
+    ];
+    // Each format is listed as UNORM and then SRGB, which are both uploaded as SRGB.
+    Some(match format {
+        // VK_FORMAT_BC1_RGBA_UNORM_BLOCK
+        133 | 134 => TextureFormat::Bc1RgbaUnormSrgb,
+        // VK_FORMAT_BC2_UNORM_BLOCK
+        135 | 136 => TextureFormat::Bc2RgbaUnormSrgb,
+        // VK_FORMAT_BC3_UNORM_BLOCK
+        137 | 138 => TextureFormat::Bc3RgbaUnormSrgb,
+        // VK_FORMAT_BC7_UNORM_BLOCK
+        145 | 146 => TextureFormat::Bc7RgbaUnormSrgb,
+        // VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK
+        147 | 148 => TextureFormat::Etc2Rgb8UnormSrgb,
+        // VK_FORMAT_ETC2_R8G8B8A1_UNORM_BLOCK
+        149 | 150 => TextureFormat::Etc2Rgb8A1UnormSrgb,
+        // VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK
+        151 | 152 => TextureFormat::Etc2Rgba8UnormSrgb,
+        // VK_FORMAT_ASTC_4x4_UNORM_BLOCK to VK_FORMAT_ASTC_12x12_SRGB_BLOCK
+        157..=184 => TextureFormat::Astc {
+            block: ASTC_BLOCKS[(format - 157) as usize / 2],
+            channel: AstcChannel::UnormSrgb,
+        },
+        _ => return None,
+    })
+}
+
+/// Cut every layer of each level of `texture` into tiles. Levels whose size isn't a whole number
+/// of tiles (of the size of the tiles at that level) are left out, along with the levels after
+/// them.
+fn cut_tiles(
+    texture: Texture,
+    size_of_tile: Vec2<u32>,
+) -> Result<MippedTileset, TextureContainerError> {
+    let size_of_tile = size_of_tile.map(|x| x.max(1));
+    let grid = texture.dimensions / size_of_tile;
+    if grid.product() == 0 || grid * size_of_tile != texture.dimensions {
+        return Err(TextureContainerError::TileSize {
+            dimensions: texture.dimensions,
+            size_of_tile,
+        });
+    }
+    if let PixelFormat::Compressed(format) = texture.format {
+        return cut_compressed_tiles(texture, format, size_of_tile, grid);
+    }
+    let mut levels = Vec::new();
+    for (level, layers) in texture.levels.iter().enumerate() {
+        let dimensions = mip_dimensions(texture.dimensions, level as u32);
+        let level_tile = mip_dimensions(size_of_tile, level as u32);
+        if grid * level_tile != dimensions {
+            break;
+        }
+        let mut pixels = Vec::new();
+        for layer in layers {
+            let layer = match texture.format {
+                PixelFormat::Bgra => {
+                    let mut rgba = layer.to_vec();
+                    for pixel in rgba.chunks_exact_mut(4) {
+                        pixel.swap(0, 2);
+                    }
+                    TilesetRef::from_rgba8(&rgba, dimensions, level_tile, 0.into())
+                }
+                _ => TilesetRef::from_rgba8(layer, dimensions, level_tile, 0.into()),
+            };
+            pixels.extend_from_slice(&layer.data);
+        }
+        levels.push(pixels);
+    }
+    let mut levels = levels.into_iter();
+    let data = levels.next().unwrap_or_default();
+    Ok(MippedTileset {
+        tileset: TilesetRef {
+            pixel_size: size_of_tile * grid * Vec2::new(1, texture.layers),
+            size_of_tile,
+            data: data.into(),
+            alpha_mode: texture.alpha_mode,
+            uv_inset: 0.0,
+            checkerboard: (&[][..]).into(),
+            compressed: None,
+        },
+        mips: levels.collect(),
+    })
+}
+
+/// Rearrange the blocks of the first level of `texture` by tile, for `TilesetRef::compressed`.
+/// There is no decoder for the blocks, so the pixels of the tileset (drawn by devices without the
+/// feature the format needs) are a `TilesetRef::placeholder`, and the other levels are left out,
+/// since compressed tiles are only uploaded without mip levels.
+fn cut_compressed_tiles(
+    texture: Texture,
+    format: wgpu::TextureFormat,
+    size_of_tile: Vec2<u32>,
+    grid: Vec2<u32>,
+) -> Result<MippedTileset, TextureContainerError> {
+    let (width, height) = format.block_dimensions();
+    let block_size = Vec2::new(width, height);
+    if size_of_tile % block_size != Vec2::zero() {
+        return Err(unsupported(&format!(
+            "{}x{} tiles of {width}x{height} blocks",
+            size_of_tile.x, size_of_tile.y
+        )));
+    }
+    let block_len = format.block_size(None).unwrap_or(0) as usize;
+    let tile_blocks = (size_of_tile / block_size).as_::<usize>();
+    let row_blocks = grid.x as usize * tile_blocks.x;
+    let tile_row_len = tile_blocks.x * block_len;
+    let mut blocks = Vec::with_capacity(texture.levels[0].iter().map(|l| l.len()).sum());
+    for layer in &texture.levels[0] {
+        for y in 0..grid.y as usize {
+            for x in 0..grid.x as usize {
+                for row in 0..tile_blocks.y {
+                    let start =
+                        ((y * tile_blocks.y + row) * row_blocks + x * tile_blocks.x) * block_len;
+                    blocks.extend_from_slice(&layer[start..start + tile_row_len]);
+                }
+            }
+        }
+    }
+    let pixel_size = size_of_tile * grid * Vec2::new(1, texture.layers);
+    Ok(MippedTileset {
+        tileset: TilesetRef {
+            alpha_mode: texture.alpha_mode,
+            compressed: Some(CompressedTiles {
+                format,
+                blocks: blocks.into(),
+            }),
+            ..TilesetRef::placeholder(pixel_size, size_of_tile)
+        },
+        mips: Vec::new(),
+    })
+}
+
+fn mip_dimensions(size: Vec2<u32>, level: u32) -> Vec2<u32> {
+    size.map(|x| (x >> level).max(1))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, TextureContainerError> {
+    let word = bytes
+        .get(offset..offset + 4)
+        .ok_or_else(|| invalid("truncated header"))?;
+    Ok(u32::from_le_bytes(word.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, TextureContainerError> {
+    let word = bytes
+        .get(offset..offset + 8)
+        .ok_or_else(|| invalid("truncated header"))?;
+    Ok(u64::from_le_bytes(word.try_into().unwrap()))
+}
+
+fn slice(bytes: &[u8], offset: u64, len: usize) -> Result<&[u8], TextureContainerError> {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..offset.checked_add(len)?))
+        .ok_or_else(|| invalid("truncated image data"))
+}
+
+fn invalid(what: &str) -> TextureContainerError {
+    TextureContainerError::InvalidData(what.to_owned())
+}
+
+fn unsupported(what: &str) -> TextureContainerError {
+    TextureContainerError::Unsupported(what.to_owned())
+}
@@ -0,0 +1,1213 @@
+//! The CPU-side map data shared by the renderer and game logic. This only depends on `alloc`, so it
+//! is available without the `std` feature.
+use crate::EdgeMode;
+use alloc::{borrow::Cow, vec, vec::Vec};
+use core::{
+    fmt::Debug,
+    hash::Hash,
+    num::ParseIntError,
+    ops::Index,
+    slice::{ChunksExact, ChunksExactMut},
+    str::FromStr,
+};
+use vek::{Rgba, Vec2};
+
+/// The tile that empty cells of imported layers are set to. The tilesets of imported maps have
+/// this tile fully transparent.
+pub const EMPTY_TILE: u8 = u8::MAX;
+
+/// The type of each tile of a tilemap, an index into its tileset. Wider types can index larger
+/// tilesets: `u8` (the default) indexes 256 tiles, and `u16` indexes 65536. Each tile of a
+/// tileset is a layer of its texture though, so a tileset can only have as many tiles as the
+/// device has layers in a texture (`wgpu::Limits::max_texture_array_layers`, 256 unless a higher
+/// limit is requested, and rarely more than 2048), and larger ones are rejected by
+/// `TilemapPipeline::upload_tilesets`. `u32` doesn't allow larger tilesets than `u16`, but leaves
+/// more room for flip flags above the index (see `TileFlip`), or matches ids used by game logic.
+pub trait TileId:
+    Copy + Default + Debug + PartialEq + Eq + Hash + FromStr + Send + Sync + 'static
+{
+    /// How tilemaps of this type are uploaded.
+    const FORMAT: TileFormat;
+
+    /// This tile as an index into its tileset.
+    fn index(self) -> u32;
+
+    /// The tile with this index, keeping only the bits that fit in this type.
+    fn from_index(index: u32) -> Self;
+
+    /// Wrap a tilemap of this type, so that it can be drawn alongside tilemaps of other types.
+    fn erase(tilemap: TilemapRef<'_, Self>) -> AnyTilemapRef<'_>;
+}
+
+impl TileId for u8 {
+    const FORMAT: TileFormat = TileFormat::R8;
+
+    fn index(self) -> u32 {
+        self as u32
+    }
+
+    fn from_index(index: u32) -> Self {
+        index as u8
+    }
+
+    fn erase(tilemap: TilemapRef<'_, Self>) -> AnyTilemapRef<'_> {
+        AnyTilemapRef::U8(tilemap)
+    }
+}
+
+impl TileId for u16 {
+    const FORMAT: TileFormat = TileFormat::R16;
+
+    fn index(self) -> u32 {
+        self as u32
+    }
+
+    fn from_index(index: u32) -> Self {
+        index as u16
+    }
+
+    fn erase(tilemap: TilemapRef<'_, Self>) -> AnyTilemapRef<'_> {
+        AnyTilemapRef::U16(tilemap)
+    }
+}
+
+impl TileId for u32 {
+    const FORMAT: TileFormat = TileFormat::R32;
+
+    fn index(self) -> u32 {
+        self
+    }
+
+    fn from_index(index: u32) -> Self {
+        index
+    }
+
+    fn erase(tilemap: TilemapRef<'_, Self>) -> AnyTilemapRef<'_> {
+        AnyTilemapRef::U32(tilemap)
+    }
+}
+
+/// A tile with a variant, for tilemaps where a logical tile (e.g. grass) has several looks, without
+/// giving each look its own tile. The variant selects a row of the tileset: the tile is drawn as
+/// tile `tile + variant * columns` of its tileset, where `columns` is the number of tiles in each
+/// row of the tileset. With `TilemapDrawData::flip_flags`, the 3 highest bits of `variant` are the
+/// flip flags. Parses from `"tile"` or `"tile:variant"`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "std", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+pub struct TileVariant {
+    pub tile: u8,
+    pub variant: u8,
+}
+
+impl TileVariant {
+    pub const fn new(tile: u8, variant: u8) -> Self {
+        TileVariant { tile, variant }
+    }
+}
+
+impl FromStr for TileVariant {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tile, variant) = s.split_once(':').unwrap_or((s, "0"));
+        Ok(TileVariant::new(tile.parse()?, variant.parse()?))
+    }
+}
+
+impl TileId for TileVariant {
+    const FORMAT: TileFormat = TileFormat::Rg8;
+
+    /// The tile in the lowest 8 bits, and the variant in the next 8.
+    fn index(self) -> u32 {
+        self.tile as u32 | (self.variant as u32) << 8
+    }
+
+    fn from_index(index: u32) -> Self {
+        TileVariant::new(index as u8, (index >> 8) as u8)
+    }
+
+    fn erase(tilemap: TilemapRef<'_, Self>) -> AnyTilemapRef<'_> {
+        AnyTilemapRef::Variant(tilemap)
+    }
+}
+
+/// A tilemap that owns its tiles, for maps that are edited as the game runs (e.g. by game logic or
+/// simulations). Use `as_ref` to draw it without copying the tiles.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Tilemap<T: TileId = u8> {
+    /// Size of this tilemap, in tiles.
+    pub tile_size: Vec2<u32>,
+    /// The tiles of this tilemap, one row after another.
+    pub data: Vec<T>,
+}
+
+impl<T: TileId> Tilemap<T> {
+    pub fn new_zeroed(size: Vec2<u32>) -> Self {
+        Self::new_filled(size, T::default())
+    }
+
+    /// Create a tilemap with every position set to `tile`.
+    pub fn new_filled(size: Vec2<u32>, tile: T) -> Self {
+        Tilemap {
+            tile_size: size,
+            data: vec![tile; size.x as usize * size.y as usize],
+        }
+    }
+
+    /// Get the tile at the specified position.
+    #[inline(always)]
+    pub fn get_tile(&self, x: u32, y: u32) -> T {
+        self.data[self.tile_size.x as usize * y as usize + x as usize]
+    }
+
+    /// Put a tile at the specified position.
+    #[inline(always)]
+    pub fn put_tile(&mut self, x: u32, y: u32, val: T) {
+        self.data[self.tile_size.x as usize * y as usize + x as usize] = val;
+    }
+
+    /// Get the tile at the specified position, where positions past each edge wrap around to the
+    /// opposite edge, or `None` if the tilemap is empty.
+    #[inline(always)]
+    pub fn get_tile_wrapping(&self, x: i32, y: i32) -> Option<T> {
+        let position = EdgeMode::Wrap.resolve(Vec2::new(x, y), self.tile_size)?;
+        Some(self.get_tile(position.x, position.y))
+    }
+
+    /// Get the tile at the specified position, where positions past each edge read the tile at the
+    /// edge, or `None` if the tilemap is empty.
+    #[inline(always)]
+    pub fn get_tile_clamped(&self, x: i32, y: i32) -> Option<T> {
+        let position = EdgeMode::Clamp.resolve(Vec2::new(x, y), self.tile_size)?;
+        Some(self.get_tile(position.x, position.y))
+    }
+
+    /// View the tiles as rows, indexed with `[y][x]`.
+    pub fn as_2d(&self) -> TileGrid<'_, T> {
+        TileGrid::new(&self.data, self.tile_size)
+    }
+
+    /// Each row of tiles, from top to bottom.
+    pub fn rows(&self) -> ChunksExact<'_, T> {
+        self.as_2d().rows()
+    }
+
+    /// Each row of tiles, from top to bottom, for editing a row at a time.
+    pub fn rows_mut(&mut self) -> ChunksExactMut<'_, T> {
+        self.data
+            .chunks_exact_mut((self.tile_size.x as usize).max(1))
+    }
+
+    /// Each tile with its position, as `(x, y, tile)` in reading order.
+    pub fn iter_coords(&self) -> impl Iterator<Item = (u32, u32, T)> + '_ {
+        self.as_2d().iter_coords()
+    }
+
+    /// Borrow the tiles of this tilemap, to be drawn.
+    pub fn as_ref(&self) -> TilemapRef<'_, T> {
+        TilemapRef {
+            tile_size: self.tile_size,
+            data: Cow::Borrowed(&self.data),
+        }
+    }
+}
+
+impl<'a, T: TileId> From<TilemapRef<'a, T>> for Tilemap<T> {
+    fn from(tilemap: TilemapRef<'a, T>) -> Self {
+        Tilemap {
+            tile_size: tilemap.tile_size,
+            data: tilemap.data.into_owned(),
+        }
+    }
+}
+
+/// A reference to tilemap data to be uploaded as a texture and used as indices into the tileset.
+/// Maps that are edited should be stored as a `Tilemap` instead, and borrowed with
+/// `Tilemap::as_ref` to be drawn.
+#[derive(Clone, Debug)]
+pub struct TilemapRef<'a, T: TileId = u8> {
+    /// Size of this tilemap, in tiles.
+    pub tile_size: Vec2<u32>,
+    /// The tiles of this tilemap, one row after another. Tilemaps with more tiles than
+    /// `tile_size` (e.g. from `TilemapStack::as_ref`) have several layers, one after another.
+    pub data: Cow<'a, [T]>,
+}
+
+/// How the tiles of a tilemap are uploaded, which depends on its `TileId`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileFormat {
+    /// `u8` tiles, uploaded as `wgpu::TextureFormat::R8Uint`.
+    #[default]
+    R8,
+    /// `u16` tiles, uploaded as `wgpu::TextureFormat::R16Uint`, for tilesets with more than 256
+    /// tiles (up to the layer limit of the device, see `TileId`).
+    R16,
+    /// `u32` tiles, uploaded as `wgpu::TextureFormat::R32Uint`. Tilesets are still limited to the
+    /// layer limit of the device (see `TileId`).
+    R32,
+    /// `TileVariant` tiles, uploaded as `wgpu::TextureFormat::Rg8Uint`.
+    Rg8,
+    /// `HeatmapRef` values. These are uploaded as the bits of each value in a
+    /// `wgpu::TextureFormat::R32Uint` texture (like `R32`) rather than as `R32Float`, so that they
+    /// share the tilemap bindings of the shader.
+    R32Float,
+}
+
+impl TileFormat {
+    /// The number of bytes used for each tile.
+    pub const fn bytes_per_tile(self) -> usize {
+        match self {
+            TileFormat::R8 => 1,
+            TileFormat::R16 | TileFormat::Rg8 => 2,
+            TileFormat::R32 | TileFormat::R32Float => 4,
+        }
+    }
+}
+
+/// How a tile is flipped when it is drawn, stored in the 3 highest bits of the tile for tilemaps
+/// drawn with `TilemapDrawData::flip_flags`. These have the same meaning as the flip flags of Tiled:
+/// the diagonal flip (swapping x and y) is applied first, then the horizontal and vertical flips.
+/// The diagonal flip assumes square tiles.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileFlip {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub diagonal: bool,
+}
+
+impl TileFlip {
+    /// The flips that rotate a tile clockwise by `quarter_turns` quarter turns.
+    pub fn rotation(quarter_turns: u32) -> Self {
+        let (horizontal, vertical, diagonal) = match quarter_turns % 4 {
+            0 => (false, false, false),
+            1 => (true, false, true),
+            2 => (true, true, false),
+            _ => (false, true, true),
+        };
+        TileFlip {
+            horizontal,
+            vertical,
+            diagonal,
+        }
+    }
+
+    /// Store these flips in the highest bits of `tile`, replacing those bits of its index.
+    pub fn encode<T: TileId>(self, tile: T) -> T {
+        let shift = Self::shift::<T>();
+        let flags =
+            (self.horizontal as u32) << 2 | (self.vertical as u32) << 1 | self.diagonal as u32;
+        T::from_index(tile.index() & ((1 << shift) - 1) | flags << shift)
+    }
+
+    /// Split a tile into its index and the flips stored in its highest bits.
+    pub fn decode<T: TileId>(tile: T) -> (T, TileFlip) {
+        let shift = Self::shift::<T>();
+        let flags = tile.index() >> shift;
+        let flip = TileFlip {
+            horizontal: flags & 4 != 0,
+            vertical: flags & 2 != 0,
+            diagonal: flags & 1 != 0,
+        };
+        (T::from_index(tile.index() & ((1 << shift) - 1)), flip)
+    }
+
+    /// The position of the lowest flip flag in tiles of type `T`.
+    fn shift<T: TileId>() -> u32 {
+        T::FORMAT.bytes_per_tile() as u32 * 8 - 3
+    }
+}
+
+impl<T: TileId> TilemapRef<'static, T> {
+    pub fn new_zeroed(size: Vec2<u32>) -> Self {
+        Tilemap::new_zeroed(size).into()
+    }
+}
+
+impl<'a, T: TileId> TilemapRef<'a, T> {
+    /// Copy the tiles of this tilemap if they are borrowed, so that it can be kept.
+    pub fn into_owned(self) -> TilemapRef<'static, T> {
+        TilemapRef {
+            tile_size: self.tile_size,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
+
+    /// Get the tile at the specified position, as an index into its tileset.
+    pub fn tile_index(&self, x: u32, y: u32) -> u32 {
+        self.get_tile(x, y).index()
+    }
+
+    /// The number of layers of this tilemap, which are drawn from bottom to top. See
+    /// `TilemapStack`.
+    pub fn layers(&self) -> u32 {
+        (self.data.len() / (self.tile_size.product() as usize).max(1)).max(1) as u32
+    }
+
+    /// Get the tile at the specified position.
+    #[inline(always)]
+    pub fn get_tile(&self, x: u32, y: u32) -> T {
+        self.data.as_ref()[self.tile_size.x as usize * y as usize + x as usize]
+    }
+
+    /// View the tiles as rows, indexed with `[y][x]`.
+    pub fn as_2d(&self) -> TileGrid<'_, T> {
+        TileGrid::new(&self.data, self.tile_size)
+    }
+
+    /// Each row of tiles, from top to bottom.
+    pub fn rows(&self) -> ChunksExact<'_, T> {
+        self.as_2d().rows()
+    }
+
+    /// Each tile with its position, as `(x, y, tile)` in reading order.
+    pub fn iter_coords(&self) -> impl Iterator<Item = (u32, u32, T)> + '_ {
+        self.as_2d().iter_coords()
+    }
+
+    /// Put a tile at the specified position. If the tiles are borrowed, this copies all of them
+    /// first.
+    #[deprecated(
+        note = "this copies the whole tilemap if it is borrowed; edit a `Tilemap` instead"
+    )]
+    #[inline(always)]
+    pub fn put_tile(&mut self, x: u32, y: u32, val: T) {
+        self.data.to_mut()[self.tile_size.x as usize * y as usize + x as usize] = val;
+    }
+}
+
+impl<T: TileId> From<Tilemap<T>> for TilemapRef<'static, T> {
+    fn from(tilemap: Tilemap<T>) -> Self {
+        TilemapRef {
+            tile_size: tilemap.tile_size,
+            data: Cow::Owned(tilemap.data),
+        }
+    }
+}
+
+impl<'a, T: TileId> From<&'a Tilemap<T>> for TilemapRef<'a, T> {
+    fn from(tilemap: &'a Tilemap<T>) -> Self {
+        tilemap.as_ref()
+    }
+}
+
+/// A `TilemapRef` of any `TileId`, as drawn by `TilemapDrawData`. Convert tilemaps into this with
+/// `into()`.
+#[derive(Clone, Debug)]
+pub enum AnyTilemapRef<'a> {
+    U8(TilemapRef<'a, u8>),
+    U16(TilemapRef<'a, u16>),
+    U32(TilemapRef<'a, u32>),
+    Variant(TilemapRef<'a, TileVariant>),
+    Heatmap(HeatmapRef<'a>),
+}
+
+impl<'a> AnyTilemapRef<'a> {
+    /// Copy the tiles of this tilemap if they are borrowed, so that it can be kept.
+    pub fn into_owned(self) -> AnyTilemapRef<'static> {
+        match self {
+            AnyTilemapRef::U8(tilemap) => AnyTilemapRef::U8(tilemap.into_owned()),
+            AnyTilemapRef::U16(tilemap) => AnyTilemapRef::U16(tilemap.into_owned()),
+            AnyTilemapRef::U32(tilemap) => AnyTilemapRef::U32(tilemap.into_owned()),
+            AnyTilemapRef::Variant(tilemap) => AnyTilemapRef::Variant(tilemap.into_owned()),
+            AnyTilemapRef::Heatmap(heatmap) => AnyTilemapRef::Heatmap(heatmap.into_owned()),
+        }
+    }
+
+    /// Size of this tilemap, in tiles.
+    pub fn tile_size(&self) -> Vec2<u32> {
+        match self {
+            AnyTilemapRef::U8(tilemap) => tilemap.tile_size,
+            AnyTilemapRef::U16(tilemap) => tilemap.tile_size,
+            AnyTilemapRef::U32(tilemap) => tilemap.tile_size,
+            AnyTilemapRef::Variant(tilemap) => tilemap.tile_size,
+            AnyTilemapRef::Heatmap(heatmap) => heatmap.tile_size,
+        }
+    }
+
+    /// The number of layers of this tilemap. See `TilemapStack`.
+    pub fn layers(&self) -> u32 {
+        match self {
+            AnyTilemapRef::U8(tilemap) => tilemap.layers(),
+            AnyTilemapRef::U16(tilemap) => tilemap.layers(),
+            AnyTilemapRef::U32(tilemap) => tilemap.layers(),
+            AnyTilemapRef::Variant(tilemap) => tilemap.layers(),
+            AnyTilemapRef::Heatmap(_) => 1,
+        }
+    }
+
+    /// How the tiles of this tilemap are uploaded.
+    pub fn format(&self) -> TileFormat {
+        match self {
+            AnyTilemapRef::U8(_) => TileFormat::R8,
+            AnyTilemapRef::U16(_) => TileFormat::R16,
+            AnyTilemapRef::U32(_) => TileFormat::R32,
+            AnyTilemapRef::Variant(_) => TileFormat::Rg8,
+            AnyTilemapRef::Heatmap(_) => TileFormat::R32Float,
+        }
+    }
+
+    /// Get the tile at the specified position, as an index into its tileset. For heatmaps, this is
+    /// the bits of the value.
+    pub fn tile_index(&self, x: u32, y: u32) -> u32 {
+        match self {
+            AnyTilemapRef::U8(tilemap) => tilemap.tile_index(x, y),
+            AnyTilemapRef::U16(tilemap) => tilemap.tile_index(x, y),
+            AnyTilemapRef::U32(tilemap) => tilemap.tile_index(x, y),
+            AnyTilemapRef::Variant(tilemap) => tilemap.tile_index(x, y),
+            AnyTilemapRef::Heatmap(heatmap) => heatmap.get_value(x, y).to_bits(),
+        }
+    }
+}
+
+impl<'a, T: TileId> From<TilemapRef<'a, T>> for AnyTilemapRef<'a> {
+    fn from(tilemap: TilemapRef<'a, T>) -> Self {
+        T::erase(tilemap)
+    }
+}
+
+impl<'a, T: TileId> From<&'a TilemapRef<'_, T>> for AnyTilemapRef<'a> {
+    fn from(tilemap: &'a TilemapRef<'_, T>) -> Self {
+        T::erase(TilemapRef {
+            tile_size: tilemap.tile_size,
+            data: Cow::Borrowed(&tilemap.data),
+        })
+    }
+}
+
+impl<T: TileId> From<Tilemap<T>> for AnyTilemapRef<'static> {
+    fn from(tilemap: Tilemap<T>) -> Self {
+        T::erase(tilemap.into())
+    }
+}
+
+impl<'a, T: TileId> From<&'a Tilemap<T>> for AnyTilemapRef<'a> {
+    fn from(tilemap: &'a Tilemap<T>) -> Self {
+        T::erase(tilemap.as_ref())
+    }
+}
+
+/// Several tilemaps of the same size, such as the ground, detail, and overlay layers of a level,
+/// drawn from bottom to top by a single `TilemapDrawData`. This is cheaper than a draw for each
+/// layer, but the layers share their tileset and the other settings of the draw.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TilemapStack<T: TileId = u8> {
+    /// Size of each layer, in tiles.
+    pub tile_size: Vec2<u32>,
+    /// The tiles of each layer, one row after another, from the bottom layer to the top one.
+    pub data: Vec<T>,
+}
+
+impl<T: TileId> TilemapStack<T> {
+    pub fn new_zeroed(size: Vec2<u32>, layers: u32) -> Self {
+        TilemapStack {
+            tile_size: size,
+            data: vec![T::default(); size.x as usize * size.y as usize * layers as usize],
+        }
+    }
+
+    /// Stack `layers`, from bottom to top. Panics if they aren't all the same size.
+    pub fn from_layers<'a, I>(layers: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<TilemapRef<'a, T>>,
+    {
+        let mut stack = TilemapStack {
+            tile_size: Vec2::zero(),
+            data: Vec::new(),
+        };
+        for (i, layer) in layers.into_iter().enumerate() {
+            let layer = layer.into();
+            if i == 0 {
+                stack.tile_size = layer.tile_size;
+            }
+            stack.push_layer(&layer);
+        }
+        stack
+    }
+
+    /// Add a layer on top of the stack. Panics if it isn't the same size as the other layers.
+    pub fn push_layer(&mut self, layer: &TilemapRef<T>) {
+        assert_eq!(
+            layer.tile_size, self.tile_size,
+            "every layer of a stack must be the same size"
+        );
+        let len = self.tile_size.product() as usize;
+        self.data.extend_from_slice(&layer.data[..len]);
+    }
+
+    /// The number of layers of this stack.
+    pub fn layers(&self) -> u32 {
+        (self.data.len() / (self.tile_size.product() as usize).max(1)) as u32
+    }
+
+    /// Get the tile at the specified position of `layer`.
+    #[inline(always)]
+    pub fn get_tile(&self, layer: u32, x: u32, y: u32) -> T {
+        self.data[self.offset(layer, x, y)]
+    }
+
+    /// Put a tile at the specified position of `layer`.
+    #[inline(always)]
+    pub fn put_tile(&mut self, layer: u32, x: u32, y: u32, val: T) {
+        let offset = self.offset(layer, x, y);
+        self.data[offset] = val;
+    }
+
+    /// Borrow the tiles of `layer`.
+    pub fn layer(&self, layer: u32) -> TilemapRef<'_, T> {
+        let len = self.tile_size.product() as usize;
+        TilemapRef {
+            tile_size: self.tile_size,
+            data: Cow::Borrowed(&self.data[layer as usize * len..][..len]),
+        }
+    }
+
+    /// Borrow the tiles of every layer, to be drawn.
+    pub fn as_ref(&self) -> TilemapRef<'_, T> {
+        TilemapRef {
+            tile_size: self.tile_size,
+            data: Cow::Borrowed(&self.data),
+        }
+    }
+
+    fn offset(&self, layer: u32, x: u32, y: u32) -> usize {
+        (layer as usize * self.tile_size.y as usize + y as usize) * self.tile_size.x as usize
+            + x as usize
+    }
+}
+
+impl<T: TileId> From<TilemapStack<T>> for AnyTilemapRef<'static> {
+    fn from(stack: TilemapStack<T>) -> Self {
+        T::erase(TilemapRef {
+            tile_size: stack.tile_size,
+            data: Cow::Owned(stack.data),
+        })
+    }
+}
+
+impl<'a, T: TileId> From<&'a TilemapStack<T>> for AnyTilemapRef<'a> {
+    fn from(stack: &'a TilemapStack<T>) -> Self {
+        T::erase(stack.as_ref())
+    }
+}
+
+/// A grid of scalar values (e.g. temperature, influence, or distance fields), drawn by mapping each
+/// value through a color ramp instead of looking up a tile. The tileset of the draw is used as the
+/// ramp: the top-left texel of each of its tiles is a stop, in tile order, and values between two
+/// stops are blended between their colors. `TilesetRef::color_ramp` makes such a tileset.
+///
+/// Heatmaps are drawn one value per cell, without shadows, layers, flip flags, or tile offsets.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeatmapRef<'a> {
+    /// Size of this heatmap, in cells.
+    pub tile_size: Vec2<u32>,
+    /// The value of each cell, one row after another.
+    pub data: Cow<'a, [f32]>,
+    /// The values drawn as the first and last stops of the ramp. Values outside of this range are
+    /// clamped to it.
+    pub range: Vec2<f32>,
+}
+
+impl HeatmapRef<'static> {
+    /// A heatmap with every value set to `range.x`.
+    pub fn new(size: Vec2<u32>, range: Vec2<f32>) -> Self {
+        HeatmapRef {
+            tile_size: size,
+            data: vec![range.x; size.product() as usize].into(),
+            range,
+        }
+    }
+}
+
+impl<'a> HeatmapRef<'a> {
+    /// Copy the values of this heatmap if they are borrowed, so that it can be kept.
+    pub fn into_owned(self) -> HeatmapRef<'static> {
+        HeatmapRef {
+            tile_size: self.tile_size,
+            data: Cow::Owned(self.data.into_owned()),
+            range: self.range,
+        }
+    }
+
+    /// Get the value at the specified position.
+    #[inline(always)]
+    pub fn get_value(&self, x: u32, y: u32) -> f32 {
+        self.data[self.tile_size.x as usize * y as usize + x as usize]
+    }
+
+    /// Set the value at the specified position. If the values are borrowed, this copies all of them
+    /// first.
+    #[inline(always)]
+    pub fn put_value(&mut self, x: u32, y: u32, value: f32) {
+        self.data.to_mut()[self.tile_size.x as usize * y as usize + x as usize] = value;
+    }
+
+    /// Where `value` falls in `range`, from `0.0` (the first stop) to `1.0` (the last stop).
+    pub fn normalize(&self, value: f32) -> f32 {
+        let span = self.range.y - self.range.x;
+        if span == 0.0 {
+            0.0
+        } else {
+            ((value - self.range.x) / span).clamp(0.0, 1.0)
+        }
+    }
+
+    /// View the values as rows, indexed with `[y][x]`.
+    pub fn as_2d(&self) -> TileGrid<'_, f32> {
+        TileGrid::new(&self.data, self.tile_size)
+    }
+}
+
+impl<'a> From<HeatmapRef<'a>> for AnyTilemapRef<'a> {
+    fn from(heatmap: HeatmapRef<'a>) -> Self {
+        AnyTilemapRef::Heatmap(heatmap)
+    }
+}
+
+impl<'a> From<&'a HeatmapRef<'_>> for AnyTilemapRef<'a> {
+    fn from(heatmap: &'a HeatmapRef<'_>) -> Self {
+        AnyTilemapRef::Heatmap(HeatmapRef {
+            tile_size: heatmap.tile_size,
+            data: Cow::Borrowed(&heatmap.data),
+            range: heatmap.range,
+        })
+    }
+}
+
+/// A view of the tiles of a tilemap as rows, returned by `as_2d`. Indexing it with `[y]` gives the
+/// row of tiles at `y`, so tiles can be read with `[y][x]`.
+#[derive(Copy, Clone, Debug)]
+pub struct TileGrid<'a, T = u8> {
+    data: &'a [T],
+    width: usize,
+}
+
+impl<'a, T: Copy> TileGrid<'a, T> {
+    pub(crate) fn new(data: &'a [T], tile_size: Vec2<u32>) -> Self {
+        TileGrid {
+            data,
+            width: tile_size.x as usize,
+        }
+    }
+
+    /// The number of rows.
+    pub fn height(&self) -> usize {
+        self.data.len().checked_div(self.width).unwrap_or(0)
+    }
+
+    /// The row of tiles at `y`, if it is within the tilemap.
+    pub fn row(&self, y: usize) -> Option<&'a [T]> {
+        if y >= self.height() {
+            return None;
+        }
+        self.data.get(y * self.width..(y + 1) * self.width)
+    }
+
+    /// The tile at the specified position, if it is within the tilemap.
+    pub fn get(&self, x: usize, y: usize) -> Option<T> {
+        self.row(y)?.get(x).copied()
+    }
+
+    /// Each row of tiles, from top to bottom.
+    pub fn rows(&self) -> ChunksExact<'a, T> {
+        self.data.chunks_exact(self.width.max(1))
+    }
+
+    /// Each tile with its position, as `(x, y, tile)` in reading order.
+    pub fn iter_coords(&self) -> impl Iterator<Item = (u32, u32, T)> + 'a {
+        self.rows().enumerate().flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &tile)| (x as u32, y as u32, tile))
+        })
+    }
+}
+
+impl<'a, T: Copy> Index<usize> for TileGrid<'a, T> {
+    type Output = [T];
+
+    fn index(&self, y: usize) -> &[T] {
+        self.row(y).expect("row out of bounds")
+    }
+}
+/// A reference to tileset data to be uploaded as a texture. This is the image data drawn for each
+/// tile of the corresponding tilemap.
+///
+/// Tilesets are created with `new` or one of the other constructors (e.g. `from_image`), and
+/// their other fields set afterwards, so that fields added later don't break existing code.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TilesetRef<'a> {
+    /// Size of this tileset, in pixels.
+    pub pixel_size: Vec2<u32>,
+    /// Size of each tile in this tileset.
+    pub size_of_tile: Vec2<u32>,
+    /// Interpreted as `wgpu::TextureFormat::Rgba8UnormSrgb`
+    pub data: Cow<'a, [u32]>,
+    /// Whether the colors of `data` are premultiplied by their alpha.
+    pub alpha_mode: AlphaMode,
+    /// How far (in texels) the positions drawn within each tile are kept from the edges of the
+    /// tile. Positions closer to an edge are clamped, so the outermost `uv_inset` texels of each
+    /// tile are replaced by the texels inside them, e.g. `1.0` hides a one-texel border or grid
+    /// line drawn into every tile. Values up to `0.5` have no visible effect: each tile is a
+    /// layer of its own in the tileset texture, so neighboring tiles never bleed in regardless.
+    pub uv_inset: f32,
+    /// Tiles drawn as another tile on every other cell, in a checkerboard pattern (e.g. to break
+    /// up large areas of grass). Tilesets with more than `MAX_CHECKERBOARD_TILES` entries are
+    /// rejected when they are uploaded.
+    pub checkerboard: Cow<'a, [CheckerboardTile]>,
+    /// The tiles compressed into blocks, uploaded instead of `data` when the device supports their
+    /// format (e.g. to fit large tilesets in a fraction of the memory). `data` is still needed, as
+    /// the fallback and for `analyze`. Tilesets whose blocks aren't the size of their tiles are
+    /// rejected when they are uploaded. These aren't serialized, and need the `std` feature (which
+    /// doesn't break code that creates tilesets, as `TilesetRef` is non-exhaustive).
+    #[cfg(feature = "std")]
+    pub compressed: Option<CompressedTiles<'a>>,
+}
+
+/// The tiles of a tileset compressed into blocks of a format that GPUs sample directly, such as
+/// BC7, ETC2, or ASTC (see `TilesetRef::compressed`).
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompressedTiles<'a> {
+    /// A compressed format, e.g. `wgpu::TextureFormat::Bc7RgbaUnormSrgb`. The blocks are sampled
+    /// as sRGB colors like `TilesetRef::data`, even if the format isn't an sRGB one.
+    pub format: wgpu::TextureFormat,
+    /// The blocks of each tile (row by row), tile after tile in the order of `TilesetRef::data`.
+    /// The size of the tiles must be a multiple of the size of the blocks. The blocks are uploaded
+    /// unchanged, so they must be compressed from premultiplied colors whatever the `alpha_mode`.
+    pub blocks: Cow<'a, [u8]>,
+}
+
+/// How the alpha channel of a tileset relates to its colors. Tilemaps are blended with
+/// premultiplied alpha, so tilesets with straight alpha are premultiplied when they are uploaded.
+/// This is done on linear colors (decoding and re-encoding the sRGB colors of the tileset), so
+/// that edges of transparent tiles don't darken or fringe.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AlphaMode {
+    /// The colors are independent of alpha, as stored by most image formats (e.g. PNG).
+    #[default]
+    Straight,
+    /// The colors have already been multiplied by alpha, so they are uploaded unchanged.
+    Premultiplied,
+}
+
+/// A tile that alternates with another tile in a checkerboard pattern, see
+/// `TilesetRef::checkerboard`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CheckerboardTile {
+    /// The tile that alternates, after any `tile_index_offset` is applied.
+    pub tile: u32,
+    /// The tile drawn instead of `tile` on cells where `x + y` is odd.
+    pub alternate: u32,
+}
+
+/// How the tiles of a tileset image are laid out, for `TilesetRef::from_rgba8_with_layout` and
+/// `TilesetRef::from_image_with_layout`. Only whole tiles are read.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilesetLayout {
+    /// The size of each tile, in pixels.
+    pub size_of_tile: Vec2<u32>,
+    /// The pixels between neighboring tiles. The spacing after the last tile in each row and
+    /// column is optional.
+    pub spacing: Vec2<u32>,
+    /// The pixels before the first tile of each row and column (e.g. the `margin` of Tiled
+    /// tilesets). A margin after the last tile is ignored.
+    pub margin: Vec2<u32>,
+}
+
+impl TilesetLayout {
+    /// Tiles of `size_of_tile` packed edge to edge, without spacing or a margin.
+    pub fn new(size_of_tile: Vec2<u32>) -> Self {
+        TilesetLayout {
+            size_of_tile,
+            ..Default::default()
+        }
+    }
+
+    /// The number of whole tiles in each row and column of an image of `dimensions` pixels.
+    pub fn tile_count(&self, dimensions: Vec2<u32>) -> Vec2<u32> {
+        let area = dimensions - Vec2::partial_min(self.margin, dimensions);
+        (area + self.spacing) / (self.size_of_tile + self.spacing).map(|x| x.max(1))
+    }
+
+    /// The position in the image of the top left pixel of the tile in column `cell.x` and row
+    /// `cell.y`.
+    pub fn tile_origin(&self, cell: Vec2<u32>) -> Vec2<u32> {
+        self.margin + (self.size_of_tile + self.spacing) * cell
+    }
+}
+
+impl<'a> TilesetRef<'a> {
+    /// A tileset of `pixel_size` with tiles of `size_of_tile`, whose pixels (with straight alpha)
+    /// are `data`, tile after tile with the rows of each tile in order.
+    pub fn new(
+        pixel_size: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+        data: impl Into<Cow<'a, [u32]>>,
+    ) -> TilesetRef<'a> {
+        TilesetRef {
+            pixel_size,
+            size_of_tile,
+            data: data.into(),
+            alpha_mode: AlphaMode::Straight,
+            uv_inset: 0.0,
+            checkerboard: Cow::Borrowed(&[]),
+            #[cfg(feature = "std")]
+            compressed: None,
+        }
+    }
+}
+
+impl TilesetRef<'static> {
+    /// Create a tileset from an image stored as rows of RGBA bytes (such as the contents of an
+    /// `image::RgbaImage`), which is `dimensions` pixels in size and has `spacing` pixels between
+    /// its tiles. Whole rows of each tile are copied at once, so this is much faster than reading
+    /// the image a pixel at a time.
+    ///
+    /// Panics if `pixels` is shorter than `dimensions.x * dimensions.y * 4` bytes.
+    pub fn from_rgba8(
+        pixels: &[u8],
+        dimensions: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+        spacing: Vec2<u32>,
+    ) -> TilesetRef<'static> {
+        let layout = TilesetLayout {
+            spacing,
+            ..TilesetLayout::new(size_of_tile)
+        };
+        Self::from_rgba8_with_layout(pixels, dimensions, layout)
+    }
+
+    /// Create a tileset from an image stored as rows of RGBA bytes, like `from_rgba8`, with its
+    /// tiles laid out as described by `layout`.
+    ///
+    /// Panics if `pixels` is shorter than `dimensions.x * dimensions.y * 4` bytes.
+    pub fn from_rgba8_with_layout(
+        pixels: &[u8],
+        dimensions: Vec2<u32>,
+        layout: TilesetLayout,
+    ) -> TilesetRef<'static> {
+        let stride = dimensions.x as usize * 4;
+        assert!(
+            pixels.len() >= stride * dimensions.y as usize,
+            "pixels is too short for a {}x{} image",
+            dimensions.x,
+            dimensions.y
+        );
+        repack(dimensions, layout, |position, row| {
+            let start = position.y as usize * stride + position.x as usize * 4;
+            let bytes = &pixels[start..start + row.len() * 4];
+            for (pixel, bytes) in row.iter_mut().zip(bytes.chunks_exact(4)) {
+                *pixel = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            }
+        })
+    }
+}
+
+impl TilesetRef<'static> {
+    /// A tileset of 1x1 tiles, one for each of `colors` (sRGB, with straight alpha), to draw
+    /// `HeatmapRef`s with. The colors are the stops of the ramp, from the lowest value to the
+    /// highest.
+    pub fn color_ramp(colors: &[Rgba<u8>]) -> TilesetRef<'static> {
+        TilesetRef {
+            pixel_size: Vec2::new(colors.len() as u32, 1),
+            size_of_tile: Vec2::one(),
+            data: colors
+                .iter()
+                .map(|color| u32::from_le_bytes(color.into_array()))
+                .collect(),
+            alpha_mode: AlphaMode::Straight,
+            uv_inset: 0.0,
+            checkerboard: Cow::Borrowed(&[]),
+            #[cfg(feature = "std")]
+            compressed: None,
+        }
+    }
+
+    /// A tileset of `pixel_size` with tiles of `size_of_tile`, each an opaque magenta and black
+    /// checkerboard of 2x2 squares, to upload in the place of a tileset that is still loading (see
+    /// `TilemapPipeline::replace_tileset`). Giving it the size of the tileset it stands in for lets
+    /// the real one reuse its allocation.
+    pub fn placeholder(pixel_size: Vec2<u32>, size_of_tile: Vec2<u32>) -> TilesetRef<'static> {
+        let tiles = (pixel_size / size_of_tile.map(|x| x.max(1))).product();
+        let magenta = u32::from_le_bytes([255, 0, 255, 255]);
+        let black = u32::from_le_bytes([0, 0, 0, 255]);
+        let tile: Vec<u32> = (0..size_of_tile.y)
+            .flat_map(|y| {
+                (0..size_of_tile.x).map(move |x| {
+                    let (left, top) = (x * 2 < size_of_tile.x, y * 2 < size_of_tile.y);
+                    if left == top {
+                        magenta
+                    } else {
+                        black
+                    }
+                })
+            })
+            .collect();
+        TilesetRef {
+            pixel_size,
+            size_of_tile,
+            data: tile.repeat(tiles as usize).into(),
+            alpha_mode: AlphaMode::Premultiplied,
+            uv_inset: 0.0,
+            checkerboard: Cow::Borrowed(&[]),
+            #[cfg(feature = "std")]
+            compressed: None,
+        }
+    }
+}
+
+impl<'a> TilesetRef<'a> {
+    /// Copy the pixels of this tileset if they are borrowed, so that it can be kept.
+    pub fn into_owned(self) -> TilesetRef<'static> {
+        TilesetRef {
+            pixel_size: self.pixel_size,
+            size_of_tile: self.size_of_tile,
+            data: Cow::Owned(self.data.into_owned()),
+            alpha_mode: self.alpha_mode,
+            uv_inset: self.uv_inset,
+            checkerboard: Cow::Owned(self.checkerboard.into_owned()),
+            #[cfg(feature = "std")]
+            compressed: self.compressed.map(|compressed| CompressedTiles {
+                format: compressed.format,
+                blocks: Cow::Owned(compressed.blocks.into_owned()),
+            }),
+        }
+    }
+
+    /// Classify each tile of this tileset by the alpha of its pixels.
+    pub fn analyze(&self) -> TilesetAnalysis {
+        let mut analysis = TilesetAnalysis::default();
+        self.analyze_into(&mut analysis);
+        analysis
+    }
+
+    /// Like `analyze`, but reusing the allocation of `analysis`.
+    pub(crate) fn analyze_into(&self, analysis: &mut TilesetAnalysis) {
+        let tile_len = self.size_of_tile.x as usize * self.size_of_tile.y as usize;
+        analysis.tiles.clear();
+        analysis
+            .tiles
+            .extend(self.data.chunks_exact(tile_len.max(1)).map(|pixels| {
+                let alpha = |pixel: &u32| pixel >> 24;
+                if pixels.iter().all(|pixel| alpha(pixel) == 0) {
+                    TileCoverage::Transparent
+                } else if pixels.iter().all(|pixel| alpha(pixel) == 0xff) {
+                    TileCoverage::Opaque
+                } else {
+                    TileCoverage::Mixed
+                }
+            }));
+    }
+}
+
+/// How much of a tile is covered by its pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileCoverage {
+    /// Every pixel is fully transparent, so drawing the tile has no effect.
+    Transparent,
+    /// Every pixel is fully opaque, so the tile hides whatever is drawn behind it.
+    Opaque,
+    /// Some pixels are partially transparent.
+    Mixed,
+}
+
+/// The `TileCoverage` of each tile of a tileset, as returned by `TilesetRef::analyze`, e.g. to skip
+/// empty tiles or to cull what opaque tiles hide.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilesetAnalysis {
+    /// The coverage of each tile, in the order of the tiles of the tileset.
+    pub tiles: Vec<TileCoverage>,
+}
+
+impl TilesetAnalysis {
+    /// The coverage of `tile`, or `None` if the tileset doesn't have that many tiles.
+    pub fn coverage(&self, tile: u32) -> Option<TileCoverage> {
+        self.tiles.get(tile as usize).copied()
+    }
+
+    /// Whether `tile` draws nothing, including tiles past the end of the tileset.
+    pub fn is_transparent(&self, tile: u32) -> bool {
+        matches!(self.coverage(tile), None | Some(TileCoverage::Transparent))
+    }
+
+    /// Whether `tile` is fully opaque.
+    pub fn is_opaque(&self, tile: u32) -> bool {
+        self.coverage(tile) == Some(TileCoverage::Opaque)
+    }
+}
+
+#[cfg(feature = "image")]
+impl TilesetRef<'static> {
+    /// Create a tileset from an image with tiles of `size_of_tile` packed edge to edge. Each tile
+    /// is uploaded to its own layer of the tileset texture, so tiles never bleed into their
+    /// neighbors when drawn, and atlases don't need padding or extruded edges. See `uv_inset` to
+    /// hide a border drawn into each tile.
+    pub fn from_image<I: image::GenericImageView<Pixel = image::Rgba<u8>>>(
+        image: &I,
+        size_of_tile: Vec2<u32>,
+    ) -> TilesetRef<'static> {
+        Self::from_image_with_spacing(image, size_of_tile, Vec2::broadcast(0))
+    }
+    /// Create a tileset from an image with `spacing` pixels between its tiles, like `from_image`.
+    /// The spacing is skipped, so atlases exported with padding between their tiles can be read by
+    /// passing the padding as the spacing, as long as the image doesn't also have a margin around
+    /// its edges. Atlases with tiles extruded by `N` pixels (e.g. by TexturePacker) also have `N`
+    /// pixels before the first tile, so read them with `from_image_with_layout` and a
+    /// `TilesetLayout` with a margin of `N` and a spacing of `2 * N` (plus any padding).
+    ///
+    /// Only whole tiles are read: an image holds `(dimensions + spacing) / (size_of_tile +
+    /// spacing)` tiles along each axis (the spacing after the last tile is optional), and the
+    /// `pixel_size` of the tileset is the size of those tiles without the spacing between them.
+    /// Earlier versions used the size of the image as the `pixel_size`, and read past the edge of
+    /// images whose last row or column of tiles didn't fit with the spacing.
+    pub fn from_image_with_spacing<I: image::GenericImageView<Pixel = image::Rgba<u8>>>(
+        image: &I,
+        size_of_tile: Vec2<u32>,
+        spacing: Vec2<u32>,
+    ) -> TilesetRef<'static> {
+        let layout = TilesetLayout {
+            spacing,
+            ..TilesetLayout::new(size_of_tile)
+        };
+        Self::from_image_with_layout(image, layout)
+    }
+
+    /// Create a tileset from an image with its tiles laid out as described by `layout`, e.g.
+    /// with a margin around the edges of the image.
+    pub fn from_image_with_layout<I: image::GenericImageView<Pixel = image::Rgba<u8>>>(
+        image: &I,
+        layout: TilesetLayout,
+    ) -> TilesetRef<'static> {
+        // The pixels are copied out first with the `rayon` feature, so that images that aren't
+        // `Sync` can still be read a row of tiles per thread.
+        #[cfg(feature = "rayon")]
+        {
+            let pixels: Vec<u8> = image.pixels().flat_map(|(_, _, pixel)| pixel.0).collect();
+            Self::from_rgba8_with_layout(&pixels, Vec2::from(image.dimensions()), layout)
+        }
+        #[cfg(not(feature = "rayon"))]
+        repack(Vec2::from(image.dimensions()), layout, |position, row| {
+            for (i, pixel) in row.iter_mut().enumerate() {
+                *pixel = u32::from_le_bytes(image.get_pixel(position.x + i as u32, position.y).0);
+            }
+        })
+    }
+
+    /// The same as `from_image_with_spacing`, but faster, since the pixels of `image` can be
+    /// copied a row at a time.
+    pub fn from_rgba_image(
+        image: &image::RgbaImage,
+        size_of_tile: Vec2<u32>,
+        spacing: Vec2<u32>,
+    ) -> TilesetRef<'static> {
+        Self::from_rgba8(
+            image.as_raw(),
+            Vec2::from(image.dimensions()),
+            size_of_tile,
+            spacing,
+        )
+    }
+}
+
+#[cfg(feature = "image")]
+impl<'a> TilesetRef<'a> {
+    /// Lay the tiles of this tileset out in an image of `pixel_size`, in the order they are read
+    /// by `from_image` (left to right, then top to bottom).
+    pub fn to_image(&self) -> image::RgbaImage {
+        let columns = (self.pixel_size.x / self.size_of_tile.x.max(1)).max(1);
+        let tile_len = self.size_of_tile.x as usize * self.size_of_tile.y as usize;
+        let mut bytes = vec![0; self.pixel_size.x as usize * self.pixel_size.y as usize * 4];
+        for_each_chunk(&mut bytes, self.pixel_size.x as usize * 4, |y, row| {
+            let y = y as u32;
+            for (x, pixel) in row.chunks_mut(4).enumerate() {
+                let x = x as u32;
+                let tile = (y / self.size_of_tile.y) * columns + x / self.size_of_tile.x;
+                let offset =
+                    (y % self.size_of_tile.y) * self.size_of_tile.x + x % self.size_of_tile.x;
+                let value = self
+                    .data
+                    .get(tile as usize * tile_len + offset as usize)
+                    .copied()
+                    .unwrap_or(0);
+                pixel.copy_from_slice(&value.to_le_bytes());
+            }
+        });
+        image::RgbaImage::from_raw(self.pixel_size.x, self.pixel_size.y, bytes)
+            .expect("the buffer is the size of the image")
+    }
+}
+
+/// Rearrange an image that is `dimensions` pixels in size into tiles, where `read_row(position,
+/// row)` fills `row` with the pixels of the image starting at `position`.
+fn repack(
+    dimensions: Vec2<u32>,
+    layout: TilesetLayout,
+    read_row: impl Fn(Vec2<u32>, &mut [u32]) + MaybeSync,
+) -> TilesetRef<'static> {
+    let size_of_tile = layout.size_of_tile;
+    let tile_size = layout.tile_count(dimensions);
+    let pixel_size = tile_size * size_of_tile;
+    let tile_len = size_of_tile.x as usize * size_of_tile.y as usize;
+    let mut pixels = vec![0; tile_size.x as usize * tile_size.y as usize * tile_len];
+    // Each row of tiles is repacked independently.
+    for_each_chunk(&mut pixels, tile_size.x as usize * tile_len, |y, tiles| {
+        for (x, tile) in tiles.chunks_mut(tile_len.max(1)).enumerate() {
+            let origin = layout.tile_origin(Vec2::new(x as u32, y as u32));
+            for (j, row) in tile.chunks_mut(size_of_tile.x.max(1) as usize).enumerate() {
+                read_row(origin + Vec2::new(0, j as u32), row);
+            }
+        }
+    });
+    TilesetRef {
+        pixel_size,
+        size_of_tile,
+        data: Cow::Owned(pixels),
+        alpha_mode: AlphaMode::Straight,
+        uv_inset: 0.0,
+        checkerboard: Cow::Borrowed(&[]),
+        #[cfg(feature = "std")]
+        compressed: None,
+    }
+}
+
+/// `Sync` with the `rayon` feature, which shares the closures of `for_each_chunk` between threads,
+/// and implemented by every type otherwise.
+#[cfg(feature = "rayon")]
+trait MaybeSync: Sync {}
+#[cfg(feature = "rayon")]
+impl<T: Sync + ?Sized> MaybeSync for T {}
+#[cfg(not(feature = "rayon"))]
+trait MaybeSync {}
+#[cfg(not(feature = "rayon"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+/// Call `f` with the index and contents of each `chunk_len` long chunk of `data`, in parallel when
+/// the `rayon` feature is enabled.
+fn for_each_chunk<T: Send>(
+    data: &mut [T],
+    chunk_len: usize,
+    f: impl Fn(usize, &mut [T]) + MaybeSync,
+) {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        data.par_chunks_mut(chunk_len.max(1))
+            .enumerate()
+            .for_each(|(i, chunk)| f(i, chunk));
+    }
+    #[cfg(not(feature = "rayon"))]
+    data.chunks_mut(chunk_len.max(1))
+        .enumerate()
+        .for_each(|(i, chunk)| f(i, chunk));
+}
@@ -0,0 +1,133 @@
+use crate::{TileFormat, TileId, Tilemap, TilemapRef};
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+use vek::Vec2;
+
+/// An error encountered while reading or writing a tilemap as CSV.
+#[derive(Debug)]
+pub enum CsvError {
+    /// The CSV could not be read or written, or isn't valid UTF-8.
+    Csv(csv::Error),
+    /// A cell isn't a valid tile, at a line and column counted from 1.
+    InvalidTile {
+        line: u64,
+        column: u64,
+        value: String,
+    },
+    /// The size of the tilemap was to be inferred, but the CSV has no tiles.
+    Empty,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::Csv(e) => write!(f, "{e}"),
+            CsvError::InvalidTile {
+                line,
+                column,
+                value,
+            } => write!(f, "line {line}, column {column}: invalid tile {value:?}"),
+            CsvError::Empty => write!(f, "the CSV has no tiles"),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl From<csv::Error> for CsvError {
+    fn from(e: csv::Error) -> Self {
+        CsvError::Csv(e)
+    }
+}
+
+impl<T: TileId> Tilemap<T> {
+    /// Read a tilemap from CSV with a row of tiles on each line, as written by `to_csv` or by
+    /// Tiled. Rows may have different lengths (e.g. with a trailing comma), and missing or empty
+    /// cells are left as the default tile. With a `size`, rows and columns past it are ignored;
+    /// without one, the size is inferred as the number of rows and the length of the longest one.
+    pub fn from_csv<R: Read>(size: Option<Vec2<u32>>, reader: R) -> Result<Self, CsvError> {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let mut rows = Vec::new();
+        for record in csv_reader.records() {
+            let record = record?;
+            if size.is_some_and(|size| rows.len() >= size.y as usize) {
+                break;
+            }
+            let line = record
+                .position()
+                .map_or(rows.len() as u64 + 1, |p| p.line());
+            let mut row = Vec::new();
+            for (x, cell) in record.iter().enumerate() {
+                if size.is_some_and(|size| x >= size.x as usize) {
+                    break;
+                }
+                row.push(if cell.is_empty() {
+                    None
+                } else {
+                    Some(T::from_str(cell).map_err(|_| CsvError::InvalidTile {
+                        line,
+                        column: x as u64 + 1,
+                        value: cell.to_owned(),
+                    })?)
+                });
+            }
+            // Trailing empty cells don't count towards the inferred width.
+            while row.last() == Some(&None) {
+                row.pop();
+            }
+            rows.push(row);
+        }
+        let size = match size {
+            Some(size) => size,
+            None => {
+                let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+                if width == 0 {
+                    return Err(CsvError::Empty);
+                }
+                Vec2::new(width as u32, rows.len() as u32)
+            }
+        };
+        let mut ret = Self::new_zeroed(size);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.iter().enumerate() {
+                if let Some(tile) = tile {
+                    ret.put_tile(x as u32, y as u32, *tile);
+                }
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl<T: TileId> TilemapRef<'static, T> {
+    /// Read a tilemap from CSV. See `Tilemap::from_csv`.
+    pub fn from_csv<R: Read>(size: Option<Vec2<u32>>, reader: R) -> Result<Self, CsvError> {
+        Tilemap::from_csv(size, reader).map(Into::into)
+    }
+}
+
+impl<T: TileId> TilemapRef<'_, T> {
+    /// Write this tilemap as CSV, with a row of tiles on each line, which `from_csv` reads back.
+    /// `TileVariant`s are written as `tile:variant`, and layers (see `TilemapStack`) are written
+    /// one below another.
+    pub fn to_csv<W: Write>(&self, writer: W) -> Result<(), CsvError> {
+        let mut csv_writer = csv::Writer::from_writer(writer);
+        for row in self.data.chunks(self.tile_size.x.max(1) as usize) {
+            csv_writer.write_record(row.iter().map(|&tile| {
+                let index = tile.index();
+                match T::FORMAT {
+                    TileFormat::Rg8 => format!("{}:{}", index & 0xff, index >> 8),
+                    _ => index.to_string(),
+                }
+            }))?;
+        }
+        csv_writer.flush().map_err(csv::Error::from)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+// Shrinks tilemaps baked at a multiple of the size of their baked texture into it, filtering the
+// premultiplied linear colors of the larger texture (see `DownscaleFilter`).
+
+struct Downscale {
+    // how many pixels of the source each pixel of the target covers along each axis
+    factor: u32,
+    // 0 for a box filter, 1 for a Kaiser-windowed sinc
+    kaiser: u32,
+    _pad: vec2<u32>,
+}
+
+@group(0) @binding(0)
+var<uniform> downscale: Downscale;
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+
+// The radius of the Kaiser-windowed sinc, in pixels of the target.
+const KAISER_RADIUS: f32 = 2.0;
+const KAISER_BETA: f32 = 4.0;
+const PI: f32 = 3.14159265;
+
+// A triangle covering the target.
+@vertex
+fn downscale_vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+// The zeroth order modified Bessel function of the first kind, from its power series.
+fn bessel_i0(x: f32) -> f32 {
+    var sum = 1.0;
+    var term = 1.0;
+    for (var k = 1; k < 12; k++) {
+        let t = x / (2.0 * f32(k));
+        term *= t * t;
+        sum += term;
+    }
+    return sum;
+}
+
+// The weight of a source pixel `x` pixels of the target from the center of a target pixel.
+fn kaiser_sinc(x: f32) -> f32 {
+    let t = x / KAISER_RADIUS;
+    if abs(t) >= 1.0 {
+        return 0.0;
+    }
+    var sinc = 1.0;
+    if abs(x) > 1e-4 {
+        sinc = sin(PI * x) / (PI * x);
+    }
+    return sinc * bessel_i0(KAISER_BETA * sqrt(1.0 - t * t)) / bessel_i0(KAISER_BETA);
+}
+
+@fragment
+fn downscale_frag_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let factor = i32(downscale.factor);
+    let size = vec2<i32>(textureDimensions(source));
+    let pixel = vec2<i32>(position.xy);
+    if downscale.kaiser == 0u {
+        var sum = vec4<f32>(0.0);
+        for (var y = 0; y < factor; y++) {
+            for (var x = 0; x < factor; x++) {
+                sum += textureLoad(source, pixel * factor + vec2<i32>(x, y), 0);
+            }
+        }
+        return sum / f32(factor * factor);
+    }
+    // The source pixels within the radius of the center of this pixel, clamped to the edges.
+    let center = position.xy * f32(factor);
+    let reach = i32(ceil(KAISER_RADIUS * f32(factor)));
+    let first = vec2<i32>(floor(center)) - reach;
+    var sum = vec4<f32>(0.0);
+    var total = 0.0;
+    for (var y = 0; y <= 2 * reach; y++) {
+        let wy = kaiser_sinc((f32(first.y + y) + 0.5 - center.y) / f32(factor));
+        for (var x = 0; x <= 2 * reach; x++) {
+            let wx = kaiser_sinc((f32(first.x + x) + 0.5 - center.x) / f32(factor));
+            let texel = clamp(first + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            sum += textureLoad(source, texel, 0) * wx * wy;
+            total += wx * wy;
+        }
+    }
+    // The negative lobes can overshoot, which premultiplied colors can't represent.
+    let color = clamp(sum / total, vec4<f32>(0.0), vec4<f32>(1.0));
+    return vec4<f32>(min(color.rgb, vec3<f32>(color.a)), color.a);
+}
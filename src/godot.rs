@@ -0,0 +1,487 @@
+//! Importing `TileMap` and `TileMapLayer` nodes from [Godot](https://godotengine.org/) 4 scenes.
+use crate::{
+    import::LayerWarnings, world::with_empty_tile, FsResolver, ImportWarning, ResourceResolver,
+    Tilemap, TilemapBlendMode, TilemapLayer, TilemapWorld, TilesetLayout, TilesetRef, WorldLayer,
+    EMPTY_TILE,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+use vek::{Mat4, Vec2};
+
+/// An error encountered while importing a Godot scene.
+#[derive(Debug)]
+pub enum GodotError {
+    /// The scene or a resource it uses could not be parsed.
+    Parse(String),
+    /// A resource used by the scene could not be read.
+    Io(String, std::io::Error),
+    /// A texture used by a tileset could not be decoded.
+    Image(image::ImageError),
+    /// The scene uses a feature that isn't supported (e.g. scene collection tileset sources).
+    Unsupported(String),
+}
+
+impl fmt::Display for GodotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GodotError::Parse(what) => write!(f, "invalid scene: {what}"),
+            GodotError::Io(path, e) => write!(f, "couldn't read {path:?}: {e}"),
+            GodotError::Image(e) => write!(f, "invalid texture: {e}"),
+            GodotError::Unsupported(what) => write!(f, "unsupported: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for GodotError {}
+
+/// The tilemaps of a Godot scene, converted into a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub struct GodotScene {
+    /// The layers of every tilemap node in the scene, covering the cells used by any of them.
+    /// There is one tileset per atlas source (in order of source id), and layers using several
+    /// sources are split into one layer per source.
+    pub world: TilemapWorld,
+    /// The Godot cell coordinates of the top left tile of `world`.
+    pub origin: Vec2<i32>,
+    /// Parts of the scene that couldn't be imported.
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Import the tilemaps of a Godot scene from a .tscn file, where `project_dir` is the directory
+/// that `res://` paths are relative to.
+pub fn load_tscn(
+    path: impl AsRef<Path>,
+    project_dir: impl AsRef<Path>,
+) -> Result<GodotScene, GodotError> {
+    let path = path.as_ref();
+    let scene =
+        std::fs::read_to_string(path).map_err(|e| GodotError::Io(path.display().to_string(), e))?;
+    let mut project = FsResolver::new(project_dir.as_ref());
+    parse_tscn(&scene, |resource: &str| {
+        project.resolve(resource.trim_start_matches("res://"))
+    })
+}
+
+/// Import the tilemaps of a Godot scene from the contents of a .tscn file, where `resolver` reads
+/// the resources it uses (external tilesets and their textures) by their `res://` path.
+pub fn parse_tscn(
+    scene: &str,
+    mut resolver: impl ResourceResolver,
+) -> Result<GodotScene, GodotError> {
+    let mut load = |path: &str| {
+        resolver
+            .resolve(path)
+            .map_err(|e| GodotError::Io(path.to_owned(), e))
+    };
+    let scene = Resource::parse(scene)?;
+    let mut warnings = Vec::new();
+    let mut tilesets = HashMap::new();
+    let mut tilemaps = Vec::new();
+    for node in scene
+        .sections
+        .iter()
+        .filter(|section| section.tag == "node")
+    {
+        let layers = match node.attribute("type") {
+            Some("TileMap") => {
+                // Layers are numbered from 0, with the properties of each prefixed by its number.
+                let mut layers = Vec::new();
+                while let Some(data) = node.property(&format!("layer_{}/tile_data", layers.len())) {
+                    let i = layers.len();
+                    let name = node
+                        .property(&format!("layer_{i}/name"))
+                        .map_or_else(|| format!("layer_{i}"), |name| unquote(name).to_owned());
+                    let enabled = node.property(&format!("layer_{i}/enabled")) != Some("false");
+                    layers.push((name, enabled, decode_tile_data(data)?));
+                }
+                layers
+            }
+            Some("TileMapLayer") => {
+                let Some(data) = node.property("tile_map_data") else {
+                    continue;
+                };
+                let name = node.attribute("name").unwrap_or_default().to_owned();
+                let enabled = node.property("enabled") != Some("false");
+                vec![(name, enabled, decode_tile_map_data(data)?)]
+            }
+            _ => continue,
+        };
+        let tileset = node
+            .property("tile_set")
+            .ok_or_else(|| GodotError::Parse("tilemap node without a tile_set".to_owned()))?;
+        if !tilesets.contains_key(tileset) {
+            let sources = scene.tileset(tileset, &mut load, &mut warnings)?;
+            tilesets.insert(tileset.to_owned(), sources);
+        }
+        tilemaps.push((tileset, layers));
+    }
+
+    // Every layer covers the cells used by all of them, so that they line up.
+    let cells = tilemaps
+        .iter()
+        .flat_map(|(_, layers)| layers.iter())
+        .flat_map(|(_, _, cells)| cells.iter().map(|cell| cell.position));
+    let (min, max) = cells.fold(
+        (Vec2::broadcast(i32::MAX), Vec2::broadcast(i32::MIN)),
+        |(min, max), position| {
+            (
+                Vec2::partial_min(min, position),
+                Vec2::partial_max(max, position),
+            )
+        },
+    );
+    let (origin, tile_size) = if min.x <= max.x {
+        (min, (max - min + Vec2::one()).as_::<u32>())
+    } else {
+        (Vec2::zero(), Vec2::zero())
+    };
+
+    let mut world = TilemapWorld {
+        tile_size,
+        ..TilemapWorld::default()
+    };
+    // Each atlas source of each tileset becomes one tileset of the world.
+    let mut world_tilesets = HashMap::new();
+    let mut missing_sources = HashSet::new();
+    for (tileset, layers) in tilemaps.iter() {
+        let sources = &tilesets[*tileset];
+        for (name, enabled, cells) in layers.iter() {
+            let mut split = BTreeMap::new();
+            let mut layer_warnings = LayerWarnings::default();
+            for cell in cells.iter() {
+                // Sources that couldn't be loaded have already been warned about.
+                let Some(Some(source)) = sources.get(&cell.source) else {
+                    if !sources.contains_key(&cell.source)
+                        && missing_sources.insert((*tileset, cell.source))
+                    {
+                        warnings.push(ImportWarning::MissingTileset {
+                            tileset: format!("source {}", cell.source),
+                            reason: "the TileSet has no such source".to_owned(),
+                        });
+                    }
+                    continue;
+                };
+                let index = source.tile_index(cell.atlas_coords);
+                let Some(tile) = u8::try_from(index).ok().filter(|&tile| tile != EMPTY_TILE) else {
+                    layer_warnings.unknown_tile(index);
+                    continue;
+                };
+                if cell.alternative != 0 {
+                    layer_warnings.transformed_tile();
+                }
+                let index = *world_tilesets
+                    .entry((*tileset, cell.source))
+                    .or_insert_with(|| {
+                        world.size_of_tile = source.tileset.size_of_tile;
+                        world.tilesets.push(with_empty_tile(&source.tileset));
+                        world.tilesets.len() as u32 - 1
+                    });
+                let position = (cell.position - origin).as_::<u32>();
+                split
+                    .entry(index)
+                    .or_insert_with(|| Tilemap::new_filled(tile_size, EMPTY_TILE))
+                    .put_tile(position.x, position.y, tile);
+            }
+            for (tileset, tilemap) in split {
+                world.layers.push(WorldLayer::Tiles(TilemapLayer {
+                    name: name.clone(),
+                    tilemap,
+                    tileset,
+                    transform: Mat4::identity(),
+                    visible: *enabled,
+                    blend_mode: TilemapBlendMode::Alpha,
+                    opacity: 1.0,
+                    parallax: Vec2::one(),
+                }));
+            }
+            layer_warnings.finish(name, &mut warnings);
+        }
+    }
+    Ok(GodotScene {
+        world,
+        origin,
+        warnings,
+    })
+}
+
+/// A cell of a Godot tilemap layer.
+struct Cell {
+    position: Vec2<i32>,
+    source: i32,
+    atlas_coords: Vec2<u32>,
+    /// The alternative of the tile, which is non-zero for flipped or otherwise modified tiles.
+    alternative: u16,
+}
+
+/// Decode the `tile_data` of a `TileMap` layer: triples of integers holding the position, source
+/// and atlas coordinates of each cell, each split into two 16 bit halves.
+fn decode_tile_data(value: &str) -> Result<Vec<Cell>, GodotError> {
+    let ints = array_items(value, "PackedInt32Array")?
+        .map(|item| {
+            item.parse::<i64>()
+                .map(|int| int as u32)
+                .map_err(|_| GodotError::Parse(format!("invalid tile data {item:?}")))
+        })
+        .collect::<Result<Vec<u32>, _>>()?;
+    Ok(ints
+        .chunks_exact(3)
+        .map(|cell| Cell {
+            position: Vec2::new(cell[0] as i16 as i32, (cell[0] >> 16) as i16 as i32),
+            source: (cell[1] & 0xffff) as i32,
+            atlas_coords: Vec2::new(cell[1] >> 16, cell[2] & 0xffff),
+            alternative: (cell[2] >> 16) as u16,
+        })
+        .collect())
+}
+
+/// Decode the `tile_map_data` of a `TileMapLayer`: a 16 bit format version, followed by 16 bit
+/// fields for the position, source, atlas coordinates, and alternative of each cell.
+fn decode_tile_map_data(value: &str) -> Result<Vec<Cell>, GodotError> {
+    let inner = array_inner(value, "PackedByteArray")?;
+    let bytes = if inner.starts_with('"') {
+        use base64::Engine;
+        base64::engine::general_purpose::STANDARD
+            .decode(unquote(inner))
+            .map_err(|e| GodotError::Parse(e.to_string()))?
+    } else {
+        array_items(value, "PackedByteArray")?
+            .map(|item| {
+                item.parse::<u8>()
+                    .map_err(|_| GodotError::Parse(format!("invalid tile data {item:?}")))
+            })
+            .collect::<Result<Vec<u8>, _>>()?
+    };
+    let field = |cell: &[u8], i: usize| u16::from_le_bytes([cell[2 * i], cell[2 * i + 1]]);
+    Ok(bytes
+        .get(2..)
+        .unwrap_or_default()
+        .chunks_exact(12)
+        .map(|cell| Cell {
+            position: Vec2::new(field(cell, 0) as i16 as i32, field(cell, 1) as i16 as i32),
+            source: field(cell, 2) as i16 as i32,
+            atlas_coords: Vec2::new(field(cell, 3) as u32, field(cell, 4) as u32),
+            alternative: field(cell, 5),
+        })
+        .collect())
+}
+
+/// A `TileSetAtlasSource`, loaded into a tileset.
+struct AtlasSource {
+    tileset: TilesetRef<'static>,
+    columns: u32,
+}
+
+impl AtlasSource {
+    /// The index of the tile at the specified atlas coordinates.
+    fn tile_index(&self, atlas_coords: Vec2<u32>) -> u32 {
+        atlas_coords.y * self.columns + atlas_coords.x
+    }
+}
+
+/// A section of a Godot text resource or scene, such as `[node name="TileMap" type="TileMap"]`,
+/// and the `key = value` properties following it.
+struct Section<'a> {
+    tag: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+    properties: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Section<'a> {
+    fn attribute(&self, key: &str) -> Option<&'a str> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+
+    fn property(&self, key: &str) -> Option<&'a str> {
+        self.properties
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| *v)
+    }
+}
+
+/// A Godot text resource (.tres) or scene (.tscn).
+struct Resource<'a> {
+    sections: Vec<Section<'a>>,
+}
+
+impl<'a> Resource<'a> {
+    fn parse(text: &'a str) -> Result<Self, GodotError> {
+        let mut sections: Vec<Section> = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if let Some(header) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                let (tag, mut rest) = header.split_once(' ').unwrap_or((header, ""));
+                let mut attributes = Vec::new();
+                while let Some((key, value)) = rest.split_once('=') {
+                    // Values are either quoted strings or run until the next space.
+                    let value = value.trim_start();
+                    let end = if let Some(quoted) = value.strip_prefix('"') {
+                        quoted.find('"').map_or(value.len(), |end| end + 2)
+                    } else {
+                        value.find(' ').unwrap_or(value.len())
+                    };
+                    attributes.push((key.trim(), unquote(&value[..end])));
+                    rest = &value[end..];
+                }
+                sections.push(Section {
+                    tag,
+                    attributes,
+                    properties: Vec::new(),
+                });
+            } else if let (Some((key, value)), Some(section)) =
+                (line.split_once(" = "), sections.last_mut())
+            {
+                section.properties.push((key.trim(), value.trim()));
+            }
+        }
+        Ok(Resource { sections })
+    }
+
+    /// Find a resource defined in this file, by its tag (`sub_resource` or `ext_resource`) and id.
+    fn resource(&self, tag: &str, id: &str) -> Result<&Section<'a>, GodotError> {
+        self.sections
+            .iter()
+            .find(|section| section.tag == tag && section.attribute("id") == Some(id))
+            .ok_or_else(|| GodotError::Parse(format!("missing {tag} {id:?}")))
+    }
+
+    /// Load the atlas sources of the tileset referenced by `value`, by source id. Sources whose
+    /// texture couldn't be loaded are `None`.
+    fn tileset(
+        &self,
+        value: &str,
+        load: &mut dyn FnMut(&str) -> Result<Vec<u8>, GodotError>,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> Result<HashMap<i32, Option<AtlasSource>>, GodotError> {
+        if let Some(id) = call_argument(value, "ExtResource") {
+            // The tileset is a separate .tres file, with the tileset as its [resource] section.
+            let path = self
+                .resource("ext_resource", id)?
+                .attribute("path")
+                .unwrap_or_default();
+            let text = String::from_utf8_lossy(&load(path)?).into_owned();
+            let resource = Resource::parse(&text)?;
+            let tileset = resource
+                .sections
+                .iter()
+                .find(|section| section.tag == "resource")
+                .ok_or_else(|| GodotError::Parse(format!("{path:?} has no [resource]")))?;
+            resource.atlas_sources(tileset, load, warnings)
+        } else if let Some(id) = call_argument(value, "SubResource") {
+            self.atlas_sources(self.resource("sub_resource", id)?, load, warnings)
+        } else {
+            Err(GodotError::Parse(format!("invalid tile_set {value:?}")))
+        }
+    }
+
+    fn atlas_sources(
+        &self,
+        tileset: &Section,
+        load: &mut dyn FnMut(&str) -> Result<Vec<u8>, GodotError>,
+        warnings: &mut Vec<ImportWarning>,
+    ) -> Result<HashMap<i32, Option<AtlasSource>>, GodotError> {
+        let mut sources = HashMap::new();
+        for (key, value) in tileset.properties.iter() {
+            let Some(source_id) = key.strip_prefix("sources/") else {
+                continue;
+            };
+            let source_id = source_id
+                .parse()
+                .map_err(|_| GodotError::Parse(format!("invalid source id {key:?}")))?;
+            let id = call_argument(value, "SubResource")
+                .ok_or_else(|| GodotError::Parse(format!("invalid source {value:?}")))?;
+            let source = self.resource("sub_resource", id)?;
+            if source.attribute("type") != Some("TileSetAtlasSource") {
+                let ty = source.attribute("type").unwrap_or_default();
+                return Err(GodotError::Unsupported(format!("{ty} tileset sources")));
+            }
+            let texture = source
+                .property("texture")
+                .and_then(|texture| call_argument(texture, "ExtResource"))
+                .ok_or_else(|| GodotError::Parse(format!("atlas source {id:?} has no texture")))?;
+            let path = self
+                .resource("ext_resource", texture)?
+                .attribute("path")
+                .unwrap_or_default();
+            let image = match load(path)
+                .and_then(|bytes| image::load_from_memory(&bytes).map_err(GodotError::Image))
+            {
+                Ok(image) => image.into_rgba8(),
+                Err(e) => {
+                    warnings.push(ImportWarning::MissingTileset {
+                        tileset: path.to_owned(),
+                        reason: e.to_string(),
+                    });
+                    sources.insert(source_id, None);
+                    continue;
+                }
+            };
+            let vector = |key, default| {
+                source
+                    .property(key)
+                    .map_or(Ok(default), parse_vector2i)
+                    .map(|v| v.map(|x: i32| x.max(0) as u32))
+            };
+            let region_size = vector("texture_region_size", Vec2::broadcast(16))?;
+            let layout = TilesetLayout {
+                size_of_tile: region_size.map(|x| x.max(1)),
+                spacing: vector("separation", Vec2::zero())?,
+                margin: vector("margins", Vec2::zero())?,
+            };
+            let tileset = TilesetRef::from_image_with_layout(&image, layout);
+            let columns = tileset.pixel_size.x / tileset.size_of_tile.x;
+            sources.insert(source_id, Some(AtlasSource { tileset, columns }));
+        }
+        Ok(sources)
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// The argument of a call-like value such as `SubResource("TileSet_abcde")`.
+fn call_argument<'a>(value: &'a str, function: &str) -> Option<&'a str> {
+    let inner = value
+        .strip_prefix(function)?
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+    Some(unquote(inner.trim()))
+}
+
+fn array_inner<'a>(value: &'a str, ty: &str) -> Result<&'a str, GodotError> {
+    value
+        .strip_prefix(ty)
+        .and_then(|value| value.strip_prefix('('))
+        .and_then(|value| value.strip_suffix(')'))
+        .map(str::trim)
+        .ok_or_else(|| GodotError::Parse(format!("expected a {ty}, found {value:?}")))
+}
+
+fn array_items<'a>(value: &'a str, ty: &str) -> Result<impl Iterator<Item = &'a str>, GodotError> {
+    Ok(array_inner(value, ty)?
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty()))
+}
+
+fn parse_vector2i(value: &str) -> Result<Vec2<i32>, GodotError> {
+    let invalid = || GodotError::Parse(format!("invalid Vector2i {value:?}"));
+    let inner = array_inner(value, "Vector2i").map_err(|_| invalid())?;
+    let (x, y) = inner.split_once(',').ok_or_else(invalid)?;
+    Ok(Vec2::new(
+        x.trim().parse().map_err(|_| invalid())?,
+        y.trim().parse().map_err(|_| invalid())?,
+    ))
+}
@@ -0,0 +1,22 @@
+// Color of the heatmap value at data, blended between the two nearest stops of the color ramp in the
+// tileset (the top-left texel of each tile), with its tint, noise, and the fragment hook applied.
+fn tilemap_layer_color(pixel: TilemapFragData, footprint: TileFootprint) -> vec4<f32> {
+    let data = tilemap_stagger_data(pixel);
+    let cell = vec2<u32>(data.tilepos);
+    let value = bitcast<f32>(textureLoad(tilemap_indices, tilemap_texel(cell), 0).r);
+    let span = tilemap.heatmap_range.y - tilemap.heatmap_range.x;
+    var t = 0.0;
+    if span != 0.0 {
+        t = clamp((value - tilemap.heatmap_range.x) / span, 0.0, 1.0);
+    }
+    let stops = (tiledata.width / tiledata.tile_width) * (tiledata.height / tiledata.tile_height);
+    let position = t * f32(max(stops, 1u) - 1u);
+    let low = u32(floor(position));
+    let high = min(low + 1u, max(stops, 1u) - 1u);
+    let low_col = textureLoad(tilemap_data, vec2(0u), low, i32(tiledata.mip_level));
+    let high_col = textureLoad(tilemap_data, vec2(0u), high, i32(tiledata.mip_level));
+    var col = mix(low_col, high_col, position - f32(low));
+    col = tilemap_tint_color(col, cell);
+    col = tilemap_noise(col, data);
+    return tilemap_hook(col, data.tilepos);
+}
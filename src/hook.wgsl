@@ -0,0 +1,4 @@
+// The default fragment hook, see TilemapPipeline::with_fragment_hook
+fn tilemap_hook(color: vec4<f32>, tilepos: vec2<f32>) -> vec4<f32> {
+    return color;
+}
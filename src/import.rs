@@ -0,0 +1,129 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+/// Reads the files that importers need besides the map itself (e.g. tileset images, external
+/// tilesets, and object templates), so that maps can be imported from asset archives, embedded
+/// assets, or the network instead of the filesystem. Paths are given exactly as they are written
+/// in the file being imported, which is usually relative to the map.
+///
+/// This is implemented for closures taking a path and returning the contents of the file.
+pub trait ResourceResolver {
+    fn resolve(&mut self, path: &str) -> io::Result<Vec<u8>>;
+}
+
+impl<F: FnMut(&str) -> io::Result<Vec<u8>>> ResourceResolver for F {
+    fn resolve(&mut self, path: &str) -> io::Result<Vec<u8>> {
+        self(path)
+    }
+}
+
+/// A `ResourceResolver` reading files relative to a directory with `std::fs`.
+#[derive(Clone, Debug)]
+pub struct FsResolver {
+    pub root: PathBuf,
+}
+
+impl FsResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsResolver { root: root.into() }
+    }
+}
+
+impl ResourceResolver for FsResolver {
+    fn resolve(&mut self, path: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.root.join(Path::new(path)))
+    }
+}
+
+/// A non-fatal issue encountered by an importer, where some of the data in the file couldn't be
+/// represented and was left out of the result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportWarning {
+    /// Cells of a layer were left empty because their tile can't be stored in a tilemap (its id
+    /// within its tileset is `EMPTY_TILE` or above) or isn't part of any tileset.
+    UnknownTiles {
+        layer: String,
+        /// The id of the first such tile, as stored in the file.
+        tile: u32,
+        /// The number of cells left empty.
+        count: usize,
+    },
+    /// Tiles of a layer were imported without the flips, rotations, or other variations applied
+    /// to them in the file.
+    TransformedTiles { layer: String, count: usize },
+    /// A layer of a type that isn't supported was skipped.
+    UnsupportedLayer { layer: String, kind: String },
+    /// A tileset (or its image) couldn't be found or loaded, so the cells using it were left
+    /// empty.
+    MissingTileset { tileset: String, reason: String },
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportWarning::UnknownTiles { layer, tile, count } => write!(
+                f,
+                "layer {layer:?}: {count} cells with unknown tiles (such as {tile}) were left empty"
+            ),
+            ImportWarning::TransformedTiles { layer, count } => write!(
+                f,
+                "layer {layer:?}: {count} tiles were imported without their flips or rotations"
+            ),
+            ImportWarning::UnsupportedLayer { layer, kind } => {
+                write!(f, "layer {layer:?}: {kind} layers aren't supported")
+            }
+            ImportWarning::MissingTileset { tileset, reason } => {
+                write!(f, "tileset {tileset:?} couldn't be loaded: {reason}")
+            }
+        }
+    }
+}
+
+/// Counts of the tiles in a layer that couldn't be imported exactly, reported as warnings once
+/// the whole layer has been read.
+#[cfg(any(
+    feature = "godot",
+    feature = "ogmo",
+    feature = "pyxel",
+    feature = "tiled"
+))]
+#[derive(Default)]
+pub(crate) struct LayerWarnings {
+    unknown: Option<(u32, usize)>,
+    transformed: usize,
+}
+
+#[cfg(any(
+    feature = "godot",
+    feature = "ogmo",
+    feature = "pyxel",
+    feature = "tiled"
+))]
+impl LayerWarnings {
+    pub(crate) fn unknown_tile(&mut self, tile: u32) {
+        self.unknown.get_or_insert((tile, 0)).1 += 1;
+    }
+
+    #[cfg(any(feature = "godot", feature = "pyxel", feature = "tiled"))]
+    pub(crate) fn transformed_tile(&mut self) {
+        self.transformed += 1;
+    }
+
+    pub(crate) fn finish(self, layer: &str, warnings: &mut Vec<ImportWarning>) {
+        if let Some((tile, count)) = self.unknown {
+            warnings.push(ImportWarning::UnknownTiles {
+                layer: layer.to_owned(),
+                tile,
+                count,
+            });
+        }
+        if self.transformed > 0 {
+            warnings.push(ImportWarning::TransformedTiles {
+                layer: layer.to_owned(),
+                count: self.transformed,
+            });
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{TilemapDrawData, TilemapWorld, TilesetRef};
+use vek::{Mat4, Rect, Vec2, Vec3};
+
+/// The side of a level that a neighboring level is on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NeighborDirection {
+    /// Above the level (towards +y in world coordinates).
+    North,
+    East,
+    South,
+    West,
+}
+
+impl NeighborDirection {
+    /// The side that this level is on, as seen from the neighbor.
+    pub fn opposite(self) -> Self {
+        match self {
+            NeighborDirection::North => NeighborDirection::South,
+            NeighborDirection::East => NeighborDirection::West,
+            NeighborDirection::South => NeighborDirection::North,
+            NeighborDirection::West => NeighborDirection::East,
+        }
+    }
+}
+
+/// A link from a level to an adjacent one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelNeighbor {
+    /// The index of the neighboring level in `WorldLayout::levels`.
+    pub level: usize,
+    pub direction: NeighborDirection,
+}
+
+/// Where a level of a `WorldLayout` is placed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LevelPlacement {
+    pub name: String,
+    /// Position of the bottom left corner of the level, in world pixels (y points up, as for
+    /// `pixel_camera`).
+    pub offset: Vec2<i32>,
+    /// Size of the level, in tiles.
+    pub tile_size: Vec2<u32>,
+    /// Size of each tile of the level, in pixels.
+    pub size_of_tile: Vec2<u32>,
+    /// The levels next to this one, e.g. to stream in before the player walks into them.
+    pub neighbors: Vec<LevelNeighbor>,
+}
+
+impl LevelPlacement {
+    /// Size of the level, in pixels.
+    pub fn pixel_size(&self) -> Vec2<u32> {
+        self.tile_size * self.size_of_tile
+    }
+
+    /// The area covered by the level, in world pixels.
+    pub fn world_rect(&self) -> Rect<i32, i32> {
+        let size = self.pixel_size().as_::<i32>();
+        Rect::new(self.offset.x, self.offset.y, size.x, size.y)
+    }
+
+    /// Whether `position` (in world pixels) is within the level.
+    pub fn contains(&self, position: Vec2<f32>) -> bool {
+        let local = self.to_level(position);
+        let size = self.pixel_size().as_::<f32>();
+        (0.0..size.x).contains(&local.x) && (0.0..size.y).contains(&local.y)
+    }
+
+    /// Convert a position in world pixels to pixels within the level, measured from its top left
+    /// corner with y pointing down (so that `local / size_of_tile` is the position in tiles).
+    pub fn to_level(&self, position: Vec2<f32>) -> Vec2<f32> {
+        let top_left = self.top_left();
+        Vec2::new(position.x - top_left.x, top_left.y - position.y)
+    }
+
+    /// Convert a position in pixels within the level (as for `to_level`) to world pixels.
+    pub fn to_world(&self, local: Vec2<f32>) -> Vec2<f32> {
+        let top_left = self.top_left();
+        Vec2::new(top_left.x + local.x, top_left.y - local.y)
+    }
+
+    /// A matrix that maps from [0, 1]x[0, 1] to the area of the level in world pixels, to pass
+    /// to `TilemapWorld::draw_list`.
+    pub fn transform(&self) -> Mat4<f32> {
+        let size = self.pixel_size().as_::<f32>();
+        Mat4::<f32>::translation_3d(self.offset.as_::<f32>().with_z(0.0))
+            * Mat4::<f32>::scaling_3d(Vec3::new(size.x, size.y, 1.0))
+    }
+
+    fn top_left(&self) -> Vec2<f32> {
+        self.offset.as_::<f32>() + Vec2::new(0.0, self.pixel_size().y as f32)
+    }
+}
+
+/// A position within a level of a `WorldLayout`, as returned by `WorldLayout::locate`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LevelPosition {
+    /// The index of the level in `WorldLayout::levels`.
+    pub level: usize,
+    /// The position in pixels within the level, from its top left corner with y pointing down.
+    pub pixel: Vec2<f32>,
+    /// The tile of the level at this position.
+    pub tile: Vec2<u32>,
+}
+
+/// The tilesets and draws of several levels of a `WorldLayout`, returned by
+/// `WorldLayout::draw_list`.
+#[derive(Clone, Debug, Default)]
+pub struct LayoutDraws<'a> {
+    /// The tilesets to provide to `TilemapPipeline::upload_tilesets`.
+    pub tilesets: Vec<TilesetRef<'a>>,
+    /// The tilemaps to provide to `TilemapPipeline::upload_tilemaps`.
+    pub draws: Vec<TilemapDrawData<'a>>,
+}
+
+/// A world made of several maps placed next to each other (like the levels of an LDtk world), so
+/// that levels can be streamed in and out and moved between without converting coordinates by
+/// hand. Coordinates of the world are in pixels, with y pointing up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WorldLayout {
+    pub levels: Vec<LevelPlacement>,
+}
+
+impl WorldLayout {
+    /// Add a level with its bottom left corner at `offset` in world pixels, returning its index.
+    pub fn add_level(
+        &mut self,
+        name: impl Into<String>,
+        offset: Vec2<i32>,
+        tile_size: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+    ) -> usize {
+        self.levels.push(LevelPlacement {
+            name: name.into(),
+            offset,
+            tile_size,
+            size_of_tile,
+            neighbors: Vec::new(),
+        });
+        self.levels.len() - 1
+    }
+
+    /// Add a level with its top left corner at `top_left`, in pixels with y pointing down (as
+    /// stored by level editors such as LDtk), returning its index.
+    pub fn add_level_y_down(
+        &mut self,
+        name: impl Into<String>,
+        top_left: Vec2<i32>,
+        tile_size: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+    ) -> usize {
+        let height = (tile_size.y * size_of_tile.y) as i32;
+        let offset = Vec2::new(top_left.x, -top_left.y - height);
+        self.add_level(name, offset, tile_size, size_of_tile)
+    }
+
+    /// The index of the level called `name`.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.levels.iter().position(|level| level.name == name)
+    }
+
+    /// Link `level` to `neighbor` on its `direction` side, and `neighbor` back to `level`.
+    pub fn link(&mut self, level: usize, neighbor: usize, direction: NeighborDirection) {
+        let mut add = |from: usize, link: LevelNeighbor| {
+            let neighbors = &mut self.levels[from].neighbors;
+            if !neighbors.contains(&link) {
+                neighbors.push(link);
+            }
+        };
+        add(
+            level,
+            LevelNeighbor {
+                level: neighbor,
+                direction,
+            },
+        );
+        add(
+            neighbor,
+            LevelNeighbor {
+                level,
+                direction: direction.opposite(),
+            },
+        );
+    }
+
+    /// Link every pair of levels whose edges touch along a nonzero length.
+    pub fn link_adjacent(&mut self) {
+        for a in 0..self.levels.len() {
+            for b in a + 1..self.levels.len() {
+                let (ra, rb) = (self.levels[a].world_rect(), self.levels[b].world_rect());
+                let overlap_x = ra.x.max(rb.x) < (ra.x + ra.w).min(rb.x + rb.w);
+                let overlap_y = ra.y.max(rb.y) < (ra.y + ra.h).min(rb.y + rb.h);
+                let direction = if overlap_x && ra.y + ra.h == rb.y {
+                    NeighborDirection::North
+                } else if overlap_x && rb.y + rb.h == ra.y {
+                    NeighborDirection::South
+                } else if overlap_y && ra.x + ra.w == rb.x {
+                    NeighborDirection::East
+                } else if overlap_y && rb.x + rb.w == ra.x {
+                    NeighborDirection::West
+                } else {
+                    continue;
+                };
+                self.link(a, b, direction);
+            }
+        }
+    }
+
+    /// The neighbors of `level`.
+    pub fn neighbors(&self, level: usize) -> &[LevelNeighbor] {
+        &self.levels[level].neighbors
+    }
+
+    /// The first level containing `position` (in world pixels).
+    pub fn level_at(&self, position: Vec2<f32>) -> Option<usize> {
+        self.levels
+            .iter()
+            .position(|level| level.contains(position))
+    }
+
+    /// The level containing `position` (in world pixels) and the position within it.
+    pub fn locate(&self, position: Vec2<f32>) -> Option<LevelPosition> {
+        let level = self.level_at(position)?;
+        Some(self.locate_in(level, position))
+    }
+
+    /// The position within `level` of `position` (in world pixels), even if it is outside of the
+    /// level. The tile is clamped to the level.
+    pub fn locate_in(&self, level: usize, position: Vec2<f32>) -> LevelPosition {
+        let placement = &self.levels[level];
+        let pixel = placement.to_level(position);
+        let tile = (pixel / placement.size_of_tile.map(|x| x.max(1)).as_::<f32>())
+            .map2(placement.tile_size, |x, size| {
+                (x.max(0.0) as u32).min(size.saturating_sub(1))
+            });
+        LevelPosition { level, pixel, tile }
+    }
+
+    /// The level that something in `level` is in after moving to `position` (in world pixels):
+    /// `level` itself while it contains `position`, otherwise one of its neighbors, otherwise any
+    /// level containing `position`. Checking the neighbors first keeps transitions between
+    /// overlapping levels stable.
+    pub fn transition(&self, level: usize, position: Vec2<f32>) -> Option<usize> {
+        if self.levels[level].contains(position) {
+            return Some(level);
+        }
+        self.levels[level]
+            .neighbors
+            .iter()
+            .map(|neighbor| neighbor.level)
+            .find(|&neighbor| self.levels[neighbor].contains(position))
+            .or_else(|| self.level_at(position))
+    }
+
+    /// The levels overlapping `rect` (in world pixels, e.g. the view of the camera grown by a
+    /// margin), to keep loaded.
+    pub fn levels_in(&self, rect: Rect<f32, f32>) -> impl Iterator<Item = usize> + '_ {
+        self.levels
+            .iter()
+            .enumerate()
+            .filter_map(move |(i, level)| {
+                let area = level.world_rect().as_::<f32, f32>();
+                (area.x < rect.x + rect.w
+                    && rect.x < area.x + area.w
+                    && area.y < rect.y + rect.h
+                    && rect.y < area.y + area.h)
+                    .then_some(i)
+            })
+    }
+
+    /// The tilesets and draws of `levels`, pairs of the index of a level and its map, with each
+    /// map placed at its level. The tileset indices of each map's draws are offset past the
+    /// tilesets of the maps before it, so they can all be uploaded to one pipeline.
+    pub fn draw_list<'a>(
+        &self,
+        levels: impl IntoIterator<Item = (usize, &'a TilemapWorld)>,
+        transform: Mat4<f32>,
+    ) -> LayoutDraws<'a> {
+        let mut result = LayoutDraws::default();
+        for (level, map) in levels {
+            let first_tileset = result.tilesets.len() as u32;
+            result.tilesets.extend(map.upload_tilesets());
+            result.draws.extend(
+                map.draw_list(transform * self.levels[level].transform())
+                    .into_iter()
+                    .map(|draw| TilemapDrawData {
+                        tileset: draw.tileset + first_tileset,
+                        ..draw
+                    }),
+            );
+        }
+        result
+    }
+}
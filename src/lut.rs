@@ -0,0 +1,165 @@
+use std::fmt;
+use vek::{Rgb, Vec2};
+
+/// A 3D lookup table that recolors everything drawn by a `TilemapPipeline` (color grading), set
+/// with `TilemapPipeline::set_color_lut`. Like the LUTs made by image editors, it maps sRGB colors
+/// to sRGB colors, and colors between its entries are blended from the nearest ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ColorLut {
+    /// The number of entries along each axis.
+    pub size: u32,
+    /// The sRGB color (with an unused alpha byte) of each entry, with red increasing fastest, then
+    /// green, then blue.
+    pub data: Vec<u32>,
+}
+
+/// An error encountered while loading a `ColorLut`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ColorLutError {
+    /// A line of a .cube file couldn't be parsed, or the file is missing entries.
+    InvalidCube { line: usize, reason: String },
+    /// The image isn't laid out as a LUT: its area must be the cube of the size of the LUT, split
+    /// into square slices of constant blue.
+    InvalidImage { dimensions: Vec2<u32> },
+}
+
+impl fmt::Display for ColorLutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorLutError::InvalidCube { line, reason } => {
+                write!(f, "invalid .cube file at line {line}: {reason}")
+            }
+            ColorLutError::InvalidImage { dimensions } => write!(
+                f,
+                "a {}x{} image isn't a LUT of square slices",
+                dimensions.x, dimensions.y
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ColorLutError {}
+
+impl ColorLut {
+    /// A LUT of `size` entries along each axis (at least 2) computed by `f`, which maps an sRGB
+    /// color to its graded color with channels from 0.0 to 1.0.
+    pub fn from_fn(size: u32, f: impl Fn(Rgb<f32>) -> Rgb<f32>) -> Self {
+        let size = size.max(2);
+        let scale = 1.0 / (size - 1) as f32;
+        let mut data = Vec::with_capacity(size.pow(3) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    let color = Rgb::new(r, g, b).as_::<f32>() * scale;
+                    data.push(encode(f(color)));
+                }
+            }
+        }
+        ColorLut { size, data }
+    }
+
+    /// A LUT that leaves colors unchanged, e.g. to edit into a grade in an image editor.
+    pub fn identity(size: u32) -> Self {
+        Self::from_fn(size, |color| color)
+    }
+
+    /// Parse the contents of an Adobe/Resolve .cube file. Only 3D LUTs with the default domain of
+    /// 0.0 to 1.0 are supported.
+    pub fn parse_cube(text: &str) -> Result<Self, ColorLutError> {
+        let mut size = None;
+        let mut data = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let invalid = |reason: &str| ColorLutError::InvalidCube {
+                line: i + 1,
+                reason: reason.to_owned(),
+            };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                None | Some("TITLE") => {}
+                Some("LUT_3D_SIZE") => {
+                    let value = words.next().and_then(|x| x.parse::<u32>().ok());
+                    size = Some(
+                        value
+                            .filter(|x| *x >= 2)
+                            .ok_or_else(|| invalid("bad size"))?,
+                    );
+                }
+                Some("LUT_1D_SIZE") => return Err(invalid("1D LUTs aren't supported")),
+                Some(keyword @ ("DOMAIN_MIN" | "DOMAIN_MAX")) => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    if !words.all(|x| x.parse::<f32>() == Ok(expected)) {
+                        return Err(invalid("only the domain 0.0 to 1.0 is supported"));
+                    }
+                }
+                Some(first) => {
+                    let channels: Vec<f32> = Some(first)
+                        .into_iter()
+                        .chain(words)
+                        .map(|word| word.parse().map_err(|_| invalid("expected 3 numbers")))
+                        .collect::<Result<_, _>>()?;
+                    let [r, g, b] = channels[..] else {
+                        return Err(invalid("expected 3 numbers"));
+                    };
+                    data.push(encode(Rgb::new(r, g, b)));
+                }
+            }
+        }
+        let size = size.ok_or(ColorLutError::InvalidCube {
+            line: 0,
+            reason: "missing LUT_3D_SIZE".to_owned(),
+        })?;
+        if data.len() != size.pow(3) as usize {
+            return Err(ColorLutError::InvalidCube {
+                line: 0,
+                reason: format!("expected {} entries, found {}", size.pow(3), data.len()),
+            });
+        }
+        Ok(ColorLut { size, data })
+    }
+
+    /// Read a LUT from an image stored as rows of RGBA bytes, laid out as square slices of constant
+    /// blue from left to right and then top to bottom (e.g. a 256x16 strip or a 512x512 grid of
+    /// 64x64 slices), with red increasing to the right and green increasing downwards.
+    pub fn from_rgba8(pixels: &[u8], dimensions: Vec2<u32>) -> Result<Self, ColorLutError> {
+        let invalid = ColorLutError::InvalidImage { dimensions };
+        let area = dimensions.x as u64 * dimensions.y as u64;
+        let size = (area as f64).cbrt().round() as u32;
+        if size < 2
+            || (size as u64).pow(3) != area
+            || dimensions.x % size != 0
+            || dimensions.y % size != 0
+            || pixels.len() < area as usize * 4
+        {
+            return Err(invalid);
+        }
+        let columns = dimensions.x / size;
+        let mut data = Vec::with_capacity(area as usize);
+        for b in 0..size {
+            let origin = Vec2::new(b % columns, b / columns) * size;
+            for g in 0..size {
+                let start = ((origin.y + g) * dimensions.x + origin.x) as usize * 4;
+                data.extend(
+                    pixels[start..start + size as usize * 4]
+                        .chunks_exact(4)
+                        .map(|pixel| u32::from_le_bytes([pixel[0], pixel[1], pixel[2], 0xff])),
+                );
+            }
+        }
+        Ok(ColorLut { size, data })
+    }
+
+    /// Read a LUT from an image. See `from_rgba8` for the layout.
+    #[cfg(feature = "image")]
+    pub fn from_image(image: &image::RgbaImage) -> Result<Self, ColorLutError> {
+        Self::from_rgba8(image.as_raw(), Vec2::from(image.dimensions()))
+    }
+}
+
+/// Store an sRGB color with channels from 0.0 to 1.0 as bytes.
+fn encode(color: Rgb<f32>) -> u32 {
+    let [r, g, b] = color
+        .map(|x| (x.clamp(0.0, 1.0) * 255.0).round() as u8)
+        .into_array();
+    u32::from_le_bytes([r, g, b, 0xff])
+}
@@ -0,0 +1,66 @@
+use crate::{TileId, TilemapRef};
+use alloc::vec::Vec;
+
+/// What a tile is made of, for the shader effects that depend on it (see
+/// `TilemapPipeline::set_tileset_materials`) and for game logic such as footstep sounds. The
+/// default material has every parameter at zero.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TileMaterial {
+    /// How strongly the tile glows, where `0.0` doesn't glow at all. This scales the light of the
+    /// emissive texture of the tileset (see `TilemapPipeline::set_tileset_emissive`).
+    pub emissive: f32,
+    /// How far the top of the tile sways back and forth (e.g. grass or leaves in the wind), in
+    /// texels. The bottom of the tile stays put, and the sway follows `FrameContext::time`.
+    pub sway: f32,
+    /// How much of its surroundings the tile reflects (e.g. water or ice), from `0.0` to `1.0`. The
+    /// built-in effects have nothing to reflect, so this is only read by fragment hooks (with
+    /// `tilemap_material`), e.g. to blend in a texture of the scene mirrored above the water.
+    pub reflectivity: f32,
+    /// How high the tile stands above the ground, in tiles (e.g. `1.0` for walls), which draws
+    /// with `TilemapDrawData::walls` offset its top by.
+    pub height: f32,
+    /// An id of the sound of footsteps on the tile, chosen by the game. This isn't uploaded.
+    pub footstep_sound: u32,
+}
+
+/// The material of each tile of a tileset, indexed by tile like the tileset. Tiles past the end of
+/// the table have the default material.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaterialTable {
+    pub materials: Vec<TileMaterial>,
+}
+
+impl MaterialTable {
+    /// A table with every tile of `tiles` set to the material returned by `material`.
+    pub fn from_fn(tiles: u32, material: impl FnMut(u32) -> TileMaterial) -> Self {
+        MaterialTable {
+            materials: (0..tiles).map(material).collect(),
+        }
+    }
+
+    /// The material of `tile`.
+    pub fn material(&self, tile: u32) -> TileMaterial {
+        self.materials
+            .get(tile as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Set the material of `tile`, growing the table if needed.
+    pub fn set_material(&mut self, tile: u32, material: TileMaterial) {
+        let tile = tile as usize;
+        if tile >= self.materials.len() {
+            self.materials.resize(tile + 1, TileMaterial::default());
+        }
+        self.materials[tile] = material;
+    }
+
+    /// The material of the tile at a position of `tilemap`, e.g. under a character's feet. The
+    /// tile is looked up as stored, so tilemaps with `TileFlip` flags should use `material` with the
+    /// decoded tile instead.
+    pub fn material_at<T: TileId>(&self, tilemap: &TilemapRef<T>, x: u32, y: u32) -> TileMaterial {
+        self.material(tilemap.get_tile(x, y).index())
+    }
+}
@@ -0,0 +1,155 @@
+use crate::{TileId, Tilemap, TilemapRef};
+use vek::Vec2;
+
+/// How neighborhood queries treat positions past the edges of a tilemap.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EdgeMode {
+    /// Positions past the edges are left out.
+    #[default]
+    Skip,
+    /// Positions past the edges read the nearest tile on the edge.
+    Clamp,
+    /// Positions past each edge wrap around to the opposite edge.
+    Wrap,
+}
+
+impl EdgeMode {
+    /// The position within a tilemap of `tile_size` that `position` reads from, if any.
+    pub fn resolve(self, position: Vec2<i32>, tile_size: Vec2<u32>) -> Option<Vec2<u32>> {
+        let size = tile_size.as_::<i32>();
+        if size.x <= 0 || size.y <= 0 {
+            return None;
+        }
+        let resolved = match self {
+            EdgeMode::Skip => position,
+            EdgeMode::Clamp => Vec2::new(
+                position.x.clamp(0, size.x - 1),
+                position.y.clamp(0, size.y - 1),
+            ),
+            EdgeMode::Wrap => position.map2(size, i32::rem_euclid),
+        };
+        let inside =
+            resolved.x >= 0 && resolved.y >= 0 && resolved.x < size.x && resolved.y < size.y;
+        inside.then(|| resolved.as_::<u32>())
+    }
+}
+
+/// An iterator over the tiles around a position, as returned by `neighbors4`, `neighbors8`, and
+/// `window`. Each item is the offset of a tile from the center and the tile itself, in reading
+/// order (left to right, then top to bottom).
+#[derive(Clone, Debug)]
+pub struct Neighborhood<'a, T = u8> {
+    data: &'a [T],
+    tile_size: Vec2<u32>,
+    center: Vec2<i32>,
+    radius: i32,
+    edge: EdgeMode,
+    include: fn(Vec2<i32>) -> bool,
+    offset: Vec2<i32>,
+}
+
+impl<'a, T> Neighborhood<'a, T> {
+    fn new(
+        data: &'a [T],
+        tile_size: Vec2<u32>,
+        center: Vec2<u32>,
+        radius: u32,
+        edge: EdgeMode,
+        include: fn(Vec2<i32>) -> bool,
+    ) -> Self {
+        let radius = radius as i32;
+        Neighborhood {
+            data,
+            tile_size,
+            center: center.as_::<i32>(),
+            radius,
+            edge,
+            include,
+            offset: Vec2::broadcast(-radius),
+        }
+    }
+}
+
+impl<'a, T: Copy> Iterator for Neighborhood<'a, T> {
+    type Item = (Vec2<i32>, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset.y <= self.radius {
+            let offset = self.offset;
+            self.offset.x += 1;
+            if self.offset.x > self.radius {
+                self.offset = Vec2::new(-self.radius, self.offset.y + 1);
+            }
+            if !(self.include)(offset) {
+                continue;
+            }
+            if let Some(position) = self.edge.resolve(self.center + offset, self.tile_size) {
+                let index = self.tile_size.x as usize * position.y as usize + position.x as usize;
+                return Some((offset, self.data[index]));
+            }
+        }
+        None
+    }
+}
+
+fn orthogonal(offset: Vec2<i32>) -> bool {
+    offset.x.abs() + offset.y.abs() == 1
+}
+
+fn surrounding(offset: Vec2<i32>) -> bool {
+    offset != Vec2::zero()
+}
+
+fn everything(_: Vec2<i32>) -> bool {
+    true
+}
+
+macro_rules! neighborhood_methods {
+    () => {
+        /// The 4 tiles sharing an edge with the tile at the specified position.
+        pub fn neighbors4(&self, x: u32, y: u32, edge: EdgeMode) -> Neighborhood<'_, T> {
+            Neighborhood::new(
+                &self.data,
+                self.tile_size,
+                Vec2::new(x, y),
+                1,
+                edge,
+                orthogonal,
+            )
+        }
+
+        /// The 8 tiles sharing an edge or corner with the tile at the specified position.
+        pub fn neighbors8(&self, x: u32, y: u32, edge: EdgeMode) -> Neighborhood<'_, T> {
+            Neighborhood::new(
+                &self.data,
+                self.tile_size,
+                Vec2::new(x, y),
+                1,
+                edge,
+                surrounding,
+            )
+        }
+
+        /// The tiles in the square of `2 * radius + 1` tiles centered on the specified position,
+        /// including the tile at the center.
+        pub fn window(&self, x: u32, y: u32, radius: u32, edge: EdgeMode) -> Neighborhood<'_, T> {
+            Neighborhood::new(
+                &self.data,
+                self.tile_size,
+                Vec2::new(x, y),
+                radius,
+                edge,
+                everything,
+            )
+        }
+    };
+}
+
+impl<'a, T: TileId> TilemapRef<'a, T> {
+    neighborhood_methods!();
+}
+
+impl<T: TileId> Tilemap<T> {
+    neighborhood_methods!();
+}
@@ -0,0 +1,4 @@
+// Used instead of noise.wgsl for tilemaps without noise
+fn tilemap_noise(col: vec4<f32>, data: TilemapFragData) -> vec4<f32> {
+    return col;
+}
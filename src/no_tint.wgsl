@@ -0,0 +1,4 @@
+// Used instead of tint.wgsl for tilemaps without a tint
+fn tilemap_tint_color(col: vec4<f32>, cell: vec2<u32>) -> vec4<f32> {
+    return col;
+}
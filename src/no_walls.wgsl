@@ -0,0 +1,4 @@
+// Used instead of walls.wgsl for tilemaps without walls
+fn tilemap_walls(data: TilemapFragData) -> TilemapFragData {
+    return data;
+}
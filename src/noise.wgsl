@@ -0,0 +1,42 @@
+// Add the TilemapNoise of this tilemap to col
+fn tilemap_noise(col: vec4<f32>, data: TilemapFragData) -> vec4<f32> {
+    let size_of_tile = vec2(tiledata.tile_width, tiledata.tile_height);
+    let noise_magnitude = f32(tilemap.noise_data & 0xffffu) / 65536.0;
+    let noise_res = f32((tilemap.noise_data >> 16u) & 0xffu);
+    var cell: vec2<f32>;
+    switch (tilemap.noise_data >> 24u) & 0x3u {
+        // NoiseSpace::World
+        case 1u: {
+            cell = floor(noise_res * data.worldpos);
+        }
+        // NoiseSpace::Screen
+        case 2u: {
+            cell = floor(data.position.xy / max(noise_res, 1.0));
+        }
+        // NoiseSpace::Tile
+        default: {
+            cell = vec2<f32>(size_of_tile * vec2<u32>(vec2<f32>(noise_res, noise_res) * data.tilepos));
+        }
+    }
+    var noise: vec3<f32> = pcg3d(cell);
+    let noisy = col + noise_magnitude * vec4(noise.x, noise.x, noise.x, 0.0);
+    return clamp(vec4(0.0, 0.0, 0.0, 0.0), vec4(1.0, 1.0, 1.0, 1.0), noisy);
+}
+
+fn pcg3d(uv: vec2<f32>) -> vec3<f32> {
+    var a = bitcast<vec2<u32>>(uv);
+    var b = vec3(a.xy, a.x ^ a.y);
+    var v: vec3<u32> = b * 1664525u + 1013904223u;
+
+    v.x += v.y*v.z;
+    v.y += v.z*v.x;
+    v.z += v.x*v.y;
+
+    v ^= vec3(v.x >> 16u, v.y >> 16u, v.z >> 16u);
+
+    v.x += v.y*v.z;
+    v.y += v.z*v.x;
+    v.z += v.x*v.y;
+
+    return vec3<f32>(vec3<u32>(v.x & 0xffu, v.y & 0xffu, v.z & 0xffu)) / 255.0;
+}
@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use vek::{Rgba, Vec2};
+
+/// Custom properties attached to objects and layers by map editors, by name.
+pub type Properties = BTreeMap<String, PropertyValue>;
+
+/// The value of a custom property.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Color(Rgba<u8>),
+    /// A path to a file, relative to the map.
+    File(String),
+    /// The id of another object in the map.
+    Object(u32),
+    /// A value of a user-defined class, with its own members.
+    Class {
+        class: String,
+        members: Properties,
+    },
+}
+
+/// The shape of a `MapObject`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ObjectShape {
+    #[default]
+    Rectangle,
+    Ellipse,
+    Point,
+    /// A closed polygon, with points relative to the object's position.
+    Polygon(Vec<Vec2<f32>>),
+    /// An open sequence of line segments, with points relative to the object's position.
+    Polyline(Vec<Vec2<f32>>),
+    /// A text box, with its contents.
+    Text(String),
+}
+
+/// An object placed in a map (e.g. a spawn point or trigger area), with the values from its
+/// template and class already applied.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapObject {
+    /// The id of this object, unique within the map.
+    pub id: u32,
+    pub name: String,
+    /// The user-defined class (or type) of this object.
+    pub class: String,
+    /// Position of this object, in pixels from the top left of the map.
+    pub position: Vec2<f32>,
+    /// Size of this object, in pixels.
+    pub size: Vec2<f32>,
+    /// Rotation of this object clockwise around its position, in degrees.
+    pub rotation: f32,
+    /// The tile drawn for this object, as a global tile id of the map editor.
+    pub gid: Option<u32>,
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+/// A layer of objects in a map.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    /// The user-defined class of this layer.
+    pub class: String,
+    pub visible: bool,
+    pub objects: Vec<MapObject>,
+    pub properties: Properties,
+}
+
+/// The objects of a map, by layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapObjects {
+    /// The object layers of this map, from bottom to top.
+    pub layers: Vec<ObjectLayer>,
+}
+
+impl MapObjects {
+    /// Iterate over the objects of every layer.
+    pub fn iter(&self) -> impl Iterator<Item = &MapObject> {
+        self.layers.iter().flat_map(|layer| layer.objects.iter())
+    }
+
+    /// Find an object by its id.
+    pub fn get(&self, id: u32) -> Option<&MapObject> {
+        self.iter().find(|object| object.id == id)
+    }
+
+    /// Fill in the properties that objects and layers inherit from their class, where `classes`
+    /// holds the default members of each class by name. Properties that are already set are kept,
+    /// and members of class-valued properties are filled in recursively.
+    pub fn inherit_class_properties(&mut self, classes: &BTreeMap<String, Properties>) {
+        for layer in self.layers.iter_mut() {
+            inherit_properties(&mut layer.properties, &layer.class, classes);
+            for object in layer.objects.iter_mut() {
+                inherit_properties(&mut object.properties, &object.class, classes);
+            }
+        }
+    }
+}
+
+fn inherit_properties(
+    properties: &mut Properties,
+    class: &str,
+    classes: &BTreeMap<String, Properties>,
+) {
+    if let Some(defaults) = classes.get(class) {
+        for (name, value) in defaults.iter() {
+            properties
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+    for value in properties.values_mut() {
+        if let PropertyValue::Class { class, members } = value {
+            inherit_properties(members, class, classes);
+        }
+    }
+}
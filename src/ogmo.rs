@@ -0,0 +1,424 @@
+//! Importing levels made with [Ogmo Editor 3](https://ogmo-editor-3.github.io/).
+use crate::{
+    import::LayerWarnings, world::with_empty_tile, AlphaMode, FsResolver, ImportWarning, MapObject,
+    ObjectLayer, ObjectShape, Properties, PropertyValue, ResourceResolver, Tilemap,
+    TilemapBlendMode, TilemapLayer, TilemapWorld, TilesetLayout, TilesetRef, WorldLayer,
+    EMPTY_TILE,
+};
+use serde_json::Value;
+use std::{fmt, path::Path};
+use vek::{Mat4, Vec2, Vec3};
+
+/// An error encountered while importing an Ogmo project or level.
+#[derive(Debug)]
+pub enum OgmoError {
+    /// A file could not be read.
+    Io(String, std::io::Error),
+    /// The file is not valid JSON.
+    Json(serde_json::Error),
+    /// A value is missing or out of range.
+    InvalidData(String),
+}
+
+impl fmt::Display for OgmoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OgmoError::Io(path, e) => write!(f, "couldn't read {path:?}: {e}"),
+            OgmoError::Json(e) => write!(f, "invalid JSON: {e}"),
+            OgmoError::InvalidData(what) => write!(f, "invalid data: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for OgmoError {}
+
+impl From<serde_json::Error> for OgmoError {
+    fn from(e: serde_json::Error) -> Self {
+        OgmoError::Json(e)
+    }
+}
+
+/// A tileset defined by an Ogmo project.
+#[derive(Clone, Debug)]
+pub struct OgmoTileset {
+    /// The name that levels refer to this tileset by.
+    pub label: String,
+    /// The path of the image of this tileset, relative to the project.
+    pub path: String,
+    /// The image of this tileset, with `EMPTY_TILE` made transparent. This is transparent if the
+    /// image couldn't be loaded.
+    pub tileset: TilesetRef<'static>,
+    /// The number of tiles in each row of the image, which tile coordinates are converted with.
+    pub columns: u32,
+}
+
+/// The tilesets of an Ogmo project (.ogmo file), which levels are imported with.
+#[derive(Clone, Debug, Default)]
+pub struct OgmoProject {
+    pub tilesets: Vec<OgmoTileset>,
+    /// Tilesets whose images couldn't be loaded.
+    pub warnings: Vec<ImportWarning>,
+}
+
+impl OgmoProject {
+    /// The tileset called `label`.
+    pub fn tileset(&self, label: &str) -> Option<&OgmoTileset> {
+        self.tilesets.iter().find(|tileset| tileset.label == label)
+    }
+}
+
+/// An Ogmo level, converted into a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub struct OgmoLevel {
+    /// The tile layers of the level from bottom to top (Ogmo lists them from top to bottom), and
+    /// its entity layers as object layers.
+    pub world: TilemapWorld,
+    /// The label of each tileset of `world.tilesets`, which holds the tilesets of the project
+    /// used by the level, in the order they are first used from the bottom layer up.
+    pub tilesets: Vec<String>,
+    /// The custom values of the level.
+    pub values: Properties,
+    /// Parts of the level that couldn't be imported.
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Import the tilesets of an Ogmo project from a .ogmo file, reading their images relative to it.
+pub fn load_ogmo_project(path: impl AsRef<Path>) -> Result<OgmoProject, OgmoError> {
+    let path = path.as_ref();
+    let json =
+        std::fs::read_to_string(path).map_err(|e| OgmoError::Io(path.display().to_string(), e))?;
+    parse_ogmo_project(
+        &json,
+        FsResolver::new(path.parent().unwrap_or(Path::new(""))),
+    )
+}
+
+/// Import the tilesets of an Ogmo project from the contents of a .ogmo file. Images embedded in
+/// the project are used if they are present, and are otherwise read with `resolver` by their path
+/// relative to the project.
+pub fn parse_ogmo_project(
+    json: &str,
+    mut resolver: impl ResourceResolver,
+) -> Result<OgmoProject, OgmoError> {
+    let project: Value = serde_json::from_str(json)?;
+    let mut result = OgmoProject::default();
+    for tileset in project["tilesets"].as_array().into_iter().flatten() {
+        let label = json_str(tileset, "label")?;
+        let path = tileset["path"].as_str().unwrap_or_default().to_owned();
+        let size_of_tile = Vec2::new(
+            json_u32(tileset, "tileWidth")?,
+            json_u32(tileset, "tileHeight")?,
+        )
+        .map(|x| x.max(1));
+        let separation = Vec2::new(
+            json_u32_or(tileset, "tileSeparationX")?,
+            json_u32_or(tileset, "tileSeparationY")?,
+        );
+        let margin = Vec2::new(
+            json_u32_or(tileset, "tileMarginX")?,
+            json_u32_or(tileset, "tileMarginY")?,
+        );
+        let image = match tileset["image"].as_str() {
+            Some(data) if data.starts_with("data:") => {
+                use base64::Engine;
+                let base64 = data.split_once(',').map_or("", |(_, base64)| base64);
+                base64::engine::general_purpose::STANDARD
+                    .decode(base64)
+                    .map_err(|e| e.to_string())
+            }
+            _ => resolver.resolve(&path).map_err(|e| e.to_string()),
+        }
+        .and_then(|bytes| image::load_from_memory(&bytes).map_err(|e| e.to_string()));
+        let (tileset, columns) = match image {
+            Ok(image) => {
+                let image = image.into_rgba8();
+                let dimensions = Vec2::from(image.dimensions());
+                let layout = TilesetLayout {
+                    size_of_tile,
+                    spacing: separation,
+                    margin,
+                };
+                let columns = layout.tile_count(dimensions).x;
+                let tileset =
+                    TilesetRef::from_rgba8_with_layout(image.as_raw(), dimensions, layout);
+                (tileset, columns)
+            }
+            Err(reason) => {
+                result.warnings.push(ImportWarning::MissingTileset {
+                    tileset: label.to_owned(),
+                    reason,
+                });
+                let tileset = TilesetRef {
+                    pixel_size: size_of_tile,
+                    size_of_tile,
+                    data: vec![0; size_of_tile.product() as usize].into(),
+                    alpha_mode: AlphaMode::Premultiplied,
+                    uv_inset: 0.0,
+                    checkerboard: (&[][..]).into(),
+                    compressed: None,
+                };
+                (tileset, 1)
+            }
+        };
+        result.tilesets.push(OgmoTileset {
+            label: label.to_owned(),
+            path,
+            tileset: with_empty_tile(&tileset),
+            columns: columns.max(1),
+        });
+    }
+    Ok(result)
+}
+
+/// Import a level from an Ogmo .json level file, with the tilesets of `project`.
+pub fn load_ogmo_level(
+    path: impl AsRef<Path>,
+    project: &OgmoProject,
+) -> Result<OgmoLevel, OgmoError> {
+    let path = path.as_ref();
+    let json =
+        std::fs::read_to_string(path).map_err(|e| OgmoError::Io(path.display().to_string(), e))?;
+    parse_ogmo_level(&json, project)
+}
+
+/// Import a level from the contents of an Ogmo .json level file, with the tilesets of `project`.
+/// Tile layers become `WorldLayer::Tiles` (with empty cells set to `EMPTY_TILE`), entity layers
+/// become object layers, and grid and decal layers are skipped. Tiles at or past `EMPTY_TILE` in
+/// their tileset are left empty.
+pub fn parse_ogmo_level(json: &str, project: &OgmoProject) -> Result<OgmoLevel, OgmoError> {
+    let level: Value = serde_json::from_str(json)?;
+    let pixel_size = Vec2::new(json_u32(&level, "width")?, json_u32(&level, "height")?);
+    let mut level_tilesets: Vec<&OgmoTileset> = Vec::new();
+    let mut warnings = Vec::new();
+    let mut world = TilemapWorld::default();
+    let empty = Vec::new();
+    // Ogmo lists layers from top to bottom.
+    for layer in level["layers"].as_array().unwrap_or(&empty).iter().rev() {
+        let name = json_str(layer, "name")?;
+        let offset = Vec2::new(
+            layer["offsetX"].as_f64().unwrap_or(0.0),
+            layer["offsetY"].as_f64().unwrap_or(0.0),
+        )
+        .as_::<f32>();
+        if let Some(entities) = layer["entities"].as_array() {
+            let objects = entities
+                .iter()
+                .map(|entity| entity_object(entity, offset))
+                .collect::<Result<_, _>>()?;
+            world.objects.layers.push(ObjectLayer {
+                name: name.to_owned(),
+                visible: true,
+                objects,
+                ..ObjectLayer::default()
+            });
+            continue;
+        }
+        let Some(label) = layer["tileset"].as_str() else {
+            let kind = if layer.get("decals").is_some() {
+                "decal"
+            } else {
+                "grid"
+            };
+            warnings.push(ImportWarning::UnsupportedLayer {
+                layer: name.to_owned(),
+                kind: kind.to_owned(),
+            });
+            continue;
+        };
+        let Some(tileset) = project.tileset(label) else {
+            warnings.push(ImportWarning::MissingTileset {
+                tileset: label.to_owned(),
+                reason: "the project has no such tileset".to_owned(),
+            });
+            continue;
+        };
+        let tileset_index = match level_tilesets
+            .iter()
+            .position(|used| std::ptr::eq(*used, tileset))
+        {
+            Some(i) => i,
+            None => {
+                level_tilesets.push(tileset);
+                level_tilesets.len() - 1
+            }
+        };
+        let size_of_cell = Vec2::new(
+            json_u32(layer, "gridCellWidth")?,
+            json_u32(layer, "gridCellHeight")?,
+        )
+        .map(|x| x.max(1));
+        let cells = Vec2::new(
+            json_u32(layer, "gridCellsX")?,
+            json_u32(layer, "gridCellsY")?,
+        );
+        if world.size_of_tile == Vec2::zero() {
+            world.size_of_tile = size_of_cell;
+            world.tile_size = pixel_size / size_of_cell;
+        }
+
+        let tiles = layer_tiles(layer, tileset.columns)?;
+        let mut tilemap = Tilemap::new_filled(cells, EMPTY_TILE);
+        let mut layer_warnings = LayerWarnings::default();
+        for (i, tile) in tiles.into_iter().enumerate() {
+            let position = Vec2::new(i as u32 % cells.x.max(1), i as u32 / cells.x.max(1));
+            match tile {
+                _ if position.y >= cells.y => break,
+                None => {}
+                Some(tile) if tile < EMPTY_TILE as u32 => {
+                    tilemap.put_tile(position.x, position.y, tile as u8)
+                }
+                Some(tile) => layer_warnings.unknown_tile(tile),
+            }
+        }
+        layer_warnings.finish(name, &mut warnings);
+
+        // Place the layer within the level, which is [0, 1]x[0, 1] with y pointing up.
+        let level_size = pixel_size.map(|x| x.max(1)).as_::<f32>();
+        let layer_size = (cells * size_of_cell).as_::<f32>() / level_size;
+        let position = Vec2::new(
+            offset.x / level_size.x,
+            1.0 - offset.y / level_size.y - layer_size.y,
+        );
+        world.layers.push(WorldLayer::Tiles(TilemapLayer {
+            name: name.to_owned(),
+            tilemap,
+            tileset: tileset_index as u32,
+            transform: Mat4::<f32>::translation_3d(position.with_z(0.0))
+                * Mat4::<f32>::scaling_3d(Vec3::new(layer_size.x, layer_size.y, 1.0)),
+            visible: true,
+            blend_mode: TilemapBlendMode::Alpha,
+            opacity: 1.0,
+            parallax: Vec2::one(),
+        }));
+    }
+    if world.size_of_tile == Vec2::zero() {
+        // Without tile layers, treat each pixel as a tile.
+        world.size_of_tile = Vec2::one();
+        world.tile_size = pixel_size;
+    }
+    world.tilesets = level_tilesets
+        .iter()
+        .map(|tileset| tileset.tileset.clone())
+        .collect();
+    Ok(OgmoLevel {
+        world,
+        tilesets: level_tilesets
+            .iter()
+            .map(|tileset| tileset.label.clone())
+            .collect(),
+        values: json_properties(&level["values"]),
+        warnings,
+    })
+}
+
+/// The tile of each cell of a tile layer, as an index into its tileset, or `None` if it is empty.
+/// Tiles are stored as indices or as coordinates in the tileset, in a flat or 2D array.
+fn layer_tiles(layer: &Value, columns: u32) -> Result<Vec<Option<u32>>, OgmoError> {
+    let invalid = || {
+        OgmoError::InvalidData(format!(
+            "tile layer {:?} has no valid tile data",
+            layer["name"].as_str().unwrap_or_default()
+        ))
+    };
+    let flatten = |key: &str| -> Option<Vec<&Value>> {
+        if let Some(data) = layer[key].as_array() {
+            return Some(data.iter().collect());
+        }
+        let rows = layer[format!("{key}2D")].as_array()?;
+        Some(
+            rows.iter()
+                .flat_map(|row| row.as_array().into_iter().flatten())
+                .collect(),
+        )
+    };
+    if let Some(data) = flatten("data") {
+        data.into_iter()
+            .map(|tile| {
+                let tile = tile.as_i64().ok_or_else(invalid)?;
+                Ok(u32::try_from(tile).ok())
+            })
+            .collect()
+    } else if let Some(data) = flatten("dataCoords") {
+        data.into_iter()
+            .map(|coords| {
+                let coords = coords.as_array().ok_or_else(invalid)?;
+                match coords[..] {
+                    [ref x, ref y] => {
+                        let x = x.as_u64().ok_or_else(invalid)? as u32;
+                        let y = y.as_u64().ok_or_else(invalid)? as u32;
+                        Ok(Some(y * columns + x))
+                    }
+                    _ => Ok(None),
+                }
+            })
+            .collect()
+    } else {
+        Err(invalid())
+    }
+}
+
+/// An entity as a map object, where `offset` is the offset of its layer.
+fn entity_object(entity: &Value, offset: Vec2<f32>) -> Result<MapObject, OgmoError> {
+    let number = |key: &str| entity[key].as_f64().unwrap_or(0.0) as f32;
+    let origin = Vec2::new(number("originX"), number("originY"));
+    let size = Vec2::new(number("width"), number("height"));
+    let name = json_str(entity, "name")?;
+    Ok(MapObject {
+        id: json_u32(entity, "id")?,
+        name: name.to_owned(),
+        class: name.to_owned(),
+        position: offset + Vec2::new(number("x"), number("y")) - origin,
+        size,
+        rotation: number("rotation"),
+        gid: None,
+        visible: true,
+        shape: if size == Vec2::zero() {
+            ObjectShape::Point
+        } else {
+            ObjectShape::Rectangle
+        },
+        properties: json_properties(&entity["values"]),
+    })
+}
+
+/// Custom values, which Ogmo stores as a JSON object.
+fn json_properties(values: &Value) -> Properties {
+    values
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                Value::Bool(value) => PropertyValue::Bool(*value),
+                Value::Number(value) => match value.as_i64() {
+                    Some(value) => PropertyValue::Int(value),
+                    None => PropertyValue::Float(value.as_f64()?),
+                },
+                Value::String(value) => PropertyValue::String(value.clone()),
+                _ => return None,
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+fn json_str<'a>(object: &'a Value, key: &str) -> Result<&'a str, OgmoError> {
+    object[key]
+        .as_str()
+        .ok_or_else(|| OgmoError::InvalidData(format!("missing or invalid {key:?}")))
+}
+
+fn json_u32(object: &Value, key: &str) -> Result<u32, OgmoError> {
+    object[key]
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| OgmoError::InvalidData(format!("missing or invalid {key:?}")))
+}
+
+/// Like `json_u32`, but 0 if the value is missing.
+fn json_u32_or(object: &Value, key: &str) -> Result<u32, OgmoError> {
+    if object.get(key).is_none() {
+        return Ok(0);
+    }
+    json_u32(object, key)
+}
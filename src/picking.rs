@@ -0,0 +1,102 @@
+//! Reading back the picking buffers drawn by `TilemapPipeline::render_picking`, to find the tile
+//! under the mouse even when tilemaps are rotated, scaled, or drawn in perspective.
+use std::sync::{Arc, Mutex};
+use vek::Vec2;
+
+/// The format of the render targets of `TilemapPipeline::render_picking`, with a draw, a column,
+/// a row, and a tile in each pixel.
+pub const PICKING_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Uint;
+
+/// The tile drawn at a pixel of a picking buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TilePick {
+    /// The position of the tilemap in the list given to `TilemapPipeline::upload_tilemaps`.
+    pub draw: usize,
+    /// The position of the tile in the tilemap.
+    pub cell: Vec2<u32>,
+    /// The tile, as stored in the topmost layer that isn't empty, without its flip flags.
+    pub tile: u32,
+}
+
+/// A pixel of a picking buffer being copied back to the CPU, which takes a frame or two. Create it
+/// after recording `TilemapPipeline::render_picking` in an encoder, call `map` after submitting
+/// the encoder, and then check `try_pick` each frame (the device must be polled, e.g. by
+/// `Queue::submit` or `Device::poll`).
+pub struct PickReadback {
+    buffer: wgpu::Buffer,
+    /// Set once the buffer is mapped, to whether mapping it succeeded.
+    mapped: Arc<Mutex<Option<bool>>>,
+}
+
+impl PickReadback {
+    /// Record a copy of the pixel at `position` of `texture` (a `PICKING_FORMAT` texture with
+    /// `COPY_SRC` usage) in `encoder`.
+    pub fn new(
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        position: Vec2<u32>,
+    ) -> Self {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_pick_readback"),
+            size: 16,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: position.x,
+                    y: position.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        PickReadback {
+            buffer,
+            mapped: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Start reading the pixel back, once the encoder given to `new` has been submitted.
+    pub fn map(&self) {
+        let mapped = self.mapped.clone();
+        self.buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                *mapped.lock().unwrap() = Some(result.is_ok());
+            });
+    }
+
+    /// `None` until the pixel has been read back, and then the tile drawn there, if any (or if it
+    /// couldn't be read, e.g. because the device was lost).
+    pub fn try_pick(&self) -> Option<Option<TilePick>> {
+        let mapped = (*self.mapped.lock().unwrap())?;
+        if !mapped {
+            return Some(None);
+        }
+        let bytes = self.buffer.slice(..).get_mapped_range();
+        let [draw, x, y, tile]: [u32; 4] = bytemuck::pod_read_unaligned(&bytes);
+        Some(draw.checked_sub(1).map(|draw| TilePick {
+            draw: draw as usize,
+            cell: Vec2::new(x, y),
+            tile,
+        }))
+    }
+}
@@ -0,0 +1,5087 @@
+use crate::{
+    AlphaMode, AnyTilemapRef, ColorLut, CompressedTiles, MaterialTable, MippedTileset,
+    PresentParams, Presentation, TileFormat, TileStagger, TilesetAnalysis, TilesetRef,
+    PICKING_FORMAT,
+};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    num::NonZeroU64,
+    ops::Range,
+    sync::Mutex,
+};
+use vek::{Mat4, Rect, Rgb, Vec2, Vec4};
+
+const fn mat4_const_from_rows(m: [[f32; 4]; 4]) -> Mat4<f32> {
+    Mat4 {
+        cols: Vec4 {
+            x: Vec4::new(m[0][0], m[1][0], m[2][0], m[3][0]),
+            y: Vec4::new(m[0][1], m[1][1], m[2][1], m[3][1]),
+            z: Vec4::new(m[0][2], m[1][2], m[2][2], m[3][2]),
+            w: Vec4::new(m[0][3], m[1][3], m[2][3], m[3][3]),
+        },
+    }
+}
+
+/// Camera matrix to scale a tilemap to the whole screen.
+/// Maps x and y from [0, 1] to [-1, 1], leaving z and w unchanged.
+#[rustfmt::skip]
+pub const FULLSCREEN_QUAD_CAMERA: Mat4<f32> = mat4_const_from_rows([
+    [2.0, 0.0, 0.0, -1.0],
+    [0.0, 2.0, 0.0, -1.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+]);
+
+/// Camera matrix that shows the part of the world in `rect`, with `rect.x` and `rect.y` at the bottom
+/// left corner of the screen (y points up, as for `FULLSCREEN_QUAD_CAMERA`). z is left unchanged,
+/// so tilemaps at z = 0 stay within wgpu's depth range.
+pub fn ortho_camera(rect: Rect<f32, f32>) -> Mat4<f32> {
+    #[rustfmt::skip]
+    let camera = Mat4::new(
+        2.0 / rect.w, 0.0, 0.0, -1.0 - 2.0 * rect.x / rect.w,
+        0.0, 2.0 / rect.h, 0.0, -1.0 - 2.0 * rect.y / rect.h,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    camera
+}
+
+/// Camera matrix for world coordinates in pixels: at a `zoom` of 1.0, each world unit covers one
+/// pixel of a viewport of `viewport_size` pixels, and `offset` is the world position at its bottom
+/// left corner.
+pub fn pixel_camera(viewport_size: Vec2<u32>, offset: Vec2<f32>, zoom: f32) -> Mat4<f32> {
+    let size = viewport_size.as_::<f32>() / zoom;
+    ortho_camera(Rect::new(offset.x, offset.y, size.x, size.y))
+}
+
+/// Apply noise to the tilemap at a multiple of the tile size (e.g. for sand effects).
+/// TilemapNoise::default() applies no noise.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapNoise {
+    /// How much noise to apply.
+    pub magnitude: f32,
+    /// Number of noise cells per unit of `space`.
+    pub resolution: u8,
+    /// What the noise cells are fixed to.
+    pub space: NoiseSpace,
+}
+
+impl Default for TilemapNoise {
+    fn default() -> TilemapNoise {
+        TilemapNoise {
+            magnitude: 0.0,
+            resolution: 1,
+            space: NoiseSpace::Tile,
+        }
+    }
+}
+
+/// What the cells of a `TilemapNoise` are fixed to, which decides how the noise moves as the
+/// tilemap and camera move.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoiseSpace {
+    /// The noise moves with the tiles (e.g. sand), with `resolution` cells per tile.
+    #[default]
+    Tile,
+    /// The noise stays in place in the world as the tilemap scrolls (e.g. heat haze), with
+    /// `resolution` cells per world unit.
+    World,
+    /// The noise stays in place on the screen (e.g. film grain), with `resolution` pixels per
+    /// cell.
+    Screen,
+}
+
+/// Draw a tilemap as a soft drop shadow of its tiles instead of the tiles themselves (e.g. a layer
+/// of walls or trees casting shadows onto the ground layer beneath it). The shadow is computed in
+/// the fragment shader from the alpha of the tiles, so the same tilemap can be drawn once with a
+/// shadow (after the base layer) and once without (on top of its shadow).
+/// TilemapShadow::default() draws no shadow.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapShadow {
+    /// Direction of the light, in tiles, with +y pointing down the rows of the tilemap.
+    pub direction: Vec2<f32>,
+    /// How far the shadow extends along `direction`, in tiles.
+    pub length: f32,
+    /// Opacity of the shadow directly next to the tile casting it.
+    pub opacity: f32,
+    /// Number of samples taken along the shadow, more samples give a smoother falloff.
+    pub samples: u8,
+}
+
+impl Default for TilemapShadow {
+    fn default() -> TilemapShadow {
+        TilemapShadow {
+            direction: Vec2::new(1.0, 1.0),
+            length: 0.0,
+            opacity: 0.5,
+            samples: 8,
+        }
+    }
+}
+
+/// Pseudo-3D walls for top-down maps: tiles with a `TileMaterial::height` (see
+/// `TilemapPipeline::set_tileset_materials`) are drawn with their tops offset away from the center
+/// of the camera in proportion to their height, as if seen from above, hiding the tiles behind
+/// them. The walls are found in the fragment shader by searching the tiles at each height, so only
+/// the tiles of the drawn part of the tilemap (and of the same chunk, for tilemaps split by
+/// `TilemapPipeline::set_max_tilemap_texture_size`) can be seen.
+/// TilemapWalls::default() draws every tile flat.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TilemapWalls {
+    /// How far the top of a tile with a height of 1 is offset, as a fraction of its distance from
+    /// the center of the camera in world units.
+    pub parallax: f32,
+    /// The height of the tallest walls, in tiles. Taller tiles are drawn as if they had this
+    /// height.
+    pub max_height: f32,
+    /// Number of heights searched for walls, more samples give smoother sides on tall walls.
+    pub samples: u8,
+}
+
+impl Default for TilemapWalls {
+    fn default() -> TilemapWalls {
+        TilemapWalls {
+            parallax: 0.0,
+            max_height: 1.0,
+            samples: 8,
+        }
+    }
+}
+
+/// Where the first row of pixels of the render target ends up when it is displayed, which differs
+/// between render targets that are presented directly and ones that are sampled as a texture by
+/// code that expects the first row at the bottom (e.g. OpenGL-style compositing).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TargetOrigin {
+    /// The first row is the top of the image, as for surfaces and wgpu textures.
+    #[default]
+    TopLeft,
+    /// The first row is the bottom of the image, so everything is drawn vertically flipped.
+    BottomLeft,
+}
+
+/// How the pixels of tiles are filtered when they are drawn larger or smaller than their size in
+/// the tileset, as set by `TilemapPipeline::set_tile_filter`. Mip levels are only used by tilesets
+/// uploaded with them (e.g. with `TilemapPipeline::upload_tilesets_mipped`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileFilter {
+    /// Always draw the nearest pixel of the finest level, without filtering.
+    Nearest,
+    /// Draw the nearest pixel when magnified, so pixel art stays crisp, and blend between the
+    /// nearest mip levels (trilinear filtering) when minified, so zoomed out views don't shimmer.
+    #[default]
+    NearestMagnified,
+    /// Blend between the nearest pixels (bilinear filtering) when magnified, and between the
+    /// nearest mip levels when minified, for smooth painted art.
+    Linear,
+    /// Like `Linear`, with anisotropic filtering of up to this many samples (clamped to 1 to 16)
+    /// for tiles that are minified more along one axis than the other, e.g. tilemaps seen at a
+    /// slant in perspective.
+    Anisotropic(u16),
+}
+
+impl TileFilter {
+    /// The value of `TILE_FILTER` in tilemap.wgsl.
+    fn shader_value(self) -> u32 {
+        match self {
+            TileFilter::Nearest => 0,
+            TileFilter::NearestMagnified => 1,
+            TileFilter::Linear => 2,
+            TileFilter::Anisotropic(_) => 3,
+        }
+    }
+
+    /// The sampler for tiles that are filtered. Each tile is a layer of its own, so clamping to
+    /// the edges keeps neighboring tiles from bleeding in.
+    fn create_sampler(self, device: &wgpu::Device) -> wgpu::Sampler {
+        let mag_filter = match self {
+            TileFilter::Nearest | TileFilter::NearestMagnified => wgpu::FilterMode::Nearest,
+            TileFilter::Linear | TileFilter::Anisotropic(_) => wgpu::FilterMode::Linear,
+        };
+        let anisotropy_clamp = match self {
+            TileFilter::Anisotropic(samples) => samples.clamp(1, 16),
+            _ => 1,
+        };
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("tilemap_tile_sampler"),
+            mag_filter,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
+}
+
+/// How `TilemapPipeline::bake` shrinks tilemaps into layers smaller than their tiles (e.g. for
+/// minimaps and zoomed out views of large maps), as set by `TilemapPipeline::set_downscale_filter`.
+/// Drawing the tilemaps larger and filtering them down averages every pixel of the tiles on linear
+/// colors, while drawing them directly samples a single point of the tiles for each pixel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DownscaleFilter {
+    /// Draw the tilemaps straight into the baked layer, filtered only by the `TileFilter`.
+    #[default]
+    Direct,
+    /// Draw the tilemaps at this many times the size of the baked layer (clamped to 1 to 16, and
+    /// to the largest texture the device supports), and average each square of that many pixels on
+    /// a side.
+    Box(u32),
+    /// Like `Box`, but weight the pixels with a Kaiser-windowed sinc two pixels of the baked layer
+    /// wide on each side, which keeps edges sharper than `Box` at the cost of slight ringing.
+    Kaiser(u32),
+}
+
+impl DownscaleFilter {
+    /// How many times larger tilemaps baked into a layer of `pixel_size` are drawn, and the value
+    /// of `kaiser` in downscale.wgsl, or `None` if they are drawn directly.
+    fn params(self, device: &wgpu::Device, pixel_size: Vec2<u32>) -> Option<DownscaleBuffer> {
+        let (factor, kaiser) = match self {
+            DownscaleFilter::Direct | DownscaleFilter::Box(0 | 1) => return None,
+            DownscaleFilter::Box(factor) => (factor, 0),
+            DownscaleFilter::Kaiser(factor) => (factor, 1),
+        };
+        let fits = device.limits().max_texture_dimension_2d / pixel_size.reduce_max().max(1);
+        Some(DownscaleBuffer {
+            factor: factor.clamp(1, 16).min(fits).max(1),
+            kaiser,
+            _pad: [0; 2],
+        })
+    }
+}
+
+/// How a tilemap is composited onto the contents of the render target, matching the layer blend
+/// modes of common art tools and map editors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapBlendMode {
+    /// Standard (premultiplied) alpha blending.
+    #[default]
+    Alpha,
+    /// Adds the tilemap's color to the render target (e.g. for glows).
+    Additive,
+    /// Multiplies the render target by the tilemap's color, darkening it (e.g. for shading).
+    Multiply,
+    /// Multiplies the inverse colors, lightening the render target (e.g. for light shafts).
+    Screen,
+    /// Multiplies or screens depending on the render target's color, increasing contrast.
+    /// This needs to read the render target, so it is only drawn when a copy of the render target
+    /// has been provided with `TilemapPipeline::set_backdrop`.
+    Overlay,
+    /// Replaces the render target with the tilemap's color without blending, for fully opaque base
+    /// layers. Pixels that are at least half opaque are drawn fully opaque, and the others aren't
+    /// drawn. If the pipeline has a depth stencil state, these tilemaps write their depth (whether
+    /// or not the state enables depth writes), so that later layers and other geometry can be
+    /// depth tested against them.
+    Opaque,
+}
+
+impl TilemapBlendMode {
+    const ALL: [TilemapBlendMode; 6] = [
+        TilemapBlendMode::Alpha,
+        TilemapBlendMode::Additive,
+        TilemapBlendMode::Multiply,
+        TilemapBlendMode::Screen,
+        TilemapBlendMode::Overlay,
+        TilemapBlendMode::Opaque,
+    ];
+
+    fn blend_state(self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+        let color = |src_factor, dst_factor| BlendState {
+            color: BlendComponent {
+                src_factor,
+                dst_factor,
+                operation: BlendOperation::Add,
+            },
+            alpha: BlendState::PREMULTIPLIED_ALPHA_BLENDING.alpha,
+        };
+        match self {
+            TilemapBlendMode::Alpha => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            TilemapBlendMode::Additive => color(BlendFactor::One, BlendFactor::One),
+            TilemapBlendMode::Multiply => color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+            TilemapBlendMode::Screen => color(BlendFactor::One, BlendFactor::OneMinusSrc),
+            // The shader composites with the backdrop itself.
+            TilemapBlendMode::Overlay => BlendState::REPLACE,
+            TilemapBlendMode::Opaque => BlendState::REPLACE,
+        }
+    }
+
+    /// The entry point of the fragment shader, which also writes the emissive light of the tiles
+    /// to a second target if `emissive_target` is set.
+    fn fragment_entry_point(self, emissive_target: bool) -> &'static str {
+        match (self, emissive_target) {
+            (TilemapBlendMode::Overlay, false) => "tilemap_frag_overlay",
+            (TilemapBlendMode::Overlay, true) => "tilemap_frag_overlay_emissive",
+            (TilemapBlendMode::Opaque, false) => "tilemap_frag_opaque",
+            (TilemapBlendMode::Opaque, true) => "tilemap_frag_opaque_emissive",
+            (_, false) => "tilemap_frag_main",
+            (_, true) => "tilemap_frag_main_emissive",
+        }
+    }
+
+    /// How the emissive light of the tiles is blended into the emissive target (see
+    /// `TilemapPipeline::set_emissive_target`). Tilemaps that cover what is below them hide its
+    /// light, and the others only add their own.
+    fn emissive_blend_state(self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendState};
+        match self {
+            TilemapBlendMode::Alpha | TilemapBlendMode::Overlay => {
+                BlendState::PREMULTIPLIED_ALPHA_BLENDING
+            }
+            TilemapBlendMode::Opaque => BlendState::REPLACE,
+            _ => BlendState {
+                color: BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: wgpu::BlendFactor::Zero,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+            },
+        }
+    }
+}
+
+/// How a tilemap uses the stencil buffer of the render pass, to mask parts of the tilemaps drawn
+/// after it (e.g. to hide the roof over the interior of a building, or to show another map only
+/// through a portal). The depth stencil state given to `TilemapPipeline::new` has to have a format
+/// with stencil (e.g. `wgpu::TextureFormat::Depth24PlusStencil8`), or this is ignored. Draws after
+/// one that isn't `Unchanged` in the same render pass keep the stencil reference it set.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TilemapStencil {
+    /// Use the stencil state of the pipeline's depth stencil state as it is.
+    #[default]
+    Unchanged,
+    /// Write this reference to the stencil buffer wherever the tilemap is drawn, which is where it
+    /// isn't fully transparent and passes the depth test.
+    Write(u8),
+    /// Only draw where the stencil buffer equals this reference.
+    Inside(u8),
+    /// Only draw where the stencil buffer doesn't equal this reference.
+    Outside(u8),
+}
+
+impl TilemapStencil {
+    /// The stencil without its reference, which pipelines are compiled for. The reference is set
+    /// for each draw instead.
+    fn mode(self) -> TilemapStencil {
+        match self {
+            TilemapStencil::Unchanged => TilemapStencil::Unchanged,
+            TilemapStencil::Write(_) => TilemapStencil::Write(0),
+            TilemapStencil::Inside(_) => TilemapStencil::Inside(0),
+            TilemapStencil::Outside(_) => TilemapStencil::Outside(0),
+        }
+    }
+
+    fn reference(self) -> Option<u32> {
+        match self {
+            TilemapStencil::Unchanged => None,
+            TilemapStencil::Write(reference)
+            | TilemapStencil::Inside(reference)
+            | TilemapStencil::Outside(reference) => Some(reference as u32),
+        }
+    }
+
+    /// `state` with this stencil applied to both faces.
+    fn stencil_state(self, state: wgpu::StencilState) -> wgpu::StencilState {
+        use wgpu::{CompareFunction, StencilFaceState, StencilOperation};
+        let face = |compare, pass_op| StencilFaceState {
+            compare,
+            fail_op: StencilOperation::Keep,
+            depth_fail_op: StencilOperation::Keep,
+            pass_op,
+        };
+        let (face, write_mask) = match self {
+            TilemapStencil::Unchanged => return state,
+            TilemapStencil::Write(_) => {
+                (face(CompareFunction::Always, StencilOperation::Replace), !0)
+            }
+            TilemapStencil::Inside(_) => (face(CompareFunction::Equal, StencilOperation::Keep), 0),
+            TilemapStencil::Outside(_) => {
+                (face(CompareFunction::NotEqual, StencilOperation::Keep), 0)
+            }
+        };
+        wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask,
+        }
+    }
+}
+
+/// What is drawn for the tiles of a draw that are past the end of its tileset, which `validate`
+/// finds ahead of time. The tile is checked after `TilemapDrawData::tile_index_offset` and the
+/// checkerboard of the tileset are applied.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InvalidTiles {
+    /// Look the tile up as it is, which samples whatever the GPU returns for layers past the end of
+    /// the tileset texture (often the last tile, but this varies between backends).
+    #[default]
+    Unchecked,
+    /// Draw the last tile of the tileset instead.
+    Clamp,
+    /// Draw this tile instead, which should be within the tileset (e.g. a tile made to stand out).
+    Substitute(u32),
+    /// Draw a magenta and black checkerboard of 4x4 squares instead, so that content errors stand
+    /// out during development (e.g. for draws of debug builds, with `cfg!(debug_assertions)`).
+    /// The tint, noise, and fragment hook of the draw still apply to it, and the rest of the
+    /// shader (e.g. shadows and materials) sees the last tile of the tileset, as with `Clamp`.
+    Highlight,
+}
+
+impl InvalidTiles {
+    /// The values of `invalid_tiles` and `invalid_tile` in the tilemap shader.
+    fn shader_values(self) -> (u32, u32) {
+        match self {
+            InvalidTiles::Unchecked => (0, 0),
+            InvalidTiles::Clamp => (1, 0),
+            InvalidTiles::Substitute(tile) => (2, tile),
+            InvalidTiles::Highlight => (3, 0),
+        }
+    }
+}
+
+/// Optional parts of the tilemap shader. Each combination that is drawn is compiled into its own
+/// shader module the first time it is needed, so tilemaps only pay for the features they use and
+/// the code for the others isn't compiled at all. The features of a draw are chosen from its
+/// `TilemapDrawData`, e.g. `noise` is only enabled for a nonzero `TilemapNoise::magnitude`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TilemapShaderFeatures {
+    /// Draw the tilemap as a `TilemapShadow`, instead of drawing its tiles.
+    pub shadow: bool,
+    /// Add `TilemapNoise` to the tiles. Unused by shadows.
+    pub noise: bool,
+    /// Multiply the tiles by `TilemapDrawData::tint`. Unused by shadows.
+    pub tint: bool,
+    /// Draw a `HeatmapRef` through the color ramp of its tileset, instead of drawing tiles.
+    /// Heatmaps are never drawn as shadows.
+    pub heatmap: bool,
+    /// Draw the tiles with a height as `TilemapDrawData::walls`. Unused by shadows and heatmaps.
+    pub walls: bool,
+}
+
+impl TilemapShaderFeatures {
+    /// The WGSL source of this permutation, calling `hook` for the color of each tile, encoding
+    /// colors as sRGB itself if `encode_srgb` is set, flipping the output for `origin`, filtering
+    /// tiles with `filter`, and writing emissive light to its own target if `emissive_target` is
+    /// set instead of adding it to the color.
+    fn shader_source(
+        self,
+        hook: &str,
+        encode_srgb: bool,
+        origin: TargetOrigin,
+        filter: TileFilter,
+        emissive_target: bool,
+    ) -> String {
+        let mut source = String::from(TilemapPipeline::wgsl_bindings());
+        source.push('\n');
+        source.push_str(include_str!("tilemap.wgsl"));
+        let chunks = if self.shadow {
+            [
+                include_str!("shadow.wgsl"),
+                include_str!("no_walls.wgsl"),
+                "",
+                "",
+                "",
+            ]
+        } else {
+            [
+                if self.heatmap {
+                    include_str!("heatmap.wgsl")
+                } else {
+                    include_str!("tiles.wgsl")
+                },
+                if self.noise {
+                    include_str!("noise.wgsl")
+                } else {
+                    include_str!("no_noise.wgsl")
+                },
+                if self.tint {
+                    include_str!("tint.wgsl")
+                } else {
+                    include_str!("no_tint.wgsl")
+                },
+                if self.walls {
+                    include_str!("walls.wgsl")
+                } else {
+                    include_str!("no_walls.wgsl")
+                },
+                hook,
+            ]
+        };
+        for chunk in chunks {
+            source.push('\n');
+            source.push_str(chunk);
+        }
+        let flip_y = origin == TargetOrigin::BottomLeft;
+        let tile_filter = filter.shader_value();
+        source.push_str(&format!(
+            "\nconst ENCODE_SRGB: bool = {encode_srgb};\nconst FLIP_Y: bool = {flip_y};\n\
+             const TILE_FILTER: u32 = {tile_filter}u;\nconst EMISSIVE_TARGET: bool = {emissive_target};\n"
+        ));
+        source
+    }
+}
+
+/// An instruction to draw a tilemap, created with `TilemapDrawData::new` and the `with_` methods
+/// for the fields that aren't left at their defaults. New fields are added as the pipeline gains
+/// features, so it can't be written as a struct literal outside of this crate, but its fields can
+/// still be read and changed.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TilemapDrawData<'a> {
+    /// A matrix that maps from [0, 1]x[0, 1] to world coordinates for this tilemap.
+    pub transform: Mat4<f32>,
+    /// The data to be used for this tilemap, e.g. `(&tilemap).into()` to borrow a `Tilemap` of
+    /// any `TileId`.
+    pub tilemap: AnyTilemapRef<'a>,
+    /// The index into the array of tilesets last provided to the most recent `TilemapPipeline::upload_tilesets` call that this tilemap should be drawn with.
+    pub tileset: u32,
+    /// How much noise this tilemap should be drawn with.
+    pub noise: TilemapNoise,
+    /// Whether this tilemap should be drawn as the shadow of its tiles.
+    pub shadow: TilemapShadow,
+    /// Whether the tiles of this tilemap with a height should be drawn as walls.
+    pub walls: TilemapWalls,
+    /// How this tilemap is composited onto what was drawn before it.
+    pub blend_mode: TilemapBlendMode,
+    /// Opacity multiplied with every pixel of this tilemap.
+    pub opacity: f32,
+    /// How far this tilemap moves relative to the camera along each axis. `Vec2::one()` moves
+    /// with the world, `Vec2::zero()` stays fixed on screen (e.g. for distant backdrops).
+    pub parallax: Vec2<f32>,
+    /// The part of the tilemap to draw, in tiles, which `transform` maps into place instead of the
+    /// whole tilemap. `None` draws the whole tilemap.
+    pub source_rect: Option<Rect<u32, u32>>,
+    /// Added to every tile index of the tilemap before it is looked up in the tileset, so the same
+    /// tilemap can be drawn with another bank of tiles (e.g. damaged variants stored after the
+    /// intact ones).
+    pub tile_index_offset: u32,
+    /// A tile that isn't drawn, so that sparse layers don't need a transparent tile in their
+    /// tileset, e.g. `Some(EMPTY_TILE as u32)` for the layers of imported maps. This is compared
+    /// with the tiles as stored, ignoring their flip flags, before `tile_index_offset` is added.
+    pub empty_tile: Option<u32>,
+    /// Whether the 3 highest bits of each tile are `TileFlip` flags (as written by
+    /// `TileFlip::encode`) instead of part of its index. This leaves 5 bits of index for `u8`
+    /// tiles, 13 for `u16` tiles, and 29 for `u32` tiles.
+    pub flip_flags: bool,
+    /// Whether to round the translation of this tilemap on screen to whole pixels of the viewport
+    /// (see `TilemapPipeline::set_viewport_size`), so that its tiles don't shimmer as the camera
+    /// moves smoothly. UI layers that should move smoothly can leave this off.
+    pub pixel_snap: bool,
+    /// The color of each tile of the tilemap, in the same order as its tiles and interpreted as
+    /// `wgpu::TextureFormat::Rgba8Unorm`, which is multiplied with the color of the tile (e.g. to
+    /// darken damaged tiles or show who owns them). White leaves a tile unchanged. Draws whose tint
+    /// doesn't have a color for each tile aren't drawn (see `TilemapPipeline::rejected_draws`).
+    pub tint: Option<Cow<'a, [u32]>>,
+    /// Parameters for the fragment hook of the pipeline (see
+    /// `TilemapPipeline::with_fragment_hook`), e.g. the phase of a wave effect.
+    pub user_data: TilemapUserData,
+    /// Lines in world space that the tilemap is cut along, keeping only what is on the side of
+    /// every one of them that its normal points to (e.g. to reveal a map behind a moving boundary).
+    /// Unused planes are `None`.
+    pub clip_planes: [Option<ClipPlane>; MAX_CLIP_PLANES],
+    /// The color of the team (or faction, or player) that owns the tiles, which the parts of the
+    /// tiles marked by the team mask of the tileset (see `TilemapPipeline::set_tileset_team_mask`)
+    /// are multiplied with, so the same tiles can be drawn for every team. The marked parts are
+    /// usually drawn in shades of gray. `None` leaves the tiles unchanged.
+    pub team_color: Option<Rgb<u8>>,
+    /// How brightly the parts of the tiles marked by the emissive texture of the tileset (see
+    /// `TilemapPipeline::set_tileset_emissive`) glow, e.g. to pulse lava or switch lamps off with
+    /// 0.0. Values above 1.0 are useful with an emissive target of a float format, which a bloom
+    /// pass can pick out. Unused by shadows and heatmaps.
+    pub emissive_intensity: f32,
+    /// The z of the tilemap before `transform` and the camera, which leave it unchanged unless
+    /// they map z themselves (as `ortho_camera` doesn't), so that it is the depth the tilemap is
+    /// tested and written at with a depth buffer (see `TilemapPipeline::new`). This interleaves
+    /// tilemaps with other depth-tested geometry in the same render pass instead of relying only
+    /// on the order they are drawn in. Must be within [0, 1] to be drawn.
+    pub depth: f32,
+    /// How this tilemap writes or is masked by the stencil buffer.
+    pub stencil: TilemapStencil,
+    /// Which rows of the tilemap are offset by half a tile, which makes the tilemap half a tile
+    /// wider (see `TileStagger`). Staggered tilemaps are drawn without walls.
+    pub stagger: TileStagger,
+    /// What is drawn for tiles past the end of the tileset.
+    pub invalid_tiles: InvalidTiles,
+}
+
+impl<'a> TilemapDrawData<'a> {
+    /// Draw `tilemap` with the tileset at index `tileset`, over the [0, 1]x[0, 1] square of the
+    /// world (see `with_transform`), with every other field at its default: fully opaque, with
+    /// no noise, shadow, walls, offset, empty tile, flip flags, snapping, or tint.
+    pub fn new(tilemap: impl Into<AnyTilemapRef<'a>>, tileset: u32) -> Self {
+        TilemapDrawData {
+            transform: Mat4::identity(),
+            tilemap: tilemap.into(),
+            tileset,
+            noise: TilemapNoise::default(),
+            shadow: TilemapShadow::default(),
+            walls: TilemapWalls::default(),
+            blend_mode: TilemapBlendMode::default(),
+            opacity: 1.0,
+            parallax: Vec2::one(),
+            source_rect: None,
+            tile_index_offset: 0,
+            empty_tile: None,
+            flip_flags: false,
+            pixel_snap: false,
+            tint: None,
+            user_data: TilemapUserData::default(),
+            clip_planes: [None; MAX_CLIP_PLANES],
+            team_color: None,
+            emissive_intensity: 1.0,
+            depth: 0.0,
+            stencil: TilemapStencil::Unchanged,
+            stagger: TileStagger::None,
+            invalid_tiles: InvalidTiles::Unchecked,
+        }
+    }
+
+    /// Set `transform`, which maps the tilemap into the world.
+    pub fn with_transform(self, transform: Mat4<f32>) -> Self {
+        TilemapDrawData { transform, ..self }
+    }
+
+    /// Set `noise`.
+    pub fn with_noise(self, noise: TilemapNoise) -> Self {
+        TilemapDrawData { noise, ..self }
+    }
+
+    /// Set `shadow`.
+    pub fn with_shadow(self, shadow: TilemapShadow) -> Self {
+        TilemapDrawData { shadow, ..self }
+    }
+
+    /// Set `walls`.
+    pub fn with_walls(self, walls: TilemapWalls) -> Self {
+        TilemapDrawData { walls, ..self }
+    }
+
+    /// Set `blend_mode`.
+    pub fn with_blend_mode(self, blend_mode: TilemapBlendMode) -> Self {
+        TilemapDrawData { blend_mode, ..self }
+    }
+
+    /// Set `opacity`.
+    pub fn with_opacity(self, opacity: f32) -> Self {
+        TilemapDrawData { opacity, ..self }
+    }
+
+    /// Set `parallax`.
+    pub fn with_parallax(self, parallax: Vec2<f32>) -> Self {
+        TilemapDrawData { parallax, ..self }
+    }
+
+    /// Draw only the tiles in `source_rect`.
+    pub fn with_source_rect(self, source_rect: Rect<u32, u32>) -> Self {
+        TilemapDrawData {
+            source_rect: Some(source_rect),
+            ..self
+        }
+    }
+
+    /// Set `tile_index_offset`.
+    pub fn with_tile_index_offset(self, tile_index_offset: u32) -> Self {
+        TilemapDrawData {
+            tile_index_offset,
+            ..self
+        }
+    }
+
+    /// Leave tiles equal to `empty_tile` undrawn.
+    pub fn with_empty_tile(self, empty_tile: u32) -> Self {
+        TilemapDrawData {
+            empty_tile: Some(empty_tile),
+            ..self
+        }
+    }
+
+    /// Set `flip_flags`.
+    pub fn with_flip_flags(self, flip_flags: bool) -> Self {
+        TilemapDrawData { flip_flags, ..self }
+    }
+
+    /// Set `pixel_snap`.
+    pub fn with_pixel_snap(self, pixel_snap: bool) -> Self {
+        TilemapDrawData { pixel_snap, ..self }
+    }
+
+    /// Tint each tile with a color, e.g. a `Vec<u32>` with one color per tile.
+    pub fn with_tint(self, tint: impl Into<Cow<'a, [u32]>>) -> Self {
+        TilemapDrawData {
+            tint: Some(tint.into()),
+            ..self
+        }
+    }
+
+    /// Set `user_data`.
+    pub fn with_user_data(self, user_data: TilemapUserData) -> Self {
+        TilemapDrawData { user_data, ..self }
+    }
+
+    /// Cut the tilemap along `clip_planes`.
+    pub fn with_clip_planes(self, clip_planes: [Option<ClipPlane>; MAX_CLIP_PLANES]) -> Self {
+        TilemapDrawData {
+            clip_planes,
+            ..self
+        }
+    }
+
+    /// Recolor the tiles with `team_color`.
+    pub fn with_team_color(self, team_color: Rgb<u8>) -> Self {
+        TilemapDrawData {
+            team_color: Some(team_color),
+            ..self
+        }
+    }
+
+    /// Set `emissive_intensity`.
+    pub fn with_emissive_intensity(self, emissive_intensity: f32) -> Self {
+        TilemapDrawData {
+            emissive_intensity,
+            ..self
+        }
+    }
+
+    /// Set `depth`.
+    pub fn with_depth(self, depth: f32) -> Self {
+        TilemapDrawData { depth, ..self }
+    }
+
+    /// Set `stencil`.
+    pub fn with_stencil(self, stencil: TilemapStencil) -> Self {
+        TilemapDrawData { stencil, ..self }
+    }
+
+    /// Set `stagger`.
+    pub fn with_stagger(self, stagger: TileStagger) -> Self {
+        TilemapDrawData { stagger, ..self }
+    }
+
+    /// Set `invalid_tiles`.
+    pub fn with_invalid_tiles(self, invalid_tiles: InvalidTiles) -> Self {
+        TilemapDrawData {
+            invalid_tiles,
+            ..self
+        }
+    }
+
+    /// Copy the tiles and tint of this draw if they are borrowed, so that it can be kept.
+    pub fn into_owned(self) -> TilemapDrawData<'static> {
+        TilemapDrawData {
+            tilemap: self.tilemap.into_owned(),
+            tint: self.tint.map(|tint| Cow::Owned(tint.into_owned())),
+            ..self
+        }
+    }
+}
+
+/// The maximum number of `TilemapDrawData::clip_planes` of a draw.
+pub const MAX_CLIP_PLANES: usize = 4;
+
+/// A line in world space (after parallax) that a tilemap is cut along, keeping the side that
+/// `normal` points to: the positions where `normal.dot(position) >= distance`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClipPlane {
+    pub normal: Vec2<f32>,
+    pub distance: f32,
+}
+
+impl ClipPlane {
+    /// The line through `point` that keeps the side `normal` points to.
+    pub fn through(point: Vec2<f32>, normal: Vec2<f32>) -> Self {
+        ClipPlane {
+            normal,
+            distance: normal.dot(point),
+        }
+    }
+}
+
+/// The size of `TilemapUserData`, in bytes.
+pub const USER_DATA_SIZE: usize = 64;
+
+/// Up to `USER_DATA_SIZE` bytes of parameters that are passed to the fragment hook of a
+/// `TilemapPipeline` along with a tilemap.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TilemapUserData(pub [u32; USER_DATA_SIZE / 4]);
+
+impl TilemapUserData {
+    /// Store any plain-old-data value (e.g. a `#[repr(C)]` struct of floats), which is available to
+    /// the hook starting at the first element of `tilemap.user_data`.
+    ///
+    /// Panics if `value` is larger than `USER_DATA_SIZE` bytes.
+    pub fn new<T: bytemuck::Pod>(value: &T) -> Self {
+        let bytes = bytemuck::bytes_of(value);
+        assert!(
+            bytes.len() <= USER_DATA_SIZE,
+            "user data can be at most {USER_DATA_SIZE} bytes"
+        );
+        let mut data = Self::default();
+        bytemuck::cast_slice_mut::<u32, u8>(&mut data.0)[..bytes.len()].copy_from_slice(bytes);
+        data
+    }
+}
+
+/// What is being drawn this frame, for effects that change over time (e.g. in fragment hooks, see
+/// `TilemapPipeline::with_fragment_hook`), as set by `TilemapPipeline::set_frame_context`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameContext {
+    /// Seconds since an arbitrary start, such as the start of the game or of the level. Effects
+    /// that loop should wrap this (e.g. to their period) before it grows large enough to lose
+    /// precision.
+    pub time: f32,
+    /// Seconds since the previous frame.
+    pub delta_time: f32,
+    /// The number of this frame, e.g. for effects that alternate between frames.
+    pub index: u32,
+    /// The size of the viewport that is drawn to, in pixels, to set as with
+    /// `TilemapPipeline::set_viewport_size`, or `None` to keep the size set before (e.g. by
+    /// `set_camera_pixels` or `set_present_params`).
+    pub viewport_size: Option<Vec2<u32>>,
+}
+
+/// The most entries that `TilesetRef::checkerboard` can have.
+pub const MAX_CHECKERBOARD_TILES: usize = 8;
+
+/// The width of the texture of a tileset's materials, which holds one texel per tile in rows.
+/// Matches `MATERIALS_ROW` in tilemap.wgsl.
+const MATERIALS_ROW: u32 = 256;
+
+const VERTEX_LAYOUT: wgpu::VertexBufferLayout = wgpu::VertexBufferLayout {
+    array_stride: 0,
+    step_mode: wgpu::VertexStepMode::Vertex,
+    attributes: &[],
+};
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TilesetBuffer {
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    checkerboard_len: u32,
+    uv_inset: f32,
+    mip_level: u32,
+    mip_levels: u32,
+    /// 1 if `set_tileset_normal_map` has given the tileset a normal map, 0 otherwise.
+    has_normal_map: u32,
+    /// 1 if `set_tileset_emissive` has given the tileset an emissive texture, 0 otherwise.
+    has_emissive: u32,
+    /// 1 if `set_tileset_materials` has given the tileset a material table, 0 otherwise.
+    has_materials: u32,
+    _pad: u32,
+    checkerboard: [[u32; 4]; MAX_CHECKERBOARD_TILES],
+}
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TilemapBuffer {
+    transform: [[f32; 4]; 4],
+    width: u32,
+    height: u32,
+    noise_data: u32,
+    shadow_samples: u32,
+    shadow_offset: [f32; 2],
+    shadow_opacity: f32,
+    opacity: f32,
+    parallax: [f32; 2],
+    source_origin: [u32; 2],
+    source_size: [u32; 2],
+    tile_index_offset: u32,
+    flag_bits: u32,
+    variant_rows: u32,
+    layers: u32,
+    has_empty_tile: u32,
+    empty_tile: u32,
+    heatmap_range: [f32; 2],
+    pixel_snap: u32,
+    /// The position of the draw in the list given to `upload_tilemaps`.
+    draw_index: u32,
+    /// The tile of the tilemap at the first texel of the index texture, which holds `width` by
+    /// `height` tiles of each layer.
+    texture_origin: [u32; 2],
+    /// The part of the tilemap covered by this draw, which is all of `source_origin` and
+    /// `source_size` unless the tilemap was split to fit its index texture.
+    quad_origin: [u32; 2],
+    quad_size: [u32; 2],
+    /// `TilemapDrawData::emissive_intensity`, or 0 for draws without emissive light.
+    emissive_intensity: f32,
+    /// `TilemapWalls::samples`, or 0 for draws without walls.
+    wall_samples: u32,
+    wall_parallax: f32,
+    wall_max_height: f32,
+    depth: f32,
+    /// `TileStagger::shader_value` of `TilemapDrawData::stagger`.
+    stagger: u32,
+    /// `InvalidTiles::shader_values` of `TilemapDrawData::invalid_tiles`.
+    invalid_tiles: u32,
+    invalid_tile: u32,
+    _pad: [u32; 2],
+    /// `(normal, -distance, 0)` of each clip plane, where unused planes are all zeros and keep
+    /// every position.
+    clip_planes: [[f32; 4]; MAX_CLIP_PLANES],
+    /// The linear team color, with an alpha of 1 if there is one and 0 otherwise.
+    team_color: [f32; 4],
+    user_data: [u32; USER_DATA_SIZE / 4],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct DownscaleBuffer {
+    factor: u32,
+    kaiser: u32,
+    _pad: [u32; 2],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct FrameBuffer {
+    time: f32,
+    delta_time: f32,
+    index: u32,
+    _pad: u32,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct CameraBuffer {
+    view_proj: [[f32; 4]; 4],
+    center: [f32; 2],
+    color_lut_size: u32,
+    _pad0: u32,
+    viewport_size: [f32; 2],
+    _pad1: [u32; 2],
+}
+
+trait HasTextureAllocation {
+    type Params: bytemuck::Pod;
+    fn active(&self) -> bool;
+    fn set_active(&mut self, active: bool);
+    fn params_buffer(&self) -> &wgpu::Buffer;
+    fn texture(&self) -> &wgpu::Texture;
+}
+
+struct FirstFitTextureAllocator<K, T> {
+    map: HashMap<K, Vec<T>>,
+    /// The keys of `map` in the order they were first allocated, which allocations are visited in
+    /// (rather than the order of `map`, which varies from run to run), so that GPU captures of the
+    /// same inputs line up.
+    order: Vec<K>,
+}
+
+impl<K: Clone + Eq + Hash, T: HasTextureAllocation> FirstFitTextureAllocator<K, T> {
+    fn new() -> Self {
+        FirstFitTextureAllocator {
+            map: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// The allocations of this size, which are created if there are none yet.
+    fn entry(&mut self, size: &K) -> &mut Vec<T> {
+        if !self.map.contains_key(size) {
+            self.order.push(size.clone());
+        }
+        self.map.entry(size.clone()).or_default()
+    }
+
+    /// Call `f` with every allocation, in the order their sizes were first allocated.
+    fn for_each_mut(&mut self, mut f: impl FnMut(&mut T)) {
+        for size in &self.order {
+            self.map
+                .get_mut(size)
+                .into_iter()
+                .flatten()
+                .for_each(&mut f);
+        }
+    }
+
+    /// Allocate until there are at least `count` allocations of this size.
+    fn reserve<F>(&mut self, size: K, count: usize, device: &wgpu::Device, mut alloc: F)
+    where
+        F: FnMut(&wgpu::Device, K) -> T,
+    {
+        let data = self.entry(&size);
+        while data.len() < count {
+            data.push(alloc(device, size.clone()));
+        }
+    }
+
+    fn mark_inactive(&mut self) {
+        self.for_each_mut(|datum| datum.set_active(false));
+    }
+
+    fn allocate_and_upload<F, G>(
+        &mut self,
+        size: K,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        alloc: F,
+        params: &T::Params,
+        callback: G,
+    ) where
+        F: FnOnce(&wgpu::Device, K) -> T,
+        G: FnOnce(usize, &mut T),
+    {
+        // Find the first inactive allocation of the correct size, or call the provided allocator if none exists.
+        let data = self.entry(&size);
+        let (i, datum) = if let Some((i, datum)) = data
+            .iter_mut()
+            .enumerate()
+            .find(|(_, datum)| !datum.active())
+        {
+            (i, datum)
+        } else {
+            let i = data.len();
+            data.push(alloc(device, size));
+            (i, data.last_mut().unwrap())
+        };
+
+        // Mark the allocation as active, and let the caller store an index to it.
+        datum.set_active(true);
+        callback(i, datum);
+
+        // Upload the parameters and texture data for it to the GPU.
+        queue.write_buffer(datum.params_buffer(), 0, bytemuck::bytes_of(params));
+    }
+}
+
+/// The size of `size` at mip `level`.
+fn mip_size(size: Vec2<u32>, level: u32) -> Vec2<u32> {
+    size.map(|x| (x >> level).max(1))
+}
+
+/// The number of bytes of an image of `size` in `format`, and of each row of its blocks.
+fn image_bytes(format: wgpu::TextureFormat, size: Vec2<u32>) -> (u64, u32) {
+    let (block_width, block_height) = format.block_dimensions();
+    let blocks = size.map2(Vec2::new(block_width, block_height), u32::div_ceil);
+    let row = blocks.x * format.block_size(None).unwrap_or(4);
+    (row as u64 * blocks.y as u64, row)
+}
+
+/// Write a mip `level` of the tiles of a tileset texture, where `data` holds `tiles` tiles of
+/// `size_of_tile` one after another, in the format of the texture.
+fn write_tileset_level(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    level: u32,
+    data: &[u8],
+    size_of_tile: Vec2<u32>,
+    tiles: u32,
+) {
+    let (_, bytes_per_row) = image_bytes(texture.format(), size_of_tile);
+    let (_, block_height) = texture.format().block_dimensions();
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: level,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(size_of_tile.y.div_ceil(block_height)),
+        },
+        wgpu::Extent3d {
+            width: size_of_tile.x,
+            height: size_of_tile.y,
+            depth_or_array_layers: tiles,
+        },
+    );
+}
+
+/// Whether `compressed` can be uploaded for a tileset with tiles of `size_of_tile`: its format is a
+/// compressed one that `device` supports, and the tiles are made of whole blocks.
+fn compressed_tiles_supported(
+    device: &wgpu::Device,
+    compressed: &CompressedTiles,
+    size_of_tile: Vec2<u32>,
+) -> bool {
+    let format = compressed.format;
+    let (block_width, block_height) = format.block_dimensions();
+    format.is_compressed()
+        && device.features().contains(format.required_features())
+        && size_of_tile.x % block_width == 0
+        && size_of_tile.y % block_height == 0
+}
+
+/// Halve the size of each of `tiles` tiles of `size` (stored one after another as premultiplied
+/// sRGB pixels), averaging each 2x2 block of pixels on linear colors.
+fn downsample_tiles(pixels: &[u32], size: Vec2<u32>, tiles: u32) -> (Vec<u32>, Vec2<u32>) {
+    let decode = srgb_decode_table();
+    let half = mip_size(size, 1);
+    let mut result = Vec::with_capacity((half.product() * tiles) as usize);
+    for tile in pixels.chunks_exact(size.product() as usize) {
+        for y in 0..half.y {
+            for x in 0..half.x {
+                let mut sum = [0.0; 4];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let source = Vec2::new(2 * x + dx, 2 * y + dy).map2(size, |p, s| p.min(s - 1));
+                    let [r, g, b, a] = tile[(source.y * size.x + source.x) as usize].to_le_bytes();
+                    for (sum, value) in sum.iter_mut().zip([
+                        decode[r as usize],
+                        decode[g as usize],
+                        decode[b as usize],
+                        a as f32 / 255.0,
+                    ]) {
+                        *sum += value / 4.0;
+                    }
+                }
+                let [r, g, b, a] = sum;
+                result.push(u32::from_le_bytes([
+                    srgb_encode(r),
+                    srgb_encode(g),
+                    srgb_encode(b),
+                    (a * 255.0).round() as u8,
+                ]));
+            }
+        }
+    }
+    (result, half)
+}
+
+/// The linear value of an sRGB byte.
+fn srgb_decode(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The linear value of each sRGB byte.
+fn srgb_decode_table() -> [f32; 256] {
+    std::array::from_fn(|i| srgb_decode(i as u8))
+}
+
+/// The sRGB byte of a linear value.
+fn srgb_encode(value: f32) -> u8 {
+    let value = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Convert sRGB pixels with straight alpha to premultiplied alpha, multiplying their linear colors
+/// by alpha, into `scratch`. Tilesets without translucent pixels are returned unchanged.
+fn premultiply_alpha<'a>(pixels: &'a [u32], scratch: &'a mut Vec<u32>) -> &'a [u32] {
+    if pixels.iter().all(|pixel| pixel >> 24 == 0xff) {
+        return pixels;
+    }
+    let decode = srgb_decode_table();
+    scratch.clear();
+    scratch.extend(pixels.iter().map(|&pixel| {
+        let [r, g, b, a] = pixel.to_le_bytes();
+        match a {
+            0xff => pixel,
+            0 => 0,
+            _ => {
+                let alpha = a as f32 / 255.0;
+                let [r, g, b] = [r, g, b].map(|c| srgb_encode(decode[c as usize] * alpha));
+                u32::from_le_bytes([r, g, b, a])
+            }
+        }
+    }));
+    scratch
+}
+
+/// The settings of a new `TilemapPipeline`, created with `TilemapPipelineDescriptor::new` and the
+/// `with_` methods for the settings that aren't left at their defaults, and passed to
+/// `TilemapPipeline::from_descriptor`. Like `TilemapDrawData`, new settings are added as the
+/// pipeline gains features, so it can't be written as a struct literal outside of this crate.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct TilemapPipelineDescriptor<'a> {
+    /// The format of the render target.
+    pub texture_format: wgpu::TextureFormat,
+    /// The depth stencil state of the render passes the tilemaps are drawn in, if they have a
+    /// depth or stencil attachment.
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    /// WGSL source defining the fragment hook (see `TilemapPipeline::with_fragment_hook`).
+    pub fragment_hook: &'a str,
+    /// See `TilemapPipeline::set_encode_srgb`.
+    pub encode_srgb: bool,
+    /// See `TilemapPipeline::set_target_origin`.
+    pub target_origin: TargetOrigin,
+    /// See `TilemapPipeline::set_emissive_target`.
+    pub emissive_target: Option<wgpu::TextureFormat>,
+    /// See `TilemapPipeline::set_multisample`.
+    pub multisample: wgpu::MultisampleState,
+    /// See `TilemapPipeline::set_tile_filter`.
+    pub tile_filter: TileFilter,
+    /// See `TilemapPipeline::set_downscale_filter`.
+    pub downscale_filter: DownscaleFilter,
+    /// See `TilemapPipeline::set_picking`.
+    pub picking: bool,
+}
+
+impl<'a> TilemapPipelineDescriptor<'a> {
+    /// Settings for a pipeline that draws to targets of `texture_format`, with every other setting
+    /// at its default: no depth stencil state, a fragment hook that leaves colors unchanged, and the
+    /// defaults of the setters of `TilemapPipeline`.
+    pub fn new(texture_format: wgpu::TextureFormat) -> Self {
+        TilemapPipelineDescriptor {
+            texture_format,
+            depth_stencil: None,
+            fragment_hook: include_str!("hook.wgsl"),
+            encode_srgb: false,
+            target_origin: TargetOrigin::default(),
+            emissive_target: None,
+            multisample: wgpu::MultisampleState::default(),
+            tile_filter: TileFilter::default(),
+            downscale_filter: DownscaleFilter::default(),
+            picking: false,
+        }
+    }
+
+    /// Set `depth_stencil`.
+    pub fn with_depth_stencil(self, depth_stencil: Option<wgpu::DepthStencilState>) -> Self {
+        TilemapPipelineDescriptor {
+            depth_stencil,
+            ..self
+        }
+    }
+
+    /// Set `fragment_hook`.
+    pub fn with_fragment_hook(self, fragment_hook: &'a str) -> Self {
+        TilemapPipelineDescriptor {
+            fragment_hook,
+            ..self
+        }
+    }
+
+    /// Set `encode_srgb`.
+    pub fn with_encode_srgb(self, encode_srgb: bool) -> Self {
+        TilemapPipelineDescriptor {
+            encode_srgb,
+            ..self
+        }
+    }
+
+    /// Set `target_origin`.
+    pub fn with_target_origin(self, target_origin: TargetOrigin) -> Self {
+        TilemapPipelineDescriptor {
+            target_origin,
+            ..self
+        }
+    }
+
+    /// Set `emissive_target`.
+    pub fn with_emissive_target(self, emissive_target: Option<wgpu::TextureFormat>) -> Self {
+        TilemapPipelineDescriptor {
+            emissive_target,
+            ..self
+        }
+    }
+
+    /// Set `multisample`.
+    pub fn with_multisample(self, multisample: wgpu::MultisampleState) -> Self {
+        TilemapPipelineDescriptor {
+            multisample,
+            ..self
+        }
+    }
+
+    /// Set `tile_filter`.
+    pub fn with_tile_filter(self, tile_filter: TileFilter) -> Self {
+        TilemapPipelineDescriptor {
+            tile_filter,
+            ..self
+        }
+    }
+
+    /// Set `downscale_filter`.
+    pub fn with_downscale_filter(self, downscale_filter: DownscaleFilter) -> Self {
+        TilemapPipelineDescriptor {
+            downscale_filter,
+            ..self
+        }
+    }
+
+    /// Set `picking`.
+    pub fn with_picking(self, picking: bool) -> Self {
+        TilemapPipelineDescriptor { picking, ..self }
+    }
+}
+
+/// The entry point to this crate.
+pub struct TilemapPipeline {
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    /// Written by `set_frame_context`, and bound with the camera.
+    frame_buffer: wgpu::Buffer,
+    /// The size of the LUT bound with the camera, or 0 if no LUT is applied.
+    color_lut_size: u32,
+    color_lut_sampler: wgpu::Sampler,
+    vertex_buffer: wgpu::Buffer,
+    tileset_bind_group_layout: wgpu::BindGroupLayout,
+    tilemap_bind_group_layout: wgpu::BindGroupLayout,
+    /// Bound in place of the tint texture of tilemaps without a tint.
+    placeholder_tint_view: wgpu::TextureView,
+    /// Bound in place of the material tables, team masks, normal maps, and emissive textures of
+    /// tilesets without them.
+    tileset_placeholders: TilesetPlaceholders,
+    /// Samples the tiles that `tile_filter` filters.
+    tile_sampler: wgpu::Sampler,
+    tile_filter: TileFilter,
+    backdrop_bind_group_layout: wgpu::BindGroupLayout,
+    backdrop_bind_group: Option<wgpu::BindGroup>,
+    texture_format: wgpu::TextureFormat,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    fragment_hook: String,
+    encode_srgb: bool,
+    target_origin: TargetOrigin,
+    /// Set by `set_emissive_target`.
+    emissive_target: Option<wgpu::TextureFormat>,
+    /// Set by `set_multisample`.
+    multisample: wgpu::MultisampleState,
+    /// Set by `set_downscale_filter`.
+    downscale_filter: DownscaleFilter,
+    downscale_bind_group_layout: wgpu::BindGroupLayout,
+    /// Compiled by the first `bake` with a `downscale_filter`.
+    downscale_pipeline: Option<wgpu::RenderPipeline>,
+    tilemap_pipeline_layout: wgpu::PipelineLayout,
+    backdrop_pipeline_layout: wgpu::PipelineLayout,
+    shader_modules: BTreeMap<TilemapShaderFeatures, wgpu::ShaderModule>,
+    tilemap_pipelines: BTreeMap<TilemapPipelineKey, wgpu::RenderPipeline>,
+    /// Set by `set_picking`.
+    picking: bool,
+    picking_pipelines: BTreeMap<TilemapShaderFeatures, wgpu::RenderPipeline>,
+    /// The shaders that `bake` draws with, which never encode sRGB or flip the target.
+    bake_shader_modules: BTreeMap<TilemapShaderFeatures, wgpu::ShaderModule>,
+    bake_pipelines: BTreeMap<(TilemapBlendMode, TilemapShaderFeatures), wgpu::RenderPipeline>,
+    draw_calls: FirstFitTextureAllocator<TilemapKey, TilemapDrawCall>,
+    /// The position in the list given to `upload_tilemaps` and the allocation of each draw call,
+    /// with a call for each chunk of tilemaps that were split (see `set_max_tilemap_texture_size`).
+    active_draw_calls: Vec<(usize, TilemapKey, u32)>,
+    /// A hash of everything about each tilemap in the list given to `upload_tilemaps` that is
+    /// drawn, so that `is_stale` can tell which baked layers were baked from other tilemaps.
+    draw_hashes: Vec<u64>,
+    /// The positions of the tilemaps in the list given to `upload_tilemaps` that were skipped
+    /// (see `rejected_draws`).
+    rejected_draws: Vec<usize>,
+    /// Set by `set_max_tilemap_texture_size`.
+    max_tilemap_texture_size: Option<u32>,
+    tilesets: FirstFitTextureAllocator<TilesetKey, TilesetCache>,
+    active_tilesets: Vec<(TilesetKey, u32)>,
+    /// Incremented by every `upload_tilesets`, so that draws of tilesets replaced since they were
+    /// uploaded can be skipped.
+    tileset_generation: u64,
+    tileset_names: HashMap<String, u32>,
+    tileset_analyses: Vec<TilesetAnalysis>,
+    allocation_observer: Option<AllocationObserver>,
+    tilemap_allocation: AllocationStrategy,
+    tileset_allocation: AllocationStrategy,
+    tilemaps_version: Option<u64>,
+    presentation: Option<Presentation>,
+    /// The size of the render target given to `set_present_params`, to flip its viewport for
+    /// `TargetOrigin::BottomLeft`.
+    present_target_size: Vec2<u32>,
+    retained: Option<RetainedUploads>,
+    streaming_mips: Vec<StreamingMips>,
+    /// Reused by every `upload_tilemaps` for the chunks of each tilemap, and by every upload of
+    /// tilesets for their premultiplied pixels, so that uploads of the same sizes as before don't
+    /// allocate (see `reserve_upload_capacity`).
+    chunk_scratch: Vec<TilemapChunk>,
+    pixel_scratch: Vec<u32>,
+}
+
+/// CPU copies of what was last uploaded to a `TilemapPipeline`, kept for `rebuild`.
+#[derive(Default)]
+struct RetainedUploads {
+    /// The tilesets, with how their mip levels were uploaded.
+    tilesets: Vec<(TilesetRef<'static>, RetainedMips)>,
+    tilemaps: Vec<TilemapDrawData<'static>>,
+    /// Set by `set_camera`, which only borrows the pipeline.
+    camera: Mutex<Option<Mat4<f32>>>,
+    /// Set by `set_viewport_size`, which only borrows the pipeline.
+    viewport_size: Mutex<Option<Vec2<f32>>>,
+    color_lut: Option<ColorLut>,
+    /// Set by `set_tileset_materials`, by tileset.
+    materials: Vec<(u32, MaterialTable)>,
+    /// Set by `set_tileset_team_mask`, by tileset.
+    team_masks: Vec<(u32, TilesetRef<'static>)>,
+    /// Set by `set_tileset_normal_map`, by tileset.
+    normal_maps: Vec<(u32, TilesetRef<'static>)>,
+    /// Set by `set_tileset_emissive`, by tileset.
+    emissive: Vec<(u32, TilesetRef<'static>)>,
+}
+
+type AllocationObserver = Box<dyn FnMut(&TilemapAllocation) + Send + Sync>;
+
+/// How a `TilemapPipeline` sizes the textures it allocates for tilemaps or tilesets. Allocations
+/// are only reused for tilemaps (or tilesets) that round to the same size, so rounding up improves
+/// reuse when sizes vary (e.g. between levels) at the cost of some unused memory.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AllocationStrategy {
+    /// Allocate exactly the size that is needed.
+    #[default]
+    ExactSize,
+    /// Round the width and height up to powers of two.
+    PowerOfTwo,
+    /// Round the width and height up to multiples of these sizes, e.g. so that maps of 257x130
+    /// and 260x128 tiles share allocations with buckets of 64x64.
+    Buckets(Vec2<u32>),
+    /// Allocate everything that fits within this size at this size, so that any allocation can
+    /// be reused for any of them (larger sizes are allocated exactly). Use
+    /// `TilemapPipeline::reserve_tilemaps` or `reserve_tilesets` to fill the pool up front.
+    Pool(Vec2<u32>),
+}
+
+impl AllocationStrategy {
+    /// The size that is allocated for `size`, in tiles.
+    pub fn round(self, size: Vec2<u32>) -> Vec2<u32> {
+        match self {
+            AllocationStrategy::ExactSize => size,
+            AllocationStrategy::PowerOfTwo => size.map(u32::next_power_of_two),
+            AllocationStrategy::Buckets(bucket) => {
+                size.map2(bucket, |size, bucket| size.next_multiple_of(bucket.max(1)))
+            }
+            AllocationStrategy::Pool(pool) if size.x <= pool.x && size.y <= pool.y => pool,
+            AllocationStrategy::Pool(_) => size,
+        }
+    }
+}
+
+/// The kind of GPU resources created for a `TilemapAllocation`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AllocationKind {
+    /// The index texture and parameters of a tilemap draw call, created by `upload_tilemaps`.
+    Tilemap,
+    /// The texture and parameters of a tileset, created by `upload_tilesets`.
+    Tileset,
+}
+
+/// GPU resources created by a `TilemapPipeline` because none of its existing allocations could be
+/// reused, as reported to the observer set with `TilemapPipeline::set_allocation_observer`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TilemapAllocation {
+    pub kind: AllocationKind,
+    /// The size of the texture, in tiles for tilemaps and in pixels for tilesets.
+    pub size: Vec2<u32>,
+    /// The approximate number of bytes of GPU memory allocated.
+    pub bytes: u64,
+}
+
+/// The approximate number of bytes of GPU memory of a tilemap draw call of `size` tiles.
+fn tilemap_bytes(size: Vec2<u32>, format: TileFormat, layers: u32, tint: bool) -> u64 {
+    let tint_bytes = if tint { 4 } else { 0 };
+    size.product() as u64 * (format.bytes_per_tile() * layers as usize + tint_bytes) as u64
+}
+
+/// Why `TilemapPipeline::upload_tilesets` (or one of its variants) rejected a list of tilesets.
+/// Nothing is uploaded when a tileset is rejected, so the tilesets uploaded before are still drawn.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TilesetError {
+    /// The tileset at index `tileset` of the list has more tiles than the device has layers in a
+    /// texture (`wgpu::Limits::max_texture_array_layers`, which is 256 unless a higher limit was
+    /// requested when creating the device), since each tile is uploaded to a layer of its own.
+    TooManyTiles { tileset: u32, tiles: u64, max: u32 },
+    /// The tileset at index `tileset` of the list has `len` `TilesetRef::checkerboard` entries,
+    /// more than `MAX_CHECKERBOARD_TILES`.
+    TooManyCheckerboardTiles { tileset: u32, len: usize },
+    /// The `TilesetRef::data` of the tileset at index `tileset` of the list has `len` pixels
+    /// instead of the `expected` ones of its tiles.
+    DataSize {
+        tileset: u32,
+        len: usize,
+        expected: usize,
+    },
+    /// The `TilesetRef::compressed` tiles of the tileset at index `tileset` of the list have `len`
+    /// bytes instead of the `expected` ones of the blocks of its tiles.
+    CompressedSize {
+        tileset: u32,
+        len: u64,
+        expected: u64,
+    },
+    /// Mip level `level` of the tileset at index `tileset` (in `MippedTileset::mips`, counting the
+    /// full size tiles as level 0) has `len` pixels instead of the `expected` ones of its tiles.
+    MipLevelSize {
+        tileset: u32,
+        level: u32,
+        len: usize,
+        expected: usize,
+    },
+}
+
+impl std::fmt::Display for TilesetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TilesetError::TooManyTiles {
+                tileset,
+                tiles,
+                max,
+            } => write!(
+                f,
+                "tileset {tileset} has {tiles} tiles, but the device supports at most {max}"
+            ),
+            TilesetError::TooManyCheckerboardTiles { tileset, len } => write!(
+                f,
+                "tileset {tileset} has {len} checkerboard tiles, but at most \
+                 {MAX_CHECKERBOARD_TILES} are supported"
+            ),
+            TilesetError::DataSize {
+                tileset,
+                len,
+                expected,
+            } => write!(
+                f,
+                "tileset {tileset} has {len} pixels instead of the {expected} of its tiles"
+            ),
+            TilesetError::CompressedSize {
+                tileset,
+                len,
+                expected,
+            } => write!(
+                f,
+                "the compressed tiles of tileset {tileset} have {len} bytes instead of {expected}"
+            ),
+            TilesetError::MipLevelSize {
+                tileset,
+                level,
+                len,
+                expected,
+            } => write!(
+                f,
+                "mip level {level} of tileset {tileset} has {len} pixels instead of {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TilesetError {}
+
+/// Check that `tileset`, at index `index` of the list it is uploaded with, can be uploaded to
+/// `device` along with `mips`.
+fn check_tileset(
+    device: &wgpu::Device,
+    index: usize,
+    tileset: &TilesetRef,
+    mips: TilesetMips,
+) -> Result<(), TilesetError> {
+    let max = device.limits().max_texture_array_layers;
+    let tiles = (tileset.pixel_size / tileset.size_of_tile.map(|x| x.max(1)))
+        .as_::<u64>()
+        .product();
+    if tiles > max as u64 {
+        return Err(TilesetError::TooManyTiles {
+            tileset: index as u32,
+            tiles,
+            max,
+        });
+    }
+    let expected = tiles as usize * tileset.size_of_tile.product() as usize;
+    if tileset.data.len() != expected {
+        return Err(TilesetError::DataSize {
+            tileset: index as u32,
+            len: tileset.data.len(),
+            expected,
+        });
+    }
+    if tileset.checkerboard.len() > MAX_CHECKERBOARD_TILES {
+        return Err(TilesetError::TooManyCheckerboardTiles {
+            tileset: index as u32,
+            len: tileset.checkerboard.len(),
+        });
+    }
+    if let Some(compressed) = &tileset.compressed {
+        let (tile_bytes, _) = image_bytes(compressed.format, tileset.size_of_tile);
+        let expected = tile_bytes * tiles;
+        if compressed.format.is_compressed() && compressed.blocks.len() as u64 != expected {
+            return Err(TilesetError::CompressedSize {
+                tileset: index as u32,
+                len: compressed.blocks.len() as u64,
+                expected,
+            });
+        }
+    }
+    if let TilesetMips::Provided(mips) = mips {
+        // Levels past the one where the tiles are 1x1 are ignored, like in `upload_tileset`.
+        let largest = tileset.size_of_tile.reduce_max().max(1);
+        let used = mips
+            .len()
+            .min((u32::BITS - largest.leading_zeros()) as usize - 1);
+        for (level, pixels) in (1..).zip(&mips[..used]) {
+            let expected =
+                mip_size(tileset.size_of_tile, level).product() as usize * tiles as usize;
+            if pixels.len() != expected {
+                return Err(TilesetError::MipLevelSize {
+                    tileset: index as u32,
+                    level,
+                    len: pixels.len(),
+                    expected,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One of the draws of the tilemaps most recently given to `TilemapPipeline::upload_tilemaps`, as
+/// listed by `TilemapPipeline::active_draws` (e.g. for a debug overlay).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawInfo {
+    /// The position of the tilemap in the list given to `upload_tilemaps`. Tilemaps that were split
+    /// to fit the maximum texture size (see `TilemapPipeline::set_max_tilemap_texture_size`) have a
+    /// draw for each part, one after another.
+    pub position: usize,
+    /// The part of the tilemap covered by this draw, in tiles.
+    pub tiles: Rect<u32, u32>,
+    /// `TilemapDrawData::tileset`.
+    pub tileset: u32,
+    /// `TilemapDrawData::transform`.
+    pub transform: Mat4<f32>,
+    pub blend_mode: TilemapBlendMode,
+    /// Whether `render` skips this draw because its tileset was replaced (see
+    /// `TilemapPipeline::stale_draws`).
+    pub stale: bool,
+    /// The number of bytes of tiles (and tint) uploaded for this draw.
+    pub uploaded_bytes: u64,
+    /// The approximate number of bytes of GPU memory of the allocation this draw uses, which is
+    /// larger than what was uploaded if it was rounded up (see `AllocationStrategy`) or reused.
+    pub allocated_bytes: u64,
+}
+
+fn notify_allocation(
+    observer: &mut Option<AllocationObserver>,
+    kind: AllocationKind,
+    size: Vec2<u32>,
+    bytes: u64,
+) {
+    if let Some(observer) = observer {
+        observer(&TilemapAllocation { kind, size, bytes });
+    }
+}
+
+fn create_camera_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera_buffer: &wgpu::Buffer,
+    color_lut_view: &wgpu::TextureView,
+    color_lut_sampler: &wgpu::Sampler,
+    frame_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("camera_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(color_lut_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Sampler(color_lut_sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: frame_buffer.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+fn create_tileset_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    params_buffer: &wgpu::Buffer,
+    data_texture: &wgpu::Texture,
+    views: TilesetViews,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    let data_view = data_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("tileset_bind_group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&data_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(views.materials),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(views.team_mask),
+            },
+            wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(views.normal_map),
+            },
+            wgpu::BindGroupEntry {
+                binding: 6,
+                resource: wgpu::BindingResource::TextureView(views.emissive),
+            },
+        ],
+    })
+}
+
+/// The texture for `lut`, or a 1x1x1 placeholder if there is no LUT.
+fn create_color_lut(
+    device: &wgpu::Device,
+    lut: Option<&ColorLut>,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let size = lut.map_or(1, |lut| lut.size);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("tilemap_color_lut_texture"),
+        size: wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: size,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D3,
+        // The entries are sRGB, but are blended as they are stored (like the LUTs of image
+        // editors), and decoded in the shader.
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+/// The tiles of `tilemap`, as uploaded to its index texture.
+fn tile_bytes<'b>(tilemap: &'b AnyTilemapRef) -> &'b [u8] {
+    match tilemap {
+        AnyTilemapRef::U8(tilemap) => &tilemap.data,
+        AnyTilemapRef::U16(tilemap) => bytemuck::cast_slice(&tilemap.data),
+        AnyTilemapRef::U32(tilemap) => bytemuck::cast_slice(&tilemap.data),
+        AnyTilemapRef::Variant(tilemap) => bytemuck::cast_slice(&tilemap.data),
+        AnyTilemapRef::Heatmap(heatmap) => bytemuck::cast_slice(&heatmap.data),
+    }
+}
+
+/// A part of a tilemap drawn with its own index texture, in tiles.
+struct TilemapChunk {
+    /// The part of the tilemap that is drawn.
+    quad_origin: Vec2<u32>,
+    quad_size: Vec2<u32>,
+    /// The part of the tilemap that is uploaded, which covers the part that is drawn.
+    texture_origin: Vec2<u32>,
+    texture_size: Vec2<u32>,
+}
+
+/// Split the part of a tilemap of `size` tiles at `source_origin` and `source_size` into chunks
+/// whose index textures are at most `limit` tiles, each uploaded with up to `apron` more tiles
+/// around the part it draws, replacing the contents of `chunks`. Tilemaps that fit are uploaded
+/// whole, as a single chunk.
+fn tilemap_chunks(
+    size: Vec2<u32>,
+    source_origin: Vec2<u32>,
+    source_size: Vec2<u32>,
+    limit: Vec2<u32>,
+    apron: Vec2<u32>,
+    chunks: &mut Vec<TilemapChunk>,
+) {
+    chunks.clear();
+    if size.x <= limit.x && size.y <= limit.y {
+        chunks.push(TilemapChunk {
+            quad_origin: source_origin,
+            quad_size: source_size,
+            texture_origin: Vec2::zero(),
+            texture_size: size,
+        });
+        return;
+    }
+    let apron = apron.map2(limit, |apron, limit| apron.min(limit.saturating_sub(1) / 2));
+    let step = limit - apron * 2;
+    for y in (0..source_size.y).step_by(step.y as usize) {
+        for x in (0..source_size.x).step_by(step.x as usize) {
+            let quad_origin = source_origin + Vec2::new(x, y);
+            let quad_size = Vec2::partial_min(step, source_size - Vec2::new(x, y));
+            let texture_origin = quad_origin - Vec2::partial_min(apron, quad_origin);
+            let texture_end = Vec2::partial_min(quad_origin + quad_size + apron, size);
+            chunks.push(TilemapChunk {
+                quad_origin,
+                quad_size,
+                texture_origin,
+                texture_size: texture_end - texture_origin,
+            });
+        }
+    }
+}
+
+/// Write the part of `layer` of a tilemap of `size` tiles that is uploaded for `chunk` to
+/// `texture`, where the layers of the chunk are one below another. `data` holds every layer of the
+/// tilemap, one after another.
+fn write_tilemap_chunk(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    data: &[u8],
+    size: Vec2<u32>,
+    bytes_per_tile: u32,
+    layer: u32,
+    chunk: &TilemapChunk,
+) {
+    if chunk.texture_size.x == 0 || chunk.texture_size.y == 0 {
+        return;
+    }
+    let first_tile = (layer * size.y + chunk.texture_origin.y) as u64 * size.x as u64
+        + chunk.texture_origin.x as u64;
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: 0,
+                y: layer * chunk.texture_size.y,
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        data,
+        wgpu::ImageDataLayout {
+            offset: first_tile * bytes_per_tile as u64,
+            bytes_per_row: Some(size.x * bytes_per_tile),
+            rows_per_image: None,
+        },
+        wgpu::Extent3d {
+            width: chunk.texture_size.x,
+            height: chunk.texture_size.y,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+/// Tilemap pipelines are compiled for each blend mode, stencil (without its reference), and set of
+/// shader features that is drawn.
+type TilemapPipelineKey = (TilemapBlendMode, TilemapStencil, TilemapShaderFeatures);
+
+/// Tilemaps are allocated by their size in tiles, the format of their tiles, their number of layers,
+/// and whether they have a tint texture.
+type TilemapKey = (Vec2<u32>, TileFormat, u32, bool);
+
+/// Tilesets are allocated by their size in pixels, the size of each tile, their number of mip
+/// levels, and the format of their texture.
+type TilesetKey = (Vec2<u32>, Vec2<u32>, u32, wgpu::TextureFormat);
+
+/// Where the mip levels of an uploaded tileset come from.
+#[derive(Copy, Clone)]
+enum TilesetMips<'a> {
+    /// The number of levels to generate by downsampling the tileset.
+    Generate(u32),
+    /// The levels after the first, as in `MippedTileset::mips`.
+    Provided(&'a [Vec<u32>]),
+}
+
+/// An owned `TilesetMips`, kept for `rebuild`.
+enum RetainedMips {
+    Generate(u32),
+    Provided(Vec<Vec<u32>>),
+}
+
+impl RetainedMips {
+    fn new(mips: TilesetMips) -> RetainedMips {
+        match mips {
+            TilesetMips::Generate(levels) => RetainedMips::Generate(levels),
+            TilesetMips::Provided(levels) => RetainedMips::Provided(levels.to_vec()),
+        }
+    }
+
+    fn as_mips(&self) -> TilesetMips<'_> {
+        match self {
+            RetainedMips::Generate(levels) => TilesetMips::Generate(*levels),
+            RetainedMips::Provided(levels) => TilesetMips::Provided(levels),
+        }
+    }
+}
+
+/// The mip levels of a tileset uploaded with `upload_tilesets_streamed` that haven't been uploaded
+/// yet.
+struct StreamingMips {
+    tilesets_index: (TilesetKey, u32),
+    /// The pixels of each level (tile after tile) and the size of its tiles, from the finest level
+    /// to the coarsest one that hasn't been uploaded.
+    levels: Vec<(Vec<u32>, Vec2<u32>)>,
+}
+
+struct TilemapDrawCall {
+    params_buffer: wgpu::Buffer,
+    index_texture: wgpu::Texture,
+    tint_texture: Option<wgpu::Texture>,
+    bind_group: wgpu::BindGroup,
+    tilesets_index: (TilesetKey, u32),
+    /// The generation of the tileset at `tilesets_index` when this was uploaded.
+    tilesets_generation: u64,
+    blend_mode: TilemapBlendMode,
+    /// `TilemapDrawData::stencil`, or `Unchanged` if the pipeline has no stencil buffer.
+    stencil: TilemapStencil,
+    features: TilemapShaderFeatures,
+    /// `TilemapDrawData::tileset` and `TilemapDrawData::transform` of the draw this was uploaded
+    /// for, the part of its tilemap that it covers, and how many bytes of it were uploaded, for
+    /// `TilemapPipeline::active_draws`.
+    tileset: u32,
+    transform: Mat4<f32>,
+    tiles: Rect<u32, u32>,
+    uploaded_bytes: u64,
+    active: bool,
+}
+
+/// Tilemaps drawn once into a texture by `TilemapPipeline::bake`, to be drawn as a single quad
+/// by `TilemapPipeline::render_baked` instead of drawing each of them every frame.
+pub struct BakedLayer {
+    texture: wgpu::Texture,
+    area: Rect<f32, f32>,
+    camera_bind_group: wgpu::BindGroup,
+    tileset_bind_group: wgpu::BindGroup,
+    draw_call: TilemapDrawCall,
+    /// The larger texture the tilemaps are drawn into and the bind group that filters it down into
+    /// `texture`, for layers baked with a `DownscaleFilter` other than `Direct`.
+    downscale: Option<(wgpu::Texture, wgpu::BindGroup)>,
+    /// The positions of the baked tilemaps, their `TilemapPipeline::draw_hashes` and the
+    /// generation of the tilesets when they were baked, for `TilemapPipeline::is_stale`.
+    draws: Range<usize>,
+    draw_hashes: Vec<u64>,
+    tileset_generation: u64,
+}
+
+impl BakedLayer {
+    /// The part of the world that was baked, which the layer is drawn over.
+    pub fn area(&self) -> Rect<f32, f32> {
+        self.area
+    }
+
+    /// The size of the texture the layer was baked into, in pixels.
+    pub fn pixel_size(&self) -> Vec2<u32> {
+        Vec2::new(self.texture.width(), self.texture.height())
+    }
+}
+
+struct TilesetCache {
+    params_buffer: wgpu::Buffer,
+    data_texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    /// Set by `set_tileset_materials`.
+    materials: Option<wgpu::Texture>,
+    /// Set by `set_tileset_team_mask`.
+    team_mask: Option<wgpu::Texture>,
+    /// Set by `set_tileset_normal_map`.
+    normal_map: Option<wgpu::Texture>,
+    /// Set by `set_tileset_emissive`.
+    emissive: Option<wgpu::Texture>,
+    /// The `upload_tilesets` call that last wrote this tileset.
+    generation: u64,
+    active: bool,
+}
+
+impl TilesetCache {
+    /// Recreate the bind group, after the materials, the team mask, the normal map, the emissive
+    /// texture, or the sampler have changed.
+    fn rebind(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        placeholders: &TilesetPlaceholders,
+        sampler: &wgpu::Sampler,
+    ) {
+        let materials_view = self
+            .materials
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let array_view = |texture: &wgpu::Texture| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            })
+        };
+        let team_mask_view = self.team_mask.as_ref().map(array_view);
+        let normal_map_view = self.normal_map.as_ref().map(array_view);
+        let emissive_view = self.emissive.as_ref().map(array_view);
+        let views = TilesetViews {
+            materials: materials_view
+                .as_ref()
+                .unwrap_or(&placeholders.materials_view),
+            team_mask: team_mask_view
+                .as_ref()
+                .unwrap_or(&placeholders.team_mask_view),
+            normal_map: normal_map_view
+                .as_ref()
+                .unwrap_or(&placeholders.normal_map_view),
+            emissive: emissive_view
+                .as_ref()
+                .unwrap_or(&placeholders.emissive_view),
+        };
+        self.bind_group = create_tileset_bind_group(
+            device,
+            layout,
+            &self.params_buffer,
+            &self.data_texture,
+            views,
+            sampler,
+        );
+    }
+}
+
+/// Bound in place of the optional textures of tilesets that don't have them.
+struct TilesetPlaceholders {
+    /// A single zero texel, for tilesets without a material table.
+    materials_view: wgpu::TextureView,
+    /// Nothing to recolor, for tilesets without a team mask.
+    team_mask_view: wgpu::TextureView,
+    /// Never read, since the shader checks `TilesetBuffer::has_normal_map` first.
+    normal_map_view: wgpu::TextureView,
+    /// Never read, since the shader checks `TilesetBuffer::has_emissive` first.
+    emissive_view: wgpu::TextureView,
+}
+
+impl TilesetPlaceholders {
+    fn views(&self) -> TilesetViews<'_> {
+        TilesetViews {
+            materials: &self.materials_view,
+            team_mask: &self.team_mask_view,
+            normal_map: &self.normal_map_view,
+            emissive: &self.emissive_view,
+        }
+    }
+}
+
+/// The optional textures bound with a tileset, or their placeholders.
+#[derive(Copy, Clone)]
+struct TilesetViews<'a> {
+    materials: &'a wgpu::TextureView,
+    team_mask: &'a wgpu::TextureView,
+    normal_map: &'a wgpu::TextureView,
+    emissive: &'a wgpu::TextureView,
+}
+
+impl HasTextureAllocation for TilemapDrawCall {
+    type Params = TilemapBuffer;
+    fn active(&self) -> bool {
+        self.active
+    }
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+    fn params_buffer(&self) -> &wgpu::Buffer {
+        &self.params_buffer
+    }
+    fn texture(&self) -> &wgpu::Texture {
+        &self.index_texture
+    }
+}
+
+impl HasTextureAllocation for TilesetCache {
+    type Params = TilesetBuffer;
+    fn active(&self) -> bool {
+        self.active
+    }
+    fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+    fn params_buffer(&self) -> &wgpu::Buffer {
+        &self.params_buffer
+    }
+    fn texture(&self) -> &wgpu::Texture {
+        &self.data_texture
+    }
+}
+
+impl TilemapPipeline {
+    /// Create a new `TilemapPipeline` capable of rendering to the provided `texture_format`.
+    pub fn new(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+    ) -> TilemapPipeline {
+        let descriptor =
+            TilemapPipelineDescriptor::new(texture_format).with_depth_stencil(depth_stencil);
+        Self::from_descriptor(device, &descriptor)
+    }
+
+    /// Create a new `TilemapPipeline` whose fragment shader passes the color of each pixel of a
+    /// tilemap through `hook`, which is WGSL source defining
+    /// `fn tilemap_hook(color: vec4<f32>, tilepos: vec2<f32>) -> vec4<f32>`. `color` is the
+    /// premultiplied color of the tile (before opacity is applied) and `tilepos` is the position
+    /// within the tilemap in tiles. The hook can read the `TilemapDrawData::user_data` of the draw
+    /// as `tilemap.user_data`, an `array<vec4<u32>, 4>` (use `bitcast` to read other types), and
+    /// the `TileMaterial` of the tile at `tilepos` as `tilemap_material(tilepos)`, a struct with
+    /// `emissive`, `sway`, `reflectivity`, and `height` fields (see `set_tileset_materials`), the
+    /// normal of the tile's pixel at `tilepos` as `tilemap_normal(tilepos)`, a `vec3<f32>` with y
+    /// pointing up (see `set_tileset_normal_map`), the linear light the tiles emit at `tilepos` as
+    /// `tilemap_emissive(tilepos)`, a `vec3<f32>` (see `set_tileset_emissive`), and the time
+    /// and frame as `frame.time`, `frame.delta_time`, and `frame.index` (see `set_frame_context`).
+    pub fn with_fragment_hook(
+        device: &wgpu::Device,
+        texture_format: wgpu::TextureFormat,
+        depth_stencil: Option<wgpu::DepthStencilState>,
+        hook: &str,
+    ) -> TilemapPipeline {
+        let descriptor = TilemapPipelineDescriptor::new(texture_format)
+            .with_depth_stencil(depth_stencil)
+            .with_fragment_hook(hook);
+        Self::from_descriptor(device, &descriptor)
+    }
+
+    /// Create a new `TilemapPipeline` with the settings of `descriptor`, which compiles its
+    /// shaders once with those settings instead of again for each setter called after `new`.
+    pub fn from_descriptor(
+        device: &wgpu::Device,
+        descriptor: &TilemapPipelineDescriptor,
+    ) -> TilemapPipeline {
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("camera_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                ::std::mem::size_of::<CameraBuffer>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D3,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                ::std::mem::size_of::<FrameBuffer>() as u64
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let frame_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_frame_buffer"),
+            size: ::std::mem::size_of::<FrameBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_camera_buffer"),
+            size: ::std::mem::size_of::<CameraBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let color_lut_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("tilemap_color_lut_sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let camera_bind_group = create_camera_bind_group(
+            device,
+            &camera_bind_group_layout,
+            &camera_buffer,
+            &create_color_lut(device, None).1,
+            &color_lut_sampler,
+            &frame_buffer,
+        );
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("vertex_buffer"),
+            size: 0,
+            usage: wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        let tileset_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("tileset_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                ::std::mem::size_of::<TilesetBuffer>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 6,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let tile_sampler = descriptor.tile_filter.create_sampler(device);
+        let tilemap_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("tilemap_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                ::std::mem::size_of::<TilemapBuffer>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Uint,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let placeholder_tint_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("tilemap_placeholder_tint_texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let placeholder_materials_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_placeholder_materials_texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Nothing to recolor, in two layers so that the GL backend creates an array texture.
+        let placeholder_team_mask_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_placeholder_team_mask_texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 2,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+        let placeholder_normal_map_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_placeholder_normal_map_texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 2,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+        let placeholder_emissive_view = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_placeholder_emissive_texture"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 2,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::D2Array),
+                ..Default::default()
+            });
+        let backdrop_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("backdrop_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+            });
+        let downscale_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("tilemap_downscale_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                ::std::mem::size_of::<DownscaleBuffer>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let tilemap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tilemap_pipeline_layout"),
+                bind_group_layouts: &[
+                    &camera_bind_group_layout,
+                    &tileset_bind_group_layout,
+                    &tilemap_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let backdrop_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tilemap_backdrop_pipeline_layout"),
+                bind_group_layouts: &[
+                    &camera_bind_group_layout,
+                    &tileset_bind_group_layout,
+                    &tilemap_bind_group_layout,
+                    &backdrop_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let draw_calls = FirstFitTextureAllocator::new();
+        let tilesets = FirstFitTextureAllocator::new();
+        let mut pipeline = TilemapPipeline {
+            camera_buffer,
+            camera_bind_group_layout,
+            camera_bind_group,
+            frame_buffer,
+            color_lut_size: 0,
+            color_lut_sampler,
+            vertex_buffer,
+            tileset_bind_group_layout,
+            tilemap_bind_group_layout,
+            placeholder_tint_view,
+            tileset_placeholders: TilesetPlaceholders {
+                materials_view: placeholder_materials_view,
+                team_mask_view: placeholder_team_mask_view,
+                normal_map_view: placeholder_normal_map_view,
+                emissive_view: placeholder_emissive_view,
+            },
+            tile_sampler,
+            tile_filter: descriptor.tile_filter,
+            backdrop_bind_group_layout,
+            backdrop_bind_group: None,
+            texture_format: descriptor.texture_format,
+            depth_stencil: descriptor.depth_stencil.clone(),
+            fragment_hook: descriptor.fragment_hook.to_owned(),
+            encode_srgb: descriptor.encode_srgb,
+            target_origin: descriptor.target_origin,
+            emissive_target: descriptor.emissive_target,
+            multisample: descriptor.multisample,
+            downscale_filter: descriptor.downscale_filter,
+            downscale_bind_group_layout,
+            downscale_pipeline: None,
+            tilemap_pipeline_layout,
+            backdrop_pipeline_layout,
+            shader_modules: BTreeMap::new(),
+            tilemap_pipelines: BTreeMap::new(),
+            picking: descriptor.picking,
+            picking_pipelines: BTreeMap::new(),
+            bake_shader_modules: BTreeMap::new(),
+            bake_pipelines: BTreeMap::new(),
+            tilesets,
+            active_tilesets: Vec::new(),
+            tileset_generation: 0,
+            tileset_names: HashMap::new(),
+            tileset_analyses: Vec::new(),
+            allocation_observer: None,
+            tilemap_allocation: AllocationStrategy::default(),
+            tileset_allocation: AllocationStrategy::default(),
+            tilemaps_version: None,
+            presentation: None,
+            present_target_size: Vec2::zero(),
+            retained: None,
+            streaming_mips: Vec::new(),
+            chunk_scratch: Vec::new(),
+            pixel_scratch: Vec::new(),
+            draw_calls,
+            active_draw_calls: Vec::new(),
+            draw_hashes: Vec::new(),
+            rejected_draws: Vec::new(),
+            max_tilemap_texture_size: None,
+        };
+        // Compile the most common permutation up front, so that errors in the fragment hook show up
+        // here.
+        pipeline.prepare_shader_features(device, TilemapShaderFeatures::default());
+        pipeline
+    }
+
+    /// Compile the shaders for `features` (for every `TilemapBlendMode`) ahead of time, so that
+    /// the first tilemap drawn with them doesn't have to wait for them to compile.
+    pub fn prepare_shader_features(
+        &mut self,
+        device: &wgpu::Device,
+        features: TilemapShaderFeatures,
+    ) {
+        for blend_mode in TilemapBlendMode::ALL {
+            self.prepare_pipeline(device, (blend_mode, TilemapStencil::Unchanged, features));
+        }
+    }
+
+    /// Free every texture and buffer allocated for tilesets, tilemaps, the backdrop, and the color
+    /// LUT (e.g. when returning to the main menu), so that nothing is drawn until tilesets and
+    /// tilemaps are uploaded again. The compiled shaders, camera, and settings of the pipeline are
+    /// kept.
+    pub fn clear(&mut self, device: &wgpu::Device) {
+        self.draw_calls = FirstFitTextureAllocator::new();
+        self.active_draw_calls.clear();
+        self.draw_hashes.clear();
+        self.rejected_draws.clear();
+        self.tilesets = FirstFitTextureAllocator::new();
+        self.active_tilesets.clear();
+        self.tileset_generation += 1;
+        self.tileset_names.clear();
+        self.tileset_analyses.clear();
+        self.streaming_mips.clear();
+        self.tilemaps_version = None;
+        self.backdrop_bind_group = None;
+        if let Some(retained) = &mut self.retained {
+            retained.tilesets.clear();
+            retained.tilemaps.clear();
+            retained.color_lut = None;
+            retained.materials.clear();
+            retained.team_masks.clear();
+            retained.normal_maps.clear();
+            retained.emissive.clear();
+        }
+        if self.color_lut_size != 0 {
+            self.color_lut_size = 0;
+            self.camera_bind_group = create_camera_bind_group(
+                device,
+                &self.camera_bind_group_layout,
+                &self.camera_buffer,
+                &create_color_lut(device, None).1,
+                &self.color_lut_sampler,
+                &self.frame_buffer,
+            );
+        }
+    }
+
+    /// Recreate this pipeline on `device`, after the device it was created with has been lost (in
+    /// which case every GPU resource of the pipeline is unusable, and `clear` isn't enough). The
+    /// texture format, depth stencil state, fragment hook, sRGB encoding, target origin, emissive
+    /// target, multisample state, tile filter, downscale filter, picking, allocation strategies,
+    /// allocation observer, and present params are kept, but like after
+    /// `clear`, tilesets and tilemaps have to be uploaded again, and the camera and color LUT have
+    /// to be set again.
+    ///
+    /// To recover from device loss, request a new adapter and device, reconfigure the surface,
+    /// call this, and then upload everything as when the game started (or call `rebuild` instead,
+    /// to restore what was uploaded since `set_retain_uploads`).
+    pub fn recover(&mut self, device: &wgpu::Device) {
+        let mut pipeline = Self::from_descriptor(device, &self.descriptor());
+        pipeline.allocation_observer = self.allocation_observer.take();
+        pipeline.tilemap_allocation = self.tilemap_allocation;
+        pipeline.tileset_allocation = self.tileset_allocation;
+        pipeline.max_tilemap_texture_size = self.max_tilemap_texture_size;
+        pipeline.presentation = self.presentation;
+        pipeline.present_target_size = self.present_target_size;
+        pipeline.tileset_generation = self.tileset_generation + 1;
+        pipeline.retained = self.retained.take().map(|_| RetainedUploads::default());
+        *self = pipeline;
+    }
+
+    /// The current settings of this pipeline, which `from_descriptor` creates a pipeline with.
+    pub fn descriptor(&self) -> TilemapPipelineDescriptor<'_> {
+        TilemapPipelineDescriptor {
+            texture_format: self.texture_format,
+            depth_stencil: self.depth_stencil.clone(),
+            fragment_hook: &self.fragment_hook,
+            encode_srgb: self.encode_srgb,
+            target_origin: self.target_origin,
+            emissive_target: self.emissive_target,
+            multisample: self.multisample,
+            tile_filter: self.tile_filter,
+            downscale_filter: self.downscale_filter,
+            picking: self.picking,
+        }
+    }
+
+    /// Keep CPU copies of the tilesets, tilemaps, camera, and color LUT set from now on (or stop
+    /// keeping them), so that `rebuild` can restore them after device loss without the application
+    /// uploading its assets again. This copies the data of every upload, so it costs memory and
+    /// time in proportion to what is uploaded.
+    pub fn set_retain_uploads(&mut self, retain: bool) {
+        if retain != self.retained.is_some() {
+            self.retained = retain.then(RetainedUploads::default);
+        }
+    }
+
+    /// Recreate this pipeline on `device` like `recover`, and then upload the tilesets, tilemaps,
+    /// camera, and color LUT kept since `set_retain_uploads`, so that it draws the same as before
+    /// the device was lost. Tilesets get the same mip levels as before, all uploaded at once (even
+    /// those that were still being streamed). The shaders compiled before are compiled again. The backdrop (see
+    /// `set_backdrop`) and allocations reserved with `reserve_tilemaps` or `reserve_tilesets`
+    /// aren't restored. If `device` rejects the tilesets (e.g. because it has lower limits than the
+    /// lost one), nothing is uploaded after them and the copies are kept.
+    pub fn rebuild(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(), TilesetError> {
+        let retained = self.retained.take();
+        let tileset_names = std::mem::take(&mut self.tileset_names);
+        let tilemaps_version = self.tilemaps_version;
+        let compiled: Vec<_> = self.tilemap_pipelines.keys().copied().collect();
+        self.recover(device);
+        for key in compiled {
+            self.prepare_pipeline(device, key);
+        }
+        let Some(retained) = retained else {
+            return Ok(());
+        };
+        // Upload while nothing is retained, so that the copies aren't copied again.
+        if let Some(camera) = *retained.camera.lock().unwrap() {
+            self.set_camera(queue, camera);
+        }
+        if let Some(viewport_size) = *retained.viewport_size.lock().unwrap() {
+            self.write_viewport_size(queue, viewport_size);
+        }
+        if retained.color_lut.is_some() {
+            self.set_color_lut(device, queue, retained.color_lut.as_ref());
+        }
+        if !retained.tilesets.is_empty() {
+            let tilesets = retained.tilesets.iter();
+            let uploaded =
+                self.upload_tileset_list(device, queue, tilesets.map(|(t, m)| (t, m.as_mips())));
+            if let Err(e) = uploaded {
+                self.retained = Some(retained);
+                return Err(e);
+            }
+            // Every level was (or was going to be) uploaded before the device was lost.
+            self.stream_tileset_mips(queue, u64::MAX);
+            self.tileset_names = tileset_names;
+            for (tileset, materials) in &retained.materials {
+                self.set_tileset_materials(device, queue, *tileset, Some(materials));
+            }
+            for (tileset, mask) in &retained.team_masks {
+                self.set_tileset_team_mask(device, queue, *tileset, Some(mask));
+            }
+            for (tileset, normal_map) in &retained.normal_maps {
+                self.set_tileset_normal_map(device, queue, *tileset, Some(normal_map));
+            }
+            for (tileset, emissive) in &retained.emissive {
+                self.set_tileset_emissive(device, queue, *tileset, Some(emissive));
+            }
+        }
+        if !retained.tilemaps.is_empty() {
+            self.upload_tilemaps(device, queue, &retained.tilemaps);
+            self.tilemaps_version = tilemaps_version;
+        }
+        self.retained = Some(retained);
+        Ok(())
+    }
+
+    /// Encode colors as sRGB in the fragment shader, for render targets whose format isn't sRGB
+    /// (e.g. `Bgra8Unorm`, the only format some Android and WebGL surfaces offer), so that they
+    /// are drawn with the same colors as on an sRGB target instead of too dark. Blending then
+    /// happens in sRGB space, as it does on those platforms anyway. The shaders compiled before
+    /// are compiled again.
+    pub fn set_encode_srgb(&mut self, device: &wgpu::Device, encode_srgb: bool) {
+        if encode_srgb == self.encode_srgb {
+            return;
+        }
+        self.encode_srgb = encode_srgb;
+        self.recompile_shaders(device);
+    }
+
+    /// Draw for a render target whose first row of pixels is displayed at `origin`, e.g.
+    /// `TargetOrigin::BottomLeft` for an offscreen texture that is composited by code that expects
+    /// the first row at the bottom, so that it looks the same as drawing to the surface directly.
+    /// The viewport of `set_present_params` is flipped as well. The shaders compiled before are
+    /// compiled again.
+    pub fn set_target_origin(&mut self, device: &wgpu::Device, origin: TargetOrigin) {
+        if origin == self.target_origin {
+            return;
+        }
+        self.target_origin = origin;
+        self.recompile_shaders(device);
+    }
+
+    /// Write the emissive light of the tiles (see `set_tileset_emissive`) to a second color
+    /// attachment of this format, e.g. for a bloom pass, instead of adding it to their color, or
+    /// add it to their color again if `format` is `None`. The light is written as linear colors
+    /// (never encoded as sRGB by the shader), with the alpha of the tiles so that tilemaps drawn
+    /// with `TilemapBlendMode::Alpha`, `Overlay`, or `Opaque` hide the light of what is below
+    /// them, while tilemaps drawn with the other blend modes only add their own. Render passes
+    /// given to `render` must then have the emissive target as their second color attachment.
+    /// Picking and baking are unaffected. The shaders compiled before are compiled again.
+    pub fn set_emissive_target(
+        &mut self,
+        device: &wgpu::Device,
+        format: Option<wgpu::TextureFormat>,
+    ) {
+        if format == self.emissive_target {
+            return;
+        }
+        self.emissive_target = format;
+        self.recompile_shaders(device);
+    }
+
+    /// Render into multisampled attachments with `multisample` from now on (e.g. with a
+    /// `count` of 4, to draw into the same render pass as the rest of a renderer that uses 4x
+    /// MSAA). The color attachments (and depth stencil attachment, if any) of render passes given
+    /// to `render` must then have that sample count. Picking and baking are unaffected. The
+    /// shaders compiled before are compiled again.
+    pub fn set_multisample(&mut self, device: &wgpu::Device, multisample: wgpu::MultisampleState) {
+        if multisample == self.multisample {
+            return;
+        }
+        self.multisample = multisample;
+        self.recompile_shaders(device);
+    }
+
+    /// Shrink the tilemaps of layers baked from now on with `filter` (see `DownscaleFilter`), e.g.
+    /// for minimaps and zoomed out views of large maps that bake many tiles into few pixels.
+    /// Layers baked before keep their filter when they are baked again with `rebake`.
+    pub fn set_downscale_filter(&mut self, filter: DownscaleFilter) {
+        self.downscale_filter = filter;
+    }
+
+    /// Filter the pixels of tiles with `filter` from now on (see `TileFilter`). The shaders
+    /// compiled before are compiled again, and layers baked before keep their filtering.
+    pub fn set_tile_filter(&mut self, device: &wgpu::Device, filter: TileFilter) {
+        if filter == self.tile_filter {
+            return;
+        }
+        self.tile_filter = filter;
+        self.tile_sampler = filter.create_sampler(device);
+        let (layout, placeholders, sampler) = (
+            &self.tileset_bind_group_layout,
+            &self.tileset_placeholders,
+            &self.tile_sampler,
+        );
+        self.tilesets
+            .for_each_mut(|datum| datum.rebind(device, layout, placeholders, sampler));
+        self.recompile_shaders(device);
+    }
+
+    fn recompile_shaders(&mut self, device: &wgpu::Device) {
+        self.shader_modules.clear();
+        self.picking_pipelines.clear();
+        self.bake_shader_modules.clear();
+        self.bake_pipelines.clear();
+        let compiled: Vec<_> = std::mem::take(&mut self.tilemap_pipelines)
+            .into_keys()
+            .collect();
+        for key in compiled {
+            self.prepare_pipeline(device, key);
+        }
+    }
+
+    fn prepare_pipeline(&mut self, device: &wgpu::Device, key: TilemapPipelineKey) {
+        let (blend_mode, stencil, features) = key;
+        if self.picking {
+            self.prepare_picking_pipeline(device, features);
+        }
+        if self.tilemap_pipelines.contains_key(&key) {
+            return;
+        }
+        self.prepare_shader_module(device, features);
+        let shader_module = &self.shader_modules[&features];
+        let layout = match blend_mode {
+            TilemapBlendMode::Overlay => &self.backdrop_pipeline_layout,
+            _ => &self.tilemap_pipeline_layout,
+        };
+        let mut targets = vec![Some(wgpu::ColorTargetState {
+            format: self.texture_format,
+            blend: Some(blend_mode.blend_state()),
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+        targets.extend(self.emissive_target.map(|format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend_mode.emissive_blend_state()),
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        }));
+        // Opaque tilemaps write their depth, for what is drawn after them to be tested against.
+        let mut depth_stencil = self.depth_stencil.clone();
+        if let Some(state) = &mut depth_stencil {
+            state.depth_write_enabled |= blend_mode == TilemapBlendMode::Opaque;
+            state.stencil = stencil.stencil_state(state.stencil.clone());
+        }
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tilemap_pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "tilemap_vert_main",
+                buffers: std::slice::from_ref(&VERTEX_LAYOUT),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil,
+            multisample: self.multisample,
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point: blend_mode.fragment_entry_point(self.emissive_target.is_some()),
+                targets: &targets,
+            }),
+            multiview: None,
+        });
+        self.tilemap_pipelines.insert(key, pipeline);
+    }
+
+    fn prepare_picking_pipeline(&mut self, device: &wgpu::Device, features: TilemapShaderFeatures) {
+        if self.picking_pipelines.contains_key(&features) {
+            return;
+        }
+        self.prepare_shader_module(device, features);
+        let shader_module = &self.shader_modules[&features];
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tilemap_picking_pipeline"),
+            layout: Some(&self.tilemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "tilemap_vert_main",
+                buffers: std::slice::from_ref(&VERTEX_LAYOUT),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point: "tilemap_frag_pick",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: PICKING_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        self.picking_pipelines.insert(features, pipeline);
+    }
+
+    fn prepare_bake_pipeline(
+        &mut self,
+        device: &wgpu::Device,
+        blend_mode: TilemapBlendMode,
+        features: TilemapShaderFeatures,
+    ) {
+        if self.bake_pipelines.contains_key(&(blend_mode, features)) {
+            return;
+        }
+        let shader_module = self.bake_shader_modules.entry(features).or_insert_with(|| {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("bake_shaders"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(features.shader_source(
+                    &self.fragment_hook,
+                    false,
+                    TargetOrigin::TopLeft,
+                    self.tile_filter,
+                    false,
+                ))),
+            })
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tilemap_bake_pipeline"),
+            layout: Some(&self.tilemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader_module,
+                entry_point: "tilemap_vert_main",
+                buffers: std::slice::from_ref(&VERTEX_LAYOUT),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader_module,
+                entry_point: blend_mode.fragment_entry_point(false),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: Some(blend_mode.blend_state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        self.bake_pipelines.insert((blend_mode, features), pipeline);
+    }
+
+    fn prepare_downscale_pipeline(&mut self, device: &wgpu::Device) {
+        if self.downscale_pipeline.is_some() {
+            return;
+        }
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("downscale_shaders"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("downscale.wgsl"))),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tilemap_downscale_pipeline_layout"),
+            bind_group_layouts: &[&self.downscale_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tilemap_downscale_pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "downscale_vert_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "downscale_frag_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        self.downscale_pipeline = Some(pipeline);
+    }
+
+    fn prepare_shader_module(&mut self, device: &wgpu::Device, features: TilemapShaderFeatures) {
+        self.shader_modules.entry(features).or_insert_with(|| {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("shaders"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(features.shader_source(
+                    &self.fragment_hook,
+                    self.encode_srgb,
+                    self.target_origin,
+                    self.tile_filter,
+                    self.emissive_target.is_some(),
+                ))),
+            })
+        });
+    }
+    fn allocate_tilesets(
+        device: &wgpu::Device,
+        tileset_bind_group_layout: &wgpu::BindGroupLayout,
+        placeholders: &TilesetPlaceholders,
+        tile_sampler: &wgpu::Sampler,
+        allocation_observer: &mut Option<AllocationObserver>,
+        (size, tilesize, mip_levels, format): TilesetKey,
+    ) -> TilesetCache {
+        let tiles = (size / tilesize).product() as u64;
+        let tile_bytes: u64 = (0..mip_levels)
+            .map(|level| image_bytes(format, mip_size(tilesize, level)).0)
+            .sum();
+        notify_allocation(
+            allocation_observer,
+            AllocationKind::Tileset,
+            size,
+            tiles * tile_bytes + std::mem::size_of::<TilesetBuffer>() as u64,
+        );
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tileset_params_buffer"),
+            size: ::std::mem::size_of::<TilesetBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let data_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("tileset_data_texture"),
+            //size: wgpu::Extent3d { width: 1368, height: 768, depth_or_array_layers: 1 },
+            size: wgpu::Extent3d {
+                width: tilesize.x,
+                height: tilesize.y,
+                depth_or_array_layers: (size.x / tilesize.x) * (size.y / tilesize.y),
+            },
+            mip_level_count: mip_levels,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let bind_group = create_tileset_bind_group(
+            device,
+            tileset_bind_group_layout,
+            &params_buffer,
+            &data_texture,
+            placeholders.views(),
+            tile_sampler,
+        );
+        TilesetCache {
+            params_buffer,
+            data_texture,
+            bind_group,
+            materials: None,
+            team_mask: None,
+            normal_map: None,
+            emissive: None,
+            generation: 0,
+            active: false,
+        }
+    }
+
+    /// Upload a list of tilesets to the GPU, replacing the previous set of tilesets, and reusing texture allocations if the sizes are compatible.
+    /// Tilemaps uploaded before this aren't drawn until they are uploaded again (see `stale_draws`).
+    /// Tilesets with `TilesetRef::compressed` tiles are uploaded in their format if the device was
+    /// created with the feature it needs (e.g. `wgpu::Features::TEXTURE_COMPRESSION_BC`), and as
+    /// RGBA otherwise. Each tile is a layer of the texture of its tileset, so tilesets with more
+    /// tiles than the `max_texture_array_layers` of the device are rejected, along with the rest
+    /// of the list.
+    pub fn upload_tilesets(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: &[TilesetRef],
+    ) -> Result<(), TilesetError> {
+        let tilesets = tilesets.iter().map(|t| (t, TilesetMips::Generate(1)));
+        self.upload_tileset_list(device, queue, tilesets)?;
+        self.tileset_names.clear();
+        Ok(())
+    }
+
+    /// Upload a list of tilesets along with their names, like `upload_tilesets`. The index of each
+    /// tileset can then be looked up by name with `tileset_index`, so that maps can refer to their
+    /// tilesets by name (as Tiled maps do) instead of by position in the list.
+    pub fn upload_tilesets_named<S: AsRef<str>>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: &[(S, TilesetRef)],
+    ) -> Result<(), TilesetError> {
+        self.upload_tileset_list(
+            device,
+            queue,
+            tilesets
+                .iter()
+                .map(|(_, tileset)| (tileset, TilesetMips::Generate(1))),
+        )?;
+        self.tileset_names.clear();
+        self.tileset_names.extend(
+            tilesets
+                .iter()
+                .enumerate()
+                .map(|(i, (name, _))| (name.as_ref().to_owned(), i as u32)),
+        );
+        Ok(())
+    }
+
+    /// Upload a list of tilesets like `upload_tilesets`, but with `mip_levels` levels of detail
+    /// (each half the size of the one before, down to at most 1x1 pixel tiles) generated by
+    /// downsampling. Tiles drawn smaller than their pixels (e.g. with the camera zoomed far out)
+    /// are then blended between the nearest levels with trilinear filtering instead of shimmering,
+    /// while tiles drawn at their size or larger stay crisp (see `set_tile_filter`). Compressed
+    /// tiles are only uploaded without mip levels, so tilesets with more than one level are
+    /// uploaded as RGBA.
+    pub fn upload_tilesets_mipped(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: &[TilesetRef],
+        mip_levels: u32,
+    ) -> Result<(), TilesetError> {
+        self.upload_tilesets_streamed(device, queue, tilesets, mip_levels)?;
+        self.stream_tileset_mips(queue, u64::MAX);
+        Ok(())
+    }
+
+    /// Upload a list of tilesets like `upload_tilesets_mipped`, but with only the coarsest level
+    /// of detail uploaded now. Tilesets are drawn with the finest level uploaded so far, and
+    /// `stream_tileset_mips` uploads the finer levels over the following frames, so that very
+    /// large tilesets show usable (if blurry) art immediately on slow platforms.
+    pub fn upload_tilesets_streamed(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: &[TilesetRef],
+        mip_levels: u32,
+    ) -> Result<(), TilesetError> {
+        let tilesets = tilesets
+            .iter()
+            .map(|t| (t, TilesetMips::Generate(mip_levels)));
+        self.upload_tileset_list(device, queue, tilesets)?;
+        self.tileset_names.clear();
+        Ok(())
+    }
+
+    /// Upload a list of tilesets like `upload_tilesets_streamed`, but with the mip levels that
+    /// came with them (e.g. from a KTX2 or DDS file) instead of generated ones. Call
+    /// `stream_tileset_mips(queue, u64::MAX)` afterwards to upload every level at once. Tilesets
+    /// with a level of the wrong size for their tiles are rejected, along with the rest of the
+    /// list.
+    pub fn upload_mipped_tilesets_streamed(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: &[MippedTileset],
+    ) -> Result<(), TilesetError> {
+        let tilesets = tilesets
+            .iter()
+            .map(|t| (&t.tileset, TilesetMips::Provided(&t.mips)));
+        self.upload_tileset_list(device, queue, tilesets)?;
+        self.tileset_names.clear();
+        Ok(())
+    }
+
+    /// Replace the tileset at index `tileset` (in the last `upload_tilesets` call) with
+    /// `replacement`, e.g. once a tileset that was still being decoded when the tilesets were
+    /// uploaded is ready, after drawing a `TilesetRef::placeholder` in its place. Unlike uploading
+    /// the tilesets again, the tilemaps uploaded since then keep being drawn, now with
+    /// `replacement`, so assets can stream in over the following frames without the tilemaps
+    /// being uploaded again. `replacement` gets as many mip levels as the tileset it replaces,
+    /// which `stream_tileset_mips` uploads after the coarsest one, and the material table, team
+    /// mask, normal map, and emissive texture of the tileset are removed. A replacement of the
+    /// same size reuses the allocation of the tileset. Does nothing if there is no tileset at that
+    /// index, and keeps the old tileset if `replacement` is rejected like in `upload_tilesets`.
+    pub fn replace_tileset(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: u32,
+        replacement: &TilesetRef,
+    ) -> Result<(), TilesetError> {
+        let Some(&old) = self.active_tilesets.get(tileset as usize) else {
+            return Ok(());
+        };
+        check_tileset(
+            device,
+            tileset as usize,
+            replacement,
+            TilesetMips::Generate(1),
+        )?;
+        let (key, i) = old;
+        if let Some(datum) = self
+            .tilesets
+            .map
+            .get_mut(&key)
+            .and_then(|data| data.get_mut(i as usize))
+        {
+            datum.set_active(false);
+        }
+        self.streaming_mips
+            .retain(|streaming| streaming.tilesets_index != old);
+        let (_, _, mip_levels, _) = key;
+        let mips = TilesetMips::Generate(mip_levels);
+        let new = self.upload_tileset(device, queue, replacement, mips);
+        self.active_tilesets[tileset as usize] = new;
+        replacement.analyze_into(&mut self.tileset_analyses[tileset as usize]);
+        // The draws of the old tileset are still current, so they are moved to the new one.
+        self.draw_calls.for_each_mut(|call| {
+            if call.tilesets_index == old {
+                call.tilesets_index = new;
+            }
+        });
+        if let Some(retained) = &mut self.retained {
+            if let Some(retained_tileset) = retained.tilesets.get_mut(tileset as usize) {
+                *retained_tileset = (replacement.clone().into_owned(), RetainedMips::new(mips));
+            }
+            retained.materials.retain(|(t, _)| *t != tileset);
+            retained.team_masks.retain(|(t, _)| *t != tileset);
+            retained.normal_maps.retain(|(t, _)| *t != tileset);
+            retained.emissive.retain(|(t, _)| *t != tileset);
+        }
+        Ok(())
+    }
+
+    /// Upload the next finer levels of the tilesets uploaded with `upload_tilesets_streamed`, until
+    /// `max_bytes` have been uploaded (but at least one level, if any are left). Every tileset is
+    /// refined by a level before any tileset is refined by two. Returns whether every level has
+    /// been uploaded.
+    pub fn stream_tileset_mips(&mut self, queue: &wgpu::Queue, max_bytes: u64) -> bool {
+        let mut uploaded = 0;
+        while let Some(stream) = (0..self.streaming_mips.len())
+            .max_by_key(|&i| (self.streaming_mips[i].levels.len(), std::cmp::Reverse(i)))
+        {
+            let streaming = &mut self.streaming_mips[stream];
+            let bytes = streaming
+                .levels
+                .last()
+                .map_or(0, |(pixels, _)| pixels.len() as u64 * 4);
+            if uploaded > 0 && uploaded + bytes > max_bytes {
+                break;
+            }
+            uploaded += bytes;
+            let (pixels, size) = streaming.levels.pop().unwrap();
+            let level = streaming.levels.len() as u32;
+            let (key, i) = streaming.tilesets_index;
+            if level == 0 {
+                self.streaming_mips.remove(stream);
+            }
+            let Some(datum) = self
+                .tilesets
+                .map
+                .get_mut(&key)
+                .and_then(|data| data.get_mut(i as usize))
+            else {
+                continue;
+            };
+            let tiles = datum.data_texture.depth_or_array_layers();
+            let data = bytemuck::cast_slice(&pixels);
+            write_tileset_level(queue, &datum.data_texture, level, data, size, tiles);
+            queue.write_buffer(
+                &datum.params_buffer,
+                std::mem::offset_of!(TilesetBuffer, mip_level) as u64,
+                bytemuck::bytes_of(&level),
+            );
+        }
+        self.streaming_mips.is_empty()
+    }
+
+    /// Call `observer` whenever new GPU resources are created for tilemaps or tilesets, e.g. to
+    /// log allocations, or to check that none happen once a game has warmed up.
+    pub fn set_allocation_observer(
+        &mut self,
+        observer: impl FnMut(&TilemapAllocation) + Send + Sync + 'static,
+    ) {
+        self.allocation_observer = Some(Box::new(observer));
+    }
+
+    /// Stop calling the observer set with `set_allocation_observer`.
+    pub fn clear_allocation_observer(&mut self) {
+        self.allocation_observer = None;
+    }
+
+    /// Choose how textures are sized for tilemaps or tilesets. Sizes of tilesets are rounded as
+    /// their number of columns and rows of tiles, and only tilesets with the same size of tile
+    /// share allocations.
+    pub fn set_allocation_strategy(&mut self, kind: AllocationKind, strategy: AllocationStrategy) {
+        match kind {
+            AllocationKind::Tilemap => self.tilemap_allocation = strategy,
+            AllocationKind::Tileset => self.tileset_allocation = strategy,
+        }
+    }
+
+    /// Limit the width and height of the index textures of tilemaps to `size` tiles, or to the
+    /// `max_texture_dimension_2d` of the device if `None` (the default). Tilemaps that don't fit
+    /// (counting every layer of a `TilemapStack`, which are one below another) are split into
+    /// several textures drawn one after another, so that e.g. very wide levels don't need to be
+    /// chunked by the application. Fragment hooks that read tiles away from `tilepos` may find
+    /// them missing at the edges of the chunks.
+    pub fn set_max_tilemap_texture_size(&mut self, size: Option<u32>) {
+        self.max_tilemap_texture_size = size;
+    }
+
+    /// Make sure that at least `count` tilemaps of `size` (in tiles), `format`, and `layers` (see
+    /// `TilemapStack`), with or without a `TilemapDrawData::tint`, can be drawn without
+    /// allocating, e.g. to fill a `AllocationStrategy::Pool` while loading.
+    pub fn reserve_tilemaps(
+        &mut self,
+        device: &wgpu::Device,
+        size: Vec2<u32>,
+        format: TileFormat,
+        layers: u32,
+        tint: bool,
+        count: usize,
+    ) {
+        let key = (
+            self.tilemap_allocation.round(size),
+            format,
+            layers.max(1),
+            tint,
+        );
+        self.draw_calls.reserve(key, count, device, |device, key| {
+            TilemapPipeline::allocate_draw_call(
+                device,
+                &self.tilemap_bind_group_layout,
+                &self.placeholder_tint_view,
+                &mut self.allocation_observer,
+                key,
+            )
+        });
+    }
+
+    /// Make sure that at least `count` tilesets of `pixel_size` with tiles of `size_of_tile` can
+    /// be uploaded without allocating.
+    pub fn reserve_tilesets(
+        &mut self,
+        device: &wgpu::Device,
+        pixel_size: Vec2<u32>,
+        size_of_tile: Vec2<u32>,
+        count: usize,
+    ) {
+        let key = (
+            self.tileset_allocation.round(pixel_size / size_of_tile) * size_of_tile,
+            size_of_tile,
+            1,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        );
+        self.tilesets.reserve(key, count, device, |device, key| {
+            TilemapPipeline::allocate_tilesets(
+                device,
+                &self.tileset_bind_group_layout,
+                &self.tileset_placeholders,
+                &self.tile_sampler,
+                &mut self.allocation_observer,
+                key,
+            )
+        });
+    }
+
+    /// Make sure that the CPU-side lists kept by uploads have room for `draws` draw calls (one per
+    /// chunk of each tilemap, see `set_max_tilemap_texture_size`), `tilesets` tilesets, and
+    /// tilesets of up to `tileset_pixels` pixels, so that uploads within those limits don't
+    /// allocate memory. These lists are reused from one upload to the next, so once the first
+    /// frames have been uploaded, frames like them don't allocate either. Uploads still allocate
+    /// for new sizes of textures (see `reserve_tilemaps` and `reserve_tilesets`), for
+    /// `upload_tilesets_named` (for the names), for mip levels, and while `set_retain_uploads` is
+    /// on.
+    pub fn reserve_upload_capacity(
+        &mut self,
+        draws: usize,
+        tilesets: usize,
+        tileset_pixels: usize,
+    ) {
+        self.active_draw_calls
+            .reserve(draws.saturating_sub(self.active_draw_calls.len()));
+        self.chunk_scratch
+            .reserve(draws.saturating_sub(self.chunk_scratch.len()));
+        self.active_tilesets
+            .reserve(tilesets.saturating_sub(self.active_tilesets.len()));
+        self.tileset_analyses
+            .reserve(tilesets.saturating_sub(self.tileset_analyses.len()));
+        self.pixel_scratch
+            .reserve(tileset_pixels.saturating_sub(self.pixel_scratch.len()));
+    }
+
+    /// The index (for `TilemapDrawData::tileset`) of the tileset with this name in the most recent
+    /// `upload_tilesets_named` call. If several tilesets have the same name, this is the last one.
+    pub fn tileset_index(&self, name: &str) -> Option<u32> {
+        self.tileset_names.get(name).copied()
+    }
+
+    /// The `TilesetAnalysis` of the tileset at this index (for `TilemapDrawData::tileset`) in the
+    /// most recent upload, computed from its pixels when it was uploaded.
+    pub fn tileset_analysis(&self, tileset: u32) -> Option<&TilesetAnalysis> {
+        self.tileset_analyses.get(tileset as usize)
+    }
+
+    fn upload_tileset_list<'b>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilesets: impl Iterator<Item = (&'b TilesetRef<'b>, TilesetMips<'b>)> + Clone,
+    ) -> Result<(), TilesetError> {
+        for (i, (tileset, mips)) in tilesets.clone().enumerate() {
+            check_tileset(device, i, tileset, mips)?;
+        }
+        self.active_tilesets.clear();
+        self.streaming_mips.clear();
+        self.tilesets.mark_inactive();
+        self.tileset_generation += 1;
+        // The tilemaps need to be uploaded again to be drawn with the new tilesets.
+        self.tilemaps_version = None;
+        if let Some(retained) = &mut self.retained {
+            retained.tilesets.clear();
+            retained.tilemaps.clear();
+            retained.materials.clear();
+            retained.team_masks.clear();
+            retained.normal_maps.clear();
+            retained.emissive.clear();
+        }
+        for (i, (tileset, mips)) in tilesets.enumerate() {
+            if let Some(retained) = &mut self.retained {
+                let retained_mips = RetainedMips::new(mips);
+                retained
+                    .tilesets
+                    .push((tileset.clone().into_owned(), retained_mips));
+            }
+            // The analyses of the previous tilesets are overwritten rather than reallocated.
+            match self.tileset_analyses.get_mut(i) {
+                Some(analysis) => tileset.analyze_into(analysis),
+                None => self.tileset_analyses.push(tileset.analyze()),
+            }
+            let index = self.upload_tileset(device, queue, tileset, mips);
+            self.active_tilesets.push(index);
+        }
+        self.tileset_analyses.truncate(self.active_tilesets.len());
+        Ok(())
+    }
+
+    /// Upload `tileset` to the first free allocation that fits it, as part of the current
+    /// generation of tilesets, and return the allocation.
+    fn upload_tileset(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: &TilesetRef,
+        mips: TilesetMips,
+    ) -> (TilesetKey, u32) {
+        // The length of the checkerboard was checked by check_tileset.
+        let mut checkerboard = [[0; 4]; MAX_CHECKERBOARD_TILES];
+        for (entry, tile) in checkerboard.iter_mut().zip(tileset.checkerboard.iter()) {
+            *entry = [tile.tile, tile.alternate, 0, 0];
+        }
+        let largest = tileset.size_of_tile.reduce_max().max(1);
+        let mip_levels = match mips {
+            TilesetMips::Generate(levels) => levels,
+            TilesetMips::Provided(levels) => levels.len() as u32 + 1,
+        }
+        .clamp(1, u32::BITS - largest.leading_zeros());
+        let params = TilesetBuffer {
+            width: tileset.pixel_size.x,
+            height: tileset.pixel_size.y,
+            tile_width: tileset.size_of_tile.x,
+            tile_height: tileset.size_of_tile.y,
+            checkerboard_len: tileset.checkerboard.len() as u32,
+            uv_inset: tileset.uv_inset,
+            mip_level: mip_levels - 1,
+            mip_levels,
+            has_normal_map: 0,
+            has_emissive: 0,
+            has_materials: 0,
+            _pad: 0,
+            checkerboard,
+        };
+
+        let tile_size = tileset.pixel_size / tileset.size_of_tile;
+        let compressed = tileset.compressed.as_ref().filter(|compressed| {
+            compressed_tiles_supported(device, compressed, tileset.size_of_tile) && mip_levels == 1
+        });
+
+        // Rounding up must not take the texture past the layer limit that the tileset fits in.
+        let mut allocated = self.tileset_allocation.round(tile_size);
+        if allocated.as_::<u64>().product() > device.limits().max_texture_array_layers as u64 {
+            allocated = tile_size;
+        }
+        let key = (
+            allocated * tileset.size_of_tile,
+            tileset.size_of_tile,
+            mip_levels,
+            compressed.map_or(wgpu::TextureFormat::Rgba8UnormSrgb, |compressed| {
+                compressed.format.add_srgb_suffix()
+            }),
+        );
+        let mut index = 0;
+        let mut scratch = std::mem::take(&mut self.pixel_scratch);
+        self.tilesets.allocate_and_upload(
+            key,
+            device,
+            queue,
+            |device, key| {
+                TilemapPipeline::allocate_tilesets(
+                    device,
+                    &self.tileset_bind_group_layout,
+                    &self.tileset_placeholders,
+                    &self.tile_sampler,
+                    &mut self.allocation_observer,
+                    key,
+                )
+            },
+            &params,
+            |i, datum| {
+                index = i as u32;
+                datum.generation = self.tileset_generation;
+                let had_extras = datum.materials.take().is_some()
+                    | datum.team_mask.take().is_some()
+                    | datum.normal_map.take().is_some()
+                    | datum.emissive.take().is_some();
+                if had_extras {
+                    datum.bind_group = create_tileset_bind_group(
+                        device,
+                        &self.tileset_bind_group_layout,
+                        &datum.params_buffer,
+                        &datum.data_texture,
+                        self.tileset_placeholders.views(),
+                        &self.tile_sampler,
+                    );
+                }
+                let tiles = tile_size.product();
+                // The size of the blocks was checked by check_tileset.
+                if let Some(compressed) = compressed {
+                    write_tileset_level(
+                        queue,
+                        datum.texture(),
+                        0,
+                        &compressed.blocks,
+                        tileset.size_of_tile,
+                        tiles,
+                    );
+                    return;
+                }
+                let texture_data = match tileset.alpha_mode {
+                    AlphaMode::Straight => premultiply_alpha(&tileset.data, &mut scratch),
+                    AlphaMode::Premultiplied => tileset.data.as_ref(),
+                };
+                if mip_levels == 1 {
+                    write_tileset_level(
+                        queue,
+                        datum.texture(),
+                        0,
+                        bytemuck::cast_slice(texture_data),
+                        tileset.size_of_tile,
+                        tiles,
+                    );
+                    return;
+                }
+                // Upload the coarsest level now, and keep the others for stream_tileset_mips.
+                // The level drawn is chosen by TilesetBuffer::mip_level rather than by the
+                // base level of the view, which the GL backend can't bind.
+                let mut levels = vec![(texture_data.to_vec(), tileset.size_of_tile)];
+                for level in 1..mip_levels {
+                    let (pixels, size) = levels.last().unwrap();
+                    let next = match mips {
+                        TilesetMips::Generate(_) => downsample_tiles(pixels, *size, tiles),
+                        TilesetMips::Provided(provided) => {
+                            let pixels = &provided[level as usize - 1];
+                            // Checked by check_tileset.
+                            let size = mip_size(tileset.size_of_tile, level);
+                            let pixels = match tileset.alpha_mode {
+                                AlphaMode::Straight => {
+                                    premultiply_alpha(pixels, &mut scratch).to_vec()
+                                }
+                                AlphaMode::Premultiplied => pixels.clone(),
+                            };
+                            (pixels, size)
+                        }
+                    };
+                    levels.push(next);
+                }
+                let (pixels, size) = levels.pop().unwrap();
+                let level = levels.len() as u32;
+                let data = bytemuck::cast_slice(&pixels);
+                write_tileset_level(queue, datum.texture(), level, data, size, tiles);
+                self.streaming_mips.push(StreamingMips {
+                    tilesets_index: (key, i as u32),
+                    levels,
+                });
+            },
+        );
+        self.pixel_scratch = scratch;
+        (key, index)
+    }
+
+    /// Like `upload_tilemaps`, but does nothing if the tilemaps uploaded last were uploaded with
+    /// this function and the same `version`, so that unchanged frames (e.g. menus or paused
+    /// scenes) keep drawing the same tilemaps without any CPU work. `version` can be a hash of the
+    /// tilemaps or a counter that is incremented whenever they change. Uploading tilesets (or
+    /// clearing or recovering the pipeline) makes the next call upload the tilemaps again, since
+    /// the draws uploaded before would be skipped as stale. Returns whether the tilemaps were
+    /// uploaded.
+    pub fn upload_tilemaps_if_changed(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        version: u64,
+        tilemaps: &[TilemapDrawData],
+    ) -> bool {
+        if self.tilemaps_version == Some(version) {
+            return false;
+        }
+        self.upload_tilemaps(device, queue, tilemaps);
+        self.tilemaps_version = Some(version);
+        true
+    }
+
+    /// Upload a list of tilemaps to be drawn this frame. Each tilemap is drawn with an independent
+    /// transform and tileset, in the order they are provided. Texture allocations of matching
+    /// sizes are reused.
+    pub fn upload_tilemaps(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tilemaps: &[TilemapDrawData],
+    ) {
+        self.tilemaps_version = None;
+        if let Some(retained) = &mut self.retained {
+            retained.tilemaps = tilemaps
+                .iter()
+                .map(|draw| draw.clone().into_owned())
+                .collect();
+        }
+        self.draw_calls.mark_inactive();
+        self.active_draw_calls.clear();
+        self.draw_hashes.clear();
+        self.rejected_draws.clear();
+        let mut chunks = std::mem::take(&mut self.chunk_scratch);
+        for (draw_index, draw) in tilemaps.iter().enumerate() {
+            let TilemapDrawData {
+                transform,
+                tilemap,
+                tileset,
+                noise,
+                shadow,
+                walls,
+                blend_mode,
+                opacity,
+                parallax,
+                source_rect,
+                tile_index_offset,
+                empty_tile,
+                flip_flags,
+                pixel_snap,
+                tint,
+                user_data,
+                clip_planes,
+                team_color,
+                emissive_intensity,
+                depth,
+                stencil,
+                stagger,
+                invalid_tiles,
+            } = draw;
+            let size = tilemap.tile_size();
+            let (source_origin, source_size) = source_rect.map_or((Vec2::zero(), size), |rect| {
+                let origin = rect.position().map2(size, u32::min);
+                (
+                    origin,
+                    Vec2::from(rect.extent()).map2(size - origin, u32::min),
+                )
+            });
+            let noise_data = ((0xffff as f32 * noise.magnitude) as u32 & 0xffff)
+                | ((noise.resolution as u32 & 0xff) << 16)
+                | ((noise.space as u32) << 24);
+            let shadow_offset = if shadow.direction != Vec2::zero() {
+                shadow.direction.normalized() * shadow.length
+            } else {
+                Vec2::zero()
+            };
+            let shadow_samples = if shadow_offset != Vec2::zero() {
+                shadow.samples.max(1) as u32
+            } else {
+                0
+            };
+            let heatmap_range = match tilemap {
+                AnyTilemapRef::Heatmap(heatmap) => Some(heatmap.range),
+                _ => None,
+            };
+            let features = TilemapShaderFeatures {
+                shadow: shadow_samples != 0 && heatmap_range.is_none(),
+                noise: noise_data & 0xffff != 0,
+                tint: tint.is_some(),
+                heatmap: heatmap_range.is_some(),
+                walls: walls.parallax != 0.0
+                    && shadow_samples == 0
+                    && heatmap_range.is_none()
+                    && *stagger == TileStagger::None,
+            };
+            if tint
+                .as_ref()
+                .is_some_and(|tint| tint.len() != size.product() as usize)
+            {
+                self.rejected_draws.push(draw_index);
+                self.draw_hashes.push(0);
+                continue;
+            }
+            // Unused planes keep everything, as their normal is zero.
+            let clip_plane_data = clip_planes.map(|plane| {
+                plane.map_or([0.0; 4], |plane| {
+                    [plane.normal.x, plane.normal.y, -plane.distance, 0.0]
+                })
+            });
+            // Stencils are ignored without a stencil buffer to use, which their pipelines would need.
+            let stencil = match &self.depth_stencil {
+                Some(state) if state.format.has_stencil_aspect() => *stencil,
+                _ => TilemapStencil::Unchanged,
+            };
+            self.prepare_pipeline(device, (*blend_mode, stencil.mode(), features));
+            let limit = self
+                .max_tilemap_texture_size
+                .unwrap_or(device.limits().max_texture_dimension_2d);
+            // The layers of a stack are one below another in its index texture.
+            let limit = Vec2::new(limit, limit / tilemap.layers().max(1)).map(|x| x.max(1));
+            // Shadows are cast by tiles up to `shadow_offset` away, which may be in another chunk.
+            let mut apron = match features.shadow {
+                true => shadow_offset.map(|x| x.abs().ceil() as u32),
+                false => Vec2::zero(),
+            };
+            // The offset rows of staggered tilemaps show half of the tile before each chunk.
+            if *stagger != TileStagger::None {
+                apron.x = apron.x.max(1);
+            }
+            tilemap_chunks(size, source_origin, source_size, limit, apron, &mut chunks);
+            let mut hasher = DefaultHasher::new();
+            (tileset, blend_mode, tile_bytes(tilemap), tint).hash(&mut hasher);
+            for chunk in &chunks {
+                let params = TilemapBuffer {
+                    transform: transform.into_col_arrays(),
+                    width: chunk.texture_size.x,
+                    height: chunk.texture_size.y,
+                    noise_data,
+                    shadow_samples,
+                    shadow_offset: shadow_offset.into_array(),
+                    shadow_opacity: shadow.opacity,
+                    opacity: *opacity,
+                    parallax: parallax.into_array(),
+                    source_origin: source_origin.into_array(),
+                    source_size: source_size.into_array(),
+                    tile_index_offset: *tile_index_offset,
+                    flag_bits: if *flip_flags && heatmap_range.is_none() {
+                        tilemap.format().bytes_per_tile() as u32 * 8
+                    } else {
+                        0
+                    },
+                    variant_rows: (tilemap.format() == TileFormat::Rg8) as u32,
+                    layers: tilemap.layers(),
+                    has_empty_tile: empty_tile.is_some() as u32,
+                    empty_tile: empty_tile.unwrap_or(0),
+                    heatmap_range: heatmap_range.unwrap_or_default().into_array(),
+                    pixel_snap: *pixel_snap as u32,
+                    draw_index: draw_index as u32,
+                    texture_origin: chunk.texture_origin.into_array(),
+                    quad_origin: chunk.quad_origin.into_array(),
+                    quad_size: chunk.quad_size.into_array(),
+                    emissive_intensity: match features.shadow || features.heatmap {
+                        true => 0.0,
+                        false => *emissive_intensity,
+                    },
+                    wall_samples: match features.walls {
+                        true => walls.samples.max(1) as u32,
+                        false => 0,
+                    },
+                    wall_parallax: walls.parallax,
+                    wall_max_height: walls.max_height,
+                    depth: *depth,
+                    stagger: stagger.shader_value(),
+                    invalid_tiles: invalid_tiles.shader_values().0,
+                    invalid_tile: invalid_tiles.shader_values().1,
+                    _pad: [0; 2],
+                    clip_planes: clip_plane_data,
+                    team_color: team_color.map_or([0.0; 4], |color| {
+                        let linear = color.map(srgb_decode);
+                        [linear.r, linear.g, linear.b, 1.0]
+                    }),
+                    user_data: user_data.0,
+                };
+                hasher.write(bytemuck::bytes_of(&params));
+                let key = (
+                    self.tilemap_allocation
+                        .round(chunk.texture_size)
+                        .map2(limit, u32::min),
+                    tilemap.format(),
+                    tilemap.layers(),
+                    tint.is_some(),
+                );
+                self.draw_calls.allocate_and_upload(
+                    key,
+                    device,
+                    queue,
+                    |device, key| {
+                        TilemapPipeline::allocate_draw_call(
+                            device,
+                            &self.tilemap_bind_group_layout,
+                            &self.placeholder_tint_view,
+                            &mut self.allocation_observer,
+                            key,
+                        )
+                    },
+                    &params,
+                    |i, call| {
+                        self.active_draw_calls.push((draw_index, key, i as u32));
+                        call.tilesets_index = self.active_tilesets[*tileset as usize];
+                        call.tilesets_generation = self.tileset_generation;
+                        call.blend_mode = *blend_mode;
+                        call.stencil = stencil;
+                        call.features = features;
+                        call.tileset = *tileset;
+                        call.transform = *transform;
+                        call.tiles = Rect::new(
+                            chunk.quad_origin.x,
+                            chunk.quad_origin.y,
+                            chunk.quad_size.x,
+                            chunk.quad_size.y,
+                        );
+                        call.uploaded_bytes = tilemap_bytes(
+                            chunk.texture_size,
+                            tilemap.format(),
+                            tilemap.layers(),
+                            tint.is_some(),
+                        );
+                        let bytes_per_tile = tilemap.format().bytes_per_tile() as u32;
+                        for layer in 0..tilemap.layers() {
+                            write_tilemap_chunk(
+                                queue,
+                                call.texture(),
+                                tile_bytes(tilemap),
+                                size,
+                                bytes_per_tile,
+                                layer,
+                                chunk,
+                            );
+                        }
+                        if let (Some(tint), Some(tint_texture)) = (tint, &call.tint_texture) {
+                            write_tilemap_chunk(
+                                queue,
+                                tint_texture,
+                                bytemuck::cast_slice::<u32, u8>(tint),
+                                size,
+                                4,
+                                0,
+                                chunk,
+                            );
+                        }
+                    },
+                );
+            }
+            self.draw_hashes.push(hasher.finish());
+        }
+        self.chunk_scratch = chunks;
+    }
+
+    fn allocate_draw_call(
+        device: &wgpu::Device,
+        tilemap_bind_group_layout: &wgpu::BindGroupLayout,
+        placeholder_tint_view: &wgpu::TextureView,
+        allocation_observer: &mut Option<AllocationObserver>,
+        (size, format, layers, tint): TilemapKey,
+    ) -> TilemapDrawCall {
+        notify_allocation(
+            allocation_observer,
+            AllocationKind::Tilemap,
+            size,
+            tilemap_bytes(size, format, layers, tint) + std::mem::size_of::<TilemapBuffer>() as u64,
+        );
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_params_buffer"),
+            size: ::std::mem::size_of::<TilemapBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("tilemap_index_texture"),
+            // Layers are stacked vertically, rather than in an array texture, since array textures
+            // with a single layer can't be bound as arrays on GL.
+            size: wgpu::Extent3d {
+                width: size.x,
+                height: size.y * layers,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: match format {
+                TileFormat::R8 => wgpu::TextureFormat::R8Uint,
+                TileFormat::R16 => wgpu::TextureFormat::R16Uint,
+                TileFormat::R32 | TileFormat::R32Float => wgpu::TextureFormat::R32Uint,
+                TileFormat::Rg8 => wgpu::TextureFormat::Rg8Uint,
+            },
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let index_view = index_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let tint_texture = tint.then(|| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tilemap_tint_texture"),
+                size: wgpu::Extent3d {
+                    width: size.x,
+                    height: size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            })
+        });
+        let tint_view = tint_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tilemap_bind_group"),
+            layout: tilemap_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&index_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        tint_view.as_ref().unwrap_or(placeholder_tint_view),
+                    ),
+                },
+            ],
+        });
+        TilemapDrawCall {
+            params_buffer,
+            index_texture,
+            tint_texture,
+            bind_group,
+            tilesets_index: (
+                (
+                    Vec2::zero(),
+                    Vec2::zero(),
+                    1,
+                    wgpu::TextureFormat::Rgba8UnormSrgb,
+                ),
+                0,
+            ),
+            tilesets_generation: 0,
+            blend_mode: TilemapBlendMode::default(),
+            stencil: TilemapStencil::Unchanged,
+            features: TilemapShaderFeatures::default(),
+            tileset: 0,
+            transform: Mat4::identity(),
+            tiles: Rect::default(),
+            uploaded_bytes: 0,
+            active: false,
+        }
+    }
+    /// Draw at the fixed virtual resolution of `params` in a render target of `target_size`
+    /// pixels: this sets the camera to `Presentation::camera`, and `render` limits drawing to its
+    /// viewport from then on. The returned `Presentation` converts positions in the target (e.g.
+    /// the cursor) to virtual pixels. Call this again when the target is resized.
+    pub fn set_present_params(
+        &mut self,
+        queue: &wgpu::Queue,
+        params: &PresentParams,
+        target_size: Vec2<u32>,
+    ) -> Presentation {
+        let presentation = params.present(target_size);
+        self.set_camera(queue, presentation.camera);
+        self.write_viewport_size(queue, presentation.viewport.extent().into());
+        self.presentation = Some(presentation);
+        self.present_target_size = target_size;
+        presentation
+    }
+
+    /// Draw to the whole render target again, after `set_present_params`. The camera is left
+    /// unchanged.
+    pub fn clear_present_params(&mut self) {
+        self.presentation = None;
+    }
+
+    /// Set the camera matrix that maps from world coordinates to Normalized Device Coordinates.
+    pub fn set_camera(&self, queue: &wgpu::Queue, camera: Mat4<f32>) {
+        // Parallax is relative to the point in the world at the center of the screen.
+        let center = camera.inverted() * Vec4::new(0.0, 0.0, 0.0, 1.0);
+        let center = Vec2::new(center.x, center.y) / center.w;
+        let params = CameraBuffer {
+            view_proj: camera.into_col_arrays(),
+            center: if center.x.is_finite() && center.y.is_finite() {
+                center.into_array()
+            } else {
+                [0.0; 2]
+            },
+            color_lut_size: self.color_lut_size,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        // The viewport size is left as it was set by `set_viewport_size`.
+        let len = std::mem::offset_of!(CameraBuffer, viewport_size);
+        queue.write_buffer(&self.camera_buffer, 0, &bytemuck::bytes_of(&params)[..len]);
+        if let Some(retained) = &self.retained {
+            *retained.camera.lock().unwrap() = Some(camera);
+        }
+    }
+
+    /// Set the size of the viewport that is drawn to, in pixels, which `TilemapDrawData::pixel_snap`
+    /// snaps draws to. `set_camera_pixels` and `set_present_params` set this as well.
+    pub fn set_viewport_size(&self, queue: &wgpu::Queue, viewport_size: Vec2<u32>) {
+        self.write_viewport_size(queue, viewport_size.as_());
+    }
+
+    /// Set the time, frame, and (if given) viewport size of the frame being drawn, which fragment
+    /// hooks can read as `frame.time`, `frame.delta_time`, `frame.index`, and
+    /// `camera.viewport_size`. Call this once a frame, before `render`.
+    pub fn set_frame_context(&self, queue: &wgpu::Queue, context: &FrameContext) {
+        let params = FrameBuffer {
+            time: context.time,
+            delta_time: context.delta_time,
+            index: context.index,
+            _pad: 0,
+        };
+        queue.write_buffer(&self.frame_buffer, 0, bytemuck::bytes_of(&params));
+        if let Some(viewport_size) = context.viewport_size {
+            self.set_viewport_size(queue, viewport_size);
+        }
+    }
+
+    fn write_viewport_size(&self, queue: &wgpu::Queue, viewport_size: Vec2<f32>) {
+        queue.write_buffer(
+            &self.camera_buffer,
+            std::mem::offset_of!(CameraBuffer, viewport_size) as u64,
+            bytemuck::bytes_of(&viewport_size.into_array()),
+        );
+        if let Some(retained) = &self.retained {
+            *retained.viewport_size.lock().unwrap() = Some(viewport_size);
+        }
+    }
+
+    /// Grade the colors of everything this pipeline draws with `lut`, or stop grading them if it is
+    /// `None`. The LUT is applied before the opacity of each draw and before blending.
+    pub fn set_color_lut(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        lut: Option<&ColorLut>,
+    ) {
+        let (texture, view) = create_color_lut(device, lut);
+        if let Some(lut) = lut {
+            let extent = wgpu::Extent3d {
+                width: lut.size,
+                height: lut.size,
+                depth_or_array_layers: lut.size,
+            };
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&lut.data),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * lut.size),
+                    rows_per_image: Some(lut.size),
+                },
+                extent,
+            );
+        }
+        self.camera_bind_group = create_camera_bind_group(
+            device,
+            &self.camera_bind_group_layout,
+            &self.camera_buffer,
+            &view,
+            &self.color_lut_sampler,
+            &self.frame_buffer,
+        );
+        self.color_lut_size = lut.map_or(0, |lut| lut.size);
+        if let Some(retained) = &mut self.retained {
+            retained.color_lut = lut.cloned();
+        }
+        queue.write_buffer(
+            &self.camera_buffer,
+            std::mem::offset_of!(CameraBuffer, color_lut_size) as u64,
+            bytemuck::bytes_of(&self.color_lut_size),
+        );
+    }
+
+    /// Give the tiles of the tileset at index `tileset` (in the last `upload_tilesets` call) the
+    /// effect parameters of `materials`, or the default material if it is `None`. Shader hooks
+    /// read them with `tilemap_material` (see `with_fragment_hook`). The tiles are drawn swaying by
+    /// `TileMaterial::sway`, the light of the emissive texture of the tileset is scaled by
+    /// `TileMaterial::emissive` (see `set_tileset_emissive`), and draws with walls raise tiles by
+    /// `TileMaterial::height`. `TileMaterial::reflectivity` is only read by fragment hooks.
+    /// The materials are reset when tilesets are uploaded again. Does nothing if there is no
+    /// tileset at that index.
+    pub fn set_tileset_materials(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: u32,
+        materials: Option<&MaterialTable>,
+    ) {
+        let Some(&(key, i)) = self.active_tilesets.get(tileset as usize) else {
+            return;
+        };
+        let Some(datum) = self
+            .tilesets
+            .map
+            .get_mut(&key)
+            .and_then(|data| data.get_mut(i as usize))
+        else {
+            return;
+        };
+        let table = materials.filter(|table| !table.materials.is_empty());
+        datum.materials = table.map(|table| {
+            let len = table.materials.len() as u32;
+            let size = Vec2::new(len.min(MATERIALS_ROW), len.div_ceil(MATERIALS_ROW));
+            let mut texels = vec![[0.0f32; 4]; size.product() as usize];
+            for (texel, material) in texels.iter_mut().zip(&table.materials) {
+                *texel = [
+                    material.emissive,
+                    material.sway,
+                    material.reflectivity,
+                    material.height,
+                ];
+            }
+            let extent = wgpu::Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            };
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_materials_texture"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                bytemuck::cast_slice(&texels),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(16 * size.x),
+                    rows_per_image: Some(size.y),
+                },
+                extent,
+            );
+            texture
+        });
+        queue.write_buffer(
+            &datum.params_buffer,
+            std::mem::offset_of!(TilesetBuffer, has_materials) as u64,
+            bytemuck::bytes_of(&(table.is_some() as u32)),
+        );
+        datum.rebind(
+            device,
+            &self.tileset_bind_group_layout,
+            &self.tileset_placeholders,
+            &self.tile_sampler,
+        );
+        if let Some(retained) = &mut self.retained {
+            retained.materials.retain(|(t, _)| *t != tileset);
+            if let Some(table) = table {
+                retained.materials.push((tileset, table.clone()));
+            }
+        }
+    }
+
+    /// Mark the parts of the tiles of the tileset at index `tileset` (in the last `upload_tilesets`
+    /// call) that are recolored by `TilemapDrawData::team_color` (e.g. the banners and trim of
+    /// units), or remove the mask if it is `None`. The alpha of each pixel of `mask`, which has
+    /// the same layout as the tileset (e.g. a second image loaded with `TilesetRef::from_image`),
+    /// is how much of that pixel is recolored. The mask is removed when tilesets are uploaded
+    /// again. Does nothing if there is no tileset at that index.
+    ///
+    /// Panics if `mask` is empty, or doesn't have exactly as many tiles of the same size as the
+    /// tileset.
+    pub fn set_tileset_team_mask(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: u32,
+        mask: Option<&TilesetRef>,
+    ) {
+        let Some(&(key, i)) = self.active_tilesets.get(tileset as usize) else {
+            return;
+        };
+        let Some(datum) = self
+            .tilesets
+            .map
+            .get_mut(&key)
+            .and_then(|data| data.get_mut(i as usize))
+        else {
+            return;
+        };
+        let (_, size_of_tile, _, _) = key;
+        // The texture can have more layers than the tileset has tiles, to reuse its allocation.
+        let tileset_len = self.tileset_analyses[tileset as usize].tiles.len();
+        datum.team_mask = mask.map(|mask| {
+            let tiles = datum.data_texture.depth_or_array_layers();
+            assert!(
+                mask.size_of_tile == size_of_tile
+                    && !mask.data.is_empty()
+                    && mask.data.len() == tileset_len * size_of_tile.product() as usize,
+                "a team mask needs as many tiles of the same size as its tileset"
+            );
+            let alpha: Vec<u8> = mask.data.iter().map(|pixel| (pixel >> 24) as u8).collect();
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_team_mask_texture"),
+                size: wgpu::Extent3d {
+                    width: size_of_tile.x,
+                    height: size_of_tile.y,
+                    // At least two layers, so that the GL backend creates an array texture.
+                    depth_or_array_layers: tiles.max(2),
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let mask_tiles = alpha.len() as u32 / size_of_tile.product().max(1);
+            write_tileset_level(queue, &texture, 0, &alpha, size_of_tile, mask_tiles);
+            texture
+        });
+        datum.rebind(
+            device,
+            &self.tileset_bind_group_layout,
+            &self.tileset_placeholders,
+            &self.tile_sampler,
+        );
+        if let Some(retained) = &mut self.retained {
+            retained.team_masks.retain(|(t, _)| *t != tileset);
+            if let Some(mask) = mask {
+                retained
+                    .team_masks
+                    .push((tileset, mask.clone().into_owned()));
+            }
+        }
+    }
+
+    /// Give the tileset at index `tileset` (in the last `upload_tilesets` call) a normal map for 2D
+    /// lighting, or remove it if it is `None`. `normal_map` has the same layout as the tileset
+    /// (e.g. a second image loaded with `TilesetRef::from_image`), with the normal of each pixel
+    /// encoded in its red, green, and blue channels as in most normal map tools: x to the right,
+    /// y up, and z out of the screen, each mapped from [-1, 1] to [0, 255]. Fragment hooks read it
+    /// with `tilemap_normal(tilepos)`. The normal map is removed when tilesets are uploaded again.
+    /// Does nothing if there is no tileset at that index.
+    ///
+    /// Panics if `normal_map` is empty, or doesn't have exactly as many tiles of the same size as
+    /// the tileset.
+    pub fn set_tileset_normal_map(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: u32,
+        normal_map: Option<&TilesetRef>,
+    ) {
+        let Some(&(key, i)) = self.active_tilesets.get(tileset as usize) else {
+            return;
+        };
+        let Some(datum) = self
+            .tilesets
+            .map
+            .get_mut(&key)
+            .and_then(|data| data.get_mut(i as usize))
+        else {
+            return;
+        };
+        let (_, size_of_tile, _, _) = key;
+        // Its tiles, rather than the layers of its texture, as for team masks.
+        let tileset_len = self.tileset_analyses[tileset as usize].tiles.len();
+        datum.normal_map = normal_map.map(|normal_map| {
+            let tiles = datum.data_texture.depth_or_array_layers();
+            assert!(
+                normal_map.size_of_tile == size_of_tile
+                    && !normal_map.data.is_empty()
+                    && normal_map.data.len() == tileset_len * size_of_tile.product() as usize,
+                "a normal map needs as many tiles of the same size as its tileset"
+            );
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_normal_map_texture"),
+                size: wgpu::Extent3d {
+                    width: size_of_tile.x,
+                    height: size_of_tile.y,
+                    // At least two layers, so that the GL backend creates an array texture.
+                    depth_or_array_layers: tiles.max(2),
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                // Normals aren't colors, so they aren't decoded from sRGB.
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let normal_map_tiles = normal_map.data.len() as u32 / size_of_tile.product().max(1);
+            let data = bytemuck::cast_slice(&normal_map.data);
+            write_tileset_level(queue, &texture, 0, data, size_of_tile, normal_map_tiles);
+            texture
+        });
+        queue.write_buffer(
+            &datum.params_buffer,
+            std::mem::offset_of!(TilesetBuffer, has_normal_map) as u64,
+            bytemuck::bytes_of(&(normal_map.is_some() as u32)),
+        );
+        datum.rebind(
+            device,
+            &self.tileset_bind_group_layout,
+            &self.tileset_placeholders,
+            &self.tile_sampler,
+        );
+        if let Some(retained) = &mut self.retained {
+            retained.normal_maps.retain(|(t, _)| *t != tileset);
+            if let Some(normal_map) = normal_map {
+                retained
+                    .normal_maps
+                    .push((tileset, normal_map.clone().into_owned()));
+            }
+        }
+    }
+
+    /// Give the tileset at index `tileset` (in the last `upload_tilesets` call) an emissive texture
+    /// for the parts of its tiles that glow (e.g. lava, lamps, and runes), or remove it if it is
+    /// `None`. `emissive` has the same layout as the tileset (e.g. a second image loaded with
+    /// `TilesetRef::from_image`), with the sRGB color of the light of each pixel in its red, green,
+    /// and blue channels and how strongly it glows in its alpha channel. The light is scaled by
+    /// `TilemapDrawData::emissive_intensity`, hidden by the opaque parts of the layers above it,
+    /// and added to the color of the tiles, or written to the emissive target instead if there is
+    /// one (see `set_emissive_target`). If the tileset has a material table (see
+    /// `set_tileset_materials`), the light of each tile is also scaled by its
+    /// `TileMaterial::emissive`, so tiles without a glowing material don't glow. Fragment hooks
+    /// read it with `tilemap_emissive(tilepos)`.
+    /// The emissive texture is removed when tilesets are uploaded again. Does nothing if there is
+    /// no tileset at that index.
+    ///
+    /// Panics if `emissive` is empty, or doesn't have exactly as many tiles of the same size as the
+    /// tileset.
+    pub fn set_tileset_emissive(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        tileset: u32,
+        emissive: Option<&TilesetRef>,
+    ) {
+        let Some(&(key, i)) = self.active_tilesets.get(tileset as usize) else {
+            return;
+        };
+        let Some(datum) = self
+            .tilesets
+            .map
+            .get_mut(&key)
+            .and_then(|data| data.get_mut(i as usize))
+        else {
+            return;
+        };
+        let (_, size_of_tile, _, _) = key;
+        let tileset_len = self.tileset_analyses[tileset as usize].tiles.len();
+        datum.emissive = emissive.map(|emissive| {
+            let tiles = datum.data_texture.depth_or_array_layers();
+            assert!(
+                emissive.size_of_tile == size_of_tile
+                    && !emissive.data.is_empty()
+                    && emissive.data.len() == tileset_len * size_of_tile.product() as usize,
+                "an emissive texture needs as many tiles of the same size as its tileset"
+            );
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tileset_emissive_texture"),
+                size: wgpu::Extent3d {
+                    width: size_of_tile.x,
+                    height: size_of_tile.y,
+                    // At least two layers, so that the GL backend creates an array texture.
+                    depth_or_array_layers: tiles.max(2),
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            let emissive_tiles = emissive.data.len() as u32 / size_of_tile.product().max(1);
+            let data = bytemuck::cast_slice(&emissive.data);
+            write_tileset_level(queue, &texture, 0, data, size_of_tile, emissive_tiles);
+            texture
+        });
+        queue.write_buffer(
+            &datum.params_buffer,
+            std::mem::offset_of!(TilesetBuffer, has_emissive) as u64,
+            bytemuck::bytes_of(&(emissive.is_some() as u32)),
+        );
+        datum.rebind(
+            device,
+            &self.tileset_bind_group_layout,
+            &self.tileset_placeholders,
+            &self.tile_sampler,
+        );
+        if let Some(retained) = &mut self.retained {
+            retained.emissive.retain(|(t, _)| *t != tileset);
+            if let Some(emissive) = emissive {
+                retained
+                    .emissive
+                    .push((tileset, emissive.clone().into_owned()));
+            }
+        }
+    }
+
+    /// Set the camera to show the part of the world in `rect`. See `ortho_camera`.
+    pub fn set_camera_ortho(&self, queue: &wgpu::Queue, rect: Rect<f32, f32>) {
+        self.set_camera(queue, ortho_camera(rect));
+    }
+
+    /// Set the camera for world coordinates in pixels. See `pixel_camera`.
+    pub fn set_camera_pixels(
+        &self,
+        queue: &wgpu::Queue,
+        viewport_size: Vec2<u32>,
+        offset: Vec2<f32>,
+        zoom: f32,
+    ) {
+        self.set_camera(queue, pixel_camera(viewport_size, offset, zoom));
+        self.set_viewport_size(queue, viewport_size);
+    }
+
+    /// Provide a copy of the render target's current contents, which tilemaps drawn with
+    /// `TilemapBlendMode::Overlay` blend with. Since a render pass can't read its own attachment,
+    /// copy the render target into an intermediate texture (with `TEXTURE_BINDING` usage and the
+    /// same size) before beginning the render pass, and provide a view of that texture here.
+    pub fn set_backdrop(&mut self, device: &wgpu::Device, backdrop: &wgpu::TextureView) {
+        self.backdrop_bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("backdrop_bind_group"),
+            layout: &self.backdrop_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(backdrop),
+            }],
+        }));
+    }
+    /// Render the tilemaps to the provided renderpass, whose color attachment must match the
+    /// texture format provided when this was created, followed by the emissive target if there is
+    /// one (see `set_emissive_target`).
+    pub fn render<'a: 'pass, 'pass>(
+        &'a self,
+        device: &wgpu::Device,
+        rpass: &mut wgpu::RenderPass<'pass>,
+    ) {
+        self.render_with_profiler_inner(device, rpass, &mut (), self.drawable_calls());
+    }
+    #[cfg(feature = "wgpu-profiler")]
+    pub fn render_with_profiler<'a: 'pass, 'pass>(
+        &'a self,
+        device: &wgpu::Device,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        gpu_profiler: &mut wgpu_profiler::GpuProfiler,
+    ) {
+        self.render_with_profiler_inner(device, rpass, gpu_profiler, self.drawable_calls());
+    }
+    /// Render only the tilemaps at `visible` (positions in the list given to `upload_tilemaps`),
+    /// in that order, for engines that determine visibility themselves. Upload every tilemap that
+    /// may come into view (e.g. each chunk of a `ChunkedTilemap`, see `ChunkedTilemap::draw_index`)
+    /// once, and then pass the ones in view each frame, instead of uploading a culled list.
+    /// Positions past the end of the list, and stale draws, are skipped.
+    pub fn render_visible<'a: 'pass, 'pass>(
+        &'a self,
+        device: &wgpu::Device,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        visible: &[usize],
+    ) {
+        self.render_with_profiler_inner(device, rpass, &mut (), self.visible_calls(visible));
+    }
+    #[cfg(feature = "wgpu-profiler")]
+    pub fn render_visible_with_profiler<'a: 'pass, 'pass>(
+        &'a self,
+        device: &wgpu::Device,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        gpu_profiler: &mut wgpu_profiler::GpuProfiler,
+        visible: &[usize],
+    ) {
+        self.render_with_profiler_inner(device, rpass, gpu_profiler, self.visible_calls(visible));
+    }
+    /// Draw the tilemaps into a picking buffer, a render target of `PICKING_FORMAT`, for finding
+    /// the tile under the mouse with `PickReadback`. Each pixel where a tilemap is visible (after
+    /// its transform, the camera, and any fragment hook) is set to the position of the tilemap in
+    /// the list given to `upload_tilemaps` plus one, the position of the tile in the tilemap, and
+    /// the tile (without flip flags) of its topmost layer that isn't empty. Clear the buffer to
+    /// zero to tell which pixels have no tilemap. The pass must not have a depth attachment, and
+    /// nothing is drawn unless `set_picking` has been called.
+    pub fn render_picking<'a: 'pass, 'pass>(&'a self, rpass: &mut wgpu::RenderPass<'pass>) {
+        self.begin_pass(rpass);
+        for (call, tilesets_bg) in self.drawable_calls() {
+            let Some(pipeline) = self.picking_pipelines.get(&call.features) else {
+                continue;
+            };
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(1, &tilesets_bg.bind_group, &[]);
+            rpass.set_bind_group(2, &call.bind_group, &[]);
+            rpass.draw(0..6, 0..1);
+        }
+    }
+
+    /// Draw the tilemaps at `draws` (positions in the list given to `upload_tilemaps`) once into
+    /// a texture of `pixel_size`, covering the part of the world in `area` (see `ortho_camera`),
+    /// and return it as a layer that `render_baked` draws as a single quad. For scenes with many
+    /// static layers, upload and bake them once, and then upload only the tilemaps that change
+    /// each frame. Bake again with `rebake` after any of the baked tilemaps change, or call
+    /// `rebake_if_stale` every frame to bake again only when they did.
+    ///
+    /// The tilemaps are drawn with parallax as if the camera were centered on `area`, without the
+    /// color LUT (which is applied when the layer is drawn), and tilemaps drawn with
+    /// `TilemapBlendMode::Overlay` are left out. Baked layers have to be baked again after
+    /// `recover`.
+    pub fn bake(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        draws: Range<usize>,
+        area: Rect<f32, f32>,
+        pixel_size: Vec2<u32>,
+    ) -> BakedLayer {
+        let downscale = self.downscale_filter.params(device, pixel_size);
+        let render_size = pixel_size * downscale.map_or(1, |params| params.factor);
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("tilemap_baked_texture"),
+            // A second, unused layer, since array textures with a single layer can't be bound as
+            // arrays on GL.
+            size: wgpu::Extent3d {
+                width: pixel_size.x,
+                height: pixel_size.y,
+                depth_or_array_layers: 2,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_bake_camera_buffer"),
+            size: std::mem::size_of::<CameraBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(
+            &camera_buffer,
+            0,
+            bytemuck::bytes_of(&CameraBuffer {
+                view_proj: ortho_camera(area).into_col_arrays(),
+                center: area.center().into_array(),
+                color_lut_size: 0,
+                _pad0: 0,
+                viewport_size: render_size.as_::<f32>().into_array(),
+                _pad1: [0; 2],
+            }),
+        );
+        let (_, color_lut_view) = create_color_lut(device, None);
+        let camera_bind_group = create_camera_bind_group(
+            device,
+            &self.camera_bind_group_layout,
+            &camera_buffer,
+            &color_lut_view,
+            &self.color_lut_sampler,
+            &self.frame_buffer,
+        );
+        // The texture is a tileset with a single tile, drawn by a 1x1 tilemap covering `area`.
+        let tileset_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap_baked_tileset_buffer"),
+            size: std::mem::size_of::<TilesetBuffer>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        queue.write_buffer(
+            &tileset_buffer,
+            0,
+            bytemuck::bytes_of(&TilesetBuffer {
+                width: pixel_size.x,
+                height: pixel_size.y,
+                tile_width: pixel_size.x,
+                tile_height: pixel_size.y,
+                ..bytemuck::Zeroable::zeroed()
+            }),
+        );
+        let tileset_bind_group = create_tileset_bind_group(
+            device,
+            &self.tileset_bind_group_layout,
+            &tileset_buffer,
+            &texture,
+            self.tileset_placeholders.views(),
+            &self.tile_sampler,
+        );
+        let draw_call = Self::allocate_draw_call(
+            device,
+            &self.tilemap_bind_group_layout,
+            &self.placeholder_tint_view,
+            &mut self.allocation_observer,
+            (Vec2::one(), TileFormat::R8, 1, false),
+        );
+        let transform = Mat4::<f32>::translation_2d(area.position())
+            * Mat4::scaling_3d(Vec2::from(area.extent()).with_z(1.0));
+        queue.write_buffer(
+            &draw_call.params_buffer,
+            0,
+            bytemuck::bytes_of(&TilemapBuffer {
+                transform: transform.into_col_arrays(),
+                width: 1,
+                height: 1,
+                opacity: 1.0,
+                parallax: [1.0; 2],
+                source_size: [1; 2],
+                layers: 1,
+                quad_size: [1; 2],
+                ..bytemuck::Zeroable::zeroed()
+            }),
+        );
+        self.prepare_pipeline(
+            device,
+            (
+                TilemapBlendMode::Alpha,
+                TilemapStencil::Unchanged,
+                TilemapShaderFeatures::default(),
+            ),
+        );
+        let downscale = downscale.map(|params| {
+            self.prepare_downscale_pipeline(device);
+            let source = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("tilemap_baked_downscale_texture"),
+                size: wgpu::Extent3d {
+                    width: render_size.x,
+                    height: render_size.y,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("tilemap_baked_downscale_buffer"),
+                size: std::mem::size_of::<DownscaleBuffer>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            queue.write_buffer(&params_buffer, 0, bytemuck::bytes_of(&params));
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("tilemap_baked_downscale_bind_group"),
+                layout: &self.downscale_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(
+                            &source.create_view(&wgpu::TextureViewDescriptor::default()),
+                        ),
+                    },
+                ],
+            });
+            (source, bind_group)
+        });
+        let mut baked = BakedLayer {
+            texture,
+            area,
+            camera_bind_group,
+            tileset_bind_group,
+            draw_call,
+            downscale,
+            draws: 0..0,
+            draw_hashes: Vec::new(),
+            tileset_generation: 0,
+        };
+        self.rebake(device, queue, &mut baked, draws);
+        baked
+    }
+
+    /// Whether the tilemaps `baked` was baked from have changed since, so that it has to be baked
+    /// again: whether any of them were uploaded with other tiles or settings, fewer were
+    /// uploaded, or tilesets were uploaded again (or the pipeline was cleared or recovered).
+    pub fn is_stale(&self, baked: &BakedLayer) -> bool {
+        baked.tileset_generation != self.tileset_generation
+            || self.draw_hashes.get(baked.draws.clone()) != Some(&baked.draw_hashes[..])
+    }
+
+    /// Bake `baked` again from the tilemaps at the same positions as before, if `is_stale`.
+    /// Returns whether it was baked again.
+    pub fn rebake_if_stale(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        baked: &mut BakedLayer,
+    ) -> bool {
+        if !self.is_stale(baked) {
+            return false;
+        }
+        let draws = baked.draws.clone();
+        self.rebake(device, queue, baked, draws);
+        true
+    }
+
+    /// Draw the tilemaps at `draws` into `baked` again, replacing what was baked before, e.g. after
+    /// uploading them again with changes. See `bake`.
+    pub fn rebake(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        baked: &mut BakedLayer,
+        draws: Range<usize>,
+    ) {
+        let end = draws.end.min(self.draw_hashes.len());
+        baked.draw_hashes = self.draw_hashes[draws.start.min(end)..end].to_vec();
+        baked.draws = draws.clone();
+        baked.tileset_generation = self.tileset_generation;
+        let draws: Vec<_> = self
+            .active_draw_calls
+            .iter()
+            .filter(|(position, _, _)| draws.contains(position))
+            .map(|(_, key, i)| (*key, *i))
+            .collect();
+        // Overlays have no bake pipeline, so they are skipped below.
+        let keys: Vec<_> = draws
+            .iter()
+            .filter_map(|(key, i)| self.draw_calls.map.get(key)?.get(*i as usize))
+            .filter(|call| call.blend_mode != TilemapBlendMode::Overlay)
+            .map(|call| (call.blend_mode, call.features))
+            .collect();
+        for (blend_mode, features) in keys {
+            self.prepare_bake_pipeline(device, blend_mode, features);
+        }
+        let view = baked.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            array_layer_count: Some(1),
+            ..Default::default()
+        });
+        // Layers with a downscale filter are drawn into the larger texture, and filtered into
+        // `view` by a second pass.
+        let source_view = baked
+            .downscale
+            .as_ref()
+            .map(|(source, _)| source.create_view(&wgpu::TextureViewDescriptor::default()));
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("tilemap_bake_encoder"),
+        });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("tilemap_bake_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: source_view.as_ref().unwrap_or(&view),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            rpass.set_bind_group(0, &baked.camera_bind_group, &[]);
+            for (key, i) in &draws {
+                let Some(call) = self
+                    .draw_calls
+                    .map
+                    .get(key)
+                    .and_then(|v| v.get(*i as usize))
+                else {
+                    continue;
+                };
+                let (Some(pipeline), Some(tileset)) = (
+                    self.bake_pipelines.get(&(call.blend_mode, call.features)),
+                    self.current_tileset(call),
+                ) else {
+                    continue;
+                };
+                rpass.set_pipeline(pipeline);
+                rpass.set_bind_group(1, &tileset.bind_group, &[]);
+                rpass.set_bind_group(2, &call.bind_group, &[]);
+                rpass.draw(0..6, 0..1);
+            }
+        }
+        if let (Some((_, bind_group)), Some(pipeline)) =
+            (&baked.downscale, &self.downscale_pipeline)
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("tilemap_downscale_rpass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(pipeline);
+            rpass.set_bind_group(0, bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Draw a layer made by `bake`, with the current camera and present params. Draw it before
+    /// `render` to draw the tilemaps uploaded since underneath it, or after to draw them on top.
+    pub fn render_baked<'a: 'pass, 'pass>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        baked: &'a BakedLayer,
+    ) {
+        let key = (
+            TilemapBlendMode::Alpha,
+            TilemapStencil::Unchanged,
+            TilemapShaderFeatures::default(),
+        );
+        let Some(pipeline) = self.tilemap_pipelines.get(&key) else {
+            return;
+        };
+        self.begin_pass(rpass);
+        rpass.set_pipeline(pipeline);
+        rpass.set_bind_group(1, &baked.tileset_bind_group, &[]);
+        rpass.set_bind_group(2, &baked.draw_call.bind_group, &[]);
+        rpass.draw(0..6, 0..1);
+    }
+
+    /// Compile the shaders that `render_picking` draws with (or free them), for every kind of
+    /// tilemap uploaded from now on.
+    pub fn set_picking(&mut self, device: &wgpu::Device, picking: bool) {
+        self.picking = picking;
+        if !picking {
+            self.picking_pipelines.clear();
+            return;
+        }
+        let compiled: Vec<_> = self.tilemap_pipelines.keys().map(|(_, _, f)| *f).collect();
+        for features in compiled {
+            self.prepare_picking_pipeline(device, features);
+        }
+    }
+
+    /// The positions (in the list most recently given to `upload_tilemaps`) of the tilemaps that
+    /// `render` skips, because `upload_tilesets` has replaced the tilesets since they were uploaded
+    /// and their `TilemapDrawData::tileset` may now refer to another tileset. Upload the tilemaps
+    /// again after the tilesets to draw them.
+    pub fn stale_draws(&self) -> impl Iterator<Item = usize> + '_ {
+        // Every chunk of a draw has the same tileset, so only the first one is checked.
+        let mut previous = None;
+        self.active_draw_calls
+            .iter()
+            .filter_map(move |(position, key, i)| {
+                if previous.replace(*position) == Some(*position) {
+                    return None;
+                }
+                let call = self.draw_calls.map.get(key)?.get(*i as usize)?;
+                self.current_tileset(call).is_none().then_some(*position)
+            })
+    }
+
+    /// The positions (in the list most recently given to `upload_tilemaps`) of the tilemaps that
+    /// weren't uploaded, and aren't drawn, because their `TilemapDrawData::tint` doesn't have a
+    /// color for each tile.
+    pub fn rejected_draws(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rejected_draws.iter().copied()
+    }
+
+    /// The draws of the tilemaps most recently given to `upload_tilemaps`, in the order `render`
+    /// draws them, with what they were uploaded with and how much memory they use. This is meant
+    /// for debug overlays of what the pipeline draws each frame.
+    pub fn active_draws(&self) -> impl Iterator<Item = DrawInfo> + '_ {
+        self.active_draw_calls
+            .iter()
+            .filter_map(|&(position, (size, format, layers, tint), i)| {
+                let call = self.draw_calls.map.get(&(size, format, layers, tint))?;
+                let call = call.get(i as usize)?;
+                Some(DrawInfo {
+                    position,
+                    tiles: call.tiles,
+                    tileset: call.tileset,
+                    transform: call.transform,
+                    blend_mode: call.blend_mode,
+                    stale: self.current_tileset(call).is_none(),
+                    uploaded_bytes: call.uploaded_bytes,
+                    allocated_bytes: tilemap_bytes(size, format, layers, tint),
+                })
+            })
+    }
+
+    /// WGSL declaring the structs and bindings of the camera (group 0), tileset (group 1), and
+    /// tilemap (group 2) that the tilemap shaders start with, for passes of your own that read the
+    /// textures and parameters of this pipeline with its bind groups (see
+    /// `camera_bind_group_layout`, `tileset_bind_group_layout`, and `tilemap_bind_group_layout`).
+    /// Reading them through this source instead of a copy of it keeps such passes compatible with
+    /// the layouts of later versions.
+    pub fn wgsl_bindings() -> &'static str {
+        include_str!("bindings.wgsl")
+    }
+
+    /// The layout of group 0 of `wgsl_bindings`, which `camera_bind_group` has. Like the other
+    /// layouts, it is replaced by `recover`.
+    pub fn camera_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.camera_bind_group_layout
+    }
+
+    /// The layout of group 1 of `wgsl_bindings`, which `tileset_bind_group` has.
+    pub fn tileset_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.tileset_bind_group_layout
+    }
+
+    /// The layout of group 2 of `wgsl_bindings`, which `tilemap_bind_groups` have. Binding 2 of
+    /// it is the tint of the tilemap, a `texture_2d<f32>` with a texel for each tile of the part
+    /// of the tilemap in `tilemap_indices`, or an unused placeholder for tilemaps without a tint.
+    pub fn tilemap_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.tilemap_bind_group_layout
+    }
+
+    /// The camera, color LUT, and frame context, as bound for every draw.
+    pub fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        &self.camera_bind_group
+    }
+
+    /// The tileset at index `tileset` of the list most recently given to `upload_tilesets`, if
+    /// there is one.
+    pub fn tileset_bind_group(&self, tileset: u32) -> Option<&wgpu::BindGroup> {
+        let (key, i) = self.active_tilesets.get(tileset as usize)?;
+        let tileset = self.tilesets.map.get(key)?.get(*i as usize)?;
+        Some(&tileset.bind_group)
+    }
+
+    /// The tilemap at `position` in the list most recently given to `upload_tilemaps`, with a bind
+    /// group for each of its draws (see `active_draws`).
+    pub fn tilemap_bind_groups(&self, position: usize) -> impl Iterator<Item = &wgpu::BindGroup> {
+        self.active_draw_calls
+            .iter()
+            .filter(move |(p, _, _)| *p == position)
+            .filter_map(|(_, key, i)| {
+                Some(&self.draw_calls.map.get(key)?.get(*i as usize)?.bind_group)
+            })
+    }
+
+    /// The tileset that `call` was uploaded with, unless `upload_tilesets` has replaced it since.
+    fn current_tileset(&self, call: &TilemapDrawCall) -> Option<&TilesetCache> {
+        let (key, i) = &call.tilesets_index;
+        self.tilesets
+            .map
+            .get(key)
+            .and_then(|v| v.get(*i as usize))
+            .filter(|tileset| tileset.active && tileset.generation == call.tilesets_generation)
+    }
+
+    /// Set the viewport, the scissor rect, and the resources shared by every draw.
+    fn begin_pass<'a: 'pass, 'pass>(&'a self, rpass: &mut wgpu::RenderPass<'pass>) {
+        if let Some(Presentation {
+            mut viewport,
+            mut scissor,
+            ..
+        }) = self.presentation
+        {
+            if self.target_origin == TargetOrigin::BottomLeft {
+                let height = self.present_target_size.y;
+                viewport.y = height as f32 - viewport.y - viewport.h;
+                scissor.y = height - scissor.y - scissor.h;
+            }
+            rpass.set_viewport(viewport.x, viewport.y, viewport.w, viewport.h, 0.0, 1.0);
+            rpass.set_scissor_rect(scissor.x, scissor.y, scissor.w, scissor.h);
+        }
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_bind_group(0, &self.camera_bind_group, &[]);
+    }
+
+    /// The draws that aren't stale, in upload order, with their tilesets.
+    fn drawable_calls(&self) -> impl Iterator<Item = (&TilemapDrawCall, &TilesetCache)> + '_ {
+        self.active_draw_calls
+            .iter()
+            .filter_map(|(_, key, i)| self.drawable_call(key, *i))
+    }
+
+    /// The draws at `positions` that aren't stale, in the order of `positions`, with their
+    /// tilesets.
+    fn visible_calls<'a: 'p, 'p>(
+        &'a self,
+        positions: &'p [usize],
+    ) -> impl Iterator<Item = (&'a TilemapDrawCall, &'a TilesetCache)> + 'p {
+        positions.iter().flat_map(move |&position| {
+            // The chunks of each draw are contiguous, in upload order.
+            let calls = &self.active_draw_calls;
+            let start = calls.partition_point(|(p, _, _)| *p < position);
+            let end = start + calls[start..].partition_point(|(p, _, _)| *p == position);
+            calls[start..end]
+                .iter()
+                .filter_map(|(_, key, i)| self.drawable_call(key, *i))
+        })
+    }
+
+    fn drawable_call(&self, key: &TilemapKey, i: u32) -> Option<(&TilemapDrawCall, &TilesetCache)> {
+        let call = self.draw_calls.map.get(key)?.get(i as usize)?;
+        Some((call, self.current_tileset(call)?))
+    }
+
+    fn render_with_profiler_inner<'a: 'pass, 'pass>(
+        &'a self,
+        device: &wgpu::Device,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        gpu_profiler: &mut impl ProfilerShim,
+        calls: impl Iterator<Item = (&'a TilemapDrawCall, &'a TilesetCache)>,
+    ) {
+        gpu_profiler.begin_scope("tilemap", rpass, device);
+        self.begin_pass(rpass);
+        // Draw in the order given (upload order, for `render`), so that layers without a depth
+        // buffer (e.g. shadows) composite correctly.
+        let mut current_pipeline = None;
+        for (call, tilesets_bg) in calls {
+            let pipeline_key = (call.blend_mode, call.stencil.mode(), call.features);
+            if current_pipeline != Some(pipeline_key) {
+                if call.blend_mode == TilemapBlendMode::Overlay {
+                    let Some(backdrop_bg) = &self.backdrop_bind_group else {
+                        continue;
+                    };
+                    rpass.set_bind_group(3, backdrop_bg, &[]);
+                }
+                rpass.set_pipeline(&self.tilemap_pipelines[&pipeline_key]);
+                current_pipeline = Some(pipeline_key);
+            }
+            if let Some(reference) = call.stencil.reference() {
+                rpass.set_stencil_reference(reference);
+            }
+            gpu_profiler.begin_scope("tilemap_draw", rpass, device);
+            rpass.set_bind_group(1, &tilesets_bg.bind_group, &[]);
+            rpass.set_bind_group(2, &call.bind_group, &[]);
+            rpass.draw(0..6, 0..1);
+            gpu_profiler.end_scope(rpass);
+        }
+        gpu_profiler.end_scope(rpass);
+    }
+}
+
+trait ProfilerShim {
+    fn begin_scope(&mut self, span: &str, rpass: &mut wgpu::RenderPass, device: &wgpu::Device);
+    fn end_scope(&mut self, rpass: &mut wgpu::RenderPass);
+}
+
+impl ProfilerShim for () {
+    fn begin_scope(&mut self, _span: &str, _rpass: &mut wgpu::RenderPass, _device: &wgpu::Device) {}
+    fn end_scope(&mut self, _rpass: &mut wgpu::RenderPass) {}
+}
+
+#[cfg(feature = "wgpu-profiler")]
+impl ProfilerShim for wgpu_profiler::GpuProfiler {
+    fn begin_scope(&mut self, span: &str, rpass: &mut wgpu::RenderPass, device: &wgpu::Device) {
+        (*self).begin_scope(span, rpass, device)
+    }
+    fn end_scope(&mut self, rpass: &mut wgpu::RenderPass) {
+        (*self).end_scope(rpass)
+    }
+}
@@ -0,0 +1,12 @@
+//! The types that most programs drawing tilemaps need, to be imported with
+//! `use wgpu_tilemap::prelude::*;`.
+#[cfg(feature = "std")]
+pub use crate::{
+    ortho_camera, pixel_camera, PresentParams, PresentScaling, TilemapBlendMode, TilemapDrawData,
+    TilemapNoise, TilemapPipeline, TilemapShadow, TilemapUserData, TilemapWalls,
+    FULLSCREEN_QUAD_CAMERA,
+};
+pub use crate::{
+    AnyTilemapRef, EdgeMode, HeatmapRef, TileAnimation, TileFlip, TileId, Tilemap, TilemapRef,
+    TilesetRef, EMPTY_TILE,
+};
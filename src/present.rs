@@ -0,0 +1,89 @@
+use crate::ortho_camera;
+use vek::{Mat4, Rect, Vec2};
+
+/// How `PresentParams::virtual_resolution` is scaled up to the size of the render target.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PresentScaling {
+    /// Scale by the largest whole number that fits, so every virtual pixel is the same size (or
+    /// like `Fit` if the target is smaller than the virtual resolution).
+    #[default]
+    Integer,
+    /// Scale by the largest factor that fits, keeping the aspect ratio.
+    Fit,
+    /// Stretch to fill the whole target, ignoring the aspect ratio.
+    Stretch,
+}
+
+/// Draw at a fixed virtual resolution (e.g. for pixel art), scaled up and centered in the render
+/// target with bars around it. See `TilemapPipeline::set_present_params`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PresentParams {
+    /// The size of the virtual screen, in virtual pixels.
+    pub virtual_resolution: Vec2<u32>,
+    pub scaling: PresentScaling,
+}
+
+/// The camera and viewport that present a virtual resolution in a render target, as returned by
+/// `PresentParams::present`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Presentation {
+    /// Maps world coordinates in virtual pixels, with (0, 0) at the bottom left of the virtual
+    /// screen, to the viewport.
+    pub camera: Mat4<f32>,
+    /// The part of the render target that is drawn to, in pixels from its top left corner.
+    pub viewport: Rect<f32, f32>,
+    /// `viewport` rounded out to whole pixels, to limit drawing to it.
+    pub scissor: Rect<u32, u32>,
+    /// The size of the virtual screen, in virtual pixels.
+    pub virtual_resolution: Vec2<u32>,
+}
+
+impl PresentParams {
+    /// The camera and viewport for a render target of `target_size` pixels. Targets with no
+    /// pixels along an axis (e.g. a minimized window) are treated as a pixel wide, so that the
+    /// viewport is never empty, which wgpu rejects.
+    pub fn present(&self, target_size: Vec2<u32>) -> Presentation {
+        let target_size = target_size.map(|x| x.max(1));
+        let virtual_resolution = self.virtual_resolution.map(|x| x.max(1));
+        let target = target_size.as_::<f32>();
+        let virtual_size = virtual_resolution.as_::<f32>();
+        let fit = (target / virtual_size).reduce_partial_min();
+        let size = match self.scaling {
+            PresentScaling::Integer if fit >= 1.0 => virtual_size * fit.floor(),
+            PresentScaling::Integer | PresentScaling::Fit => virtual_size * fit,
+            PresentScaling::Stretch => target,
+        };
+        let origin = ((target - size) / 2.0).map(f32::floor);
+        let scissor_origin = origin.map2(target_size, |x, max| (x as u32).min(max));
+        let scissor_size = (origin + size)
+            .map(f32::ceil)
+            .map2(target_size, |x, max| (x as u32).min(max))
+            - scissor_origin;
+        Presentation {
+            camera: ortho_camera(Rect::new(0.0, 0.0, virtual_size.x, virtual_size.y)),
+            viewport: Rect::new(origin.x, origin.y, size.x, size.y),
+            scissor: Rect::new(
+                scissor_origin.x,
+                scissor_origin.y,
+                scissor_size.x,
+                scissor_size.y,
+            ),
+            virtual_resolution,
+        }
+    }
+}
+
+impl Presentation {
+    /// Convert a position in the render target (in pixels from its top left corner, e.g. the
+    /// cursor) to virtual pixels, with (0, 0) at the bottom left of the virtual screen. Returns
+    /// `None` for positions outside of the viewport.
+    pub fn target_to_virtual(&self, position: Vec2<f32>) -> Option<Vec2<f32>> {
+        let uv = (position - self.viewport.position()) / Vec2::from(self.viewport.extent());
+        if uv.x < 0.0 || uv.y < 0.0 || uv.x >= 1.0 || uv.y >= 1.0 {
+            return None;
+        }
+        Some(Vec2::new(uv.x, 1.0 - uv.y) * self.virtual_resolution.as_::<f32>())
+    }
+}
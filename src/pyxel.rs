@@ -0,0 +1,200 @@
+//! Importing documents made with [Pyxel Edit](https://pyxeledit.com/).
+use crate::{
+    import::LayerWarnings, world::with_empty_tile, AlphaMode, ImportWarning, Tilemap,
+    TilemapBlendMode, TilemapLayer, TilemapWorld, TilesetRef, WorldLayer, EMPTY_TILE,
+};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{Read, Seek},
+    path::Path,
+};
+use vek::{Mat4, Vec2};
+
+/// An error encountered while importing a Pyxel Edit document.
+#[derive(Debug)]
+pub enum PyxelError {
+    /// The document could not be read.
+    Io(String, std::io::Error),
+    /// The document is not a valid archive.
+    Zip(zip::result::ZipError),
+    /// The description of the document (`docData.json`) is not valid JSON.
+    Json(serde_json::Error),
+    /// An image of a tile could not be decoded.
+    Image(image::ImageError),
+    /// The description of the document is missing a value or has one that is out of range.
+    InvalidData(String),
+    /// The document uses a feature that isn't supported (e.g. an unusual blend mode).
+    Unsupported(String),
+}
+
+impl fmt::Display for PyxelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PyxelError::Io(path, e) => write!(f, "couldn't read {path:?}: {e}"),
+            PyxelError::Zip(e) => write!(f, "invalid archive: {e}"),
+            PyxelError::Json(e) => write!(f, "invalid docData.json: {e}"),
+            PyxelError::Image(e) => write!(f, "invalid tile image: {e}"),
+            PyxelError::InvalidData(what) => write!(f, "invalid document: {what}"),
+            PyxelError::Unsupported(what) => write!(f, "unsupported: {what}"),
+        }
+    }
+}
+
+impl std::error::Error for PyxelError {}
+
+impl From<zip::result::ZipError> for PyxelError {
+    fn from(e: zip::result::ZipError) -> Self {
+        PyxelError::Zip(e)
+    }
+}
+
+/// A Pyxel Edit document, converted into a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub struct PyxelDocument {
+    /// The layers of the document, drawn with its tileset.
+    pub world: TilemapWorld,
+    /// Parts of the document that couldn't be imported.
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Import a document from a Pyxel Edit .pyxel file.
+pub fn load_pyxel(path: impl AsRef<Path>) -> Result<PyxelDocument, PyxelError> {
+    let path = path.as_ref();
+    let file =
+        std::fs::File::open(path).map_err(|e| PyxelError::Io(path.display().to_string(), e))?;
+    parse_pyxel(std::io::BufReader::new(file))
+}
+
+/// Import a document from the contents of a Pyxel Edit .pyxel file. The tileset of the document
+/// becomes the only tileset of the world, and each layer becomes a `WorldLayer::Tiles` made of
+/// the tiles placed on it, with cells without a tile set to `EMPTY_TILE`. Pixels drawn directly
+/// onto a layer without placing a tile aren't imported, and tiles are imported without their
+/// rotations and flips.
+pub fn parse_pyxel(reader: impl Read + Seek) -> Result<PyxelDocument, PyxelError> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    let mut warnings = Vec::new();
+    let doc: serde_json::Value =
+        serde_json::from_reader(archive.by_name("docData.json")?).map_err(PyxelError::Json)?;
+    let canvas = &doc["canvas"];
+    let size_of_tile = Vec2::new(
+        json_u32(canvas, "tileWidth")?,
+        json_u32(canvas, "tileHeight")?,
+    );
+    if size_of_tile.x == 0 || size_of_tile.y == 0 {
+        return Err(PyxelError::InvalidData("tiles are empty".to_owned()));
+    }
+    let tile_size =
+        Vec2::new(json_u32(canvas, "width")?, json_u32(canvas, "height")?) / size_of_tile;
+
+    // Each tile of the tileset is stored as its own image.
+    let num_tiles = json_u32(&doc["tileset"], "numTiles")?.min(EMPTY_TILE as u32);
+    let mut data =
+        Vec::with_capacity(num_tiles as usize * size_of_tile.x as usize * size_of_tile.y as usize);
+    for tile in 0..num_tiles {
+        // Tiles whose image is missing are left transparent.
+        let file_name = format!("tile{tile}.png");
+        let mut png = Vec::new();
+        match archive.by_name(&file_name) {
+            Ok(mut file) => {
+                file.read_to_end(&mut png)
+                    .map_err(|e| PyxelError::Io(file_name.clone(), e))?;
+            }
+            Err(zip::result::ZipError::FileNotFound) => {
+                warnings.push(ImportWarning::MissingTileset {
+                    tileset: file_name,
+                    reason: "the image of this tile is missing".to_owned(),
+                });
+                data.resize(data.len() + size_of_tile.product() as usize, 0);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+            .map_err(PyxelError::Image)?
+            .into_rgba8();
+        for y in 0..size_of_tile.y {
+            for x in 0..size_of_tile.x {
+                let pixel = image
+                    .get_pixel_checked(x, y)
+                    .map_or([0; 4], |pixel| pixel.0);
+                data.push(u32::from_le_bytes(pixel));
+            }
+        }
+    }
+    let tileset = TilesetRef {
+        pixel_size: size_of_tile * Vec2::new(num_tiles, 1),
+        size_of_tile,
+        data: Cow::Owned(data),
+        alpha_mode: AlphaMode::Straight,
+        uv_inset: 0.0,
+        checkerboard: Cow::Borrowed(&[]),
+        compressed: None,
+    };
+
+    // Layers are numbered from the top, so import them in reverse.
+    let num_layers = json_u32(canvas, "numLayers")?;
+    let mut layers = Vec::with_capacity(num_layers as usize);
+    for i in (0..num_layers).rev() {
+        let layer = &canvas["layers"][i.to_string()];
+        let blend_mode = match layer["blendMode"].as_str() {
+            None | Some("normal") => TilemapBlendMode::Alpha,
+            Some("add") => TilemapBlendMode::Additive,
+            Some("multiply") => TilemapBlendMode::Multiply,
+            Some("screen") => TilemapBlendMode::Screen,
+            Some("overlay") => TilemapBlendMode::Overlay,
+            Some(mode) => return Err(PyxelError::Unsupported(format!("{mode} blend mode"))),
+        };
+        let name = layer["name"].as_str().unwrap_or_default();
+        let mut tilemap = Tilemap::new_filled(tile_size, EMPTY_TILE);
+        let mut layer_warnings = LayerWarnings::default();
+        if let Some(refs) = layer["tileRefs"].as_object() {
+            for (cell, tile_ref) in refs.iter() {
+                let cell: u32 = cell
+                    .parse()
+                    .map_err(|_| PyxelError::InvalidData(format!("invalid cell {cell:?}")))?;
+                let position = Vec2::new(cell % tile_size.x.max(1), cell / tile_size.x.max(1));
+                let tile = json_u32(tile_ref, "index")?;
+                if position.y >= tile_size.y {
+                    continue;
+                }
+                if tile >= num_tiles {
+                    layer_warnings.unknown_tile(tile);
+                    continue;
+                }
+                let rotated = tile_ref["rot"].as_u64().unwrap_or(0) % 4 != 0;
+                if rotated || tile_ref["flipX"].as_bool().unwrap_or(false) {
+                    layer_warnings.transformed_tile();
+                }
+                tilemap.put_tile(position.x, position.y, tile as u8);
+            }
+        }
+        layer_warnings.finish(name, &mut warnings);
+        layers.push(WorldLayer::Tiles(TilemapLayer {
+            name: name.to_owned(),
+            tilemap,
+            tileset: 0,
+            transform: Mat4::identity(),
+            visible: !layer["hidden"].as_bool().unwrap_or(false),
+            blend_mode,
+            opacity: layer["alpha"].as_f64().unwrap_or(255.0) as f32 / 255.0,
+            parallax: Vec2::one(),
+        }));
+    }
+
+    let world = TilemapWorld {
+        tile_size,
+        size_of_tile,
+        tilesets: vec![with_empty_tile(&tileset)],
+        layers,
+        ..TilemapWorld::default()
+    };
+    Ok(PyxelDocument { world, warnings })
+}
+
+fn json_u32(object: &serde_json::Value, key: &str) -> Result<u32, PyxelError> {
+    object[key]
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| PyxelError::InvalidData(format!("missing or invalid {key:?}")))
+}
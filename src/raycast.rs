@@ -0,0 +1,201 @@
+use crate::{TileId, TilemapRef};
+use vek::Vec2;
+
+/// The first tile hit by a `raycast`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RaycastHit<T> {
+    /// The tile that was hit.
+    pub tile: T,
+    /// The position of the tile in the tilemap.
+    pub cell: Vec2<u32>,
+    /// Where the ray entered the tile, in tiles.
+    pub pos: Vec2<f32>,
+    /// The side of the tile the ray entered through, pointing out of the tile (e.g. `(-1, 0)` for
+    /// its left side), or zero if the ray started inside it.
+    pub normal: Vec2<i32>,
+}
+
+/// The largest integer at most `x`, without `f32::floor`, which needs `std`.
+fn floor(x: f32) -> i32 {
+    let truncated = x as i32;
+    truncated - (x < truncated as f32) as i32
+}
+
+/// Trace a ray from `from` to `to` through the first layer of `tilemap`, and return the first
+/// tile it passes through for which `predicate` returns `true` (e.g. walls, for line of sight,
+/// bullets, or audio occlusion). Positions are in tiles, with tile `(x, y)` covering `x..x + 1`
+/// and `y..y + 1` like the `tilepos` of `TilemapPipeline::with_fragment_hook`. Tiles are visited in
+/// order along the ray (a DDA traversal), and parts of the ray past the edges of the tilemap don't
+/// hit anything.
+pub fn raycast<T: TileId>(
+    tilemap: &TilemapRef<T>,
+    from: Vec2<f32>,
+    to: Vec2<f32>,
+    mut predicate: impl FnMut(T) -> bool,
+) -> Option<RaycastHit<T>> {
+    let size = tilemap.tile_size.as_::<i32>();
+    let dir = to - from;
+    let step = dir.map(|d| (d > 0.0) as i32 - (d < 0.0) as i32);
+    let mut cell = from.map(floor);
+    // How far along the ray (from 0 at `from` to 1 at `to`) the next edge is on each axis, and
+    // how far apart the edges are.
+    let mut t_max = Vec2::new(0usize, 1).map(|axis| match step[axis] {
+        0 => f32::INFINITY,
+        1 => (cell[axis] as f32 + 1.0 - from[axis]) / dir[axis],
+        _ => (cell[axis] as f32 - from[axis]) / dir[axis],
+    });
+    let t_delta = dir.map2(
+        step,
+        |d, s| if s == 0 { f32::INFINITY } else { 1.0 / d.abs() },
+    );
+    let mut t = 0.0;
+    let mut normal = Vec2::zero();
+    loop {
+        let inside = cell.x >= 0 && cell.y >= 0 && cell.x < size.x && cell.y < size.y;
+        if inside {
+            let tile = tilemap.get_tile(cell.x as u32, cell.y as u32);
+            if predicate(tile) {
+                return Some(RaycastHit {
+                    tile,
+                    cell: cell.as_::<u32>(),
+                    pos: from + dir * t,
+                    normal,
+                });
+            }
+        }
+        // Stop once the ray has left the tilemap for good.
+        let leaving = |axis: usize| {
+            (cell[axis] < 0 && step[axis] <= 0) || (cell[axis] >= size[axis] && step[axis] >= 0)
+        };
+        if leaving(0) || leaving(1) {
+            return None;
+        }
+        let axis = (t_max.y < t_max.x) as usize;
+        if t_max[axis] > 1.0 {
+            return None;
+        }
+        t = t_max[axis];
+        t_max[axis] += t_delta[axis];
+        cell[axis] += step[axis];
+        normal = Vec2::zero();
+        normal[axis] = -step[axis];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tilemap;
+    use alloc::vec::Vec;
+
+    /// An 8x8 tilemap where each tile is numbered after its cell, starting from 1.
+    fn numbered() -> TilemapRef<'static, u8> {
+        let mut tilemap = Tilemap::new_zeroed(Vec2::new(8, 8));
+        for y in 0..8 {
+            for x in 0..8 {
+                tilemap.put_tile(x, y, tile_at(x, y));
+            }
+        }
+        tilemap.into()
+    }
+
+    fn tile_at(x: u32, y: u32) -> u8 {
+        (y * 8 + x + 1) as u8
+    }
+
+    /// The cells visited by a ray that doesn't hit anything, in order.
+    fn visited(from: Vec2<f32>, to: Vec2<f32>) -> Vec<Vec2<u32>> {
+        let mut cells = Vec::new();
+        let hit = raycast(&numbered(), from, to, |tile| {
+            let index = tile as u32 - 1;
+            cells.push(Vec2::new(index % 8, index / 8));
+            false
+        });
+        assert_eq!(hit, None);
+        cells
+    }
+
+    fn hit(from: Vec2<f32>, to: Vec2<f32>, wall: Vec2<u32>) -> Option<RaycastHit<u8>> {
+        raycast(&numbered(), from, to, |tile| {
+            tile == tile_at(wall.x, wall.y)
+        })
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_side_they_enter_through() {
+        let right = hit(Vec2::new(0.5, 2.5), Vec2::new(7.5, 2.5), Vec2::new(5, 2));
+        assert_eq!(
+            right,
+            Some(RaycastHit {
+                tile: tile_at(5, 2),
+                cell: Vec2::new(5, 2),
+                pos: Vec2::new(5.0, 2.5),
+                normal: Vec2::new(-1, 0),
+            })
+        );
+        let left = hit(Vec2::new(7.5, 2.5), Vec2::new(0.5, 2.5), Vec2::new(5, 2)).unwrap();
+        assert_eq!(
+            (left.pos, left.normal),
+            (Vec2::new(6.0, 2.5), Vec2::new(1, 0))
+        );
+        let down = hit(Vec2::new(3.5, 0.5), Vec2::new(3.5, 7.5), Vec2::new(3, 6)).unwrap();
+        assert_eq!(
+            (down.pos, down.normal),
+            (Vec2::new(3.5, 6.0), Vec2::new(0, -1))
+        );
+        let up = hit(Vec2::new(3.5, 7.5), Vec2::new(3.5, 0.5), Vec2::new(3, 1)).unwrap();
+        assert_eq!((up.pos, up.normal), (Vec2::new(3.5, 2.0), Vec2::new(0, 1)));
+        assert_eq!(
+            visited(Vec2::new(1.5, 4.5), Vec2::new(4.5, 4.5)),
+            [1, 2, 3, 4].map(|x| Vec2::new(x, 4))
+        );
+    }
+
+    #[test]
+    fn diagonal_rays_through_corners_step_along_x_first() {
+        let cells = visited(Vec2::new(0.5, 0.5), Vec2::new(2.5, 2.5));
+        assert_eq!(
+            cells,
+            [(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)].map(Vec2::from)
+        );
+        let corner = hit(Vec2::new(0.5, 0.5), Vec2::new(2.5, 2.5), Vec2::new(2, 2)).unwrap();
+        assert_eq!(corner.pos, Vec2::new(2.0, 2.0));
+        assert_eq!(corner.normal, Vec2::new(0, -1));
+    }
+
+    #[test]
+    fn rays_from_outside_hit_once_they_enter() {
+        let entering = hit(Vec2::new(-2.5, 3.5), Vec2::new(5.5, 3.5), Vec2::new(0, 3)).unwrap();
+        assert_eq!(entering.cell, Vec2::new(0, 3));
+        assert_eq!(
+            (entering.pos, entering.normal),
+            (Vec2::new(0.0, 3.5), Vec2::new(-1, 0))
+        );
+        let below = hit(Vec2::new(4.5, 10.5), Vec2::new(1.5, 4.5), Vec2::new(3, 7)).unwrap();
+        assert_eq!(
+            (below.pos, below.normal),
+            (Vec2::new(3.25, 8.0), Vec2::new(0, 1))
+        );
+        assert_eq!(visited(Vec2::new(-2.5, -1.5), Vec2::new(-2.5, 9.5)), []);
+        assert_eq!(visited(Vec2::new(9.5, 0.5), Vec2::new(12.5, 0.5)), []);
+    }
+
+    #[test]
+    fn rays_stop_at_their_end() {
+        assert_eq!(
+            hit(Vec2::new(0.5, 0.5), Vec2::new(3.5, 0.5), Vec2::new(4, 0)),
+            None
+        );
+        assert_eq!(visited(Vec2::new(0.5, 0.5), Vec2::new(3.0, 0.5)).len(), 4);
+    }
+
+    #[test]
+    fn rays_starting_in_a_hit_tile_have_no_normal() {
+        let from = Vec2::new(2.25, 3.75);
+        let zero_length = hit(from, from, Vec2::new(2, 3)).unwrap();
+        assert_eq!((zero_length.pos, zero_length.normal), (from, Vec2::zero()));
+        let inside = hit(from, Vec2::new(7.5, 0.5), Vec2::new(2, 3)).unwrap();
+        assert_eq!((inside.pos, inside.normal), (from, Vec2::zero()));
+        assert_eq!(visited(from, from), [Vec2::new(2, 3)]);
+    }
+}
@@ -0,0 +1,193 @@
+//! Serde support for the map data types, with their tiles and pixels stored compactly: as base64
+//! strings for human-readable formats (e.g. JSON or RON), and as byte arrays otherwise.
+use crate::{AlphaMode, CheckerboardTile, TileFormat, TileId, TilemapRef, TilesetRef};
+use base64::Engine;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{borrow::Cow, fmt};
+use vek::Vec2;
+
+/// Little-endian bytes, serialized as base64 or as a byte array.
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "base64 or bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Bytes, E> {
+                base64::engine::general_purpose::STANDARD
+                    .decode(v)
+                    .map(Bytes)
+                    .map_err(E::custom)
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Bytes, E> {
+                Ok(Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                Ok(Bytes(v))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Bytes(bytes))
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(BytesVisitor)
+        } else {
+            deserializer.deserialize_bytes(BytesVisitor)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "TilemapRef")]
+struct TilemapRepr {
+    tile_size: Vec2<u32>,
+    format: TileFormat,
+    /// Each tile as `format.bytes_per_tile()` little-endian bytes.
+    data: Bytes,
+}
+
+impl<T: TileId> Serialize for TilemapRef<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes_per_tile = T::FORMAT.bytes_per_tile();
+        let data = self
+            .data
+            .iter()
+            .flat_map(|tile| tile.index().to_le_bytes().into_iter().take(bytes_per_tile))
+            .collect();
+        TilemapRepr {
+            tile_size: self.tile_size,
+            format: T::FORMAT,
+            data: Bytes(data),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: TileId> Deserialize<'de> for TilemapRef<'_, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = TilemapRepr::deserialize(deserializer)?;
+        if repr.format != T::FORMAT {
+            return Err(de::Error::custom(format!(
+                "expected a tilemap of {:?} tiles, but it has {:?} tiles",
+                T::FORMAT,
+                repr.format
+            )));
+        }
+        let bytes_per_tile = T::FORMAT.bytes_per_tile();
+        if repr.data.0.len() % bytes_per_tile != 0 {
+            return Err(de::Error::custom("the tiles end partway through a tile"));
+        }
+        // Tilemaps with several layers (see `TilemapStack`) have their layers one after another.
+        let tiles = repr.data.0.len() / bytes_per_tile;
+        let layer_len = repr.tile_size.as_::<u64>().product();
+        let whole_layers = match layer_len {
+            0 => tiles == 0,
+            _ => tiles as u64 % layer_len == 0,
+        };
+        if !whole_layers {
+            let expected = format!("a multiple of {layer_len} tiles");
+            return Err(de::Error::invalid_length(tiles, &expected.as_str()));
+        }
+        let data = repr
+            .data
+            .0
+            .chunks_exact(bytes_per_tile)
+            .map(|bytes| {
+                let mut index = [0; 4];
+                index[..bytes_per_tile].copy_from_slice(bytes);
+                T::from_index(u32::from_le_bytes(index))
+            })
+            .collect();
+        Ok(TilemapRef {
+            tile_size: repr.tile_size,
+            data: Cow::Owned(data),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "TilesetRef")]
+struct TilesetRepr<'a> {
+    pixel_size: Vec2<u32>,
+    size_of_tile: Vec2<u32>,
+    /// The RGBA bytes of each pixel, in the order of `TilesetRef::data`.
+    data: Bytes,
+    alpha_mode: AlphaMode,
+    uv_inset: f32,
+    #[serde(default)]
+    checkerboard: Cow<'a, [CheckerboardTile]>,
+}
+
+impl Serialize for TilesetRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TilesetRepr {
+            pixel_size: self.pixel_size,
+            size_of_tile: self.size_of_tile,
+            data: Bytes(
+                self.data
+                    .iter()
+                    .flat_map(|pixel| pixel.to_le_bytes())
+                    .collect(),
+            ),
+            alpha_mode: self.alpha_mode,
+            uv_inset: self.uv_inset,
+            checkerboard: Cow::Borrowed(&self.checkerboard),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TilesetRef<'_> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = TilesetRepr::deserialize(deserializer)?;
+        if repr.data.0.len() % 4 != 0 {
+            return Err(de::Error::custom("the pixels end partway through a pixel"));
+        }
+        let pixels = repr.data.0.len() / 4;
+        let expected = repr.pixel_size.as_::<u64>().product();
+        if pixels as u64 != expected {
+            let expected = format!("{expected} pixels");
+            return Err(de::Error::invalid_length(pixels, &expected.as_str()));
+        }
+        Ok(TilesetRef {
+            pixel_size: repr.pixel_size,
+            size_of_tile: repr.size_of_tile,
+            data: Cow::Owned(
+                repr.data
+                    .0
+                    .chunks_exact(4)
+                    .map(|pixel| u32::from_le_bytes(pixel.try_into().unwrap()))
+                    .collect(),
+            ),
+            alpha_mode: repr.alpha_mode,
+            uv_inset: repr.uv_inset,
+            checkerboard: Cow::Owned(repr.checkerboard.into_owned()),
+            compressed: None,
+        })
+    }
+}
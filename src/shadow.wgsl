@@ -0,0 +1,34 @@
+// Alpha of the tile pixels at gridpos composited over every layer, or 0.0 outside of the part of
+// the tilemap that is drawn
+fn tile_alpha(gridpos: vec2<f32>) -> f32 {
+    let tilepos = tilemap_stagger(gridpos);
+    let source_min = vec2<f32>(tilemap.source_origin);
+    let source_max = source_min + vec2<f32>(tilemap.source_size);
+    if any(tilepos < source_min) || any(tilepos >= source_max) {
+        return 0.0;
+    }
+    let size_of_tile = vec2(tiledata.tile_width, tiledata.tile_height);
+    var alpha = 0.0;
+    for (var layer = 0u; layer < tilemap.layers; layer++) {
+        if tile_is_empty(vec2<u32>(tilepos), layer) {
+            continue;
+        }
+        let tile = tile_at(vec2<u32>(tilepos), layer);
+        let subpos = tile_texel(tilepos, tilepos * vec2<f32>(size_of_tile), layer);
+        let layer_alpha = textureLoad(tilemap_data, subpos, tile, i32(tiledata.mip_level)).a;
+        alpha = layer_alpha + alpha * (1.0 - layer_alpha);
+    }
+    return alpha;
+}
+
+// Shadow cast onto tilepos by the tiles up to shadow_offset away from it, fading out with distance
+fn tilemap_layer_color(data: TilemapFragData, footprint: TileFootprint) -> vec4<f32> {
+    var coverage = 0.0;
+    for (var i = 1u; i <= tilemap.shadow_samples; i++) {
+        let t = f32(i) / f32(tilemap.shadow_samples);
+        let falloff = 1.0 - (f32(i) - 1.0) / f32(tilemap.shadow_samples);
+        coverage = max(coverage, falloff * tile_alpha(data.tilepos - t * tilemap.shadow_offset));
+    }
+    // Premultiplied black
+    return vec4(0.0, 0.0, 0.0, tilemap.shadow_opacity * coverage);
+}
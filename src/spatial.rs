@@ -0,0 +1,211 @@
+use std::{collections::HashMap, hash::Hash};
+use vek::{Rect, Vec2};
+
+/// Game entities (e.g. characters, items, or projectiles) bucketed by the tile they are on, to find
+/// the ones near a position or in an area without checking every entity. Positions are in tiles,
+/// with tile `(x, y)` covering `x..x + 1` and `y..y + 1` like `raycast`, and may be outside of any
+/// tilemap. Entities are identified by a key chosen by the game, such as an index or an id.
+#[derive(Clone, Debug)]
+pub struct TileSpatialIndex<K> {
+    cells: HashMap<Vec2<i32>, Vec<K>>,
+    entities: HashMap<K, Vec2<i32>>,
+}
+
+impl<K> Default for TileSpatialIndex<K> {
+    fn default() -> Self {
+        TileSpatialIndex {
+            cells: HashMap::new(),
+            entities: HashMap::new(),
+        }
+    }
+}
+
+/// The tile that `position` is on.
+fn cell_at(position: Vec2<f32>) -> Vec2<i32> {
+    position.map(|x| x.floor() as i32)
+}
+
+impl<K: Copy + Eq + Hash> TileSpatialIndex<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an entity at `position`, or move it there if it has already been added.
+    pub fn insert(&mut self, key: K, position: Vec2<f32>) {
+        let cell = cell_at(position);
+        if let Some(old) = self.entities.insert(key, cell) {
+            if old == cell {
+                return;
+            }
+            self.remove_from_cell(key, old);
+        }
+        self.cells.entry(cell).or_default().push(key);
+    }
+
+    /// Move an entity to `position`. Returns `false` (and does nothing) if it hasn't been added.
+    /// Moving within a tile is cheap, so this can be called every time an entity moves.
+    pub fn move_to(&mut self, key: K, position: Vec2<f32>) -> bool {
+        if !self.entities.contains_key(&key) {
+            return false;
+        }
+        self.insert(key, position);
+        true
+    }
+
+    /// Remove an entity. Returns whether it had been added.
+    pub fn remove(&mut self, key: K) -> bool {
+        let Some(cell) = self.entities.remove(&key) else {
+            return false;
+        };
+        self.remove_from_cell(key, cell);
+        true
+    }
+
+    fn remove_from_cell(&mut self, key: K, cell: Vec2<i32>) {
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            keys.retain(|k| *k != key);
+            if keys.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// The tile an entity is on, if it has been added.
+    pub fn cell(&self, key: K) -> Option<Vec2<i32>> {
+        self.entities.get(&key).copied()
+    }
+
+    /// The entities on the tile at `cell`, in the order they were moved there.
+    pub fn query_cell(&self, cell: Vec2<i32>) -> &[K] {
+        self.cells.get(&cell).map_or(&[], Vec::as_slice)
+    }
+
+    /// The entities on the tiles in `rect` (e.g. the tiles on screen, or around an explosion),
+    /// along with the tile each one is on.
+    pub fn query_rect(&self, rect: Rect<i32, u32>) -> impl Iterator<Item = (Vec2<i32>, K)> + '_ {
+        let contains = move |cell: Vec2<i32>| {
+            let offset = cell - Vec2::new(rect.x, rect.y);
+            offset.x >= 0
+                && offset.y >= 0
+                && (offset.x as u32) < rect.w
+                && (offset.y as u32) < rect.h
+        };
+        // Look up each tile of small areas, and check each occupied tile against large ones.
+        let tiles = rect.w as u64 * rect.h as u64;
+        let cells: Box<dyn Iterator<Item = (Vec2<i32>, &Vec<K>)>> =
+            if tiles <= self.cells.len() as u64 {
+                Box::new((0..rect.h as i32).flat_map(move |y| {
+                    (0..rect.w as i32).filter_map(move |x| {
+                        let cell = Vec2::new(rect.x + x, rect.y + y);
+                        self.cells.get(&cell).map(|keys| (cell, keys))
+                    })
+                }))
+            } else {
+                Box::new(
+                    self.cells
+                        .iter()
+                        .filter(move |(cell, _)| contains(**cell))
+                        .map(|(cell, keys)| (*cell, keys)),
+                )
+            };
+        cells.flat_map(|(cell, keys)| keys.iter().map(move |key| (cell, *key)))
+    }
+
+    /// The number of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Remove every entity.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entities.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The entities in `rect`, sorted.
+    fn query(index: &TileSpatialIndex<u32>, rect: Rect<i32, u32>) -> Vec<(Vec2<i32>, u32)> {
+        let mut found: Vec<_> = index.query_rect(rect).collect();
+        found.sort_by_key(|&(cell, key)| (cell.y, cell.x, key));
+        found
+    }
+
+    #[test]
+    fn insert_moves_entities_between_cells() {
+        let mut index = TileSpatialIndex::new();
+        index.insert(1, Vec2::new(0.5, 0.5));
+        index.insert(2, Vec2::new(0.75, 0.25));
+        index.insert(1, Vec2::new(0.25, 0.75));
+        assert_eq!(index.query_cell(Vec2::zero()), [1, 2]);
+        index.insert(1, Vec2::new(-0.5, 3.0));
+        assert_eq!(index.cell(1), Some(Vec2::new(-1, 3)));
+        assert_eq!(index.query_cell(Vec2::zero()), [2]);
+        assert_eq!(index.query_cell(Vec2::new(-1, 3)), [1]);
+        assert_eq!(index.len(), 2);
+        assert!(index.move_to(2, Vec2::new(-0.25, 3.5)));
+        assert!(!index.move_to(3, Vec2::zero()));
+        assert_eq!(index.query_cell(Vec2::new(-1, 3)), [1, 2]);
+        assert_eq!(index.cell(3), None);
+    }
+
+    #[test]
+    fn remove_drops_empty_cells() {
+        let mut index = TileSpatialIndex::new();
+        index.insert(1, Vec2::new(2.5, 2.5));
+        index.insert(2, Vec2::new(2.5, 2.5));
+        assert!(index.remove(1));
+        assert!(!index.remove(1));
+        assert_eq!(index.cells.len(), 1);
+        assert!(index.remove(2));
+        assert!(index.cells.is_empty());
+        assert!(index.is_empty());
+        // Moving the last entity out of a cell drops it too.
+        index.insert(3, Vec2::new(0.5, 0.5));
+        index.insert(3, Vec2::new(1.5, 0.5));
+        assert_eq!(index.cells.keys().collect::<Vec<_>>(), [&Vec2::new(1, 0)]);
+    }
+
+    #[test]
+    fn query_rect_finds_the_entities_inside_it() {
+        let mut index = TileSpatialIndex::new();
+        let positions = [
+            (0.5, 0.5),
+            (1.5, 0.5),
+            (1.25, 0.75),
+            (-1.5, 2.5),
+            (4.5, -3.5),
+        ];
+        for (key, position) in positions.into_iter().enumerate() {
+            index.insert(key as u32, Vec2::from(position));
+        }
+        // Fewer tiles than occupied cells, which are looked up one by one.
+        let small = Rect::new(1, 0, 2, 1);
+        assert_eq!(
+            query(&index, small),
+            [(Vec2::new(1, 0), 1), (Vec2::new(1, 0), 2)]
+        );
+        assert_eq!(query(&index, Rect::new(2, 0, 1, 1)), []);
+        // More tiles than occupied cells, which are each checked against the rect.
+        let large = Rect::new(-2, -1, 4, 4);
+        assert_eq!(
+            query(&index, large),
+            [
+                (Vec2::new(0, 0), 0),
+                (Vec2::new(1, 0), 1),
+                (Vec2::new(1, 0), 2),
+                (Vec2::new(-2, 2), 3),
+            ]
+        );
+        assert_eq!(query(&index, Rect::new(-2, -1, 4, 3)).len(), 3);
+        assert_eq!(query(&index, Rect::new(10, 10, 20, 20)), []);
+        assert_eq!(query(&index, Rect::new(0, 0, 0, 0)), []);
+    }
+}
@@ -0,0 +1,76 @@
+use vek::Vec2;
+
+/// Which rows of a square grid are offset by half a tile to the right, for staggered grids (e.g.
+/// brick walls, or the staggered maps of some 2.5D games). Staggered tilemaps are drawn half a
+/// tile wider than their tiles, so that the offset rows fit, and positions on them are converted
+/// to tiles with `tile_at` instead of by rounding down. Rows are counted from 0 at the top of the
+/// whole tilemap, even when only part of it is drawn.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TileStagger {
+    /// A plain grid, where every tile is below the one above it.
+    #[default]
+    None,
+    /// Rows 1, 3, 5, and so on are offset.
+    OddRows,
+    /// Rows 0, 2, 4, and so on are offset.
+    EvenRows,
+}
+
+impl TileStagger {
+    /// Whether the tiles of `row` are offset by half a tile.
+    pub fn is_offset(self, row: u32) -> bool {
+        let odd = row % 2 == 1;
+        match self {
+            TileStagger::None => false,
+            TileStagger::OddRows => odd,
+            TileStagger::EvenRows => !odd,
+        }
+    }
+
+    /// The size of the grid of a tilemap of `size` tiles, in tiles, which is half a tile wider
+    /// unless it isn't staggered. `TilemapDrawData::transform` maps the [0, 1]x[0, 1] square over
+    /// this size.
+    pub fn grid_size(self, size: Vec2<u32>) -> Vec2<f32> {
+        let extra = match self {
+            TileStagger::None => 0.0,
+            _ => 0.5,
+        };
+        Vec2::new(size.x as f32 + extra, size.y as f32)
+    }
+
+    /// The position in the grid of the top left corner of the tile at `cell`, in tiles with y
+    /// pointing down.
+    pub fn tile_origin(self, cell: Vec2<u32>) -> Vec2<f32> {
+        let offset = if self.is_offset(cell.y) { 0.5 } else { 0.0 };
+        Vec2::new(cell.x as f32 + offset, cell.y as f32)
+    }
+
+    /// The tile of a tilemap of `size` tiles at `position` in its grid (in tiles with y pointing
+    /// down), if there is one. The half tiles at the ends of the rows are outside of every tile.
+    pub fn tile_at(self, size: Vec2<u32>, position: Vec2<f32>) -> Option<Vec2<u32>> {
+        if position.y < 0.0 || position.y >= size.y as f32 {
+            return None;
+        }
+        let row = position.y as u32;
+        let x = position.x - if self.is_offset(row) { 0.5 } else { 0.0 };
+        (x >= 0.0 && x < size.x as f32).then(|| Vec2::new(x as u32, row))
+    }
+
+    /// The tile of a tilemap of `size` tiles at `uv` in the [0, 1]x[0, 1] square that
+    /// `TilemapDrawData::transform` maps into the world (with y pointing up), if there is one.
+    pub fn tile_at_uv(self, size: Vec2<u32>, uv: Vec2<f32>) -> Option<Vec2<u32>> {
+        let grid = self.grid_size(size);
+        self.tile_at(size, Vec2::new(uv.x * grid.x, (1.0 - uv.y) * grid.y))
+    }
+
+    /// The value of `stagger` in the tilemap shader.
+    #[cfg(feature = "std")]
+    pub(crate) fn shader_value(self) -> u32 {
+        match self {
+            TileStagger::None => 0,
+            TileStagger::OddRows => 1,
+            TileStagger::EvenRows => 2,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Importing texture atlases in the JSON formats written by
+//! [TexturePacker](https://www.codeandweb.com/texturepacker) (and many other sprite packers).
+use crate::{AlphaMode, TilesetRef};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, path::Path};
+use vek::Vec2;
+
+/// The number of tiles in each row of the tileset of an imported atlas.
+const COLUMNS: u32 = 16;
+
+/// An error encountered while importing a texture atlas.
+#[derive(Debug)]
+pub enum TexturePackerError {
+    /// A file could not be read.
+    Io(String, std::io::Error),
+    /// The description of the atlas is not valid JSON.
+    Json(serde_json::Error),
+    /// The image of the atlas could not be decoded.
+    Image(image::ImageError),
+    /// A value is missing or out of range, e.g. a region extends past the edge of the image.
+    InvalidData(String),
+    /// The tileset would have more tiles (counting the empty ones that fill its last row) than
+    /// `max_tiles`, so it couldn't be uploaded.
+    TooManyTiles { tiles: u32, max: u32 },
+}
+
+impl fmt::Display for TexturePackerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TexturePackerError::Io(path, e) => write!(f, "couldn't read {path:?}: {e}"),
+            TexturePackerError::Json(e) => write!(f, "invalid JSON: {e}"),
+            TexturePackerError::Image(e) => write!(f, "invalid atlas image: {e}"),
+            TexturePackerError::InvalidData(what) => write!(f, "invalid atlas: {what}"),
+            TexturePackerError::TooManyTiles { tiles, max } => write!(
+                f,
+                "the atlas needs a tileset of {tiles} tiles, but at most {max} are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TexturePackerError {}
+
+impl From<serde_json::Error> for TexturePackerError {
+    fn from(e: serde_json::Error) -> Self {
+        TexturePackerError::Json(e)
+    }
+}
+
+/// A texture atlas converted into a tileset, with a tile for each named region of the atlas.
+#[derive(Clone, Debug)]
+pub struct TextureAtlas {
+    /// A tileset whose tiles are the size of the largest region (before trimming), in rows of 16
+    /// tiles. Smaller regions are placed at the top left of their tiles, and the rest is
+    /// transparent.
+    pub tileset: TilesetRef<'static>,
+    /// The tile of each region, by its name (usually the file name of the sprite it was packed
+    /// from), so that levels can refer to tiles by name.
+    pub tiles: BTreeMap<String, u32>,
+}
+
+impl TextureAtlas {
+    /// The tile of the region called `name`.
+    pub fn tile(&self, name: &str) -> Option<u32> {
+        self.tiles.get(name).copied()
+    }
+}
+
+/// Import an atlas from a .json file, with the image named by its `meta.image` (relative to the
+/// file). See `parse_texturepacker` for `max_tiles`.
+pub fn load_texturepacker(
+    path: impl AsRef<Path>,
+    max_tiles: u32,
+) -> Result<TextureAtlas, TexturePackerError> {
+    let path = path.as_ref();
+    let read_error = |path: &Path, e| TexturePackerError::Io(path.display().to_string(), e);
+    let json = std::fs::read_to_string(path).map_err(|e| read_error(path, e))?;
+    let description: Value = serde_json::from_str(&json)?;
+    let image_path = description["meta"]["image"]
+        .as_str()
+        .ok_or_else(|| TexturePackerError::InvalidData("missing \"meta\".\"image\"".to_owned()))?;
+    let image_path = path.parent().unwrap_or(Path::new("")).join(image_path);
+    let bytes = std::fs::read(&image_path).map_err(|e| read_error(&image_path, e))?;
+    let image = image::load_from_memory(&bytes).map_err(TexturePackerError::Image)?;
+    atlas_from_json(&description, &image.into_rgba8(), max_tiles)
+}
+
+/// Import an atlas from the contents of its .json file and its image. Both the "hash" format
+/// (where `frames` is an object keyed by name) and the "array" format (where `frames` is a list of
+/// regions with a `filename`) are supported, including trimmed and rotated regions. Tiles are
+/// numbered in the order the regions are listed (by name, for the hash format). Each tile is a
+/// layer of the tileset texture, so atlases whose tileset would have more than `max_tiles` tiles
+/// (e.g. `device.limits().max_texture_array_layers`) are rejected with
+/// `TexturePackerError::TooManyTiles`.
+pub fn parse_texturepacker(
+    json: &str,
+    image: &image::RgbaImage,
+    max_tiles: u32,
+) -> Result<TextureAtlas, TexturePackerError> {
+    atlas_from_json(&serde_json::from_str(json)?, image, max_tiles)
+}
+
+/// A region of the atlas, as described by its entry in `frames`.
+struct Region {
+    name: String,
+    /// The area of the atlas holding the (trimmed) sprite, with the size it has before rotation.
+    frame: (Vec2<u32>, Vec2<u32>),
+    /// Whether the sprite is stored rotated by 90 degrees clockwise.
+    rotated: bool,
+    /// Where the trimmed sprite is placed within the original one.
+    offset: Vec2<u32>,
+    /// The size of the original sprite, before trimming.
+    source_size: Vec2<u32>,
+}
+
+fn atlas_from_json(
+    description: &Value,
+    image: &image::RgbaImage,
+    max_tiles: u32,
+) -> Result<TextureAtlas, TexturePackerError> {
+    let frames: Vec<(&str, &Value)> = match &description["frames"] {
+        Value::Object(frames) => frames
+            .iter()
+            .map(|(name, frame)| (name.as_str(), frame))
+            .collect(),
+        Value::Array(frames) => frames
+            .iter()
+            .map(|frame| Ok((json_str(frame, "filename")?, frame)))
+            .collect::<Result<_, TexturePackerError>>()?,
+        _ => return Err(invalid("missing \"frames\"")),
+    };
+    let regions = frames
+        .into_iter()
+        .map(|(name, frame)| region(name, frame))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let dimensions = Vec2::from(image.dimensions());
+    let size_of_tile = regions.iter().fold(Vec2::one(), |size, region| {
+        Vec2::partial_max(size, region.source_size)
+    });
+    let count = regions.len() as u32;
+    let columns = COLUMNS.clamp(1, count.max(1));
+    let rows = count.div_ceil(columns).max(1);
+    if columns as u64 * rows as u64 > max_tiles as u64 {
+        return Err(TexturePackerError::TooManyTiles {
+            tiles: columns.saturating_mul(rows),
+            max: max_tiles,
+        });
+    }
+    let tile_len = size_of_tile.product() as usize;
+    let mut data = vec![0; tile_len * (columns * rows) as usize];
+    let mut tiles = BTreeMap::new();
+    for (i, region) in regions.into_iter().enumerate() {
+        let (position, size) = region.frame;
+        let stored = if region.rotated { size.yx() } else { size };
+        if (position + stored)
+            .map2(dimensions, |end, max| end > max)
+            .reduce_or()
+        {
+            return Err(invalid(&format!(
+                "region {:?} extends past the edge of the image",
+                region.name
+            )));
+        }
+        let tile = &mut data[i * tile_len..(i + 1) * tile_len];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let destination = region.offset + Vec2::new(x, y);
+                if destination.x >= size_of_tile.x || destination.y >= size_of_tile.y {
+                    continue;
+                }
+                // Rotated sprites are stored turned clockwise, so their left column is stored as
+                // the top row, from right to left.
+                let source = if region.rotated {
+                    position + Vec2::new(size.y - 1 - y, x)
+                } else {
+                    position + Vec2::new(x, y)
+                };
+                tile[(destination.y * size_of_tile.x + destination.x) as usize] =
+                    u32::from_le_bytes(image.get_pixel(source.x, source.y).0);
+            }
+        }
+        tiles.insert(region.name, i as u32);
+    }
+    Ok(TextureAtlas {
+        tileset: TilesetRef {
+            pixel_size: size_of_tile * Vec2::new(columns, rows),
+            size_of_tile,
+            data: data.into(),
+            alpha_mode: AlphaMode::Straight,
+            uv_inset: 0.0,
+            checkerboard: (&[][..]).into(),
+            compressed: None,
+        },
+        tiles,
+    })
+}
+
+fn region(name: &str, frame: &Value) -> Result<Region, TexturePackerError> {
+    let rect = json_rect(&frame["frame"], name, "frame")?;
+    let trimmed = frame["trimmed"].as_bool().unwrap_or(false);
+    let (offset, source_size) = if trimmed {
+        let sprite_source = json_rect(&frame["spriteSourceSize"], name, "spriteSourceSize")?;
+        let source_size = &frame["sourceSize"];
+        let source_size = Vec2::new(
+            json_u32(source_size, "w", name)?,
+            json_u32(source_size, "h", name)?,
+        );
+        (sprite_source.0, source_size)
+    } else {
+        (Vec2::zero(), rect.1)
+    };
+    Ok(Region {
+        name: name.to_owned(),
+        frame: rect,
+        rotated: frame["rotated"].as_bool().unwrap_or(false),
+        offset,
+        source_size,
+    })
+}
+
+/// The position and size of an `{"x", "y", "w", "h"}` object.
+fn json_rect(
+    rect: &Value,
+    name: &str,
+    key: &str,
+) -> Result<(Vec2<u32>, Vec2<u32>), TexturePackerError> {
+    if !rect.is_object() {
+        return Err(invalid(&format!("region {name:?} is missing {key:?}")));
+    }
+    Ok((
+        Vec2::new(json_u32(rect, "x", name)?, json_u32(rect, "y", name)?),
+        Vec2::new(json_u32(rect, "w", name)?, json_u32(rect, "h", name)?),
+    ))
+}
+
+fn json_u32(object: &Value, key: &str, name: &str) -> Result<u32, TexturePackerError> {
+    object[key]
+        .as_u64()
+        .and_then(|value| u32::try_from(value).ok())
+        .ok_or_else(|| invalid(&format!("region {name:?} has a missing or invalid {key:?}")))
+}
+
+fn json_str<'a>(object: &'a Value, key: &str) -> Result<&'a str, TexturePackerError> {
+    object[key]
+        .as_str()
+        .ok_or_else(|| invalid(&format!("missing or invalid {key:?}")))
+}
+
+fn invalid(what: &str) -> TexturePackerError {
+    TexturePackerError::InvalidData(what.to_owned())
+}
@@ -0,0 +1,109 @@
+//! Every public type is `Send` and `Sync`, so that tilemaps can be loaded and built on other
+//! threads than the one that uploads and draws them, and a `TilemapPipeline` can live on a render
+//! thread (or be shared with one behind a lock). These are checked at compile time, so that a field
+//! that isn't (e.g. an `Rc` or a callback without `Send + Sync`) fails the build instead of
+//! breaking applications.
+//!
+//! On the web, wgpu's own types aren't `Send` or `Sync` (unless wgpu is built with its
+//! `fragile-send-sync-non-atomic-wasm` feature), so the types holding GPU resources are only
+//! checked on other targets.
+
+use crate::*;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+const _: fn() = || {
+    assert_send_sync::<AnimationFrame>();
+    assert_send_sync::<TileAnimation>();
+    assert_send_sync::<TilemapArray<1, 1>>();
+    assert_send_sync::<DoubleBuffered<Tilemap>>();
+    assert_send_sync::<Tilemap>();
+    assert_send_sync::<TilemapRef<'static>>();
+    assert_send_sync::<TilemapStack>();
+    assert_send_sync::<AnyTilemapRef<'static>>();
+    assert_send_sync::<HeatmapRef<'static>>();
+    assert_send_sync::<TileGrid<'static>>();
+    assert_send_sync::<TilesetRef<'static>>();
+    assert_send_sync::<TilesetLayout>();
+    assert_send_sync::<TilesetAnalysis>();
+    assert_send_sync::<MaterialTable>();
+    assert_send_sync::<Neighborhood<'static>>();
+    assert_send_sync::<RaycastHit<u8>>();
+    assert_send_sync::<TileStagger>();
+    assert_send_sync::<FixedTimestep>();
+    assert_send_sync::<InvalidTile>();
+};
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+const _: fn() = || {
+    assert_send_sync::<TilemapPipeline>();
+    assert_send_sync::<BakedLayer>();
+    assert_send_sync::<PickReadback>();
+};
+
+#[cfg(feature = "std")]
+const _: fn() = || {
+    assert_send_sync::<TilemapPipelineDescriptor<'static>>();
+    assert_send_sync::<TilemapDrawData<'static>>();
+    assert_send_sync::<DrawInfo>();
+    assert_send_sync::<InvalidTiles>();
+    assert_send_sync::<TilesetError>();
+    assert_send_sync::<TilePick>();
+    assert_send_sync::<PresentParams>();
+    assert_send_sync::<Presentation>();
+    assert_send_sync::<ColorLut>();
+    assert_send_sync::<ColorLutError>();
+    assert_send_sync::<CompressedTiles<'static>>();
+    assert_send_sync::<MippedTileset>();
+    assert_send_sync::<TextureContainerError>();
+    assert_send_sync::<BinaryMapError>();
+    assert_send_sync::<ChunkedTilemap>();
+    assert_send_sync::<FsResolver>();
+    assert_send_sync::<ImportWarning>();
+    assert_send_sync::<WorldLayout>();
+    assert_send_sync::<LayoutDraws<'static>>();
+    assert_send_sync::<MapObjects>();
+    assert_send_sync::<TileSpatialIndex<u32>>();
+    assert_send_sync::<TilemapWorld>();
+};
+
+#[cfg(feature = "image")]
+const _: fn() = || {
+    assert_send_sync::<TileImagesError>();
+};
+
+#[cfg(feature = "csv")]
+const _: fn() = || {
+    assert_send_sync::<CsvError>();
+};
+
+#[cfg(feature = "godot")]
+const _: fn() = || {
+    assert_send_sync::<godot::GodotError>();
+    assert_send_sync::<godot::GodotScene>();
+};
+
+#[cfg(feature = "ogmo")]
+const _: fn() = || {
+    assert_send_sync::<ogmo::OgmoError>();
+    assert_send_sync::<ogmo::OgmoProject>();
+    assert_send_sync::<ogmo::OgmoLevel>();
+};
+
+#[cfg(feature = "pyxel")]
+const _: fn() = || {
+    assert_send_sync::<pyxel::PyxelError>();
+    assert_send_sync::<pyxel::PyxelDocument>();
+};
+
+#[cfg(feature = "texturepacker")]
+const _: fn() = || {
+    assert_send_sync::<texturepacker::TexturePackerError>();
+    assert_send_sync::<texturepacker::TextureAtlas>();
+};
+
+#[cfg(feature = "tiled")]
+const _: fn() = || {
+    assert_send_sync::<tiled::TiledError>();
+    assert_send_sync::<tiled::TiledMap>();
+};
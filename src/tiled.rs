@@ -0,0 +1,1269 @@
+//! Importing and exporting maps made with the [Tiled](https://www.mapeditor.org/) map editor.
+use crate::{
+    import::LayerWarnings, world::with_empty_tile, AlphaMode, AnimationFrame, ChunkedLayer,
+    ChunkedTilemap, MapObject, ObjectLayer, ObjectShape, Properties, PropertyValue, TileAnimation,
+    Tilemap, TilemapBlendMode, TilemapLayer, TilemapWorld, TilesetLayout, TilesetRef, WorldLayer,
+};
+pub use crate::{FsResolver, ImportWarning, ResourceResolver};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+    io::Read,
+    path::Path,
+    time::Duration,
+};
+use vek::{Mat4, Rgba, Vec2, Vec3};
+
+/// Tiled stores flip flags in the high bits of each global tile id.
+const GID_MASK: u32 = 0x0fff_ffff;
+
+pub use crate::EMPTY_TILE;
+
+/// An error encountered while importing a Tiled map.
+#[derive(Debug)]
+pub enum TiledError {
+    /// The file is not valid XML.
+    Xml(roxmltree::Error),
+    /// A required attribute is missing from an element.
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+    /// An attribute has a value that could not be parsed.
+    InvalidAttribute {
+        element: String,
+        attribute: &'static str,
+        value: String,
+    },
+    /// The map uses a feature that isn't supported (e.g. a non-orthogonal orientation).
+    Unsupported(String),
+    /// The tile data of a layer could not be decoded.
+    InvalidData(String),
+    /// A file referenced by the map could not be read.
+    Io(String, std::io::Error),
+    /// The Tiled project file is not valid JSON.
+    Json(serde_json::Error),
+    /// The image of a tileset could not be decoded.
+    Image(image::ImageError),
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TiledError::Xml(e) => write!(f, "invalid XML: {e}"),
+            TiledError::MissingAttribute { element, attribute } => {
+                write!(f, "<{element}> is missing the \"{attribute}\" attribute")
+            }
+            TiledError::InvalidAttribute {
+                element,
+                attribute,
+                value,
+            } => write!(f, "<{element}> has an invalid \"{attribute}\": {value:?}"),
+            TiledError::Unsupported(what) => write!(f, "unsupported: {what}"),
+            TiledError::InvalidData(what) => write!(f, "invalid layer data: {what}"),
+            TiledError::Io(path, e) => write!(f, "couldn't read {path:?}: {e}"),
+            TiledError::Json(e) => write!(f, "invalid project file: {e}"),
+            TiledError::Image(e) => write!(f, "invalid tileset image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+impl From<roxmltree::Error> for TiledError {
+    fn from(e: roxmltree::Error) -> Self {
+        TiledError::Xml(e)
+    }
+}
+
+/// A tileset referenced by a Tiled map.
+#[derive(Clone, Debug)]
+pub struct TiledTilesetInfo {
+    /// The global id of the first tile of this tileset.
+    pub firstgid: u32,
+    /// The name of this tileset, if it is embedded in the map.
+    pub name: String,
+    /// The path of the .tsx file defining this tileset, if it isn't embedded in the map.
+    pub source: Option<String>,
+    /// The animated tiles of this tileset, by their id within the tileset, which are also in
+    /// `world.animations` to be drawn with `TilemapWorld::draw_list_at`. These are only read from
+    /// .tsx files when the map is imported with `load_tmx` or `parse_tmx_with`.
+    pub animations: BTreeMap<u32, TileAnimation>,
+    /// The path of the image of this tileset, relative to the map.
+    pub image: Option<String>,
+}
+
+/// A Tiled map, converted into a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub struct TiledMap {
+    /// The layers of the map. The `tileset` of each layer is an index into `tilesets`, and layers
+    /// using tiles from several tilesets are split into one layer per tileset. When the map is
+    /// imported with `load_tmx` or `parse_tmx_with`, `world.tilesets` holds the image of each
+    /// tileset (transparent if it couldn't be loaded), with `EMPTY_TILE` made transparent.
+    pub world: TilemapWorld,
+    /// The tilesets referenced by the map, in the order they are declared.
+    pub tilesets: Vec<TiledTilesetInfo>,
+    /// Parts of the map that couldn't be imported.
+    pub warnings: Vec<ImportWarning>,
+}
+
+/// Import a map from the contents of a Tiled .tmx file. Both finite and infinite maps are
+/// supported, with the layers of infinite maps imported as `WorldLayer::Chunks`. Maps containing
+/// objects created from templates need to be imported with `load_tmx` or `parse_tmx_with`, which
+/// can read the template files.
+pub fn parse_tmx(xml: &str) -> Result<TiledMap, TiledError> {
+    import_tmx(xml, None)
+}
+
+/// Import a map from the contents of a Tiled .tmx file, reading the files it refers to (such as
+/// object templates) with `resolver`, by their path relative to the map.
+pub fn parse_tmx_with(
+    xml: &str,
+    mut resolver: impl ResourceResolver,
+) -> Result<TiledMap, TiledError> {
+    import_tmx(xml, Some(&mut resolver))
+}
+
+/// Import a map from a Tiled .tmx file, reading the files it refers to relative to it.
+pub fn load_tmx(path: impl AsRef<Path>) -> Result<TiledMap, TiledError> {
+    let path = path.as_ref();
+    let xml =
+        std::fs::read_to_string(path).map_err(|e| TiledError::Io(path.display().to_string(), e))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    parse_tmx_with(&xml, FsResolver::new(dir))
+}
+
+/// Export a map to the contents of a Tiled .tmx file, so that it can be edited in Tiled. The map
+/// refers to its tilesets as `tileset0.tsx`, `tileset1.tsx`, etc. (in the order of
+/// `world.tilesets`), which can be created with `export_tsx`. Empty cells (`EMPTY_TILE`) are
+/// exported as empty, and the map is infinite if it has any `WorldLayer::Chunks`. Object layers
+/// are exported above the tile layers, with the tiles of tile objects renumbered from
+/// `world.firstgids` to the exported tilesets. Image layers and background images aren't
+/// exported.
+pub fn export_tmx(world: &TilemapWorld) -> String {
+    let infinite = world
+        .layers
+        .iter()
+        .any(|layer| matches!(layer, WorldLayer::Chunks(_)));
+    let next_object_id = world.objects.iter().map(|object| object.id + 1).max();
+    let mut tmx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = write!(
+        tmx,
+        "<map version=\"1.10\" orientation=\"orthogonal\" renderorder=\"right-down\" \
+         width=\"{}\" height=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" infinite=\"{}\" \
+         nextlayerid=\"{}\" nextobjectid=\"{}\"",
+        world.tile_size.x,
+        world.tile_size.y,
+        world.size_of_tile.x,
+        world.size_of_tile.y,
+        infinite as u8,
+        world.layers.len() + world.objects.layers.len() + 1,
+        next_object_id.unwrap_or(1),
+    );
+    if let Some(color) = world.background.color {
+        let _ = write!(tmx, " backgroundcolor=\"{}\"", format_color(color));
+    }
+    tmx.push_str(">\n");
+
+    // Global tile ids are assigned to each tileset in turn, starting from 1.
+    let mut firstgids = Vec::with_capacity(world.tilesets.len());
+    let mut firstgid = 1;
+    for (i, tileset) in world.tilesets.iter().enumerate() {
+        let _ = writeln!(
+            tmx,
+            " <tileset firstgid=\"{firstgid}\" source=\"tileset{i}.tsx\"/>"
+        );
+        firstgids.push(firstgid);
+        firstgid += tile_count(tileset);
+    }
+    let gid = |tileset: u32, tile: u8| match firstgids.get(tileset as usize) {
+        Some(firstgid) if tile != EMPTY_TILE => firstgid + tile as u32,
+        _ => 0,
+    };
+    // Objects refer to their tiles by the global ids of the map they were imported from.
+    let object_gid = |gid: u32| {
+        let flags = gid & !GID_MASK;
+        let imported = gid & GID_MASK;
+        let tileset = world
+            .firstgids
+            .partition_point(|&firstgid| firstgid <= imported)
+            .checked_sub(1);
+        match tileset.and_then(|i| Some((world.firstgids[i], *firstgids.get(i)?))) {
+            Some((from, to)) => (imported - from + to) | flags,
+            None => gid,
+        }
+    };
+
+    let pixel_size = world.pixel_size().as_::<f32>();
+    let mut layer_id = 0;
+    for layer in world.layers.iter() {
+        let (name, size, transform, visible, blend_mode, opacity, parallax) = match layer {
+            WorldLayer::Tiles(layer) => (
+                &layer.name,
+                layer.tilemap.tile_size,
+                layer.transform,
+                layer.visible,
+                layer.blend_mode,
+                layer.opacity,
+                layer.parallax,
+            ),
+            WorldLayer::Chunks(layer) => (
+                &layer.name,
+                layer
+                    .tiles
+                    .bounds()
+                    .map_or(Vec2::zero(), |bounds| Vec2::new(bounds.w, bounds.h).as_()),
+                layer.transform,
+                layer.visible,
+                layer.blend_mode,
+                layer.opacity,
+                layer.parallax,
+            ),
+            WorldLayer::Image(_) => continue,
+        };
+        layer_id += 1;
+        let _ = write!(
+            tmx,
+            " <layer id=\"{layer_id}\" name=\"{}\" width=\"{}\" height=\"{}\"",
+            escape(name),
+            size.x,
+            size.y,
+        );
+        // Only the translation of the layer's transform can be represented.
+        let offset = Vec2::new(transform.cols.w.x, -transform.cols.w.y) * pixel_size;
+        for (attribute, value, default) in [
+            ("offsetx", offset.x, 0.0),
+            ("offsety", offset.y, 0.0),
+            ("opacity", opacity, 1.0),
+            ("parallaxx", parallax.x, 1.0),
+            ("parallaxy", parallax.y, 1.0),
+        ] {
+            if value != default {
+                let _ = write!(tmx, " {attribute}=\"{value}\"");
+            }
+        }
+        if !visible {
+            tmx.push_str(" visible=\"0\"");
+        }
+        let mode = match blend_mode {
+            TilemapBlendMode::Alpha | TilemapBlendMode::Opaque => None,
+            TilemapBlendMode::Additive => Some("add"),
+            TilemapBlendMode::Multiply => Some("multiply"),
+            TilemapBlendMode::Screen => Some("screen"),
+            TilemapBlendMode::Overlay => Some("overlay"),
+        };
+        if let Some(mode) = mode {
+            let _ = write!(tmx, " mode=\"{mode}\"");
+        }
+        tmx.push_str(">\n  <data encoding=\"csv\">\n");
+        match layer {
+            WorldLayer::Tiles(layer) if infinite => {
+                let size = layer.tilemap.tile_size;
+                let _ = writeln!(
+                    tmx,
+                    "   <chunk x=\"0\" y=\"0\" width=\"{}\" height=\"{}\">",
+                    size.x, size.y
+                );
+                write_csv(&mut tmx, &layer.tilemap, |tile| gid(layer.tileset, tile));
+                tmx.push_str("   </chunk>\n");
+            }
+            WorldLayer::Tiles(layer) => {
+                write_csv(&mut tmx, &layer.tilemap, |tile| gid(layer.tileset, tile));
+            }
+            WorldLayer::Chunks(layer) => {
+                let mut chunks: Vec<_> = layer.tiles.chunks.iter().collect();
+                chunks.sort_by_key(|(position, _)| (position.y, position.x));
+                for (position, chunk) in chunks {
+                    let origin = *position * layer.tiles.chunk_size.as_::<i32>();
+                    let _ = writeln!(
+                        tmx,
+                        "   <chunk x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\">",
+                        origin.x, origin.y, chunk.tile_size.x, chunk.tile_size.y
+                    );
+                    write_csv(&mut tmx, chunk, |tile| gid(layer.tileset, tile));
+                    tmx.push_str("   </chunk>\n");
+                }
+            }
+            WorldLayer::Image(_) => unreachable!(),
+        }
+        tmx.push_str("  </data>\n </layer>\n");
+    }
+
+    for layer in world.objects.layers.iter() {
+        layer_id += 1;
+        let _ = write!(
+            tmx,
+            " <objectgroup id=\"{layer_id}\" name=\"{}\"",
+            escape(&layer.name)
+        );
+        if !layer.class.is_empty() {
+            let _ = write!(tmx, " class=\"{}\"", escape(&layer.class));
+        }
+        if !layer.visible {
+            tmx.push_str(" visible=\"0\"");
+        }
+        tmx.push_str(">\n");
+        write_properties(&mut tmx, &layer.properties, 2);
+        for object in layer.objects.iter() {
+            write_object(&mut tmx, object, object.gid.map(object_gid));
+        }
+        tmx.push_str(" </objectgroup>\n");
+    }
+    tmx.push_str("</map>\n");
+    tmx
+}
+
+/// Export a tileset to the contents of a Tiled .tsx file, where `image_source` is the path
+/// (relative to the .tsx file) that the result of `TilesetRef::to_image` is saved at.
+pub fn export_tsx(tileset: &TilesetRef, name: &str, image_source: &str) -> String {
+    let columns = tileset.pixel_size.x / tileset.size_of_tile.x.max(1);
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <tileset version=\"1.10\" name=\"{}\" tilewidth=\"{}\" tileheight=\"{}\" \
+         tilecount=\"{}\" columns=\"{columns}\">\n \
+         <image source=\"{}\" width=\"{}\" height=\"{}\"/>\n\
+         </tileset>\n",
+        escape(name),
+        tileset.size_of_tile.x,
+        tileset.size_of_tile.y,
+        tile_count(tileset),
+        escape(image_source),
+        tileset.pixel_size.x,
+        tileset.pixel_size.y,
+    )
+}
+
+/// Parse the classes defined in a Tiled project file (.tiled-project), returning the default
+/// values of their members by class name, to be provided to
+/// `MapObjects::inherit_class_properties`.
+pub fn parse_project_classes(json: &str) -> Result<BTreeMap<String, Properties>, TiledError> {
+    let project: serde_json::Value = serde_json::from_str(json).map_err(TiledError::Json)?;
+    let types = project["propertyTypes"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    Ok(types
+        .iter()
+        .filter(|ty| ty["type"] == "class")
+        .filter_map(|ty| {
+            let members = ty["members"]
+                .as_array()?
+                .iter()
+                .filter_map(|member| {
+                    let name = member["name"].as_str()?.to_owned();
+                    Some((name, json_property(member)?))
+                })
+                .collect();
+            Some((ty["name"].as_str()?.to_owned(), members))
+        })
+        .collect())
+}
+
+fn json_property(property: &serde_json::Value) -> Option<PropertyValue> {
+    let value = &property["value"];
+    Some(match property["type"].as_str()? {
+        "string" => PropertyValue::String(value.as_str()?.to_owned()),
+        "int" => PropertyValue::Int(value.as_i64()?),
+        "float" => PropertyValue::Float(value.as_f64()?),
+        "bool" => PropertyValue::Bool(value.as_bool()?),
+        "color" => PropertyValue::Color(parse_color(value.as_str()?)?),
+        "file" => PropertyValue::File(value.as_str()?.to_owned()),
+        "object" => PropertyValue::Object(value.as_u64()? as u32),
+        "class" => PropertyValue::Class {
+            class: property["propertyType"].as_str()?.to_owned(),
+            members: value
+                .as_object()
+                .map(|members| {
+                    members
+                        .iter()
+                        .filter_map(|(name, value)| {
+                            // Members of nested classes are stored without their types, so only
+                            // the ones with unambiguous JSON types are kept.
+                            let value = match value {
+                                serde_json::Value::Bool(b) => PropertyValue::Bool(*b),
+                                serde_json::Value::String(s) => PropertyValue::String(s.clone()),
+                                serde_json::Value::Number(n) => match n.as_i64() {
+                                    Some(i) => PropertyValue::Int(i),
+                                    None => PropertyValue::Float(n.as_f64()?),
+                                },
+                                _ => return None,
+                            };
+                            Some((name.clone(), value))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        },
+        _ => return None,
+    })
+}
+
+fn import_tmx(
+    xml: &str,
+    resolver: Option<&mut dyn ResourceResolver>,
+) -> Result<TiledMap, TiledError> {
+    let doc = roxmltree::Document::parse(xml)?;
+    let map = doc.root_element();
+    let orientation = map.attribute("orientation").unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        return Err(TiledError::Unsupported(format!("{orientation} maps")));
+    }
+    let mut world = TilemapWorld {
+        tile_size: Vec2::new(
+            parse_attribute(map, "width")?,
+            parse_attribute(map, "height")?,
+        ),
+        size_of_tile: Vec2::new(
+            parse_attribute(map, "tilewidth")?,
+            parse_attribute(map, "tileheight")?,
+        ),
+        ..TilemapWorld::default()
+    };
+    if let Some(color) = map.attribute("backgroundcolor") {
+        world.background.color =
+            Some(parse_color(color).ok_or_else(|| invalid(map, "backgroundcolor", color))?);
+    }
+    let mut resolver = resolver;
+    let mut warnings = Vec::new();
+    let mut tilesets = Vec::new();
+    for node in map.children().filter(|node| node.has_tag_name("tileset")) {
+        let source = node.attribute("source");
+        let name = node.attribute("name").unwrap_or_default();
+        let (animations, image) = match (source, resolver.as_deref_mut()) {
+            (None, resolver) => {
+                let image = tileset_image(node, "")?;
+                let loaded =
+                    resolver.map(|resolver| load_tileset(node, image.as_deref(), resolver));
+                (parse_animations(node)?, Some((image, loaded)))
+            }
+            (Some(source), Some(resolver)) => match load_tsx(source, resolver) {
+                Ok((animations, image, loaded)) => (animations, Some((image, Some(loaded)))),
+                Err(e) => (BTreeMap::new(), Some((None, Some(Err(e))))),
+            },
+            (Some(_), None) => (BTreeMap::new(), None),
+        };
+        let (image, loaded) = image.unwrap_or_default();
+        if let Some(loaded) = loaded {
+            world.tilesets.push(loaded.unwrap_or_else(|e| {
+                warnings.push(ImportWarning::MissingTileset {
+                    tileset: source.unwrap_or(name).to_owned(),
+                    reason: e.to_string(),
+                });
+                with_empty_tile(&TilesetRef {
+                    pixel_size: world.size_of_tile,
+                    size_of_tile: world.size_of_tile,
+                    data: vec![0; world.size_of_tile.product() as usize].into(),
+                    alpha_mode: AlphaMode::Premultiplied,
+                    uv_inset: 0.0,
+                    checkerboard: (&[][..]).into(),
+                    compressed: None,
+                })
+            }));
+        }
+        let firstgid = parse_attribute(node, "firstgid")?;
+        world.animations.push(animations.clone());
+        world.firstgids.push(firstgid);
+        tilesets.push(TiledTilesetInfo {
+            firstgid,
+            name: name.to_owned(),
+            source: source.map(str::to_owned),
+            animations,
+            image,
+        });
+    }
+    let infinite = map.attribute("infinite") == Some("1");
+    let mut importer = LayerImporter {
+        world: &mut world,
+        tilesets: &tilesets,
+        infinite,
+        resolver: resolver.map(|resolver| resolver as &mut dyn ResourceResolver),
+        templates: HashMap::new(),
+        warnings,
+    };
+    importer.import_layers(map, LayerContext::default())?;
+    let warnings = importer.warnings;
+    Ok(TiledMap {
+        world,
+        tilesets,
+        warnings,
+    })
+}
+
+/// The animated tiles defined by the `<tile>` elements of a tileset.
+fn parse_animations(tileset: roxmltree::Node) -> Result<BTreeMap<u32, TileAnimation>, TiledError> {
+    let mut animations = BTreeMap::new();
+    for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+        let Some(animation) = tile.children().find(|node| node.has_tag_name("animation")) else {
+            continue;
+        };
+        let frames = animation
+            .children()
+            .filter(|node| node.has_tag_name("frame"))
+            .map(|frame| {
+                Ok(AnimationFrame {
+                    tile: parse_attribute(frame, "tileid")?,
+                    duration: Duration::from_millis(parse_attribute(frame, "duration")?),
+                })
+            })
+            .collect::<Result<Vec<_>, TiledError>>()?;
+        animations.insert(parse_attribute(tile, "id")?, TileAnimation { frames });
+    }
+    Ok(animations)
+}
+
+/// The animated tiles, the path of the image (relative to the map), and the loaded image of an
+/// external tileset. The tileset is returned even if its image can't be loaded.
+#[allow(clippy::type_complexity)]
+fn load_tsx(
+    path: &str,
+    resolver: &mut dyn ResourceResolver,
+) -> Result<
+    (
+        BTreeMap<u32, TileAnimation>,
+        Option<String>,
+        Result<TilesetRef<'static>, TiledError>,
+    ),
+    TiledError,
+> {
+    let bytes = resolver
+        .resolve(path)
+        .map_err(|e| TiledError::Io(path.to_owned(), e))?;
+    let xml = String::from_utf8(bytes)
+        .map_err(|_| TiledError::InvalidData(format!("tileset {path:?} isn't UTF-8")))?;
+    let doc = roxmltree::Document::parse(&xml)?;
+    let tileset = doc.root_element();
+    let image = tileset_image(tileset, path)?;
+    let loaded = load_tileset(tileset, image.as_deref(), resolver);
+    Ok((parse_animations(tileset)?, image, loaded))
+}
+
+/// The path of the image of a tileset, relative to the map, where `base` is the path of the file
+/// defining the tileset (or `""` for tilesets embedded in the map).
+fn tileset_image(tileset: roxmltree::Node, base: &str) -> Result<Option<String>, TiledError> {
+    let Some(image) = tileset.children().find(|node| node.has_tag_name("image")) else {
+        return Ok(None);
+    };
+    let source: String = parse_attribute(image, "source")?;
+    let dir = Path::new(base).parent().unwrap_or(Path::new(""));
+    Ok(Some(dir.join(source).to_string_lossy().into_owned()))
+}
+
+/// Load the image of a tileset at `image` (as returned by `tileset_image`), cutting it into tiles
+/// with the tile size, margin, and spacing of the tileset.
+fn load_tileset(
+    tileset: roxmltree::Node,
+    image: Option<&str>,
+    resolver: &mut dyn ResourceResolver,
+) -> Result<TilesetRef<'static>, TiledError> {
+    let path = image.ok_or_else(|| {
+        TiledError::Unsupported("tilesets made of a collection of images".to_owned())
+    })?;
+    let bytes = resolver
+        .resolve(path)
+        .map_err(|e| TiledError::Io(path.to_owned(), e))?;
+    let mut pixels = image::load_from_memory(&bytes)
+        .map_err(TiledError::Image)?
+        .into_rgba8();
+    let node = tileset
+        .children()
+        .find(|node| node.has_tag_name("image"))
+        .unwrap_or(tileset);
+    if let Some(trans) = node.attribute("trans") {
+        let key = parse_color(trans).ok_or_else(|| invalid(node, "trans", trans))?;
+        for pixel in pixels.pixels_mut() {
+            if pixel.0[..3] == [key.r, key.g, key.b] {
+                pixel.0 = [0; 4];
+            }
+        }
+    }
+    let size_of_tile = Vec2::new(
+        parse_attribute::<u32>(tileset, "tilewidth")?,
+        parse_attribute::<u32>(tileset, "tileheight")?,
+    );
+    let layout = TilesetLayout {
+        size_of_tile: size_of_tile.map(|x| x.max(1)),
+        spacing: Vec2::broadcast(parse_attribute_or(tileset, "spacing", 0)?),
+        margin: Vec2::broadcast(parse_attribute_or(tileset, "margin", 0)?),
+    };
+    Ok(with_empty_tile(&TilesetRef::from_rgba8_with_layout(
+        pixels.as_raw(),
+        Vec2::from(pixels.dimensions()),
+        layout,
+    )))
+}
+
+/// Properties that group layers apply to the layers within them.
+#[derive(Copy, Clone)]
+struct LayerContext {
+    offset: Vec2<f32>,
+    opacity: f32,
+    visible: bool,
+    parallax: Vec2<f32>,
+}
+
+impl Default for LayerContext {
+    fn default() -> Self {
+        LayerContext {
+            offset: Vec2::zero(),
+            opacity: 1.0,
+            visible: true,
+            parallax: Vec2::one(),
+        }
+    }
+}
+
+impl LayerContext {
+    fn nested(self, node: roxmltree::Node) -> Result<Self, TiledError> {
+        Ok(LayerContext {
+            offset: self.offset
+                + Vec2::new(
+                    parse_attribute_or(node, "offsetx", 0.0)?,
+                    parse_attribute_or(node, "offsety", 0.0)?,
+                ),
+            opacity: self.opacity * parse_attribute_or(node, "opacity", 1.0)?,
+            visible: self.visible && node.attribute("visible") != Some("0"),
+            parallax: self.parallax
+                * Vec2::new(
+                    parse_attribute_or(node, "parallaxx", 1.0)?,
+                    parse_attribute_or(node, "parallaxy", 1.0)?,
+                ),
+        })
+    }
+}
+
+struct LayerImporter<'a> {
+    world: &'a mut TilemapWorld,
+    tilesets: &'a [TiledTilesetInfo],
+    infinite: bool,
+    resolver: Option<&'a mut dyn ResourceResolver>,
+    /// The objects defined by each template file that has been loaded.
+    templates: HashMap<String, ObjectAttributes>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl<'a> LayerImporter<'a> {
+    fn import_layers(
+        &mut self,
+        parent: roxmltree::Node,
+        context: LayerContext,
+    ) -> Result<(), TiledError> {
+        for node in parent.children().filter(|node| node.is_element()) {
+            match node.tag_name().name() {
+                "layer" => self.import_tile_layer(node, context.nested(node)?)?,
+                "objectgroup" => self.import_object_layer(node, context.nested(node)?)?,
+                "group" => self.import_layers(node, context.nested(node)?)?,
+                "imagelayer" => self.warnings.push(ImportWarning::UnsupportedLayer {
+                    layer: node.attribute("name").unwrap_or_default().to_owned(),
+                    kind: "image".to_owned(),
+                }),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn import_tile_layer(
+        &mut self,
+        node: roxmltree::Node,
+        context: LayerContext,
+    ) -> Result<(), TiledError> {
+        let name = node.attribute("name").unwrap_or_default();
+        let blend_mode = match node.attribute("mode") {
+            None | Some("normal") => TilemapBlendMode::Alpha,
+            Some("add") => TilemapBlendMode::Additive,
+            Some("multiply") => TilemapBlendMode::Multiply,
+            Some("screen") => TilemapBlendMode::Screen,
+            Some("overlay") => TilemapBlendMode::Overlay,
+            Some(mode) => return Err(TiledError::Unsupported(format!("{mode} blend mode"))),
+        };
+        let pixel_size = self.world.pixel_size().map(|x| x.max(1)).as_::<f32>();
+        let transform = Mat4::<f32>::translation_3d(Vec3::new(
+            context.offset.x / pixel_size.x,
+            -context.offset.y / pixel_size.y,
+            0.0,
+        ));
+        let data = node
+            .children()
+            .find(|node| node.has_tag_name("data"))
+            .ok_or_else(|| TiledError::InvalidData(format!("layer {name:?} has no <data>")))?;
+
+        // Split the cells of this layer by the tileset they use, keeping the layers in the order
+        // of their tilesets.
+        let mut warnings = LayerWarnings::default();
+        if self.infinite {
+            let mut layers = BTreeMap::new();
+            let mut chunk_size = None;
+            for chunk in data.children().filter(|node| node.has_tag_name("chunk")) {
+                let origin =
+                    Vec2::<i32>::new(parse_attribute(chunk, "x")?, parse_attribute(chunk, "y")?);
+                let size = Vec2::<u32>::new(
+                    parse_attribute(chunk, "width")?,
+                    parse_attribute(chunk, "height")?,
+                );
+                // Empty chunks would make every chunk of the layer empty, if they came first.
+                for (attribute, value) in [("width", size.x), ("height", size.y)] {
+                    if value == 0 {
+                        return Err(invalid(chunk, attribute, "0"));
+                    }
+                }
+                let chunk_size = *chunk_size.get_or_insert(size);
+                let gids = decode_data(data, chunk, size)?;
+                for (i, gid) in gids.into_iter().enumerate() {
+                    let Some((tileset, tile)) = self.local_tile(gid, &mut warnings) else {
+                        continue;
+                    };
+                    let pos = origin + Vec2::new(i as u32 % size.x, i as u32 / size.x).as_::<i32>();
+                    layers
+                        .entry(tileset)
+                        .or_insert_with(|| ChunkedTilemap::new(chunk_size, EMPTY_TILE))
+                        .put_tile(pos.x, pos.y, tile);
+                }
+            }
+            for (tileset, tiles) in layers {
+                self.world.layers.push(WorldLayer::Chunks(ChunkedLayer {
+                    name: name.to_owned(),
+                    tiles,
+                    tileset,
+                    transform,
+                    visible: context.visible,
+                    blend_mode,
+                    opacity: context.opacity,
+                    parallax: context.parallax,
+                }));
+            }
+        } else {
+            let size = Vec2::new(
+                parse_attribute(node, "width")?,
+                parse_attribute(node, "height")?,
+            );
+            let gids = decode_data(data, data, size)?;
+            let mut layers = BTreeMap::new();
+            for (i, gid) in gids.into_iter().enumerate() {
+                let Some((tileset, tile)) = self.local_tile(gid, &mut warnings) else {
+                    continue;
+                };
+                layers
+                    .entry(tileset)
+                    .or_insert_with(|| Tilemap::new_filled(size, EMPTY_TILE))
+                    .data[i] = tile;
+            }
+            for (tileset, tilemap) in layers {
+                self.world.layers.push(WorldLayer::Tiles(TilemapLayer {
+                    name: name.to_owned(),
+                    tilemap,
+                    tileset,
+                    transform,
+                    visible: context.visible,
+                    blend_mode,
+                    opacity: context.opacity,
+                    parallax: context.parallax,
+                }));
+            }
+        }
+        warnings.finish(name, &mut self.warnings);
+        Ok(())
+    }
+
+    fn import_object_layer(
+        &mut self,
+        node: roxmltree::Node,
+        context: LayerContext,
+    ) -> Result<(), TiledError> {
+        let mut objects = Vec::new();
+        for object in node.children().filter(|node| node.has_tag_name("object")) {
+            let mut attributes = ObjectAttributes::parse(object)?;
+            if let Some(template) = object.attribute("template") {
+                attributes = attributes.inherit(self.template(template)?.clone());
+            }
+            let mut object = attributes.finish(parse_attribute(object, "id")?);
+            object.position += context.offset;
+            objects.push(object);
+        }
+        self.world.objects.layers.push(ObjectLayer {
+            name: node.attribute("name").unwrap_or_default().to_owned(),
+            class: class_attribute(node),
+            visible: context.visible,
+            objects,
+            properties: parse_properties(node)?,
+        });
+        Ok(())
+    }
+
+    /// The object defined by a template file, loading it if necessary.
+    fn template(&mut self, path: &str) -> Result<&ObjectAttributes, TiledError> {
+        if !self.templates.contains_key(path) {
+            let resolver = self.resolver.as_mut().ok_or_else(|| {
+                TiledError::Unsupported(format!(
+                    "object template {path:?} (use load_tmx or parse_tmx_with)"
+                ))
+            })?;
+            let bytes = resolver
+                .resolve(path)
+                .map_err(|e| TiledError::Io(path.to_owned(), e))?;
+            let xml = String::from_utf8(bytes)
+                .map_err(|_| TiledError::InvalidData(format!("template {path:?} isn't UTF-8")))?;
+            let doc = roxmltree::Document::parse(&xml)?;
+            let template = doc.root_element();
+            let object = template
+                .children()
+                .find(|node| node.has_tag_name("object"))
+                .ok_or_else(|| {
+                    TiledError::InvalidData(format!("template {path:?} has no object"))
+                })?;
+            let mut attributes = ObjectAttributes::parse(object)?;
+            // Tile objects in templates refer to the template's own tileset, so find the same
+            // tileset in the map.
+            if let (Some(gid), Some(tileset)) = (
+                attributes.gid,
+                template
+                    .children()
+                    .find(|node| node.has_tag_name("tileset")),
+            ) {
+                let firstgid: u32 = parse_attribute(tileset, "firstgid")?;
+                let file_name =
+                    |source: &str| Path::new(source).file_name().map(|name| name.to_owned());
+                let source = tileset.attribute("source").and_then(file_name);
+                if let Some(map_tileset) = self.tilesets.iter().find(|map_tileset| {
+                    source.is_some() && map_tileset.source.as_deref().and_then(file_name) == source
+                }) {
+                    let flags = gid & !GID_MASK;
+                    let gid = gid & GID_MASK;
+                    let map_gid = gid
+                        .checked_sub(firstgid)
+                        .and_then(|tile| tile.checked_add(map_tileset.firstgid))
+                        .filter(|&map_gid| map_gid <= GID_MASK)
+                        .ok_or_else(|| {
+                            TiledError::InvalidData(format!(
+                                "template {path:?} has tile {gid}, which isn't in its tileset \
+                                 starting at {firstgid}"
+                            ))
+                        })?;
+                    attributes.gid = Some(map_gid | flags);
+                }
+            }
+            self.templates.insert(path.to_owned(), attributes);
+        }
+        Ok(&self.templates[path])
+    }
+
+    /// The index of the tileset containing a global tile id, and the id of the tile within it,
+    /// or `None` for empty cells and tiles that can't be imported.
+    fn local_tile(&self, gid: u32, warnings: &mut LayerWarnings) -> Option<(u32, u8)> {
+        if gid & GID_MASK == 0 {
+            return None;
+        }
+        if gid & !GID_MASK != 0 {
+            warnings.transformed_tile();
+        }
+        let local = self
+            .tilesets
+            .partition_point(|tileset| tileset.firstgid <= gid & GID_MASK)
+            .checked_sub(1)
+            .and_then(|tileset| {
+                let tile = u8::try_from((gid & GID_MASK) - self.tilesets[tileset].firstgid).ok()?;
+                (tile != EMPTY_TILE).then_some((tileset as u32, tile))
+            });
+        if local.is_none() {
+            warnings.unknown_tile(gid & GID_MASK);
+        }
+        local
+    }
+}
+
+/// The attributes of an object that have been specified, either by the object itself or by its
+/// template.
+#[derive(Clone, Default)]
+struct ObjectAttributes {
+    name: Option<String>,
+    class: Option<String>,
+    x: Option<f32>,
+    y: Option<f32>,
+    width: Option<f32>,
+    height: Option<f32>,
+    rotation: Option<f32>,
+    gid: Option<u32>,
+    visible: Option<bool>,
+    shape: Option<ObjectShape>,
+    properties: Properties,
+}
+
+impl ObjectAttributes {
+    fn parse(node: roxmltree::Node) -> Result<Self, TiledError> {
+        let optional = |attribute| parse_optional_attribute(node, attribute);
+        let points = |node: roxmltree::Node| -> Result<Vec<Vec2<f32>>, TiledError> {
+            let points = node.attribute("points").unwrap_or_default();
+            points
+                .split_whitespace()
+                .map(|point| {
+                    let (x, y) = point.split_once(',')?;
+                    Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| invalid(node, "points", points))
+        };
+        let mut shape = None;
+        for child in node.children().filter(|node| node.is_element()) {
+            shape = Some(match child.tag_name().name() {
+                "ellipse" => ObjectShape::Ellipse,
+                "point" => ObjectShape::Point,
+                "polygon" => ObjectShape::Polygon(points(child)?),
+                "polyline" => ObjectShape::Polyline(points(child)?),
+                "text" => ObjectShape::Text(child.text().unwrap_or_default().to_owned()),
+                _ => continue,
+            });
+        }
+        Ok(ObjectAttributes {
+            name: node.attribute("name").map(str::to_owned),
+            class: node
+                .attribute("class")
+                .or(node.attribute("type"))
+                .map(str::to_owned),
+            x: optional("x")?,
+            y: optional("y")?,
+            width: optional("width")?,
+            height: optional("height")?,
+            rotation: optional("rotation")?,
+            gid: parse_optional_attribute(node, "gid")?,
+            visible: node.attribute("visible").map(|visible| visible != "0"),
+            shape,
+            properties: parse_properties(node)?,
+        })
+    }
+
+    /// Fill in the attributes that aren't specified from `template`.
+    fn inherit(self, template: ObjectAttributes) -> Self {
+        let mut properties = template.properties;
+        properties.extend(self.properties);
+        ObjectAttributes {
+            name: self.name.or(template.name),
+            class: self.class.or(template.class),
+            x: self.x.or(template.x),
+            y: self.y.or(template.y),
+            width: self.width.or(template.width),
+            height: self.height.or(template.height),
+            rotation: self.rotation.or(template.rotation),
+            gid: self.gid.or(template.gid),
+            visible: self.visible.or(template.visible),
+            shape: self.shape.or(template.shape),
+            properties,
+        }
+    }
+
+    fn finish(self, id: u32) -> MapObject {
+        MapObject {
+            id,
+            name: self.name.unwrap_or_default(),
+            class: self.class.unwrap_or_default(),
+            position: Vec2::new(self.x.unwrap_or(0.0), self.y.unwrap_or(0.0)),
+            size: Vec2::new(self.width.unwrap_or(0.0), self.height.unwrap_or(0.0)),
+            rotation: self.rotation.unwrap_or(0.0),
+            gid: self.gid,
+            visible: self.visible.unwrap_or(true),
+            shape: self.shape.unwrap_or_default(),
+            properties: self.properties,
+        }
+    }
+}
+
+/// The class of an element, which older versions of Tiled call its type.
+fn class_attribute(node: roxmltree::Node) -> String {
+    node.attribute("class")
+        .or(node.attribute("type"))
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Parse the `<properties>` of an element.
+fn parse_properties(node: roxmltree::Node) -> Result<Properties, TiledError> {
+    let mut properties = Properties::new();
+    let Some(list) = node.children().find(|node| node.has_tag_name("properties")) else {
+        return Ok(properties);
+    };
+    for property in list.children().filter(|node| node.has_tag_name("property")) {
+        let name: String = parse_attribute(property, "name")?;
+        // Multi-line strings are stored as the text of the element instead of an attribute.
+        let text = property
+            .attribute("value")
+            .or(property.text())
+            .unwrap_or_default();
+        let value = match property.attribute("type").unwrap_or("string") {
+            "string" => PropertyValue::String(text.to_owned()),
+            "int" => PropertyValue::Int(parse_attribute_or(property, "value", 0)?),
+            "float" => PropertyValue::Float(parse_attribute_or(property, "value", 0.0)?),
+            "bool" => PropertyValue::Bool(text == "true"),
+            "color" if text.is_empty() => PropertyValue::Color(Rgba::zero()),
+            "color" => PropertyValue::Color(
+                parse_color(text).ok_or_else(|| invalid(property, "value", text))?,
+            ),
+            "file" => PropertyValue::File(text.to_owned()),
+            "object" => PropertyValue::Object(parse_attribute_or(property, "value", 0)?),
+            "class" => PropertyValue::Class {
+                class: property
+                    .attribute("propertytype")
+                    .unwrap_or_default()
+                    .to_owned(),
+                members: parse_properties(property)?,
+            },
+            ty => return Err(invalid(property, "type", ty)),
+        };
+        properties.insert(name, value);
+    }
+    Ok(properties)
+}
+
+/// Decode the global tile ids stored in `node` (either a `<data>` element or one of its
+/// `<chunk>`s), using the encoding specified by `data`.
+fn decode_data(
+    data: roxmltree::Node,
+    node: roxmltree::Node,
+    size: Vec2<u32>,
+) -> Result<Vec<u32>, TiledError> {
+    let len = size.x as usize * size.y as usize;
+    let text = node
+        .children()
+        .filter(|node| node.is_text())
+        .filter_map(|node| node.text())
+        .collect::<String>();
+    let gids = match data.attribute("encoding") {
+        None => node
+            .children()
+            .filter(|node| node.has_tag_name("tile"))
+            .map(|tile| parse_attribute_or(tile, "gid", 0))
+            .collect::<Result<Vec<u32>, _>>()?,
+        Some("csv") => text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| {
+                gid.parse()
+                    .map_err(|_| TiledError::InvalidData(format!("invalid tile id {gid:?}")))
+            })
+            .collect::<Result<Vec<u32>, _>>()?,
+        Some("base64") => {
+            use base64::Engine;
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(text.trim())
+                .map_err(|e| TiledError::InvalidData(e.to_string()))?;
+            let bytes = match data.attribute("compression") {
+                None => bytes,
+                Some("zlib") => decompress(flate2::read::ZlibDecoder::new(&bytes[..]))?,
+                Some("gzip") => decompress(flate2::read::GzDecoder::new(&bytes[..]))?,
+                Some(compression) => {
+                    return Err(TiledError::Unsupported(format!(
+                        "{compression} compression"
+                    )))
+                }
+            };
+            bytes
+                .chunks_exact(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect()
+        }
+        Some(encoding) => return Err(TiledError::Unsupported(format!("{encoding} encoding"))),
+    };
+    if gids.len() != len {
+        return Err(TiledError::InvalidData(format!(
+            "expected {len} tiles, found {}",
+            gids.len()
+        )));
+    }
+    Ok(gids)
+}
+
+fn decompress(mut reader: impl Read) -> Result<Vec<u8>, TiledError> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(|e| TiledError::InvalidData(e.to_string()))?;
+    Ok(bytes)
+}
+
+/// The number of whole tiles in a tileset.
+fn tile_count(tileset: &TilesetRef) -> u32 {
+    let size_of_tile = tileset.size_of_tile.map(|x| x.max(1));
+    let tiles = tileset.pixel_size / size_of_tile;
+    tiles.x * tiles.y
+}
+
+/// Write the tiles of a tilemap as comma-separated global tile ids, one row per line.
+fn write_csv(out: &mut String, tilemap: &Tilemap, gid: impl Fn(u8) -> u32) {
+    let width = tilemap.tile_size.x.max(1) as usize;
+    let rows = tilemap.data.chunks(width).count();
+    for (y, row) in tilemap.data.chunks(width).enumerate() {
+        for (x, &tile) in row.iter().enumerate() {
+            let _ = write!(out, "{}", gid(tile));
+            if x + 1 < row.len() || y + 1 < rows {
+                out.push(',');
+            }
+        }
+        out.push('\n');
+    }
+}
+
+/// Write `object`, with `gid` as its tile.
+fn write_object(out: &mut String, object: &MapObject, gid: Option<u32>) {
+    let _ = write!(out, "  <object id=\"{}\"", object.id);
+    for (attribute, value) in [("name", &object.name), ("class", &object.class)] {
+        if !value.is_empty() {
+            let _ = write!(out, " {attribute}=\"{}\"", escape(value));
+        }
+    }
+    if let Some(gid) = gid {
+        let _ = write!(out, " gid=\"{gid}\"");
+    }
+    let _ = write!(
+        out,
+        " x=\"{}\" y=\"{}\"",
+        object.position.x, object.position.y
+    );
+    for (attribute, value) in [
+        ("width", object.size.x),
+        ("height", object.size.y),
+        ("rotation", object.rotation),
+    ] {
+        if value != 0.0 {
+            let _ = write!(out, " {attribute}=\"{value}\"");
+        }
+    }
+    if !object.visible {
+        out.push_str(" visible=\"0\"");
+    }
+    out.push_str(">\n");
+    write_properties(out, &object.properties, 3);
+    let points = |points: &[Vec2<f32>]| {
+        points
+            .iter()
+            .map(|point| format!("{},{}", point.x, point.y))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    match &object.shape {
+        ObjectShape::Rectangle => {}
+        ObjectShape::Ellipse => out.push_str("   <ellipse/>\n"),
+        ObjectShape::Point => out.push_str("   <point/>\n"),
+        ObjectShape::Polygon(shape) => {
+            let _ = writeln!(out, "   <polygon points=\"{}\"/>", points(shape));
+        }
+        ObjectShape::Polyline(shape) => {
+            let _ = writeln!(out, "   <polyline points=\"{}\"/>", points(shape));
+        }
+        ObjectShape::Text(text) => {
+            let _ = writeln!(out, "   <text>{}</text>", escape(text));
+        }
+    }
+    out.push_str("  </object>\n");
+}
+
+/// Write `properties` as a `<properties>` element, if there are any.
+fn write_properties(out: &mut String, properties: &Properties, indent: usize) {
+    if properties.is_empty() {
+        return;
+    }
+    let pad = " ".repeat(indent);
+    let _ = writeln!(out, "{pad}<properties>");
+    for (name, value) in properties.iter() {
+        let (ty, value) = match value {
+            PropertyValue::String(s) => ("string", escape(s)),
+            PropertyValue::Int(i) => ("int", i.to_string()),
+            PropertyValue::Float(f) => ("float", f.to_string()),
+            PropertyValue::Bool(b) => ("bool", b.to_string()),
+            PropertyValue::Color(color) => ("color", format_color(*color)),
+            PropertyValue::File(path) => ("file", escape(path)),
+            PropertyValue::Object(id) => ("object", id.to_string()),
+            PropertyValue::Class { class, members } => {
+                let _ = writeln!(
+                    out,
+                    "{pad} <property name=\"{}\" type=\"class\" propertytype=\"{}\">",
+                    escape(name),
+                    escape(class)
+                );
+                write_properties(out, members, indent + 2);
+                let _ = writeln!(out, "{pad} </property>");
+                continue;
+            }
+        };
+        let _ = writeln!(
+            out,
+            "{pad} <property name=\"{}\" type=\"{ty}\" value=\"{value}\"/>",
+            escape(name)
+        );
+    }
+    let _ = writeln!(out, "{pad}</properties>");
+}
+
+/// Escape text for use in XML attributes and elements.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a color as `#AARRGGBB`.
+fn format_color(color: Rgba<u8>) -> String {
+    format!(
+        "#{:02x}{:02x}{:02x}{:02x}",
+        color.a, color.r, color.g, color.b
+    )
+}
+
+/// Parse a color of the form `#RRGGBB` or `#AARRGGBB`.
+fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let [a, r, g, b] = match hex.len() {
+        6 => (value | 0xff00_0000).to_be_bytes(),
+        8 => value.to_be_bytes(),
+        _ => return None,
+    };
+    Some(Rgba::new(r, g, b, a))
+}
+
+fn invalid(node: roxmltree::Node, attribute: &'static str, value: &str) -> TiledError {
+    TiledError::InvalidAttribute {
+        element: node.tag_name().name().to_owned(),
+        attribute,
+        value: value.to_owned(),
+    }
+}
+
+fn parse_attribute<T: std::str::FromStr>(
+    node: roxmltree::Node,
+    attribute: &'static str,
+) -> Result<T, TiledError> {
+    let value = node
+        .attribute(attribute)
+        .ok_or_else(|| TiledError::MissingAttribute {
+            element: node.tag_name().name().to_owned(),
+            attribute,
+        })?;
+    value.parse().map_err(|_| invalid(node, attribute, value))
+}
+
+fn parse_optional_attribute<T: std::str::FromStr>(
+    node: roxmltree::Node,
+    attribute: &'static str,
+) -> Result<Option<T>, TiledError> {
+    node.attribute(attribute)
+        .map(|value| value.parse().map_err(|_| invalid(node, attribute, value)))
+        .transpose()
+}
+
+fn parse_attribute_or<T: std::str::FromStr>(
+    node: roxmltree::Node,
+    attribute: &'static str,
+    default: T,
+) -> Result<T, TiledError> {
+    match node.attribute(attribute) {
+        Some(value) => value.parse().map_err(|_| invalid(node, attribute, value)),
+        None => Ok(default),
+    }
+}
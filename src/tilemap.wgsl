@@ -1,32 +1,12 @@
-struct Tiledata {
-    width: u32,
-    height: u32,
-    tile_width: u32,
-    tile_height: u32,
-}
-
-struct Tilemap {
-    // transform maps from [0, 1]x[0,1] to world coordinates
-    transform: mat4x4<f32>,
-    width: u32,
-    height: u32,
-    noise_data: u32,
-    pad: u32,
-}
-
-// camera maps from world coordinates to NDC
-@group(0) @binding(0) var<uniform> camera: mat4x4<f32>;
-
-@group(1) @binding(0) var<uniform> tiledata: Tiledata;
-@group(1) @binding(1) var tilemap_data: texture_2d_array<f32>;
-
-@group(2) @binding(0) var<uniform> tilemap: Tilemap;
-@group(2) @binding(1) var tilemap_indices: texture_2d<u32>;
+// copy of the render target, only bound for TilemapBlendMode::Overlay
+@group(3) @binding(0) var backdrop: texture_2d<f32>;
 
 struct TilemapFragData {
     @builtin(position) position: vec4<f32>,
     @location(0) tilepos: vec2<f32>,
     @location(1) pixelpos: vec2<f32>,
+    // position in world coordinates, after parallax
+    @location(2) worldpos: vec2<f32>,
 }
 
 const QUAD_VERTICES: array<vec4<f32>, 6> = array<vec4<f32>, 6>(
@@ -38,55 +18,526 @@ const QUAD_VERTICES: array<vec4<f32>, 6> = array<vec4<f32>, 6>(
     vec4<f32>(1.0, 1.0, 0.0, 1.0),
 );
 
+// Maps position from [0, 1]x[0, 1] to world coordinates, after parallax
+fn tilemap_world_position(position: vec4<f32>) -> vec4<f32> {
+    let world_position = tilemap.transform * position;
+    return world_position + vec4((1.0 - tilemap.parallax) * camera.center * world_position.w, 0.0, 0.0);
+}
+
+// How far to move the tilemap in NDC so that its origin is at a corner of a pixel of the viewport
+fn tilemap_pixel_snap() -> vec2<f32> {
+    if tilemap.pixel_snap == 0u || any(camera.viewport_size <= vec2(0.0)) {
+        return vec2(0.0);
+    }
+    let origin = camera.view_proj * tilemap_world_position(vec4(0.0, 0.0, 0.0, 1.0));
+    let half_size = camera.viewport_size * 0.5;
+    let pixel = (origin.xy / origin.w + 1.0) * half_size;
+    return (round(pixel) - pixel) / half_size;
+}
+
 @vertex
 fn tilemap_vert_main(@builtin(vertex_index) vertex_index: u32) -> TilemapFragData {
     var quad_vertices = QUAD_VERTICES;
-    let position = quad_vertices[vertex_index % 6u]; 
+    let quad = quad_vertices[vertex_index % 6u];
     var ret: TilemapFragData;
-    ret.position = camera * tilemap.transform * position;
-    let uvpos = position.xy;
-    let uvflip = vec2(uvpos.x, 1.0 - uvpos.y);
-    let size_in_tiles = vec2<f32>(f32(tilemap.width), f32(tilemap.height));
+    // Staggered tilemaps are half a tile wider, which the chunk at their right edge covers
+    let stagger_width = select(0.0, 0.5, tilemap.stagger != 0u);
+    let right_edge = tilemap.quad_origin.x + tilemap.quad_size.x == tilemap.source_origin.x + tilemap.source_size.x;
+    let quad_size = vec2<f32>(tilemap.quad_size) + vec2(select(0.0, stagger_width, right_edge), 0.0);
+    let source_size = vec2<f32>(max(tilemap.source_size, vec2(1u))) + vec2(stagger_width, 0.0);
+    // Tile rows go down, while y goes up in [0, 1]x[0, 1]
+    ret.tilepos = vec2<f32>(tilemap.quad_origin) + vec2(quad.x, 1.0 - quad.y) * quad_size;
+    let uvflip = (ret.tilepos - vec2<f32>(tilemap.source_origin)) / source_size;
+    let position = vec4(uvflip.x, 1.0 - uvflip.y, tilemap.depth, 1.0);
+    let world_position = tilemap_world_position(position);
+    ret.position = camera.view_proj * world_position;
+    ret.position += vec4(tilemap_pixel_snap() * ret.position.w, 0.0, 0.0);
+    // FLIP_Y is defined after this file, see TilemapPipeline::set_target_origin
+    if FLIP_Y {
+        ret.position.y = -ret.position.y;
+    }
+    ret.worldpos = world_position.xy / world_position.w;
     let size_of_tile = vec2(tiledata.tile_width, tiledata.tile_height);
-    let size_in_pixels = size_in_tiles * vec2<f32>(size_of_tile);
-    ret.tilepos = uvflip * size_in_tiles;
-    ret.pixelpos = uvflip * size_in_pixels;
+    ret.pixelpos = ret.tilepos * vec2<f32>(size_of_tile);
     return ret;
 }
 
-@fragment
-fn tilemap_frag_main(data: TilemapFragData) -> @location(0) vec4<f32> {
-    var tile: u32 = textureLoad(tilemap_indices, vec2<u32>(data.tilepos), 0).r;
+// tilepos in the grid of the tilemap moved onto the tile it is within, which is half a tile to the
+// left in the rows that are offset by TileStagger
+fn tilemap_stagger(tilepos: vec2<f32>) -> vec2<f32> {
+    let row = u32(max(tilepos.y, 0.0));
+    if tilemap.stagger != 0u && row % 2u == 2u - tilemap.stagger {
+        return tilepos - vec2(0.5, 0.0);
+    }
+    return tilepos;
+}
+
+// data with its position moved onto the tile it is within, see tilemap_stagger
+fn tilemap_stagger_data(data: TilemapFragData) -> TilemapFragData {
+    var staggered = data;
+    staggered.tilepos = tilemap_stagger(data.tilepos);
+    staggered.pixelpos = data.pixelpos + (staggered.tilepos - data.tilepos) * f32(tiledata.tile_width);
+    return staggered;
+}
+
+// The texel of tilemap_indices (and of the tint) that holds the tile at cell of the first layer
+fn tilemap_texel(cell: vec2<u32>) -> vec2<u32> {
+    return cell - tilemap.texture_origin;
+}
+
+// The tile at cell of layer as stored in the tilemap, with the second channel of Rg8Uint tilemaps in
+// bits 8 to 15
+fn tile_value(cell: vec2<u32>, layer: u32) -> u32 {
+    let value = textureLoad(tilemap_indices, tilemap_texel(cell) + vec2(0u, layer * tilemap.height), 0);
+    return value.r | (value.g << 8u);
+}
+
+// Flip flags of the tile at cell of layer, as (horizontal << 2) | (vertical << 1) | diagonal
+fn tile_flags(cell: vec2<u32>, layer: u32) -> u32 {
+    if tilemap.flag_bits == 0u {
+        return 0u;
+    }
+    return tile_value(cell, layer) >> (tilemap.flag_bits - 3u);
+}
+
+// The tile at cell of layer as stored in the tilemap, without its flip flags
+fn tile_id(cell: vec2<u32>, layer: u32) -> u32 {
+    let tile = tile_value(cell, layer);
+    if tilemap.flag_bits != 0u {
+        return tile & ((1u << (tilemap.flag_bits - 3u)) - 1u);
+    }
+    return tile;
+}
+
+// Whether the tile at cell of layer is TilemapDrawData::empty_tile, which isn't drawn
+fn tile_is_empty(cell: vec2<u32>, layer: u32) -> bool {
+    return tilemap.has_empty_tile != 0u && tile_id(cell, layer) == tilemap.empty_tile;
+}
+
+// Index into the tileset of the tile at cell of layer, which is within the tileset unless
+// tilemap.invalid_tiles is 0
+fn tile_at(cell: vec2<u32>, layer: u32) -> u32 {
+    return valid_tile(tile_index(cell, layer));
+}
+
+// Index into the tileset of the tile at cell of layer as it is stored, which may be past the end
+// of the tileset
+fn tile_index(cell: vec2<u32>, layer: u32) -> u32 {
+    var tile = tile_id(cell, layer);
+    if tilemap.variant_rows != 0u {
+        let columns = tiledata.width / tiledata.tile_width;
+        tile = (tile & 0xffu) + (tile >> 8u) * columns;
+    }
+    tile += tilemap.tile_index_offset;
+    if ((cell.x + cell.y) & 1u) == 1u {
+        for (var i = 0u; i < tiledata.checkerboard_len; i++) {
+            if tiledata.checkerboard[i].x == tile {
+                tile = tiledata.checkerboard[i].y;
+                break;
+            }
+        }
+    }
+    return tile;
+}
+
+// The number of tiles of the tileset
+fn tileset_tile_count() -> u32 {
+    return (tiledata.width / tiledata.tile_width) * (tiledata.height / tiledata.tile_height);
+}
+
+// tile, or what is looked up instead if it is past the end of the tileset (see InvalidTiles).
+// Highlighted tiles look up the last tile, for the parts of the shader other than their color.
+fn valid_tile(tile: u32) -> u32 {
+    let count = tileset_tile_count();
+    if tile < count || tilemap.invalid_tiles == 0u {
+        return tile;
+    }
+    if tilemap.invalid_tiles == 2u {
+        return tilemap.invalid_tile;
+    }
+    return max(count, 1u) - 1u;
+}
+
+// Whether the tile at cell of layer is past the end of the tileset and drawn as the error tile
+fn tile_highlighted(cell: vec2<u32>, layer: u32) -> bool {
+    return tilemap.invalid_tiles == 3u && tile_index(cell, layer) >= tileset_tile_count();
+}
+
+// Color of the error tile at tilepos, a magenta and black checkerboard of 4x4 squares
+fn error_tile_color(tilepos: vec2<f32>) -> vec4<f32> {
+    let square = vec2<u32>(fract(tilepos) * 4.0);
+    if ((square.x + square.y) & 1u) == 0u {
+        return vec4(1.0, 0.0, 1.0, 1.0);
+    }
+    return vec4(0.0, 0.0, 0.0, 1.0);
+}
+
+const MATERIALS_ROW: u32 = 256u;
+
+// The effect parameters of a tile, see TileMaterial
+struct TileMaterial {
+    emissive: f32,
+    sway: f32,
+    reflectivity: f32,
+    height: f32,
+}
+
+// Material of tile of the tileset, which is all zeros past the end of its MaterialTable
+fn tile_material(tile: u32) -> TileMaterial {
+    let texel = vec2(tile % MATERIALS_ROW, tile / MATERIALS_ROW);
+    if any(texel >= textureDimensions(tile_materials)) {
+        return TileMaterial(0.0, 0.0, 0.0, 0.0);
+    }
+    let material = textureLoad(tile_materials, texel, 0);
+    return TileMaterial(material.r, material.g, material.b, material.a);
+}
+
+// Material of the topmost tile at tilepos that isn't empty, for fragment hooks
+fn tilemap_material(tilepos: vec2<f32>) -> TileMaterial {
+    let cell = vec2<u32>(tilepos);
+    for (var layer = tilemap.layers; layer > 0u; layer--) {
+        if !tile_is_empty(cell, layer - 1u) {
+            return tile_material(tile_at(cell, layer - 1u));
+        }
+    }
+    return TileMaterial(0.0, 0.0, 0.0, 0.0);
+}
+
+// Horizontal offset in texels of row y (from 0 at the top to 1 at the bottom) of the tile at cell of
+// layer, which sways back and forth by the sway of its material: the top of the tile moves the
+// most and the bottom stays put, with a phase that differs between neighboring cells
+fn tile_sway(cell: vec2<u32>, layer: u32, y: f32) -> f32 {
+    if tiledata.has_materials == 0u {
+        return 0.0;
+    }
+    let sway = tile_material(tile_at(cell, layer)).sway;
+    let phase = f32(cell.x) * 0.7 + f32(cell.y) * 1.3;
+    return sway * (1.0 - y) * sin(frame.time * 2.0 + phase);
+}
+
+// Texel within its tile of the pixel at tilepos (and pixelpos), kept uv_inset texels away from the
+// edges of the tile, moved by tile_sway, flipped by the flip flags of the tile in layer, and scaled
+// to the finest mip level of the tileset that has been uploaded
+fn tile_texel(tilepos: vec2<f32>, pixelpos: vec2<f32>, layer: u32) -> vec2<u32> {
     let size_of_tile = vec2(tiledata.tile_width, tiledata.tile_height);
-    let subpos = vec2<u32>(data.pixelpos) % size_of_tile;
-    var col: vec4<f32> = textureLoad(tilemap_data, subpos, tile, 0);
-    let noise_magnitude = f32(tilemap.noise_data & 0xffffu) / 65536.0;
-    if noise_magnitude != 0.0 {
-        let noise_res = f32((tilemap.noise_data >> 16u) & 0xffu);
-        var noise: vec3<f32> = pcg3d(vec2<f32>(size_of_tile * vec2<u32>(vec2<f32>(noise_res, noise_res) * data.tilepos)));
-        col += noise_magnitude * vec4(noise.x, noise.x, noise.x, 0.0);
-        col = clamp(vec4(0.0, 0.0, 0.0, 0.0), vec4(1.0, 1.0, 1.0, 1.0), col);
+    var texel: vec2<u32>;
+    if tiledata.uv_inset == 0.0 {
+        texel = vec2<u32>(pixelpos) % size_of_tile;
+    } else {
+        let size = vec2<f32>(size_of_tile);
+        let inset = min(vec2(tiledata.uv_inset), size * 0.5);
+        let local = clamp(fract(tilepos) * size, inset, size - inset);
+        texel = min(vec2<u32>(local), size_of_tile - 1u);
+    }
+    let sway = tile_sway(vec2<u32>(tilepos), layer, (f32(texel.y) + 0.5) / f32(size_of_tile.y));
+    if sway != 0.0 {
+        texel.x = u32(clamp(f32(texel.x) + round(sway), 0.0, f32(size_of_tile.x - 1u)));
+    }
+    let flags = tile_flags(vec2<u32>(tilepos), layer);
+    if (flags & 4u) != 0u {
+        texel.x = size_of_tile.x - 1u - texel.x;
+    }
+    if (flags & 2u) != 0u {
+        texel.y = size_of_tile.y - 1u - texel.y;
+    }
+    if (flags & 1u) != 0u {
+        texel = min(texel.yx, size_of_tile - 1u);
+    }
+    return texel * textureDimensions(tilemap_data, tiledata.mip_level) / size_of_tile;
+}
+
+// How pixelpos changes from a pixel to its neighbors on the right (dx) and below (dy). The
+// derivatives are taken by the fragment entry points, since the GL backend can't compile them
+// into the vertex shader.
+struct TileFootprint {
+    dx: vec2<f32>,
+    dy: vec2<f32>,
+}
+
+// Flip v (a position or derivative within a tile of size) by the flip flags of the tile at
+// tilepos in layer, like tile_texel, mirroring positions around the center if is_position is set
+fn flip_in_tile(v: vec2<f32>, size: vec2<f32>, tilepos: vec2<f32>, layer: u32, is_position: bool) -> vec2<f32> {
+    var flipped = v;
+    let flags = tile_flags(vec2<u32>(tilepos), layer);
+    let mirror = select(vec2(0.0), size, is_position);
+    if (flags & 4u) != 0u {
+        flipped.x = mirror.x - flipped.x;
+    }
+    if (flags & 2u) != 0u {
+        flipped.y = mirror.y - flipped.y;
+    }
+    if (flags & 1u) != 0u {
+        flipped = flipped.yx;
+    }
+    return flipped;
+}
+
+// Premultiplied color of tile at the pixel at tilepos (and pixelpos) in layer, filtered as set by
+// TILE_FILTER (see TileFilter), which is defined after this file. Levels finer than the finest
+// uploaded level of the tileset are never read.
+fn tile_color(tilepos: vec2<f32>, pixelpos: vec2<f32>, layer: u32, tile: u32, footprint: TileFootprint) -> vec4<f32> {
+    let finest = f32(tiledata.mip_level);
+    // The mip level of detail, from the number of texels of the finest level the pixel covers
+    let lod = log2(max(max(length(footprint.dx), length(footprint.dy)), 1e-6));
+    let minified = tiledata.mip_levels > 1u && lod > finest;
+    if TILE_FILTER == 0u || (TILE_FILTER == 1u && !minified) {
+        let subpos = tile_texel(tilepos, pixelpos, layer);
+        return textureLoad(tilemap_data, subpos, tile, i32(tiledata.mip_level));
+    }
+    let size = vec2<f32>(vec2(tiledata.tile_width, tiledata.tile_height));
+    let inset = min(vec2(tiledata.uv_inset), size * 0.5);
+    var local = clamp(fract(tilepos) * size, inset, size - inset);
+    local.x = clamp(local.x + tile_sway(vec2<u32>(tilepos), layer, local.y / size.y), inset.x, size.x - inset.x);
+    let uv = min(flip_in_tile(local, size, tilepos, layer, true), size) / size;
+    if TILE_FILTER == 3u {
+        // Scale the footprint up to at least the finest uploaded level
+        let scale = exp2(max(finest - lod, 0.0)) / size;
+        let dx = flip_in_tile(footprint.dx, size, tilepos, layer, false) * scale;
+        let dy = flip_in_tile(footprint.dy, size, tilepos, layer, false) * scale;
+        return textureSampleGrad(tilemap_data, tile_sampler, uv, tile, dx, dy);
+    }
+    return textureSampleLevel(tilemap_data, tile_sampler, uv, tile, max(lod, finest));
+}
+
+// col (premultiplied) of tile at tilepos (and pixelpos) in layer, with the part of it marked by the
+// team mask of the tileset multiplied by the team color
+fn team_color(col: vec4<f32>, tilepos: vec2<f32>, pixelpos: vec2<f32>, layer: u32, tile: u32) -> vec4<f32> {
+    // Masks have a layer for every tile of their tileset, and the placeholder is zero everywhere
+    if tilemap.team_color.a == 0.0 {
+        return col;
+    }
+    // tile_texel is scaled to the drawn mip level of the tileset, and the mask only has the first
+    let texel = tile_texel(tilepos, pixelpos, layer) * textureDimensions(tile_team_mask, 0) / textureDimensions(tilemap_data, tiledata.mip_level);
+    let mask = textureLoad(tile_team_mask, texel, tile, 0).r;
+    return vec4(mix(col.rgb, col.rgb * tilemap.team_color.rgb, mask), col.a);
+}
+
+// Normal of the topmost tile at tilepos that isn't empty, for fragment hooks, with x pointing right,
+// y pointing up, and z out of the tilemap as drawn (before its transform). Tiles without a normal
+// map, and pixels without a tile, face straight out.
+fn tilemap_normal(tilepos: vec2<f32>) -> vec3<f32> {
+    if tiledata.has_normal_map == 0u {
+        return vec3(0.0, 0.0, 1.0);
+    }
+    let cell = vec2<u32>(tilepos);
+    for (var layer = tilemap.layers; layer > 0u; layer--) {
+        if tile_is_empty(cell, layer - 1u) {
+            continue;
+        }
+        let pixelpos = tilepos * vec2<f32>(vec2(tiledata.tile_width, tiledata.tile_height));
+        // tile_texel is scaled to the drawn mip level of the tileset, and the normal map only has
+        // the first
+        let texel = tile_texel(tilepos, pixelpos, layer - 1u) * textureDimensions(tile_normal_map, 0) / textureDimensions(tilemap_data, tiledata.mip_level);
+        var normal = textureLoad(tile_normal_map, texel, tile_at(cell, layer - 1u), 0).xyz * 2.0 - 1.0;
+        // Undo the flips of tile_texel, which map from the tile as drawn to the tileset. The
+        // diagonal flip transposes rows and columns, which swaps and negates x and y, since y
+        // points up rather than down the rows.
+        let flags = tile_flags(cell, layer - 1u);
+        if (flags & 1u) != 0u {
+            normal = vec3(-normal.y, -normal.x, normal.z);
+        }
+        if (flags & 2u) != 0u {
+            normal.y = -normal.y;
+        }
+        if (flags & 4u) != 0u {
+            normal.x = -normal.x;
+        }
+        return normalize(normal);
+    }
+    return vec3(0.0, 0.0, 1.0);
+}
+
+// Linear light emitted by the tiles at tilepos, for fragment hooks and the emissive target, scaled
+// by tilemap.emissive_intensity and by the emissive material of each tile if the tileset has
+// materials. The light of each layer is hidden by the opaque parts of the layers above it.
+fn tilemap_emissive(tilepos: vec2<f32>) -> vec3<f32> {
+    if tiledata.has_emissive == 0u || tilemap.emissive_intensity == 0.0 {
+        return vec3(0.0);
     }
+    let cell = vec2<u32>(tilepos);
+    let pixelpos = tilepos * vec2<f32>(vec2(tiledata.tile_width, tiledata.tile_height));
+    var light = vec3(0.0);
+    for (var layer = 0u; layer < tilemap.layers; layer++) {
+        if tile_is_empty(cell, layer) {
+            continue;
+        }
+        let tile = tile_at(cell, layer);
+        let texel = tile_texel(tilepos, pixelpos, layer);
+        let alpha = textureLoad(tilemap_data, texel, tile, i32(tiledata.mip_level)).a;
+        // tile_texel is scaled to the drawn mip level of the tileset, and the emissive texture
+        // only has the first
+        let emissive_texel = texel * textureDimensions(tile_emissive, 0) / textureDimensions(tilemap_data, tiledata.mip_level);
+        let emissive = textureLoad(tile_emissive, emissive_texel, tile, 0);
+        var strength = emissive.a;
+        if tiledata.has_materials != 0u {
+            strength *= tile_material(tile).emissive;
+        }
+        light = emissive.rgb * strength + light * (1.0 - alpha);
+    }
+    return light * tilemap.emissive_intensity;
+}
+
+fn srgb_encode(c: vec3<f32>) -> vec3<f32> {
+    return select(1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
+}
+
+fn srgb_decode(c: vec3<f32>) -> vec3<f32> {
+    return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
+}
+
+// col (premultiplied) graded by color_lut, which maps between sRGB colors without alpha
+fn color_grade(col: vec4<f32>) -> vec4<f32> {
+    if camera.color_lut_size == 0u || col.a == 0.0 {
+        return col;
+    }
+    let size = f32(camera.color_lut_size);
+    let srgb = srgb_encode(clamp(col.rgb / col.a, vec3(0.0), vec3(1.0)));
+    // Sample at the centers of the first and last entries for 0.0 and 1.0
+    let coord = (srgb * (size - 1.0) + 0.5) / size;
+    let graded = textureSampleLevel(color_lut, color_lut_sampler, coord, 0.0).rgb;
+    return vec4(srgb_decode(graded) * col.a, col.a);
+}
+
+// Premultiplied color of this tilemap at data, with the footprint of its pixel, and its emissive
+// light unless EMISSIVE_TARGET is set (which is defined after this file). tilemap_layer_color is
+// defined by tiles.wgsl, or by shadow.wgsl for tilemaps drawn as shadows, and tilemap_walls by
+// walls.wgsl or no_walls.wgsl (see TilemapShaderFeatures). Each of them is given positions in the
+// grid of the tilemap, and staggers them itself (see tilemap_stagger).
+fn tilemap_color(pixel: TilemapFragData, footprint: TileFootprint) -> vec4<f32> {
+    for (var i = 0u; i < 4u; i++) {
+        let plane = tilemap.clip_planes[i];
+        if dot(plane.xy, pixel.worldpos) + plane.z < 0.0 {
+            discard;
+        }
+    }
+    // The half tiles at the ends of the rows of staggered tilemaps are outside of every tile
+    let column = tilemap_stagger(pixel.tilepos).x;
+    let source_end = tilemap.source_origin.x + tilemap.source_size.x;
+    if tilemap.stagger != 0u && (column < f32(tilemap.source_origin.x) || column >= f32(source_end)) {
+        discard;
+    }
+    let data = tilemap_walls(pixel);
+    var graded = color_grade(tilemap_layer_color(data, footprint));
+    if !EMISSIVE_TARGET {
+        graded = vec4(graded.rgb + tilemap_emissive(tilemap_stagger(data.tilepos)), graded.a);
+    }
+    let col = graded * tilemap.opacity;
     if col.a == 0.0 {
         discard;
     }
     return col;
 }
 
-fn pcg3d(uv: vec2<f32>) -> vec3<f32> {
-    var a = bitcast<vec2<u32>>(uv);
-    var b = vec3(a.xy, a.x ^ a.y);
-    var v: vec3<u32> = b * 1664525u + 1013904223u;
+// col (premultiplied) as written to the render target, encoded as sRGB by the shader if
+// ENCODE_SRGB is set (see TilemapPipeline::set_encode_srgb), which is defined after this file
+fn target_color(col: vec4<f32>) -> vec4<f32> {
+    if !ENCODE_SRGB || col.a == 0.0 {
+        return col;
+    }
+    return vec4(srgb_encode(clamp(col.rgb / col.a, vec3(0.0), vec3(1.0))) * col.a, col.a);
+}
+
+@fragment
+fn tilemap_frag_main(data: TilemapFragData) -> @location(0) vec4<f32> {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    return target_color(tilemap_color(data, footprint));
+}
+
+// The color of a pixel, and its emissive light with the alpha of the tilemap, see
+// TilemapPipeline::set_emissive_target
+struct TilemapEmissiveOutput {
+    @location(0) color: vec4<f32>,
+    @location(1) emissive: vec4<f32>,
+}
+
+// Emissive light of this tilemap at data, where col is its color
+fn emissive_output(data: TilemapFragData, col: vec4<f32>) -> vec4<f32> {
+    let tilepos = tilemap_stagger(tilemap_walls(data).tilepos);
+    return vec4(tilemap_emissive(tilepos) * tilemap.opacity, col.a);
+}
+
+@fragment
+fn tilemap_frag_main_emissive(data: TilemapFragData) -> TilemapEmissiveOutput {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    let col = tilemap_color(data, footprint);
+    return TilemapEmissiveOutput(target_color(col), emissive_output(data, col));
+}
+
+// col drawn without blending, for pixels that are at least half opaque (the others are
+// discarded by the entry points), see TilemapBlendMode::Opaque
+fn opaque_color(col: vec4<f32>) -> vec4<f32> {
+    return target_color(vec4(col.rgb / col.a, 1.0));
+}
+
+@fragment
+fn tilemap_frag_opaque(data: TilemapFragData) -> @location(0) vec4<f32> {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    let col = tilemap_color(data, footprint);
+    if col.a < 0.5 {
+        discard;
+    }
+    return opaque_color(col);
+}
 
-    v.x += v.y*v.z;
-    v.y += v.z*v.x;
-    v.z += v.x*v.y;
+@fragment
+fn tilemap_frag_opaque_emissive(data: TilemapFragData) -> TilemapEmissiveOutput {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    let col = tilemap_color(data, footprint);
+    if col.a < 0.5 {
+        discard;
+    }
+    let emissive = vec4(emissive_output(data, col).rgb, 1.0);
+    return TilemapEmissiveOutput(opaque_color(col), emissive);
+}
 
-    v ^= vec3(v.x >> 16u, v.y >> 16u, v.z >> 16u);
+// (draw_index + 1, column, row, tile) of this tilemap at data where it is visible, see
+// TilemapPipeline::render_picking
+@fragment
+fn tilemap_frag_pick(data: TilemapFragData) -> @location(0) vec4<u32> {
+    // Discards the pixel where the tilemap is transparent
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    let col = tilemap_color(data, footprint);
+    let cell = vec2<u32>(tilemap_stagger(tilemap_walls(data).tilepos));
+    for (var layer = tilemap.layers; layer > 0u; layer--) {
+        if !tile_is_empty(cell, layer - 1u) {
+            return vec4(tilemap.draw_index + 1u, cell, tile_id(cell, layer - 1u));
+        }
+    }
+    return vec4(tilemap.draw_index + 1u, cell, 0u);
+}
 
-    v.x += v.y*v.z;
-    v.y += v.z*v.x;
-    v.z += v.x*v.y;
+fn overlay(base: f32, blend: f32) -> f32 {
+    if base < 0.5 {
+        return 2.0 * base * blend;
+    }
+    return 1.0 - 2.0 * (1.0 - base) * (1.0 - blend);
+}
 
-    return vec3<f32>(vec3<u32>(v.x & 0xffu, v.y & 0xffu, v.z & 0xffu)) / 255.0;
+// col (premultiplied) overlaid onto the backdrop at data, as written to the render target
+fn overlay_color(data: TilemapFragData, col: vec4<f32>) -> vec4<f32> {
+    var dst = textureLoad(backdrop, vec2<u32>(data.position.xy), 0);
+    if ENCODE_SRGB {
+        dst = vec4(srgb_decode(dst.rgb), dst.a);
+    }
+    // Un-premultiply the tile's color, overlay it onto the backdrop, and composite by its alpha
+    let src = col.rgb / col.a;
+    let blended = vec3(overlay(dst.r, src.r), overlay(dst.g, src.g), overlay(dst.b, src.b));
+    var rgb = mix(dst.rgb, blended, col.a);
+    if ENCODE_SRGB {
+        rgb = srgb_encode(clamp(rgb, vec3(0.0), vec3(1.0)));
+    }
+    return vec4(rgb, col.a + dst.a * (1.0 - col.a));
+}
+
+@fragment
+fn tilemap_frag_overlay(data: TilemapFragData) -> @location(0) vec4<f32> {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    return overlay_color(data, tilemap_color(data, footprint));
+}
+
+@fragment
+fn tilemap_frag_overlay_emissive(data: TilemapFragData) -> TilemapEmissiveOutput {
+    let footprint = TileFootprint(dpdx(data.pixelpos), dpdy(data.pixelpos));
+    let col = tilemap_color(data, footprint);
+    return TilemapEmissiveOutput(overlay_color(data, col), emissive_output(data, col));
 }
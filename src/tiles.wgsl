@@ -0,0 +1,27 @@
+// Color of the tiles at data (whose pixel has footprint), composited from the bottom layer to the top one, with their tint,
+// noise, and the fragment hook applied. Pixels where every layer has the empty tile are discarded.
+fn tilemap_layer_color(pixel: TilemapFragData, footprint: TileFootprint) -> vec4<f32> {
+    let data = tilemap_stagger_data(pixel);
+    let cell = vec2<u32>(data.tilepos);
+    var col = vec4(0.0);
+    var empty = true;
+    for (var layer = 0u; layer < tilemap.layers; layer++) {
+        if tile_is_empty(cell, layer) {
+            continue;
+        }
+        empty = false;
+        let tile = tile_at(cell, layer);
+        let tile_col = tile_color(data.tilepos, data.pixelpos, layer, tile, footprint);
+        var layer_col = team_color(tile_col, data.tilepos, data.pixelpos, layer, tile);
+        if tile_highlighted(cell, layer) {
+            layer_col = error_tile_color(data.tilepos);
+        }
+        col = layer_col + col * (1.0 - layer_col.a);
+    }
+    if empty {
+        discard;
+    }
+    col = tilemap_tint_color(col, vec2<u32>(data.tilepos));
+    col = tilemap_noise(col, data);
+    return tilemap_hook(col, data.tilepos);
+}
@@ -0,0 +1,166 @@
+use core::time::Duration;
+use vek::ops::Lerp;
+
+/// Runs a simulation (e.g. a `DoubleBuffered` cellular automaton) at a fixed rate, independently of
+/// the frame rate, and says how far between two steps each frame is drawn so that cameras and
+/// sprites can be interpolated instead of stuttering. Time is accumulated as a `Duration`, so no
+/// rounding error builds up however long the simulation runs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FixedTimestep {
+    step: Duration,
+    max_steps: u32,
+    accumulator: Duration,
+    ticks: u64,
+    skipped: u64,
+}
+
+impl FixedTimestep {
+    /// Run a step every `step` (which is at least a nanosecond), catching up on at most 8 steps
+    /// per frame.
+    pub fn new(step: Duration) -> Self {
+        FixedTimestep {
+            step: step.max(Duration::from_nanos(1)),
+            max_steps: 8,
+            accumulator: Duration::ZERO,
+            ticks: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Run `rate` steps per second, or `None` if `rate` isn't positive or is too small for the
+    /// time between steps to fit in a `Duration`.
+    pub fn from_rate(rate: f64) -> Option<Self> {
+        // Zero, negative, and NaN rates give steps that can't be represented.
+        Duration::try_from_secs_f64(rate.recip())
+            .ok()
+            .map(Self::new)
+    }
+
+    /// Catch up on at most `max_steps` steps per frame (at least 1). When a frame takes longer than
+    /// that many steps (e.g. while the window is being dragged), the rest of the missed steps are
+    /// skipped rather than run on later frames, which would only fall further behind.
+    pub fn with_max_steps(self, max_steps: u32) -> Self {
+        FixedTimestep {
+            max_steps: max_steps.max(1),
+            ..self
+        }
+    }
+
+    /// The time between steps.
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// The number of steps run so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// The number of steps skipped so far because of `with_max_steps`.
+    pub fn skipped_steps(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Add the `elapsed` time since the last frame, returning how many steps to run for this frame.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let due = self.accumulator.as_nanos() / self.step.as_nanos();
+        let steps = due.min(self.max_steps as u128) as u32;
+        self.skipped += (due - steps as u128) as u64;
+        // Skipped steps are dropped along with the time they would have taken.
+        self.accumulator =
+            Duration::from_nanos((self.accumulator.as_nanos() % self.step.as_nanos()) as u64);
+        self.ticks += steps as u64;
+        steps
+    }
+
+    /// Add the `elapsed` time since the last frame, and call `step` for each step that is due,
+    /// returning how many were run.
+    pub fn update(&mut self, elapsed: Duration, mut step: impl FnMut()) -> u32 {
+        let steps = self.advance(elapsed);
+        for _ in 0..steps {
+            step();
+        }
+        steps
+    }
+
+    /// How far the current frame is between the latest step and the next one, from 0.0 to 1.0.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+    }
+
+    /// Blend between a value (e.g. a camera position or transform) before the latest step and
+    /// after it, for drawing the current frame. This draws the simulation up to one step behind,
+    /// but moves smoothly at any frame rate.
+    pub fn interpolate<T: Lerp<f32, Output = T>>(&self, previous: T, current: T) -> T {
+        T::lerp_unclamped(previous, current, self.alpha())
+    }
+}
+
+impl Default for FixedTimestep {
+    /// 60 steps per second.
+    fn default() -> Self {
+        Self::new(Duration::from_nanos(1_000_000_000 / 60))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn steps_are_run_as_time_accumulates() {
+        let mut timestep = FixedTimestep::new(STEP);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 0);
+        assert_eq!(timestep.advance(Duration::from_millis(4)), 1);
+        assert_eq!(timestep.advance(Duration::from_millis(25)), 2);
+        assert_eq!(timestep.ticks(), 3);
+        assert_eq!(timestep.skipped_steps(), 0);
+    }
+
+    #[test]
+    fn the_remainder_is_carried_to_the_next_frame() {
+        let mut timestep = FixedTimestep::new(STEP);
+        assert_eq!(timestep.advance(Duration::from_millis(27)), 2);
+        assert_eq!(timestep.alpha(), 0.7);
+        assert_eq!(timestep.interpolate(0.0, 10.0), 7.0);
+        assert_eq!(timestep.advance(Duration::from_millis(3)), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+    }
+
+    #[test]
+    fn steps_past_the_maximum_are_skipped() {
+        let mut timestep = FixedTimestep::new(STEP).with_max_steps(3);
+        assert_eq!(timestep.advance(Duration::from_millis(105)), 3);
+        assert_eq!(timestep.ticks(), 3);
+        assert_eq!(timestep.skipped_steps(), 7);
+        // The time of the skipped steps is dropped, but not what is left of the step after them.
+        assert_eq!(timestep.alpha(), 0.5);
+        assert_eq!(timestep.advance(Duration::from_millis(5)), 1);
+        assert_eq!(timestep.skipped_steps(), 7);
+    }
+
+    #[test]
+    fn update_runs_each_step() {
+        let mut timestep = FixedTimestep::new(STEP).with_max_steps(2);
+        let mut runs = 0;
+        assert_eq!(timestep.update(Duration::from_millis(35), || runs += 1), 2);
+        assert_eq!(runs, 2);
+    }
+
+    #[test]
+    fn rates_without_a_step_are_rejected() {
+        assert_eq!(FixedTimestep::from_rate(0.0), None);
+        assert_eq!(FixedTimestep::from_rate(-1.0), None);
+        assert_eq!(FixedTimestep::from_rate(f64::NAN), None);
+        assert_eq!(
+            FixedTimestep::from_rate(f64::INFINITY).unwrap().step(),
+            Duration::from_nanos(1)
+        );
+        assert_eq!(FixedTimestep::from_rate(100.0).unwrap().step(), STEP);
+    }
+}
@@ -0,0 +1,8 @@
+@group(2) @binding(2) var tilemap_tint: texture_2d<f32>;
+
+// Multiply col by the tint of the tile at cell
+fn tilemap_tint_color(col: vec4<f32>, cell: vec2<u32>) -> vec4<f32> {
+    let tint = textureLoad(tilemap_tint, tilemap_texel(cell), 0);
+    // col is premultiplied, so the tint's alpha scales its color too
+    return col * vec4(tint.rgb * tint.a, tint.a);
+}
@@ -0,0 +1,45 @@
+use crate::{TileFormat, TileId, TilemapRef, TilesetRef};
+use alloc::vec::Vec;
+use vek::Vec2;
+
+/// A tile found by `validate` that is past the end of its tileset.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InvalidTile {
+    /// The position of the tile in the tilemap.
+    pub cell: Vec2<u32>,
+    /// The layer of the tile, for tilemaps with several (see `TilemapStack`).
+    pub layer: u32,
+    /// The index of the tile into the tileset, as it would be drawn (for `TileVariant`s, the tile
+    /// in the row of its variant).
+    pub tile: u32,
+}
+
+/// Find the tiles of every layer of `tilemap` that `tileset` has no tile for, in reading order from
+/// the bottom layer up. Drawing them samples whatever the GPU returns for layers past the end of
+/// the tileset texture, which varies between backends, unless the draw has
+/// `TilemapDrawData::invalid_tiles` set. Tiles are checked as they are stored, so maps drawn with
+/// `flip_flags`, a `tile_index_offset`, or an `empty_tile` should account for those first (e.g.
+/// with `TileFlip::decode`).
+pub fn validate<T: TileId>(tilemap: &TilemapRef<T>, tileset: &TilesetRef) -> Vec<InvalidTile> {
+    let grid = tileset.pixel_size / tileset.size_of_tile.map(|x| x.max(1));
+    let count = grid.product();
+    let len = (tilemap.tile_size.product() as usize).max(1);
+    let mut invalid = Vec::new();
+    for (i, layer) in tilemap.data.chunks(len).enumerate() {
+        for (j, &tile) in layer.iter().enumerate() {
+            let mut index = tile.index();
+            if T::FORMAT == TileFormat::Rg8 {
+                index = (index & 0xff) + (index >> 8) * grid.x;
+            }
+            if index >= count {
+                let width = tilemap.tile_size.x.max(1);
+                invalid.push(InvalidTile {
+                    cell: Vec2::new(j as u32 % width, j as u32 / width),
+                    layer: i as u32,
+                    tile: index,
+                });
+            }
+        }
+    }
+    invalid
+}
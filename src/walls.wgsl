@@ -0,0 +1,35 @@
+// The pixel of the tilemap seen at data, where tiles with a height (see TileMaterial::height) are
+// drawn with their tops offset away from the center of the camera in proportion to their height,
+// hiding what is behind them (see TilemapWalls). The heights are searched from the tallest down,
+// and the first tile that reaches the height at the position it is offset from is drawn.
+fn tilemap_walls(data: TilemapFragData) -> TilemapFragData {
+    // The offset of the top of a tile with a height of 1, mapped from world coordinates to tiles
+    // by the inverse of the transform of the tilemap
+    let world = (data.worldpos - camera.center) * tilemap.wall_parallax;
+    let x = tilemap.transform[0].xy / f32(max(tilemap.source_size.x, 1u));
+    let y = -tilemap.transform[1].xy / f32(max(tilemap.source_size.y, 1u));
+    let det = x.x * y.y - y.x * x.y;
+    if det == 0.0 {
+        return data;
+    }
+    let offset = vec2(world.x * y.y - world.y * y.x, world.y * x.x - world.x * x.y) / det;
+    // Only the tiles of this draw (or chunk of it) can be looked up
+    let texture_max = tilemap.texture_origin + vec2(tilemap.width, tilemap.height);
+    let source_min = vec2<f32>(max(vec2<u32>(tilemap.source_origin), tilemap.texture_origin));
+    let source_max = vec2<f32>(min(tilemap.source_origin + tilemap.source_size, texture_max));
+    let size_of_tile = vec2<f32>(vec2(tiledata.tile_width, tiledata.tile_height));
+    for (var i = tilemap.wall_samples; i > 0u; i--) {
+        let height = tilemap.wall_max_height * f32(i) / f32(tilemap.wall_samples);
+        let tilepos = data.tilepos - height * offset;
+        if any(tilepos < source_min) || any(tilepos >= source_max) {
+            continue;
+        }
+        if tilemap_material(tilepos).height >= height {
+            var wall = data;
+            wall.tilepos = tilepos;
+            wall.pixelpos = tilepos * size_of_tile;
+            return wall;
+        }
+    }
+    return data;
+}
@@ -0,0 +1,354 @@
+use crate::{
+    animate_tiles, AlphaMode, AnyTilemapRef, ChunkedTilemap, CompressedTiles, MapObjects,
+    TileAnimation, Tilemap, TilemapBlendMode, TilemapDrawData, TilemapRef, TilesetRef,
+};
+use std::{borrow::Cow, collections::BTreeMap, time::Duration};
+use vek::{Mat4, Rgba, Vec2, Vec3};
+
+/// Convert a tileset into one with exactly 256 tiles, where `EMPTY_TILE` (and any tiles past the
+/// end of `tileset`) are fully transparent.
+#[cfg(any(
+    feature = "godot",
+    feature = "ogmo",
+    feature = "pyxel",
+    feature = "tiled"
+))]
+pub(crate) fn with_empty_tile(tileset: &TilesetRef<'_>) -> TilesetRef<'static> {
+    let tile_len = tileset.size_of_tile.x as usize * tileset.size_of_tile.y as usize;
+    let mut data = tileset.data.to_vec();
+    data.resize(tile_len * crate::EMPTY_TILE as usize, 0);
+    data.resize(tile_len * (crate::EMPTY_TILE as usize + 1), 0);
+    TilesetRef {
+        pixel_size: tileset.size_of_tile * Vec2::new(crate::EMPTY_TILE as u32 + 1, 1),
+        size_of_tile: tileset.size_of_tile,
+        data: Cow::Owned(data),
+        alpha_mode: tileset.alpha_mode,
+        uv_inset: tileset.uv_inset,
+        checkerboard: Cow::Owned(tileset.checkerboard.to_vec()),
+        compressed: None,
+    }
+}
+
+/// How a background image fills the map.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BackgroundRepeat {
+    /// Stretch a single copy of the image over the whole map.
+    #[default]
+    Stretch,
+    /// Repeat the image at its native size, starting from the top left corner of the map.
+    /// The last row and column of repetitions may extend past the edges of the map.
+    Tile,
+}
+
+/// An image drawn behind all the layers of a map.
+#[derive(Clone, Debug)]
+pub struct BackgroundImage {
+    /// The image, as a tileset containing a single tile.
+    pub image: TilesetRef<'static>,
+    /// How the image fills the map.
+    pub repeat: BackgroundRepeat,
+}
+
+/// What is drawn behind all the layers of a map, as specified by map editors.
+/// MapBackground::default() draws nothing.
+#[derive(Clone, Debug, Default)]
+pub struct MapBackground {
+    /// A solid color (in sRGB) filling the map.
+    pub color: Option<Rgba<u8>>,
+    /// An image drawn on top of `color`.
+    pub image: Option<BackgroundImage>,
+}
+
+/// A layer of tiles in a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub struct TilemapLayer {
+    /// The name of this layer, as given by the map editor.
+    pub name: String,
+    /// The tiles of this layer.
+    pub tilemap: Tilemap,
+    /// The index into `TilemapWorld::tilesets` that this layer is drawn with.
+    pub tileset: u32,
+    /// A matrix that maps from [0, 1]x[0, 1] to the map's [0, 1]x[0, 1] for this layer.
+    pub transform: Mat4<f32>,
+    /// Whether this layer is drawn.
+    pub visible: bool,
+    /// How this layer is composited onto the layers below it.
+    pub blend_mode: TilemapBlendMode,
+    /// Opacity multiplied with every pixel of this layer.
+    pub opacity: f32,
+    /// How far this layer moves relative to the camera, see `TilemapDrawData::parallax`.
+    pub parallax: Vec2<f32>,
+}
+
+/// A layer of tiles stored in chunks in a `TilemapWorld` (e.g. from an infinite map).
+#[derive(Clone, Debug)]
+pub struct ChunkedLayer {
+    /// The name of this layer, as given by the map editor.
+    pub name: String,
+    /// The tiles of this layer, where the tile at `(0, 0)` is drawn at the top left of the map.
+    pub tiles: ChunkedTilemap,
+    /// The index into `TilemapWorld::tilesets` that this layer is drawn with.
+    pub tileset: u32,
+    /// A matrix that maps from [0, 1]x[0, 1] to the map's [0, 1]x[0, 1] for this layer.
+    pub transform: Mat4<f32>,
+    /// Whether this layer is drawn.
+    pub visible: bool,
+    /// How this layer is composited onto the layers below it.
+    pub blend_mode: TilemapBlendMode,
+    /// Opacity multiplied with every pixel of this layer.
+    pub opacity: f32,
+    /// How far this layer moves relative to the camera, see `TilemapDrawData::parallax`.
+    pub parallax: Vec2<f32>,
+}
+
+/// A single picture drawn as a layer instead of tiles (e.g. a decorative backdrop).
+#[derive(Clone, Debug)]
+pub struct ImageLayer {
+    /// The name of this layer, as given by the map editor.
+    pub name: String,
+    /// The picture, as a tileset containing a single tile.
+    pub image: TilesetRef<'static>,
+    /// A matrix that maps from [0, 1]x[0, 1] to the map's [0, 1]x[0, 1] for this layer.
+    pub transform: Mat4<f32>,
+    /// Whether this layer is drawn.
+    pub visible: bool,
+    /// How this layer is composited onto the layers below it.
+    pub blend_mode: TilemapBlendMode,
+    /// Opacity multiplied with every pixel of this layer.
+    pub opacity: f32,
+    /// How far this layer moves relative to the camera, see `TilemapDrawData::parallax`.
+    pub parallax: Vec2<f32>,
+}
+
+impl ImageLayer {
+    /// The instruction to draw this picture with `transform` (mapping from the [0, 1]x[0, 1] of
+    /// the map to world coordinates), where `tileset` is the index that `image` was uploaded at.
+    pub fn draw_data<'a>(&self, transform: Mat4<f32>, tileset: u32) -> TilemapDrawData<'a> {
+        TilemapDrawData::new(Tilemap::<u8>::new_zeroed(Vec2::one()), tileset)
+            .with_transform(transform * self.transform)
+            .with_blend_mode(self.blend_mode)
+            .with_opacity(self.opacity)
+            .with_parallax(self.parallax)
+    }
+}
+
+/// A single layer of a `TilemapWorld`.
+#[derive(Clone, Debug)]
+pub enum WorldLayer {
+    Tiles(TilemapLayer),
+    Chunks(ChunkedLayer),
+    Image(ImageLayer),
+}
+
+impl WorldLayer {
+    /// The name of this layer, as given by the map editor.
+    pub fn name(&self) -> &str {
+        match self {
+            WorldLayer::Tiles(layer) => &layer.name,
+            WorldLayer::Chunks(layer) => &layer.name,
+            WorldLayer::Image(layer) => &layer.name,
+        }
+    }
+
+    /// Whether this layer is drawn.
+    pub fn visible(&self) -> bool {
+        match self {
+            WorldLayer::Tiles(layer) => layer.visible,
+            WorldLayer::Chunks(layer) => layer.visible,
+            WorldLayer::Image(layer) => layer.visible,
+        }
+    }
+}
+
+/// A map made up of several layers sharing a list of tilesets, as produced by the importers.
+#[derive(Clone, Debug, Default)]
+pub struct TilemapWorld {
+    /// Size of this map, in tiles.
+    pub tile_size: Vec2<u32>,
+    /// Size of each tile of this map, in pixels.
+    pub size_of_tile: Vec2<u32>,
+    /// The tilesets used by the layers of this map.
+    pub tilesets: Vec<TilesetRef<'static>>,
+    /// The layers of this map, from bottom to top.
+    pub layers: Vec<WorldLayer>,
+    /// What is drawn behind the layers of this map.
+    pub background: MapBackground,
+    /// The objects placed in this map.
+    pub objects: MapObjects,
+    /// The animated tiles of each of `tilesets` (by the index of the tileset, and then of the
+    /// tile), which `draw_list_at` draws. Tilesets past the end of this have no animated tiles.
+    pub animations: Vec<BTreeMap<u32, TileAnimation>>,
+    /// The global tile id of the first tile of each of `tilesets` in the map editor this map was
+    /// imported from, which `MapObject::gid` refers to. Empty for maps whose objects number their
+    /// tiles as `tiled::export_tmx` does (from 1, with each tileset after the one before).
+    pub firstgids: Vec<u32>,
+}
+
+impl TilemapWorld {
+    /// Size of this map, in pixels.
+    pub fn pixel_size(&self) -> Vec2<u32> {
+        self.tile_size * self.size_of_tile
+    }
+
+    /// The tilesets to provide to `TilemapPipeline::upload_tilesets` before uploading the result
+    /// of `draw_list`. These are `tilesets`, followed by the tilesets used for the background and
+    /// image layers.
+    pub fn upload_tilesets(&self) -> Vec<TilesetRef<'_>> {
+        let mut tilesets: Vec<TilesetRef<'_>> = self.tilesets.iter().map(borrow_tileset).collect();
+        if let Some(color) = self.background.color {
+            tilesets.push(TilesetRef {
+                pixel_size: Vec2::one(),
+                size_of_tile: Vec2::one(),
+                data: Cow::Owned(vec![u32::from_le_bytes(color.into_array())]),
+                alpha_mode: AlphaMode::Straight,
+                uv_inset: 0.0,
+                checkerboard: Cow::Borrowed(&[]),
+                compressed: None,
+            });
+        }
+        if let Some(background) = &self.background.image {
+            tilesets.push(borrow_tileset(&background.image));
+        }
+        for layer in self.layers.iter() {
+            if let WorldLayer::Image(layer) = layer {
+                tilesets.push(borrow_tileset(&layer.image));
+            }
+        }
+        tilesets
+    }
+
+    /// The list of tilemaps to provide to `TilemapPipeline::upload_tilemaps` to draw the
+    /// background and visible layers of this map, where `transform` maps from [0, 1]x[0, 1] to
+    /// world coordinates for the whole map.
+    pub fn draw_list(&self, transform: Mat4<f32>) -> Vec<TilemapDrawData<'_>> {
+        let mut draws = Vec::with_capacity(self.layers.len() + 2);
+        let mut extra_tileset = self.tilesets.len() as u32;
+        if self.background.color.is_some() {
+            draws.push(background_draw(
+                transform,
+                TilemapRef::new_zeroed(Vec2::one()),
+                extra_tileset,
+            ));
+            extra_tileset += 1;
+        }
+        if let Some(background) = &self.background.image {
+            let (tiles, layer_transform) = match background.repeat {
+                BackgroundRepeat::Stretch => (Vec2::one(), Mat4::identity()),
+                BackgroundRepeat::Tile => {
+                    let image_size = background.image.pixel_size.map(|x| x.max(1));
+                    let pixel_size = self.pixel_size().map(|x| x.max(1));
+                    let tiles = (pixel_size + image_size - Vec2::one()) / image_size;
+                    let scale = (tiles * image_size).as_::<f32>() / pixel_size.as_::<f32>();
+                    // Anchor the repetitions to the top left, where the first row of tiles is drawn.
+                    let layer_transform =
+                        Mat4::<f32>::translation_3d(Vec3::new(0.0, 1.0 - scale.y, 0.0))
+                            * Mat4::<f32>::scaling_3d(Vec3::new(scale.x, scale.y, 1.0));
+                    (tiles, layer_transform)
+                }
+            };
+            draws.push(background_draw(
+                transform * layer_transform,
+                TilemapRef::new_zeroed(tiles),
+                extra_tileset,
+            ));
+            extra_tileset += 1;
+        }
+        for layer in self.layers.iter() {
+            match layer {
+                WorldLayer::Tiles(layer) if layer.visible => draws.push(
+                    TilemapDrawData::new(&layer.tilemap, layer.tileset)
+                        .with_transform(transform * layer.transform)
+                        .with_blend_mode(layer.blend_mode)
+                        .with_opacity(layer.opacity)
+                        .with_parallax(layer.parallax),
+                ),
+                WorldLayer::Chunks(layer) if layer.visible => {
+                    // Chunks are positioned in tiles, so scale a tile to its size within the map.
+                    let size = self.tile_size.map(|x| x.max(1)).as_::<f32>();
+                    let tile_transform = transform
+                        * layer.transform
+                        * Mat4::<f32>::translation_3d(Vec3::new(0.0, 1.0 - 1.0 / size.y, 0.0))
+                        * Mat4::<f32>::scaling_3d(Vec3::new(1.0 / size.x, 1.0 / size.y, 1.0));
+                    draws.extend(
+                        layer
+                            .tiles
+                            .draw_list(tile_transform, layer.tileset, None)
+                            .into_iter()
+                            .map(|draw| TilemapDrawData {
+                                blend_mode: layer.blend_mode,
+                                opacity: layer.opacity,
+                                parallax: layer.parallax,
+                                ..draw
+                            }),
+                    );
+                }
+                WorldLayer::Tiles(_) | WorldLayer::Chunks(_) => {}
+                WorldLayer::Image(layer) => {
+                    if layer.visible {
+                        draws.push(layer.draw_data(transform, extra_tileset));
+                    }
+                    extra_tileset += 1;
+                }
+            }
+        }
+        draws
+    }
+
+    /// The list of tilemaps to draw this map like `draw_list`, with each animated tile (see
+    /// `animations`) replaced by the tile its animation shows when `elapsed` time has passed.
+    /// Layers with animated tiles are copied, so upload the result again whenever the shown
+    /// tiles change (or every frame).
+    pub fn draw_list_at(
+        &self,
+        transform: Mat4<f32>,
+        elapsed: Duration,
+    ) -> Vec<TilemapDrawData<'_>> {
+        let mut draws = self.draw_list(transform);
+        for draw in draws.iter_mut() {
+            let Some(animations) = self.animations.get(draw.tileset as usize) else {
+                continue;
+            };
+            match &mut draw.tilemap {
+                AnyTilemapRef::U8(tilemap) => animate_tiles(&mut tilemap.data, animations, elapsed),
+                AnyTilemapRef::U16(tilemap) => {
+                    animate_tiles(&mut tilemap.data, animations, elapsed)
+                }
+                AnyTilemapRef::U32(tilemap) => {
+                    animate_tiles(&mut tilemap.data, animations, elapsed)
+                }
+                AnyTilemapRef::Variant(tilemap) => {
+                    animate_tiles(&mut tilemap.data, animations, elapsed)
+                }
+                AnyTilemapRef::Heatmap(_) => {}
+            }
+        }
+        draws
+    }
+}
+
+fn borrow_tileset<'a>(tileset: &'a TilesetRef<'_>) -> TilesetRef<'a> {
+    TilesetRef {
+        pixel_size: tileset.pixel_size,
+        size_of_tile: tileset.size_of_tile,
+        data: Cow::Borrowed(tileset.data.as_ref()),
+        alpha_mode: tileset.alpha_mode,
+        uv_inset: tileset.uv_inset,
+        checkerboard: Cow::Borrowed(tileset.checkerboard.as_ref()),
+        compressed: tileset
+            .compressed
+            .as_ref()
+            .map(|compressed| CompressedTiles {
+                format: compressed.format,
+                blocks: Cow::Borrowed(compressed.blocks.as_ref()),
+            }),
+    }
+}
+
+fn background_draw<'a>(
+    transform: Mat4<f32>,
+    tilemap: TilemapRef<'static>,
+    tileset: u32,
+) -> TilemapDrawData<'a> {
+    TilemapDrawData::new(tilemap, tileset).with_transform(transform)
+}
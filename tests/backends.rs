@@ -0,0 +1,2079 @@
+//! Renders reference scenes on every available adapter and compares them with the expected images,
+//! to catch backend-specific differences (e.g. texture array quirks on GL, or row padding). Run
+//! with `cargo test --features gpu-tests`.
+use vek::{Mat4, Rect, Rgb, Rgba, Vec2, Vec3};
+use wgpu_tilemap::{
+    parse_dds, parse_ktx2, validate, AllocationKind, AllocationStrategy, AlphaMode, AnyTilemapRef,
+    CheckerboardTile, ClipPlane, ColorLut, CompressedTiles, DownscaleFilter, FrameContext,
+    HeatmapRef, InvalidTile, InvalidTiles, MaterialTable, MippedTileset, PickReadback,
+    PresentParams, PresentScaling, TargetOrigin, TileFilter, TileFlip, TileFormat, TileId,
+    TileMaterial, TilePick, TileStagger, TileVariant, Tilemap, TilemapBlendMode, TilemapDrawData,
+    TilemapPipeline, TilemapPipelineDescriptor, TilemapStack, TilemapStencil, TilemapUserData,
+    TilemapWalls, TilesetError, TilesetRef, FULLSCREEN_QUAD_CAMERA, MAX_CHECKERBOARD_TILES,
+    MAX_CLIP_PLANES, PICKING_FORMAT,
+};
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const SIZE_OF_TILE: Vec2<u32> = Vec2::new(4, 4);
+const NUM_TILES: u32 = 4;
+/// The number of tiles in each row of the test tileset.
+const TILESET_COLUMNS: u32 = 2;
+/// The format of the depth buffer of scenes with `Scene::opaque`.
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+/// The format of the depth stencil buffer of scenes with `Scene::stencil`.
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+/// The most each channel may differ from the expected image, to allow for sRGB round trips.
+const TOLERANCE: u8 = 2;
+
+/// The color of a texel of the test tileset, which is different for every opaque texel. The right
+/// half of the last tile is transparent.
+fn texel(tile: u32, x: u32, y: u32) -> [u8; 4] {
+    if tile == NUM_TILES - 1 && x >= SIZE_OF_TILE.x / 2 {
+        return [0; 4];
+    }
+    [
+        (tile * 60 + x * 12) as u8,
+        (y * 50 + 20) as u8,
+        (230 - tile * 50) as u8,
+        255,
+    ]
+}
+
+fn tileset() -> TilesetRef<'static> {
+    let dimensions = SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS);
+    let mut pixels = Vec::new();
+    for y in 0..dimensions.y {
+        for x in 0..dimensions.x {
+            let tile = y / SIZE_OF_TILE.y * TILESET_COLUMNS + x / SIZE_OF_TILE.x;
+            pixels.extend(texel(tile, x % SIZE_OF_TILE.x, y % SIZE_OF_TILE.y));
+        }
+    }
+    let mut tileset = TilesetRef::from_rgba8(&pixels, dimensions, SIZE_OF_TILE, Vec2::zero());
+    tileset.alpha_mode = AlphaMode::Premultiplied;
+    tileset
+}
+
+/// The texture compression features requested from adapters that support them, so that scenes
+/// with `Scene::compressed` tiles in these formats are drawn from the compressed tiles.
+const COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC;
+
+/// The color of every texel of a tile of the compressed test tileset, which is exactly
+/// representable in the 5:6:5 bit colors of BC1 blocks.
+fn compressed_texel(tile: u32) -> [u8; 4] {
+    let [r, g, b] = [tile * 7 + 3, 60 - tile * 13, 31 - tile * 9];
+    let expand = |x: u32, bits: u32| ((x << (8 - bits)) | (x >> (2 * bits - 8))) as u8;
+    [expand(r, 5), expand(g, 6), expand(b, 5), 255]
+}
+
+/// The BC1 blocks of the compressed test tileset, a solid-colored 4x4 block per tile.
+fn bc1_blocks() -> Vec<u8> {
+    let mut blocks = Vec::new();
+    for tile in 0..NUM_TILES {
+        let [r, g, b, _] = compressed_texel(tile).map(u16::from);
+        let color = (r >> 3) << 11 | (g >> 2) << 5 | b >> 3;
+        // Both endpoints are the color, and every texel uses the first.
+        blocks.extend(color.to_le_bytes());
+        blocks.extend(color.to_le_bytes());
+        blocks.extend([0; 4]);
+    }
+    blocks
+}
+
+/// The test tileset with `bc1_blocks` tiles in `format`, which has 8 byte blocks. The blocks are
+/// only meant to be drawn in BC1 formats.
+fn compressed_tileset(format: wgpu::TextureFormat) -> TilesetRef<'static> {
+    let mut tileset = tileset();
+    tileset.compressed = Some(CompressedTiles {
+        format,
+        blocks: bc1_blocks().into(),
+    });
+    tileset
+}
+
+/// The stops of the color ramp that heatmap scenes are drawn with.
+const RAMP: [[u8; 4]; 3] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+
+fn ramp() -> TilesetRef<'static> {
+    TilesetRef::color_ramp(&RAMP.map(Rgba::from))
+}
+
+/// A container file for the test tileset, with a layer per tile and a second mip level, or for
+/// its `bc1_blocks` (for scenes with `Scene::compressed` tiles in a BC1 format).
+#[derive(Copy, Clone)]
+enum Container {
+    /// Premultiplied RGBA in a KTX2 file.
+    Ktx2,
+    /// BGRA in a DDS file with a legacy header.
+    Dds,
+    /// BC1 blocks in a KTX2 file, with the tiles in a 2x2 grid.
+    Ktx2Bc1,
+    /// BC1 blocks in a DDS file with a DXT1 header, with the tiles stacked vertically.
+    DdsDxt1,
+}
+
+/// The pixels of each tile of the test tileset at `level`, where the second level is a solid
+/// color per tile.
+fn tile_pixels(tile: u32, level: u32) -> Vec<u8> {
+    let size = SIZE_OF_TILE >> level;
+    let mut pixels = Vec::new();
+    for y in 0..size.y {
+        for x in 0..size.x {
+            pixels.extend(match level {
+                0 => texel(tile, x, y),
+                _ => [tile as u8 * 40, 0, 255, 255],
+            });
+        }
+    }
+    pixels
+}
+
+fn container_tileset(container: Container) -> MippedTileset {
+    let words = |words: &[u32]| words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    let bytes: Vec<u8> = match container {
+        Container::Ktx2 => {
+            // 37 is VK_FORMAT_R8G8B8A8_UNORM, and the descriptor's flags mark it premultiplied.
+            let mut bytes = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+            bytes.extend::<Vec<u8>>(words(&[37, 1, 4, 4, 0, NUM_TILES, 1, 2, 0, 128, 16]));
+            bytes.resize(80, 0);
+            let level_len = |level: u32| ((NUM_TILES * 64) >> (2 * level)) as u64;
+            for (offset, level) in [(176, 0), (176 + level_len(0), 1)] {
+                for word in [offset, level_len(level), level_len(level)] {
+                    bytes.extend(word.to_le_bytes());
+                }
+            }
+            bytes.resize(128, 0);
+            bytes.extend::<Vec<u8>>(words(&[16, 0, 0, 0x0100_0000]));
+            bytes.resize(176, 0);
+            for level in 0..2 {
+                for tile in 0..NUM_TILES {
+                    bytes.extend(tile_pixels(tile, level));
+                }
+            }
+            bytes
+        }
+        Container::Dds => {
+            // DDSD_MIPMAPCOUNT and a 32-bit DDPF_RGB pixel format with blue in the low byte.
+            let mut bytes = b"DDS ".to_vec();
+            let height = SIZE_OF_TILE.y * NUM_TILES;
+            bytes.extend::<Vec<u8>>(words(&[124, 0x2_1007, height, SIZE_OF_TILE.x, 0, 0, 2]));
+            bytes.resize(76, 0);
+            bytes.extend::<Vec<u8>>(words(&[32, 0x41, 0, 32, 0xff_0000, 0xff00, 0xff]));
+            bytes.extend::<Vec<u8>>(words(&[0xff00_0000, 0x1000]));
+            bytes.resize(128, 0);
+            for level in 0..2 {
+                for tile in 0..NUM_TILES {
+                    for pixel in tile_pixels(tile, level).chunks(4) {
+                        bytes.extend([pixel[2], pixel[1], pixel[0], pixel[3]]);
+                    }
+                }
+            }
+            bytes
+        }
+        Container::Ktx2Bc1 => {
+            // 133 is VK_FORMAT_BC1_RGBA_UNORM_BLOCK, and each block is a tile, row by row.
+            let dimensions = SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS);
+            let mut bytes = b"\xabKTX 20\xbb\r\n\x1a\n".to_vec();
+            bytes.extend::<Vec<u8>>(words(&[133, 1, dimensions.x, dimensions.y, 0, 0, 1, 1, 0]));
+            bytes.extend::<Vec<u8>>(words(&[104, 16]));
+            bytes.resize(80, 0);
+            for word in [120, NUM_TILES as u64 * 8, NUM_TILES as u64 * 8] {
+                bytes.extend(word.to_le_bytes());
+            }
+            bytes.resize(120, 0);
+            bytes.extend(bc1_blocks());
+            bytes
+        }
+        Container::DdsDxt1 => {
+            let mut bytes = b"DDS ".to_vec();
+            let height = SIZE_OF_TILE.y * NUM_TILES;
+            bytes.extend::<Vec<u8>>(words(&[124, 0x1007, height, SIZE_OF_TILE.x]));
+            bytes.resize(76, 0);
+            bytes.extend::<Vec<u8>>(words(&[32, 0x4]));
+            bytes.extend(b"DXT1");
+            bytes.resize(108, 0);
+            bytes.extend::<Vec<u8>>(words(&[0x1000]));
+            bytes.resize(128, 0);
+            bytes.extend(bc1_blocks());
+            bytes
+        }
+    };
+    match container {
+        Container::Ktx2 | Container::Ktx2Bc1 => parse_ktx2(&bytes, SIZE_OF_TILE).unwrap(),
+        Container::Dds | Container::DdsDxt1 => parse_dds(&bytes, SIZE_OF_TILE).unwrap(),
+    }
+}
+
+/// Encode a linear color channel as sRGB.
+fn to_srgb(linear: f64) -> u8 {
+    let srgb = if linear <= 0.0031308 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (srgb * 255.0).round() as u8
+}
+
+/// The zeroth order modified Bessel function of the first kind, from its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..12 {
+        term *= (x / (2.0 * k as f64)).powi(2);
+        sum += term;
+    }
+    sum
+}
+
+/// Decode an sRGB color channel to linear.
+fn from_srgb(srgb: u8) -> f64 {
+    let srgb = srgb as f64 / 255.0;
+    if srgb <= 0.04045 {
+        srgb / 12.92
+    } else {
+        ((srgb + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+struct Scene {
+    name: &'static str,
+    target_size: Vec2<u32>,
+    tilemap: AnyTilemapRef<'static>,
+    source_rect: Option<Rect<u32, u32>>,
+    tile_index_offset: u32,
+    empty_tile: Option<u32>,
+    allocation: AllocationStrategy,
+    /// Swap the red and blue channels of the scene with a fragment hook, if this is set.
+    swap_red_blue: bool,
+    flip_flags: bool,
+    /// The tint of each tile, with every channel either 0 or 255 so the expected colors don't
+    /// depend on how the tint is converted.
+    tint: Option<Vec<[u8; 4]>>,
+    /// Draw the tilemap over the whole virtual screen of these params, instead of the whole target.
+    present: Option<PresentParams>,
+    /// Draw the tilemap in world coordinates of pixels, with `TilemapPipeline::set_camera_pixels`.
+    pixel_camera: bool,
+    /// Swap the red and blue channels of the scene with a color LUT, if this is set.
+    color_lut: bool,
+    /// Rebuild the pipeline from its retained uploads before drawing, as after device loss.
+    rebuild: bool,
+    /// Upload the tilesets with mip levels streamed in a level at a time, before drawing.
+    stream_mips: bool,
+    /// Load the test tileset from a container file with mip levels, streaming them in before
+    /// drawing.
+    container: Option<Container>,
+    /// Move the tilemap by this much (in world units, which are pixels with `pixel_camera`) and
+    /// snap it back to whole pixels with `TilemapDrawData::pixel_snap`.
+    pixel_snap: Option<Vec2<f32>>,
+    /// Draw to an `Rgba8Unorm` target with `TilemapPipeline::set_encode_srgb`, instead of to an
+    /// sRGB target.
+    encode_srgb: bool,
+    /// Draw with `TargetOrigin::BottomLeft`, so that the rows of the target are flipped.
+    flip_y: bool,
+    /// Give `REFLECTIVE_TILE` a reflective material, which the fragment hook draws with its red
+    /// and blue channels swapped.
+    materials: bool,
+    /// Bake the scene with `TilemapPipeline::bake`, and draw only the baked layer.
+    bake: bool,
+    /// Split the tilemap into chunks of index textures of at most this size.
+    max_texture_size: Option<u32>,
+    /// The target is half the size of the tilemap's texels, so the tiles are drawn from the second
+    /// mip level of the tileset (which needs a `container`).
+    minified: bool,
+    /// How the pixels of tiles are filtered, where `TileFilter::Linear` is only expected to work
+    /// for scenes with a single layer.
+    filter: TileFilter,
+    /// The `FrameContext::index` of the frame, where the fragment hook swaps the red and blue
+    /// channels of frame 1.
+    frame_index: u32,
+    /// Upload the test tileset with `compressed_tileset` tiles in this format, which are expected
+    /// to be drawn for formats of `COMPRESSION_FEATURES` and to fall back to the tileset's pixels
+    /// otherwise.
+    compressed: Option<wgpu::TextureFormat>,
+    /// Cut the tilemap along these planes, in the world coordinates of the default camera (where
+    /// the target covers [0, 1]x[0, 1], with y pointing up).
+    clip_planes: Vec<ClipPlane>,
+    /// Give the test tileset a team mask covering the left half of each tile, and draw it with a
+    /// magenta team color, which removes the green of that half.
+    team_color: bool,
+    /// Upload a black copy of the tilemap after it, and draw only the tilemap with
+    /// `TilemapPipeline::render_visible`, as if the copy were culled by the application.
+    external_culling: bool,
+    /// Give the test tileset a normal map where each quarter of a tile faces away from its center,
+    /// and swap the red and blue channels of pixels facing up and to the left in the fragment hook,
+    /// which is the top left quarter of each tile however it is flipped.
+    normal_map: bool,
+    /// Give the test tileset an emissive texture where the right half of each tile glows red,
+    /// except for the last tile, which saturates the red channel of that half.
+    emissive: bool,
+    /// Give every tile but `DARK_TILE` a fully emissive material, so that only its light is
+    /// hidden.
+    emissive_materials: bool,
+    /// Give `SWAY_TILE` a material that sways by `SWAY` texels.
+    sway: bool,
+    /// Write the emissive light to a second target with `TilemapPipeline::set_emissive_target`,
+    /// and compare that target instead of the color of the tiles.
+    emissive_target: bool,
+    /// Upload a `TilesetRef::placeholder` of this size in place of the test tileset, and replace it
+    /// with the test tileset with `TilemapPipeline::replace_tileset` after uploading the tilemap.
+    placeholder: Option<Vec2<u32>>,
+    /// Render into multisampled attachments with this sample count (set with
+    /// `TilemapPipeline::set_multisample`), resolved into the target.
+    sample_count: u32,
+    /// Bake the tilemap with this `DownscaleFilter`, at a factor of `SIZE_OF_TILE.x`. The target
+    /// has to be the size of the tilemap, so that each pixel covers a tile.
+    downscale: DownscaleFilter,
+    /// Draw the tilemap with `TilemapBlendMode::Opaque` into a target with a depth buffer, whose
+    /// depth stencil state doesn't enable depth writes, and a black copy of it at the same depth
+    /// after it, which fails the depth test against the depth the tilemap wrote.
+    opaque: bool,
+    /// Draw the tilemap at a `TilemapDrawData::depth` of 0.25 into a target with a depth buffer
+    /// that it writes, and a black copy of it after it at a depth of 0.5, which fails the
+    /// `LessEqual` depth test against the tilemap.
+    depth: bool,
+    /// Draw the tilemap with `TilemapStencil::Write` into a target with a stencil buffer, and a
+    /// black copy of it without its clip planes after it with `TilemapStencil::Outside`, which
+    /// covers every pixel where the tilemap wasn't drawn.
+    stencil: bool,
+    /// Draw the tilemap with `custom_pipeline` instead of `TilemapPipeline::render`. The target has
+    /// to have a pixel for each texel of the tiles.
+    custom_pass: bool,
+    /// Draw the tilemap with this `TileStagger`, which makes it half a tile wider.
+    stagger: TileStagger,
+    /// Upload other tilesets and the draws in reverse order before uploading the scene, after
+    /// `TilemapPipeline::reserve_upload_capacity`, so that the uploads of the scene reuse what the
+    /// first ones left behind.
+    reupload: bool,
+    /// Give `WALL_TILE` a height of 1, and draw the tilemap with these `TilemapWalls`.
+    walls: Option<TilemapWalls>,
+    /// Create the pipeline and upload the tilemaps on other threads than the one drawing them, as
+    /// render architectures with a thread for game logic do.
+    other_threads: bool,
+    /// What the tiles past the end of the tileset (see `with_invalid_tiles`) are drawn as.
+    invalid_tiles: InvalidTiles,
+}
+
+impl Scene {
+    fn new(name: &'static str, target_size: Vec2<u32>, tile_size: Vec2<u32>) -> Self {
+        Scene {
+            name,
+            target_size,
+            tilemap: pattern::<u8>(tile_size).into(),
+            source_rect: None,
+            tile_index_offset: 0,
+            empty_tile: None,
+            allocation: AllocationStrategy::ExactSize,
+            swap_red_blue: false,
+            flip_flags: false,
+            tint: None,
+            present: None,
+            pixel_camera: false,
+            color_lut: false,
+            rebuild: false,
+            stream_mips: false,
+            container: None,
+            pixel_snap: None,
+            encode_srgb: false,
+            flip_y: false,
+            materials: false,
+            bake: false,
+            max_texture_size: None,
+            minified: false,
+            filter: TileFilter::default(),
+            frame_index: 0,
+            compressed: None,
+            clip_planes: Vec::new(),
+            team_color: false,
+            external_culling: false,
+            normal_map: false,
+            emissive: false,
+            emissive_materials: false,
+            sway: false,
+            emissive_target: false,
+            placeholder: None,
+            sample_count: 1,
+            downscale: DownscaleFilter::Direct,
+            opaque: false,
+            depth: false,
+            stencil: false,
+            custom_pass: false,
+            stagger: TileStagger::None,
+            walls: None,
+            reupload: false,
+            other_threads: false,
+            invalid_tiles: InvalidTiles::Unchecked,
+        }
+    }
+
+    /// Store the tiles of this scene as `T`.
+    fn with_tile_id<T: TileId + From<u8>>(self) -> Self {
+        Scene {
+            tilemap: pattern::<T>(self.tilemap.tile_size()).into(),
+            ..self
+        }
+    }
+
+    /// Store the tiles of this scene as `T`, each flipped differently depending on its position.
+    fn with_flips<T: TileId + From<u8>>(self) -> Self {
+        let mut tilemap = pattern::<T>(self.tilemap.tile_size());
+        for y in 0..tilemap.tile_size.y {
+            for x in 0..tilemap.tile_size.x {
+                let flip = TileFlip {
+                    horizontal: x % 2 == 1,
+                    vertical: y % 2 == 1,
+                    diagonal: (x + y) % 3 == 0,
+                };
+                tilemap.put_tile(x, y, flip.encode(tilemap.get_tile(x, y)));
+            }
+        }
+        Scene {
+            tilemap: tilemap.into(),
+            flip_flags: true,
+            ..self
+        }
+    }
+
+    /// Replace the tiles of this scene with a heatmap whose values are below, between, and above
+    /// the stops of `RAMP`.
+    fn with_heatmap(self) -> Self {
+        const VALUES: [f32; 6] = [-0.5, 0.0, 0.25, 0.5, 1.0, 1.5];
+        let size = self.tilemap.tile_size();
+        let mut heatmap = HeatmapRef::new(size, Vec2::new(0.0, 1.0));
+        for y in 0..size.y {
+            for x in 0..size.x {
+                heatmap.put_value(x, y, VALUES[((x + 2 * y) % 6) as usize]);
+            }
+        }
+        Scene {
+            tilemap: heatmap.into(),
+            ..self
+        }
+    }
+
+    /// Store the tiles of this scene as `TileVariant`s, using every tile of the tileset.
+    fn with_variants(self) -> Self {
+        let size = self.tilemap.tile_size();
+        let mut tilemap = Tilemap::new_zeroed(size);
+        for y in 0..size.y {
+            for x in 0..size.x {
+                tilemap.put_tile(
+                    x,
+                    y,
+                    TileVariant::new((x % 2) as u8, ((x / 2 + y) % 2) as u8),
+                );
+            }
+        }
+        Scene {
+            tilemap: tilemap.into(),
+            ..self
+        }
+    }
+
+    /// Stack a layer above the tiles of this scene that covers the left half of every tile.
+    fn with_stack(self) -> Self {
+        let AnyTilemapRef::U8(tilemap) = self.tilemap else {
+            panic!("only u8 scenes can be stacked");
+        };
+        let cover = Tilemap::new_filled(tilemap.tile_size, (NUM_TILES - 1) as u8);
+        Scene {
+            tilemap: TilemapStack::from_layers([tilemap, cover.into()]).into(),
+            ..self
+        }
+    }
+
+    /// Replace every third tile with one past the end of the tileset, and draw those with `mode`.
+    fn with_invalid_tiles(self, mode: InvalidTiles) -> Self {
+        let AnyTilemapRef::U8(mut tilemap) = self.tilemap else {
+            panic!("only u8 scenes can have invalid tiles");
+        };
+        for y in 0..tilemap.tile_size.y {
+            for x in 0..tilemap.tile_size.x {
+                if (x + y) % 3 == 0 {
+                    tilemap.data.to_mut()[(y * tilemap.tile_size.x + x) as usize] =
+                        (NUM_TILES + x) as u8;
+                }
+            }
+        }
+        Scene {
+            tilemap: tilemap.into(),
+            invalid_tiles: mode,
+            ..self
+        }
+    }
+
+    /// Tint each tile with one of a few colors, depending on its position.
+    fn with_tint(self) -> Self {
+        const TINTS: [[u8; 4]; 4] = [
+            [255, 255, 255, 255],
+            [255, 0, 255, 255],
+            [0, 255, 255, 255],
+            [255, 255, 0, 255],
+        ];
+        let size = self.tilemap.tile_size();
+        let tint = (0..size.product())
+            .map(|i| TINTS[(i % size.x + i / size.x) as usize % TINTS.len()])
+            .collect();
+        Scene {
+            tint: Some(tint),
+            ..self
+        }
+    }
+
+    /// The expected color of a pixel, or `None` if its center is too close to the edge of a texel
+    /// for the result to be well defined.
+    fn expected(&self, pixel: Vec2<u32>) -> Option<[u8; 4]> {
+        let rect = self.source_rect.unwrap_or(Rect::new(
+            0,
+            0,
+            self.tilemap.tile_size().x,
+            self.tilemap.tile_size().y,
+        ));
+        let (origin, size) = match self.present {
+            Some(params) => {
+                let viewport = params.present(self.target_size).viewport;
+                (viewport.position(), Vec2::from(viewport.extent()))
+            }
+            None => (Vec2::zero(), self.target_size.as_::<f32>()),
+        };
+        let uv = (pixel.as_::<f64>() + 0.5 - origin.as_::<f64>()) / size.as_::<f64>();
+        if uv.map(|x| !(0.0..1.0).contains(&x)).reduce_or() {
+            return Some([0; 4]);
+        }
+        let world = Vec2::new(uv.x, 1.0 - uv.y);
+        for plane in &self.clip_planes {
+            let side = plane.normal.as_::<f64>().dot(world) - plane.distance as f64;
+            // Pixels whose centers are within about a pixel of the plane could go either way.
+            if side.abs() * size.reduce_partial_max() as f64 / plane.normal.magnitude() as f64
+                <= 1.0
+            {
+                return None;
+            }
+            if side < 0.0 {
+                return Some([0; 4]);
+            }
+        }
+        let grid = self
+            .stagger
+            .grid_size(Vec2::new(rect.w, rect.h))
+            .as_::<f64>();
+        let mut tilepos = Vec2::new(rect.x, rect.y).as_::<f64>() + uv * grid;
+        if self.stagger.is_offset(tilepos.y as u32) {
+            tilepos.x -= 0.5;
+        }
+        if tilepos.x < rect.x as f64 || tilepos.x >= (rect.x + rect.w) as f64 {
+            return Some([0; 4]);
+        }
+        if let Some(walls) = self.walls {
+            tilepos = self.wall_position(walls, tilepos, world)?;
+        }
+        let cell = tilepos.map(|x| x as u32);
+        if self.downscale != DownscaleFilter::Direct {
+            return Some(self.downscaled_color(cell));
+        }
+        if self.minified {
+            let tile = self.tile(0, cell) & 0x1f;
+            return Some(tile_pixels(tile, 1)[..4].try_into().unwrap());
+        }
+        if self.filter == TileFilter::Linear {
+            return Some(self.bilinear_color(cell, tilepos));
+        }
+        let texelpos = tilepos * SIZE_OF_TILE.as_::<f64>();
+        let distance = texelpos.map(|x| (x - x.round()).abs());
+        if distance.reduce_partial_min() < 0.05 {
+            return None;
+        }
+        // The top layer that isn't transparent at this pixel, which hides the light of the layers
+        // below it.
+        let mut color = [0; 4];
+        let mut glows = false;
+        for layer in (0..self.tilemap.layers()).rev() {
+            let local = self.swayed(layer, cell, texelpos.map(|x| x as u32) % SIZE_OF_TILE)?;
+            color = self.layer_color(layer, cell, local);
+            if color[3] != 0 {
+                if self.emissive {
+                    let (tile, local) = self.flipped_texel(layer, cell, local);
+                    glows = tile != NUM_TILES - 1
+                        && local.x >= SIZE_OF_TILE.x / 2
+                        && !(self.emissive_materials && tile == DARK_TILE);
+                }
+                break;
+            }
+        }
+        if self.emissive_target {
+            return Some([if glows { 255 } else { 0 }, 0, 0, color[3]]);
+        }
+        if glows {
+            color[0] = 255;
+        }
+        if let Some(tint) = &self.tint {
+            let tint = tint[(cell.y * self.tilemap.tile_size().x + cell.x) as usize];
+            for (channel, tint) in color.iter_mut().zip(tint) {
+                *channel = if tint == 0 { 0 } else { *channel };
+            }
+        }
+        let reflective =
+            self.materials && self.tile(self.tilemap.layers() - 1, cell) == REFLECTIVE_TILE;
+        let local = texelpos.map(|x| x as u32) % SIZE_OF_TILE;
+        let facing =
+            self.normal_map && local.x < SIZE_OF_TILE.x / 2 && local.y < SIZE_OF_TILE.y / 2;
+        let [r, g, b, a] = color;
+        let frame = self.frame_index == 1;
+        Some(
+            if self.swap_red_blue ^ self.color_lut ^ reflective ^ frame ^ facing {
+                [b, g, r, a]
+            } else {
+                [r, g, b, a]
+            },
+        )
+    }
+
+    /// The position of the tilemap seen at `tilepos` (at `world` in the world) with `walls`, like
+    /// walls.wgsl, or `None` if it is too close to the edge of a tile for the result to be well
+    /// defined. The camera is centered on the tilemap, which covers [0, 1]x[0, 1] of the world.
+    fn wall_position(
+        &self,
+        walls: TilemapWalls,
+        tilepos: Vec2<f64>,
+        world: Vec2<f64>,
+    ) -> Option<Vec2<f64>> {
+        let size = self.tilemap.tile_size().as_::<f64>();
+        let offset = (world - 0.5) * walls.parallax as f64 * Vec2::new(size.x, -size.y);
+        for i in (1..=walls.samples as u32).rev() {
+            let height = walls.max_height as f64 * i as f64 / walls.samples as f64;
+            let position = tilepos - offset * height;
+            if position
+                .map2(size, |x, size| !(0.0..size).contains(&x))
+                .reduce_or()
+            {
+                continue;
+            }
+            let texelpos = position * SIZE_OF_TILE.as_::<f64>();
+            if texelpos.map(|x| (x - x.round()).abs()).reduce_partial_min() < 0.05 {
+                return None;
+            }
+            if self.tile(0, position.map(|x| x as u32)) == WALL_TILE {
+                return Some(position);
+            }
+        }
+        Some(tilepos)
+    }
+
+    /// The color of the first layer at `tilepos` (in the tile at `cell`), blended between the
+    /// nearest texels of its tile on linear colors, like bilinear filtering on the GPU.
+    fn bilinear_color(&self, cell: Vec2<u32>, tilepos: Vec2<f64>) -> [u8; 4] {
+        let texelpos = (tilepos - cell.as_::<f64>()) * SIZE_OF_TILE.as_::<f64>() - 0.5;
+        let max = SIZE_OF_TILE.as_::<f64>() - 1.0;
+        let low = texelpos.map(f64::floor);
+        let t = texelpos - low;
+        let mut color = [0.0; 4];
+        for (offset, weight) in [
+            (Vec2::new(0.0, 0.0), (1.0 - t.x) * (1.0 - t.y)),
+            (Vec2::new(1.0, 0.0), t.x * (1.0 - t.y)),
+            (Vec2::new(0.0, 1.0), (1.0 - t.x) * t.y),
+            (Vec2::new(1.0, 1.0), t.x * t.y),
+        ] {
+            let local = (low + offset).map2(max, |x, max| x.clamp(0.0, max) as u32);
+            let texel = self.layer_color(0, cell, local);
+            for c in 0..3 {
+                color[c] += from_srgb(texel[c]) * weight;
+            }
+            color[3] += texel[3] as f64 / 255.0 * weight;
+        }
+        let [r, g, b, a] = color;
+        [
+            to_srgb(r),
+            to_srgb(g),
+            to_srgb(b),
+            (a * 255.0).round() as u8,
+        ]
+    }
+
+    /// The color of the pixel covering the tile at `cell`, filtered from the texels of the first
+    /// layer on premultiplied linear colors, like downscale.wgsl. Texels past the edges of the
+    /// tilemap repeat the texels at the edges.
+    fn downscaled_color(&self, cell: Vec2<u32>) -> [u8; 4] {
+        let factor = SIZE_OF_TILE.x as i32;
+        let max = (self.tilemap.tile_size() * SIZE_OF_TILE).as_::<i32>() - 1;
+        let (reach, weight): (i32, fn(f64) -> f64) = match self.downscale {
+            DownscaleFilter::Box(_) => (0, |_| 1.0),
+            _ => (2 * factor, |x| {
+                let t = x / 2.0;
+                if t.abs() >= 1.0 {
+                    return 0.0;
+                }
+                let sinc = match x.abs() > 1e-4 {
+                    true => (std::f64::consts::PI * x).sin() / (std::f64::consts::PI * x),
+                    false => 1.0,
+                };
+                sinc * bessel_i0(4.0 * (1.0 - t * t).sqrt()) / bessel_i0(4.0)
+            }),
+        };
+        // The box covers the texels of the cell, and the Kaiser filter reaches around its center.
+        let (first, last) = match reach {
+            0 => (
+                cell.as_::<i32>() * factor,
+                cell.as_::<i32>() * factor + factor - 1,
+            ),
+            _ => {
+                let center = cell.as_::<i32>() * factor + factor / 2;
+                (center - reach, center + reach)
+            }
+        };
+        let center = (cell.as_::<f64>() + 0.5) * factor as f64;
+        let mut sum = [0.0; 4];
+        let mut total = 0.0;
+        for y in first.y..=last.y {
+            for x in first.x..=last.x {
+                let offset = (Vec2::new(x, y).as_::<f64>() + 0.5 - center) / factor as f64;
+                let w = weight(offset.x) * weight(offset.y);
+                let texel = Vec2::new(x, y).map2(max, |x, max| x.clamp(0, max) as u32);
+                let color = self.layer_color(0, texel / SIZE_OF_TILE, texel % SIZE_OF_TILE);
+                for c in 0..3 {
+                    sum[c] += from_srgb(color[c]) * w;
+                }
+                sum[3] += color[3] as f64 / 255.0 * w;
+                total += w;
+            }
+        }
+        let [r, g, b, a] = sum.map(|x| (x / total).clamp(0.0, 1.0));
+        [
+            to_srgb(r.min(a)),
+            to_srgb(g.min(a)),
+            to_srgb(b.min(a)),
+            (a * 255.0).round() as u8,
+        ]
+    }
+
+    /// The tile at `cell` of `layer`, as stored in the tilemap.
+    fn tile(&self, layer: u32, cell: Vec2<u32>) -> u32 {
+        let size = self.tilemap.tile_size();
+        let i = ((layer * size.y + cell.y) * size.x + cell.x) as usize;
+        match &self.tilemap {
+            AnyTilemapRef::U8(tilemap) => tilemap.data[i].index(),
+            AnyTilemapRef::U16(tilemap) => tilemap.data[i].index(),
+            AnyTilemapRef::U32(tilemap) => tilemap.data[i].index(),
+            AnyTilemapRef::Variant(tilemap) => tilemap.data[i].index(),
+            AnyTilemapRef::Heatmap(_) => unreachable!(),
+        }
+    }
+
+    /// The tile at `cell` of `layer` without its flip flags, and the texel of that tile that is
+    /// drawn at `local` within the cell.
+    /// The texel drawn at `local` within the tile at `cell` of `layer`, moved by the sway of scenes
+    /// with `sway` as at the start of time, or `None` if it could round either way.
+    fn swayed(&self, layer: u32, cell: Vec2<u32>, local: Vec2<u32>) -> Option<Vec2<u32>> {
+        if !self.sway || self.flipped_texel(layer, cell, local).0 != SWAY_TILE {
+            return Some(local);
+        }
+        let y = (local.y as f64 + 0.5) / SIZE_OF_TILE.y as f64;
+        let phase = cell.x as f64 * 0.7 + cell.y as f64 * 1.3;
+        let offset = SWAY as f64 * (1.0 - y) * phase.sin();
+        if (offset.abs().fract() - 0.5).abs() < 0.05 {
+            return None;
+        }
+        let x = (local.x as f64 + offset.round()).clamp(0.0, SIZE_OF_TILE.x as f64 - 1.0);
+        Some(Vec2::new(x as u32, local.y))
+    }
+
+    fn flipped_texel(&self, layer: u32, cell: Vec2<u32>, mut local: Vec2<u32>) -> (u32, Vec2<u32>) {
+        let mut tile = self.tile(layer, cell);
+        if self.flip_flags {
+            let shift = self.tilemap.format().bytes_per_tile() as u32 * 8 - 3;
+            let flags = tile >> shift;
+            tile &= (1 << shift) - 1;
+            if flags & 4 != 0 {
+                local.x = SIZE_OF_TILE.x - 1 - local.x;
+            }
+            if flags & 2 != 0 {
+                local.y = SIZE_OF_TILE.y - 1 - local.y;
+            }
+            if flags & 1 != 0 {
+                local = local.yx();
+            }
+        }
+        (tile, local)
+    }
+
+    /// The color of the texel at `local` within the tile at `cell` of `layer`.
+    fn layer_color(&self, layer: u32, cell: Vec2<u32>, local: Vec2<u32>) -> [u8; 4] {
+        if let AnyTilemapRef::Heatmap(heatmap) = &self.tilemap {
+            // Blend the nearest stops on linear colors, like the blending of the GPU.
+            let position = heatmap.normalize(heatmap.get_value(cell.x, cell.y)) as f64
+                * (RAMP.len() - 1) as f64;
+            let low = position.floor() as usize;
+            let high = (low + 1).min(RAMP.len() - 1);
+            let t = position - low as f64;
+            return [0, 1, 2, 3].map(|c| {
+                let decode = |x: u8| x as f64 / 255.0;
+                to_srgb(decode(RAMP[low][c]) * (1.0 - t) + decode(RAMP[high][c]) * t)
+            });
+        }
+        let (mut tile, flipped) = self.flipped_texel(layer, cell, local);
+        if Some(tile) == self.empty_tile {
+            return [0; 4];
+        }
+        if self.tilemap.format() == TileFormat::Rg8 {
+            tile = (tile & 0xff) + (tile >> 8) * TILESET_COLUMNS;
+        }
+        if self.invalid_tiles == InvalidTiles::Highlight
+            && tile + self.tile_index_offset >= NUM_TILES
+        {
+            // The error tile has a square for each texel of the test tileset.
+            return match (local.x + local.y) % 2 == 1 {
+                true => [0, 0, 0, 255],
+                false => [255, 0, 255, 255],
+            };
+        }
+        tile = match self.invalid_tiles {
+            InvalidTiles::Clamp if tile + self.tile_index_offset >= NUM_TILES => {
+                NUM_TILES - 1 - self.tile_index_offset
+            }
+            InvalidTiles::Substitute(substitute) if tile + self.tile_index_offset >= NUM_TILES => {
+                substitute - self.tile_index_offset
+            }
+            _ => tile,
+        };
+        if self
+            .compressed
+            .is_some_and(|format| COMPRESSION_FEATURES.contains(format.required_features()))
+        {
+            return compressed_texel(tile + self.tile_index_offset);
+        }
+        let [r, g, b, a] = texel(tile + self.tile_index_offset, flipped.x, flipped.y);
+        match self.team_color && flipped.x < SIZE_OF_TILE.x / 2 {
+            true => [r, 0, b, a],
+            false => [r, g, b, a],
+        }
+    }
+}
+
+/// A tilemap that uses every tile but the last, in a different order on each row.
+fn pattern<T: TileId + From<u8>>(tile_size: Vec2<u32>) -> Tilemap<T> {
+    let mut tilemap = Tilemap::new_zeroed(tile_size);
+    for y in 0..tile_size.y {
+        for x in 0..tile_size.x {
+            tilemap.put_tile(x, y, T::from(((x + 2 * y) % (NUM_TILES - 1)) as u8));
+        }
+    }
+    tilemap
+}
+
+fn scenes() -> Vec<Scene> {
+    vec![
+        Scene::new("aligned", Vec2::new(64, 48), Vec2::new(4, 3)),
+        // Rows of 50 pixels need padding when they are read back.
+        Scene::new("odd_sizes", Vec2::new(50, 30), Vec2::new(5, 3)),
+        Scene {
+            source_rect: Some(Rect::new(1, 1, 3, 2)),
+            tile_index_offset: 1,
+            ..Scene::new("source_rect", Vec2::new(48, 32), Vec2::new(6, 4))
+        },
+        // Textures larger than the tilemap and tileset.
+        Scene {
+            source_rect: Some(Rect::new(2, 0, 5, 3)),
+            allocation: AllocationStrategy::Buckets(Vec2::new(4, 4)),
+            ..Scene::new("buckets", Vec2::new(50, 30), Vec2::new(7, 3))
+        },
+        Scene {
+            allocation: AllocationStrategy::Pool(Vec2::new(8, 8)),
+            ..Scene::new("pool", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene::new("r16", Vec2::new(50, 30), Vec2::new(5, 3)).with_tile_id::<u16>(),
+        Scene::new("r32", Vec2::new(50, 30), Vec2::new(5, 3)).with_tile_id::<u32>(),
+        Scene::new("flips_u8", Vec2::new(64, 48), Vec2::new(4, 3)).with_flips::<u8>(),
+        Scene::new("flips_u32", Vec2::new(64, 48), Vec2::new(4, 3)).with_flips::<u32>(),
+        Scene::new("variants", Vec2::new(64, 48), Vec2::new(4, 3)).with_variants(),
+        Scene::new("stack", Vec2::new(64, 48), Vec2::new(4, 3)).with_stack(),
+        Scene {
+            empty_tile: Some(1),
+            ..Scene::new("empty_tile", Vec2::new(64, 48), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        Scene {
+            empty_tile: Some(2),
+            ..Scene::new("stack_empty_tile", Vec2::new(64, 48), Vec2::new(4, 3)).with_stack()
+        },
+        Scene::new("tint", Vec2::new(50, 30), Vec2::new(5, 3)).with_tint(),
+        Scene::new("heatmap", Vec2::new(60, 30), Vec2::new(6, 3)).with_heatmap(),
+        Scene::new("heatmap_tint", Vec2::new(60, 30), Vec2::new(6, 3))
+            .with_heatmap()
+            .with_tint(),
+        // Integer scaling of 8x6 to 50x30 leaves 5 pixels of bars on the left and right.
+        Scene {
+            present: Some(PresentParams {
+                virtual_resolution: Vec2::new(8, 6),
+                scaling: PresentScaling::Integer,
+            }),
+            ..Scene::new("present", Vec2::new(50, 30), Vec2::new(4, 3))
+        },
+        Scene {
+            pixel_camera: true,
+            ..Scene::new("pixel_camera", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            swap_red_blue: true,
+            ..Scene::new("fragment_hook", Vec2::new(64, 48), Vec2::new(4, 3))
+        },
+        Scene {
+            color_lut: true,
+            ..Scene::new("color_lut", Vec2::new(64, 48), Vec2::new(4, 3)).with_stack()
+        },
+        Scene {
+            pixel_camera: true,
+            color_lut: true,
+            rebuild: true,
+            ..Scene::new("rebuild", Vec2::new(50, 30), Vec2::new(5, 3)).with_tint()
+        },
+        Scene {
+            stream_mips: true,
+            ..Scene::new("stream_mips", Vec2::new(64, 48), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        Scene {
+            container: Some(Container::Ktx2),
+            ..Scene::new("ktx2", Vec2::new(64, 48), Vec2::new(4, 3))
+        },
+        Scene {
+            container: Some(Container::Dds),
+            ..Scene::new("dds", Vec2::new(40, 30), Vec2::new(5, 3)).with_flips::<u16>()
+        },
+        Scene {
+            pixel_camera: true,
+            rebuild: true,
+            pixel_snap: Some(Vec2::new(0.45, -0.35)),
+            ..Scene::new("pixel_snap", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            encode_srgb: true,
+            ..Scene::new("encode_srgb", Vec2::new(40, 30), Vec2::new(4, 3)).with_tint()
+        },
+        Scene {
+            flip_y: true,
+            // The bars above and below the viewport differ by a pixel, so the viewport has to be
+            // flipped as well.
+            present: Some(PresentParams {
+                virtual_resolution: Vec2::new(20, 10),
+                scaling: PresentScaling::Integer,
+            }),
+            ..Scene::new("flip_y", Vec2::new(50, 33), Vec2::new(5, 2)).with_flips::<u8>()
+        },
+        Scene {
+            materials: true,
+            rebuild: true,
+            ..Scene::new("materials", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            bake: true,
+            ..Scene::new("bake", Vec2::new(50, 30), Vec2::new(5, 3))
+                .with_flips::<u16>()
+                .with_tint()
+        },
+        // Chunks of 2x1 tiles, since both layers of the stack share a texture.
+        Scene {
+            max_texture_size: Some(2),
+            ..Scene::new("split_stack", Vec2::new(50, 30), Vec2::new(5, 3))
+                .with_stack()
+                .with_tint()
+        },
+        Scene {
+            source_rect: Some(Rect::new(1, 0, 5, 3)),
+            max_texture_size: Some(3),
+            ..Scene::new("split_source_rect", Vec2::new(50, 30), Vec2::new(7, 4)).with_flips::<u8>()
+        },
+        Scene {
+            container: Some(Container::Ktx2),
+            minified: true,
+            ..Scene::new("minified", Vec2::new(8, 6), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        // The mip levels that came with the tileset are uploaded again.
+        Scene {
+            container: Some(Container::Ktx2),
+            minified: true,
+            rebuild: true,
+            ..Scene::new("minified_rebuild", Vec2::new(8, 6), Vec2::new(4, 3))
+        },
+        // Not quite half the size, so mip levels would be blended without `TileFilter::Nearest`.
+        Scene {
+            container: Some(Container::Ktx2),
+            filter: TileFilter::Nearest,
+            ..Scene::new("nearest_minified", Vec2::new(12, 9), Vec2::new(4, 3))
+        },
+        // Each texel is magnified to 4x4 pixels.
+        Scene {
+            filter: TileFilter::Linear,
+            ..Scene::new("linear", Vec2::new(48, 32), Vec2::new(3, 2)).with_flips::<u8>()
+        },
+        Scene {
+            frame_index: 1,
+            ..Scene::new("frame_context", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            compressed: Some(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+            ..Scene::new("compressed", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            compressed: Some(wgpu::TextureFormat::Etc2Rgb8UnormSrgb),
+            ..Scene::new("compressed_fallback", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            container: Some(Container::Ktx2Bc1),
+            compressed: Some(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+            ..Scene::new("ktx2_bc1", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            container: Some(Container::DdsDxt1),
+            compressed: Some(wgpu::TextureFormat::Bc1RgbaUnormSrgb),
+            ..Scene::new("dds_dxt1", Vec2::new(40, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            clip_planes: vec![
+                ClipPlane::through(Vec2::new(0.5, 0.0), Vec2::new(1.0, 1.0)),
+                ClipPlane::through(Vec2::new(0.8, 0.0), Vec2::new(-1.0, 0.0)),
+            ],
+            ..Scene::new("clip_planes", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            team_color: true,
+            ..Scene::new("team_color", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            team_color: true,
+            rebuild: true,
+            ..Scene::new("team_color_rebuild", Vec2::new(50, 30), Vec2::new(5, 3)).with_stack()
+        },
+        Scene {
+            external_culling: true,
+            max_texture_size: Some(2),
+            ..Scene::new("external_culling", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            normal_map: true,
+            ..Scene::new("normal_map", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            normal_map: true,
+            rebuild: true,
+            ..Scene::new("normal_map_rebuild", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            emissive: true,
+            ..Scene::new("emissive", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            emissive: true,
+            rebuild: true,
+            ..Scene::new("emissive_stack", Vec2::new(64, 48), Vec2::new(4, 3)).with_stack()
+        },
+        Scene {
+            sway: true,
+            ..Scene::new("sway", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            emissive: true,
+            emissive_materials: true,
+            rebuild: true,
+            ..Scene::new("emissive_materials", Vec2::new(50, 30), Vec2::new(5, 3))
+                .with_flips::<u8>()
+        },
+        // A placeholder of a single row of tiles, which the test tileset doesn't fit in.
+        Scene {
+            placeholder: Some(SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, 1)),
+            ..Scene::new("placeholder", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            placeholder: Some(
+                SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS),
+            ),
+            rebuild: true,
+            ..Scene::new("placeholder_rebuild", Vec2::new(50, 30), Vec2::new(5, 3))
+        },
+        Scene {
+            emissive: true,
+            emissive_target: true,
+            ..Scene::new("emissive_target", Vec2::new(64, 48), Vec2::new(4, 3)).with_stack()
+        },
+        Scene {
+            opaque: true,
+            ..Scene::new("opaque", Vec2::new(40, 30), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        Scene {
+            stagger: TileStagger::OddRows,
+            ..Scene::new("stagger", Vec2::new(44, 24), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        // Chunks of 1 tile, whose offset rows show half of the tile of the chunk before them.
+        Scene {
+            stagger: TileStagger::EvenRows,
+            max_texture_size: Some(3),
+            ..Scene::new("stagger_chunks", Vec2::new(44, 24), Vec2::new(5, 3))
+        },
+        Scene {
+            reupload: true,
+            max_texture_size: Some(2),
+            ..Scene::new("reupload", Vec2::new(50, 30), Vec2::new(5, 3)).with_tint()
+        },
+        Scene {
+            other_threads: true,
+            ..Scene::new("other_threads", Vec2::new(50, 30), Vec2::new(5, 3)).with_stack()
+        },
+        Scene::new("invalid_clamp", Vec2::new(50, 30), Vec2::new(5, 3))
+            .with_invalid_tiles(InvalidTiles::Clamp),
+        Scene {
+            tile_index_offset: 1,
+            ..Scene::new("invalid_substitute", Vec2::new(50, 30), Vec2::new(5, 3))
+                .with_invalid_tiles(InvalidTiles::Substitute(1))
+        },
+        Scene::new("invalid_highlight", Vec2::new(50, 30), Vec2::new(5, 3))
+            .with_invalid_tiles(InvalidTiles::Highlight),
+        Scene {
+            custom_pass: true,
+            ..Scene::new("custom_pass", Vec2::new(20, 12), Vec2::new(5, 3))
+        },
+        Scene {
+            stencil: true,
+            clip_planes: vec![ClipPlane::through(
+                Vec2::new(0.6, 0.0),
+                Vec2::new(-1.0, 0.5),
+            )],
+            ..Scene::new("stencil", Vec2::new(50, 30), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            depth: true,
+            ..Scene::new("depth", Vec2::new(40, 30), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        Scene {
+            walls: Some(TilemapWalls {
+                parallax: 0.5,
+                max_height: 1.0,
+                samples: 8,
+            }),
+            ..Scene::new("walls", Vec2::new(60, 36), Vec2::new(5, 3))
+        },
+        Scene {
+            sample_count: 4,
+            ..Scene::new("multisample", Vec2::new(40, 30), Vec2::new(4, 3)).with_flips::<u8>()
+        },
+        Scene {
+            bake: true,
+            downscale: DownscaleFilter::Box(SIZE_OF_TILE.x),
+            ..Scene::new("downscale_box", Vec2::new(5, 3), Vec2::new(5, 3)).with_flips::<u8>()
+        },
+        Scene {
+            bake: true,
+            downscale: DownscaleFilter::Kaiser(SIZE_OF_TILE.x),
+            ..Scene::new("downscale_kaiser", Vec2::new(6, 4), Vec2::new(6, 4)).with_flips::<u8>()
+        },
+        Scene {
+            sample_count: 4,
+            emissive: true,
+            emissive_target: true,
+            ..Scene::new("multisample_emissive", Vec2::new(32, 24), Vec2::new(4, 3))
+        },
+    ]
+}
+
+/// The tile given a swaying material in scenes with `sway`, and how far its top sways.
+const SWAY_TILE: u32 = 2;
+const SWAY: f32 = 1.5;
+
+/// The tile whose material doesn't glow in scenes with `emissive_materials`.
+const DARK_TILE: u32 = 0;
+
+/// The tile given a reflective material in scenes with `materials`.
+const REFLECTIVE_TILE: u32 = 1;
+
+/// The tile given a height of 1 in scenes with `walls`.
+const WALL_TILE: u32 = 2;
+
+/// Swaps the red and blue channels when the first word of the user data is 1, of tiles with a
+/// reflective material, of frame 1, and of pixels whose normal faces up and to the left.
+const SWAP_HOOK: &str = "
+fn tilemap_hook(color: vec4<f32>, tilepos: vec2<f32>) -> vec4<f32> {
+    let reflective = tilemap_material(tilepos).reflectivity > 0.5;
+    let normal = tilemap_normal(tilepos);
+    let facing = normal.x < -0.1 && normal.y > 0.1;
+    if (((tilemap.user_data[0].x == 1u) != reflective) != (frame.index == 1u)) != facing {
+        return color.bgra;
+    }
+    return color;
+}
+";
+
+/// Looks up the tile and texel of each pixel of a target with a pixel for each texel of the tiles,
+/// through the bindings declared by `TilemapPipeline::wgsl_bindings`.
+const CUSTOM_PASS: &str = "
+@vertex
+fn custom_vert_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn custom_frag_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<u32>(position.xy);
+    let size_of_tile = vec2(tiledata.tile_width, tiledata.tile_height);
+    let cell = pixel / size_of_tile - tilemap.texture_origin;
+    let tile = textureLoad(tilemap_indices, cell, 0).r;
+    return textureLoad(tilemap_data, pixel % size_of_tile, tile, 0);
+}
+";
+
+/// A pipeline of the test's own that draws `CUSTOM_PASS` with the bind groups of `pipeline`.
+fn custom_pipeline(
+    device: &wgpu::Device,
+    pipeline: &TilemapPipeline,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("test_custom_shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            format!("{}\n{CUSTOM_PASS}", TilemapPipeline::wgsl_bindings()).into(),
+        ),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("test_custom_pipeline_layout"),
+        bind_group_layouts: &[
+            pipeline.camera_bind_group_layout(),
+            pipeline.tileset_bind_group_layout(),
+            pipeline.tilemap_bind_group_layout(),
+        ],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("test_custom_pipeline"),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &module,
+            entry_point: "custom_vert_main",
+            buffers: &[],
+        },
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &module,
+            entry_point: "custom_frag_main",
+            targets: &[Some(format.into())],
+        }),
+        multiview: None,
+    })
+}
+
+fn render(device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) -> Vec<u8> {
+    let format = match scene.encode_srgb {
+        true => wgpu::TextureFormat::Rgba8Unorm,
+        false => FORMAT,
+    };
+    let depth_stencil =
+        (scene.opaque || scene.depth || scene.stencil).then(|| wgpu::DepthStencilState {
+            format: match scene.stencil {
+                true => STENCIL_FORMAT,
+                false => DEPTH_FORMAT,
+            },
+            depth_write_enabled: scene.depth,
+            depth_compare: match scene.depth {
+                true => wgpu::CompareFunction::LessEqual,
+                false => wgpu::CompareFunction::Less,
+            },
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        });
+    let descriptor = TilemapPipelineDescriptor::new(format)
+        .with_depth_stencil(depth_stencil.clone())
+        .with_fragment_hook(SWAP_HOOK)
+        .with_encode_srgb(scene.encode_srgb)
+        .with_target_origin(match scene.flip_y {
+            true => TargetOrigin::BottomLeft,
+            false => TargetOrigin::TopLeft,
+        })
+        .with_emissive_target(scene.emissive_target.then_some(FORMAT))
+        .with_multisample(wgpu::MultisampleState {
+            count: scene.sample_count,
+            ..wgpu::MultisampleState::default()
+        })
+        .with_tile_filter(scene.filter);
+    let mut pipeline = match scene.other_threads {
+        true => std::thread::scope(|scope| {
+            scope
+                .spawn(|| TilemapPipeline::from_descriptor(device, &descriptor))
+                .join()
+                .unwrap()
+        }),
+        false => TilemapPipeline::from_descriptor(device, &descriptor),
+    };
+    pipeline.set_retain_uploads(scene.rebuild);
+    let transform = match scene.present {
+        Some(params) => {
+            pipeline.set_present_params(queue, &params, scene.target_size);
+            Mat4::scaling_3d(params.virtual_resolution.as_::<f32>().with_z(1.0))
+        }
+        None if scene.pixel_camera => {
+            pipeline.set_camera_pixels(queue, scene.target_size, Vec2::zero(), 1.0);
+            Mat4::scaling_3d(scene.target_size.as_::<f32>().with_z(1.0))
+        }
+        None => {
+            pipeline.set_camera(queue, FULLSCREEN_QUAD_CAMERA);
+            Mat4::identity()
+        }
+    };
+    let transform = match scene.pixel_snap {
+        Some(offset) => Mat4::<f32>::translation_2d(offset) * transform,
+        None => transform,
+    };
+    if scene.color_lut {
+        let lut = ColorLut::from_fn(4, |color| Rgb::new(color.b, color.g, color.r));
+        pipeline.set_color_lut(device, queue, Some(&lut));
+    }
+    pipeline.set_allocation_strategy(AllocationKind::Tilemap, scene.allocation);
+    pipeline.set_allocation_strategy(AllocationKind::Tileset, scene.allocation);
+    pipeline.set_max_tilemap_texture_size(scene.max_texture_size);
+    if scene.frame_index != 0 {
+        let frame = FrameContext {
+            time: 0.5,
+            delta_time: 0.5,
+            index: scene.frame_index,
+            viewport_size: None,
+        };
+        pipeline.set_frame_context(queue, &frame);
+    }
+    if let Some(container) = scene.container {
+        let ramp = MippedTileset {
+            tileset: ramp(),
+            mips: Vec::new(),
+        };
+        pipeline
+            .upload_mipped_tilesets_streamed(device, queue, &[container_tileset(container), ramp])
+            .unwrap();
+        pipeline.stream_tileset_mips(queue, u64::MAX);
+    } else if scene.stream_mips {
+        pipeline
+            .upload_tilesets_streamed(device, queue, &[tileset(), ramp()], 8)
+            .unwrap();
+        while !pipeline.stream_tileset_mips(queue, 1) {}
+    } else if let Some(format) = scene.compressed {
+        pipeline
+            .upload_tilesets(device, queue, &[compressed_tileset(format), ramp()])
+            .unwrap();
+    } else if let Some(pixel_size) = scene.placeholder {
+        let placeholder = TilesetRef::placeholder(pixel_size, SIZE_OF_TILE);
+        pipeline
+            .upload_tilesets(device, queue, &[placeholder, ramp()])
+            .unwrap();
+    } else {
+        if scene.reupload {
+            let pixels = tileset().data.len();
+            pipeline.reserve_upload_capacity(64, 3, pixels);
+            pipeline
+                .upload_tilesets(device, queue, &[ramp(), ramp(), tileset()])
+                .unwrap();
+        }
+        pipeline
+            .upload_tilesets(device, queue, &[tileset(), ramp()])
+            .unwrap();
+        assert_eq!(pipeline.tileset_analysis(0), Some(&tileset().analyze()));
+        assert_eq!(pipeline.tileset_analysis(1), Some(&ramp().analyze()));
+        assert_eq!(pipeline.tileset_analysis(2), None);
+    }
+    if scene.team_color {
+        let dimensions = SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS);
+        let mut pixels = Vec::new();
+        for _ in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let marked = x % SIZE_OF_TILE.x < SIZE_OF_TILE.x / 2;
+                pixels.extend([0, 0, 0, if marked { 255 } else { 0 }]);
+            }
+        }
+        let mask = TilesetRef::from_rgba8(&pixels, dimensions, SIZE_OF_TILE, Vec2::zero());
+        pipeline.set_tileset_team_mask(device, queue, 0, Some(&mask));
+    }
+    if scene.normal_map {
+        let dimensions = SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS);
+        let mut pixels = Vec::new();
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                // (±1, ±1, 1) normalized, with y pointing up.
+                let left = x % SIZE_OF_TILE.x < SIZE_OF_TILE.x / 2;
+                let top = y % SIZE_OF_TILE.y < SIZE_OF_TILE.y / 2;
+                let encode = |positive: bool| if positive { 201 } else { 54 };
+                pixels.extend([encode(!left), encode(top), 201, 255]);
+            }
+        }
+        let normal_map = TilesetRef::from_rgba8(&pixels, dimensions, SIZE_OF_TILE, Vec2::zero());
+        pipeline.set_tileset_normal_map(device, queue, 0, Some(&normal_map));
+    }
+    if scene.emissive {
+        let dimensions = SIZE_OF_TILE * Vec2::new(TILESET_COLUMNS, NUM_TILES / TILESET_COLUMNS);
+        let mut pixels = Vec::new();
+        for y in 0..dimensions.y {
+            for x in 0..dimensions.x {
+                let tile = y / SIZE_OF_TILE.y * TILESET_COLUMNS + x / SIZE_OF_TILE.x;
+                let glows = tile != NUM_TILES - 1 && x % SIZE_OF_TILE.x >= SIZE_OF_TILE.x / 2;
+                pixels.extend(if glows { [255, 0, 0, 255] } else { [0; 4] });
+            }
+        }
+        let emissive = TilesetRef::from_rgba8(&pixels, dimensions, SIZE_OF_TILE, Vec2::zero());
+        pipeline.set_tileset_emissive(device, queue, 0, Some(&emissive));
+    }
+    if scene.sway {
+        let mut materials = MaterialTable::default();
+        let sway = TileMaterial {
+            sway: SWAY,
+            ..TileMaterial::default()
+        };
+        materials.set_material(SWAY_TILE, sway);
+        pipeline.set_tileset_materials(device, queue, 0, Some(&materials));
+    }
+    if scene.emissive_materials {
+        let materials = MaterialTable::from_fn(NUM_TILES, |tile| TileMaterial {
+            emissive: if tile == DARK_TILE { 0.0 } else { 1.0 },
+            ..TileMaterial::default()
+        });
+        pipeline.set_tileset_materials(device, queue, 0, Some(&materials));
+    }
+    if scene.materials {
+        let mut materials = MaterialTable::default();
+        let reflective = TileMaterial {
+            reflectivity: 1.0,
+            ..TileMaterial::default()
+        };
+        materials.set_material(REFLECTIVE_TILE, reflective);
+        pipeline.set_tileset_materials(device, queue, 0, Some(&materials));
+    }
+    if scene.walls.is_some() {
+        let mut materials = MaterialTable::default();
+        let wall = TileMaterial {
+            height: 1.0,
+            ..TileMaterial::default()
+        };
+        materials.set_material(WALL_TILE, wall);
+        pipeline.set_tileset_materials(device, queue, 0, Some(&materials));
+    }
+    if scene.invalid_tiles != InvalidTiles::Unchecked {
+        let AnyTilemapRef::U8(tilemap) = &scene.tilemap else {
+            unreachable!()
+        };
+        let invalid: Vec<_> = tilemap
+            .iter_coords()
+            .filter(|&(_, _, tile)| tile as u32 >= NUM_TILES)
+            .map(|(x, y, tile)| InvalidTile {
+                cell: Vec2::new(x, y),
+                layer: 0,
+                tile: tile as u32,
+            })
+            .collect();
+        assert!(!invalid.is_empty());
+        assert_eq!(validate(tilemap, &tileset()), invalid, "{}", scene.name);
+    }
+    let mut draws = vec![{
+        let tileset = matches!(scene.tilemap, AnyTilemapRef::Heatmap(_)) as u32;
+        let mut draw = TilemapDrawData::new(scene.tilemap.clone(), tileset)
+            .with_transform(transform)
+            .with_stagger(scene.stagger)
+            .with_invalid_tiles(scene.invalid_tiles)
+            .with_tile_index_offset(scene.tile_index_offset)
+            .with_flip_flags(scene.flip_flags)
+            .with_pixel_snap(scene.pixel_snap.is_some())
+            .with_user_data(TilemapUserData::new(&(scene.swap_red_blue as u32)));
+        draw.source_rect = scene.source_rect;
+        draw.empty_tile = scene.empty_tile;
+        for (slot, plane) in draw.clip_planes.iter_mut().zip(&scene.clip_planes) {
+            *slot = Some(*plane);
+        }
+        if scene.team_color {
+            draw = draw.with_team_color(Rgb::new(255, 0, 255));
+        }
+        if scene.opaque {
+            draw = draw.with_blend_mode(TilemapBlendMode::Opaque);
+        }
+        if scene.depth {
+            draw = draw.with_depth(0.25);
+        }
+        if scene.stencil {
+            draw = draw.with_stencil(TilemapStencil::Write(1));
+        }
+        if let Some(walls) = scene.walls {
+            draw = draw.with_walls(walls);
+        }
+        if let Some(tint) = &scene.tint {
+            draw = draw.with_tint(
+                tint.iter()
+                    .map(|&color| u32::from_le_bytes(color))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        draw
+    }];
+    if scene.external_culling || scene.opaque || scene.depth || scene.stencil {
+        let black = u32::from_le_bytes([0, 0, 0, 255]);
+        let mut cover = draws[0]
+            .clone()
+            .with_blend_mode(TilemapBlendMode::Alpha)
+            .with_tint(vec![black; scene.tilemap.tile_size().product() as usize]);
+        if scene.depth {
+            cover = cover.with_depth(0.5);
+        }
+        if scene.stencil {
+            cover = cover.with_stencil(TilemapStencil::Outside(1));
+            cover.clip_planes = [None; MAX_CLIP_PLANES];
+        }
+        draws.push(cover);
+    }
+    if scene.reupload {
+        let reversed: Vec<_> = draws.iter().rev().cloned().collect();
+        pipeline.upload_tilemaps(device, queue, &reversed);
+    }
+    if scene.other_threads {
+        std::thread::scope(|scope| {
+            scope.spawn(|| pipeline.upload_tilemaps(device, queue, &draws));
+        });
+    } else {
+        pipeline.upload_tilemaps(device, queue, &draws);
+    }
+    // Each draw is listed with what it was uploaded with, and the parts of a split tilemap cover
+    // its source rect.
+    for (position, draw) in draws.iter().enumerate() {
+        let size = draw.tilemap.tile_size();
+        let source = draw.source_rect.map_or(size.product(), |rect| {
+            let origin = rect.position().map2(size, u32::min);
+            Vec2::from(rect.extent())
+                .map2(size - origin, u32::min)
+                .product()
+        });
+        let infos: Vec<_> = pipeline
+            .active_draws()
+            .filter(|info| info.position == position)
+            .collect();
+        assert!(infos.iter().all(|info| info.tileset == draw.tileset
+            && info.transform == draw.transform
+            && info.uploaded_bytes <= info.allocated_bytes));
+        let covered: u32 = infos.iter().map(|info| info.tiles.w * info.tiles.h).sum();
+        assert_eq!(covered, source, "{}: draw {position}", scene.name);
+    }
+    if scene.placeholder.is_some() {
+        pipeline
+            .replace_tileset(device, queue, 0, &tileset())
+            .unwrap();
+    }
+    if scene.rebuild {
+        pipeline.rebuild(device, queue).unwrap();
+    }
+    let baked = scene.bake.then(|| {
+        pipeline.set_downscale_filter(scene.downscale);
+        let area = Rect::new(0.0, 0.0, 1.0, 1.0);
+        let baked = pipeline.bake(device, queue, 0..1, area, scene.target_size);
+        // Uploading the same tilemaps again leaves the layer as it was, but changing the baked
+        // one or leaving it out makes it stale.
+        pipeline.upload_tilemaps(device, queue, &draws);
+        assert!(!pipeline.is_stale(&baked), "{}", scene.name);
+        let mut changed = draws.clone();
+        changed[0].opacity *= 0.5;
+        pipeline.upload_tilemaps(device, queue, &changed);
+        assert!(pipeline.is_stale(&baked), "{}", scene.name);
+        pipeline.upload_tilemaps(device, queue, &[]);
+        assert!(pipeline.is_stale(&baked), "{}", scene.name);
+        baked
+    });
+
+    let extent = wgpu::Extent3d {
+        width: scene.target_size.x,
+        height: scene.target_size.y,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("test_target"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let emissive_target = scene.emissive_target.then(|| {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("test_emissive_target"),
+            size: extent,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    });
+    let emissive_view = emissive_target
+        .as_ref()
+        .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+    // The multisampled attachments resolved into `view` and `emissive_view`, if any.
+    let multisampled = |format| {
+        (scene.sample_count > 1).then(|| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some("test_multisampled_target"),
+                    size: extent,
+                    mip_level_count: 1,
+                    sample_count: scene.sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        })
+    };
+    let depth_view = depth_stencil.as_ref().map(|state| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("test_depth"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: scene.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: state.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+    let multisampled_view = multisampled(format);
+    let multisampled_emissive_view = multisampled(FORMAT);
+    let row_len = 4 * scene.target_size.x;
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("test_readback"),
+        size: (padded_row_len * scene.target_size.y) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let custom_pipeline = scene
+        .custom_pass
+        .then(|| custom_pipeline(device, &pipeline, format));
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("test_encoder"),
+    });
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test_rpass"),
+            color_attachments: &[
+                color_attachment(&view, &multisampled_view),
+                emissive_view
+                    .as_ref()
+                    .and_then(|view| color_attachment(view, &multisampled_emissive_view)),
+            ][..1 + scene.emissive_target as usize],
+            depth_stencil_attachment: depth_view.as_ref().map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: scene.stencil.then_some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: true,
+                    }),
+                }
+            }),
+        });
+        if let Some(baked) = &baked {
+            pipeline.render_baked(&mut rpass, baked);
+        }
+        if let Some(custom_pipeline) = &custom_pipeline {
+            rpass.set_pipeline(custom_pipeline);
+            rpass.set_bind_group(0, pipeline.camera_bind_group(), &[]);
+            rpass.set_bind_group(1, pipeline.tileset_bind_group(0).unwrap(), &[]);
+            for bind_group in pipeline.tilemap_bind_groups(0) {
+                rpass.set_bind_group(2, bind_group, &[]);
+                rpass.draw(0..3, 0..1);
+            }
+        } else if scene.external_culling {
+            pipeline.render_visible(device, &mut rpass, &[0]);
+        } else {
+            pipeline.render(device, &mut rpass);
+        }
+    }
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture: emissive_target.as_ref().unwrap_or(&target),
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &readback,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len),
+                rows_per_image: Some(scene.target_size.y),
+            },
+        },
+        extent,
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = readback.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::Maintain::Wait);
+    let padded = slice.get_mapped_range();
+    padded
+        .chunks_exact(padded_row_len as usize)
+        .flat_map(|row| &row[..row_len as usize])
+        .copied()
+        .collect()
+}
+
+/// A color attachment that is cleared and drawn to, through `multisampled` if the scene is
+/// multisampled (which is then resolved into `view`).
+fn color_attachment<'a>(
+    view: &'a wgpu::TextureView,
+    multisampled: &'a Option<wgpu::TextureView>,
+) -> Option<wgpu::RenderPassColorAttachment<'a>> {
+    Some(wgpu::RenderPassColorAttachment {
+        view: multisampled.as_ref().unwrap_or(view),
+        resolve_target: multisampled.as_ref().map(|_| view),
+        ops: wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+            store: true,
+        },
+    })
+}
+
+/// Compare a rendered scene with its expected image, describing the first few mismatches.
+fn compare(scene: &Scene, pixels: &[u8]) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for y in 0..scene.target_size.y {
+        for x in 0..scene.target_size.x {
+            let Some(mut expected) = scene.expected(Vec2::new(x, y)) else {
+                continue;
+            };
+            // The black cover is drawn where the tilemap wasn't, which is fully transparent.
+            if scene.stencil && expected[3] == 0 {
+                expected = [0, 0, 0, 255];
+            }
+            let row = match scene.flip_y {
+                true => scene.target_size.y - 1 - y,
+                false => y,
+            };
+            let start = 4 * (row * scene.target_size.x + x) as usize;
+            let actual = &pixels[start..start + 4];
+            if expected
+                .iter()
+                .zip(actual)
+                .any(|(e, a)| e.abs_diff(*a) > TOLERANCE)
+            {
+                mismatches.push(format!(
+                    "{}: pixel ({x}, {y}) is {actual:?}, expected {expected:?}",
+                    scene.name
+                ));
+            }
+        }
+    }
+    mismatches.truncate(5);
+    mismatches
+}
+
+/// Pick a few pixels of a 64x48 picking buffer, with a 4x3 tilemap covering the left three
+/// quarters, and a 2x2 tilemap covering everything but only drawing its top right tile.
+fn check_picking(device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<String> {
+    let mut pipeline = TilemapPipeline::new(device, FORMAT, None);
+    pipeline.set_picking(device, true);
+    pipeline.set_camera(queue, FULLSCREEN_QUAD_CAMERA);
+    pipeline
+        .upload_tilesets(device, queue, &[tileset()])
+        .unwrap();
+    let mut top_right = Tilemap::<u8>::new_zeroed(Vec2::new(2, 2));
+    top_right.put_tile(1, 0, 2);
+    let draw = |transform, tilemap: Tilemap<u8>, empty_tile| {
+        let mut draw = TilemapDrawData::new(tilemap, 0).with_transform(transform);
+        draw.empty_tile = empty_tile;
+        draw
+    };
+    pipeline.upload_tilemaps(
+        device,
+        queue,
+        &[
+            draw(
+                Mat4::scaling_3d(Vec3::new(0.75, 1.0, 1.0)),
+                pattern(Vec2::new(4, 3)),
+                None,
+            ),
+            draw(Mat4::identity(), top_right, Some(0)),
+        ],
+    );
+    let extent = wgpu::Extent3d {
+        width: 64,
+        height: 48,
+        depth_or_array_layers: 1,
+    };
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("test_picking_target"),
+        size: extent,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: PICKING_FORMAT,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("test_picking_encoder"),
+    });
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("test_picking_rpass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pipeline.render_picking(&mut rpass);
+    }
+    let pick = |draw, cell: [u32; 2], tile| TilePick {
+        draw,
+        cell: cell.into(),
+        tile,
+    };
+    let expected = [
+        (Vec2::new(40, 8), Some(pick(1, [1, 0], 2))),
+        (Vec2::new(4, 40), Some(pick(0, [0, 2], 1))),
+        (Vec2::new(20, 4), Some(pick(0, [1, 0], 1))),
+        (Vec2::new(56, 40), None),
+    ];
+    let readbacks: Vec<_> = expected
+        .iter()
+        .map(|(position, _)| PickReadback::new(device, &mut encoder, &target, *position))
+        .collect();
+    queue.submit([encoder.finish()]);
+    for readback in &readbacks {
+        readback.map();
+    }
+    device.poll(wgpu::Maintain::Wait);
+    let mut mismatches = Vec::new();
+    for ((position, expected), readback) in expected.iter().zip(&readbacks) {
+        let actual = readback.try_pick();
+        if actual != Some(*expected) {
+            mismatches.push(format!(
+                "picking: pixel {position} is {actual:?}, expected {expected:?}"
+            ));
+        }
+    }
+    mismatches
+}
+
+/// Upload tilesets with as many 1x1 tiles as the device allows, and one more.
+fn check_tileset_limits(device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<String> {
+    let max = device.limits().max_texture_array_layers;
+    let mut pipeline = TilemapPipeline::new(device, FORMAT, None);
+    // Rounding up the largest tileset would take it past the limit.
+    let buckets = AllocationStrategy::Buckets(Vec2::new(max, 2));
+    pipeline.set_allocation_strategy(AllocationKind::Tileset, buckets);
+    let mut mismatches = Vec::new();
+    let largest = TilesetRef::placeholder(Vec2::new(max, 1), Vec2::one());
+    if let Err(e) = pipeline.upload_tilesets(device, queue, std::slice::from_ref(&largest)) {
+        mismatches.push(format!("tileset limits: {max} tiles were rejected: {e}"));
+    }
+    let too_large = TilesetRef::placeholder(Vec2::new(max + 1, 1), Vec2::one());
+    let expected = Err(TilesetError::TooManyTiles {
+        tileset: 1,
+        tiles: max as u64 + 1,
+        max,
+    });
+    let actual = pipeline.upload_tilesets(device, queue, &[ramp(), too_large]);
+    if actual != expected {
+        mismatches.push(format!(
+            "tileset limits: uploading {} tiles returned {actual:?}, expected {expected:?}",
+            max + 1
+        ));
+    }
+    // The rejected list mustn't replace the tilesets uploaded before it.
+    if pipeline.tileset_analysis(0) != Some(&largest.analyze()) {
+        mismatches.push("tileset limits: a rejected list replaced the tilesets".to_owned());
+    }
+    let mut checkerboard = ramp();
+    checkerboard.checkerboard = vec![
+        CheckerboardTile {
+            tile: 0,
+            alternate: 1
+        };
+        MAX_CHECKERBOARD_TILES + 1
+    ]
+    .into();
+    let expected = Err(TilesetError::TooManyCheckerboardTiles {
+        tileset: 0,
+        len: MAX_CHECKERBOARD_TILES + 1,
+    });
+    let actual = pipeline.upload_tilesets(device, queue, &[checkerboard]);
+    if actual != expected {
+        mismatches.push(format!(
+            "tileset limits: too many checkerboard tiles returned {actual:?}, expected {expected:?}"
+        ));
+    }
+    let short = TilesetRef::new(
+        SIZE_OF_TILE * Vec2::new(NUM_TILES, 1),
+        SIZE_OF_TILE,
+        vec![0; 5],
+    );
+    let expected = Err(TilesetError::DataSize {
+        tileset: 1,
+        len: 5,
+        expected: (NUM_TILES * SIZE_OF_TILE.product()) as usize,
+    });
+    let actual = pipeline.upload_tilesets(device, queue, &[ramp(), short]);
+    if actual != expected {
+        mismatches.push(format!(
+            "tileset limits: short pixels returned {actual:?}, expected {expected:?}"
+        ));
+    }
+    let mut compressed = compressed_tileset(wgpu::TextureFormat::Bc1RgbaUnormSrgb);
+    compressed
+        .compressed
+        .as_mut()
+        .unwrap()
+        .blocks
+        .to_mut()
+        .pop();
+    let expected = Err(TilesetError::CompressedSize {
+        tileset: 0,
+        len: NUM_TILES as u64 * 8 - 1,
+        expected: NUM_TILES as u64 * 8,
+    });
+    let actual = pipeline.upload_tilesets(device, queue, &[compressed]);
+    if actual != expected {
+        mismatches.push(format!(
+            "tileset limits: short compressed tiles returned {actual:?}, expected {expected:?}"
+        ));
+    }
+    // The second level of the 4x4 tiles has 2x2 pixels per tile.
+    let mut mipped = container_tileset(Container::Ktx2);
+    mipped.mips[0].pop();
+    let expected = Err(TilesetError::MipLevelSize {
+        tileset: 0,
+        level: 1,
+        len: NUM_TILES as usize * 4 - 1,
+        expected: NUM_TILES as usize * 4,
+    });
+    let actual = pipeline.upload_mipped_tilesets_streamed(device, queue, &[mipped]);
+    if actual != expected {
+        mismatches.push(format!(
+            "tileset limits: a short mip level returned {actual:?}, expected {expected:?}"
+        ));
+    }
+    mismatches
+}
+
+/// Upload the same tilemaps with `upload_tilemaps_if_changed`, which skips them until the tilesets
+/// are uploaded again, since the draws uploaded before would be stale.
+fn check_unchanged_uploads(device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<String> {
+    let mut pipeline = TilemapPipeline::new(device, FORMAT, None);
+    pipeline
+        .upload_tilesets(device, queue, &[tileset()])
+        .unwrap();
+    let draws = [TilemapDrawData::new(pattern::<u8>(Vec2::new(4, 3)), 0)];
+    let upload = |pipeline: &mut TilemapPipeline| {
+        pipeline.upload_tilemaps_if_changed(device, queue, 1, &draws)
+    };
+    let mut mismatches = Vec::new();
+    if !upload(&mut pipeline) {
+        mismatches.push("unchanged uploads: the first upload was skipped".to_owned());
+    }
+    if upload(&mut pipeline) {
+        mismatches.push("unchanged uploads: the same version was uploaded again".to_owned());
+    }
+    pipeline
+        .upload_tilesets(device, queue, &[tileset()])
+        .unwrap();
+    if !upload(&mut pipeline) || pipeline.stale_draws().next().is_some() {
+        mismatches.push("unchanged uploads: new tilesets left the draws stale".to_owned());
+    }
+    mismatches
+}
+
+/// Upload a draw with a tint of the wrong length between two valid ones, which is skipped and
+/// reported instead of being uploaded.
+fn check_rejected_draws(device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<String> {
+    let mut pipeline = TilemapPipeline::new(device, FORMAT, None);
+    pipeline
+        .upload_tilesets(device, queue, &[tileset()])
+        .unwrap();
+    let tilemap = pattern::<u8>(Vec2::new(4, 3));
+    let draws = [
+        TilemapDrawData::new(tilemap.clone(), 0),
+        TilemapDrawData::new(tilemap.clone(), 0).with_tint(vec![u32::MAX; 11]),
+        TilemapDrawData::new(tilemap, 0).with_tint(vec![u32::MAX; 12]),
+    ];
+    pipeline.upload_tilemaps(device, queue, &draws);
+    let rejected: Vec<_> = pipeline.rejected_draws().collect();
+    let drawn: Vec<_> = pipeline.active_draws().map(|draw| draw.position).collect();
+    let mut mismatches = Vec::new();
+    if rejected != [1] || drawn != [0, 2] {
+        mismatches.push(format!(
+            "rejected draws: rejected {rejected:?} and drew {drawn:?}, expected [1] and [0, 2]"
+        ));
+    }
+    mismatches
+}
+
+#[test]
+fn reference_scenes_match_on_every_adapter() {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+    let adapters: Vec<_> = instance.enumerate_adapters(wgpu::Backends::all()).collect();
+    if adapters.is_empty() {
+        eprintln!("no adapters are available, so no scenes were rendered");
+        return;
+    }
+    let mut failures = Vec::new();
+    for adapter in adapters {
+        let info = adapter.get_info();
+        let adapter_name = format!("{} ({:?})", info.name, info.backend);
+        let request = adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("test_device"),
+                features: adapter.features() & COMPRESSION_FEATURES,
+                limits: wgpu::Limits::downlevel_webgl2_defaults()
+                    .using_resolution(adapter.limits()),
+            },
+            None,
+        );
+        let (device, queue) = match pollster::block_on(request) {
+            Ok(device) => device,
+            Err(err) => {
+                eprintln!("{adapter_name}: skipped, no device could be created: {err}");
+                continue;
+            }
+        };
+        for scene in scenes() {
+            // Adapters without a requested compression feature can't draw the expected colors.
+            let unsupported = scene.compressed.is_some_and(|format| {
+                let needed = format.required_features();
+                COMPRESSION_FEATURES.contains(needed) && !device.features().contains(needed)
+            });
+            if unsupported {
+                eprintln!(
+                    "{adapter_name}: skipped {}, its format isn't supported",
+                    scene.name
+                );
+                continue;
+            }
+            let pixels = render(&device, &queue, &scene);
+            failures.extend(
+                compare(&scene, &pixels)
+                    .into_iter()
+                    .map(|mismatch| format!("{adapter_name}: {mismatch}")),
+            );
+        }
+        failures.extend(
+            check_picking(&device, &queue)
+                .into_iter()
+                .chain(check_tileset_limits(&device, &queue))
+                .chain(check_unchanged_uploads(&device, &queue))
+                .chain(check_rejected_draws(&device, &queue))
+                .map(|mismatch| format!("{adapter_name}: {mismatch}")),
+        );
+        eprintln!("{adapter_name}: rendered every scene");
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,114 @@
+//! The binary tilemap format. Run with `cargo test`.
+use std::borrow::Cow;
+use vek::Vec2;
+use wgpu_tilemap::{BinaryMapError, MapCompression, TileFormat, TilemapRef, TilemapStack};
+
+/// A 4x3 tilemap with runs of identical tiles and a few odd ones.
+fn tilemap() -> TilemapRef<'static, u16> {
+    TilemapRef {
+        tile_size: Vec2::new(4, 3),
+        data: Cow::Owned(vec![7, 7, 7, 7, 7, 300, 300, 1, 0, 0, 0, 65535]),
+    }
+}
+
+fn write(tilemap: &TilemapRef<u16>, compression: MapCompression) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    tilemap.write_binary(&mut bytes, compression).unwrap();
+    bytes
+}
+
+#[test]
+fn tilemaps_round_trip() {
+    for compression in [MapCompression::None, MapCompression::RunLength] {
+        let bytes = write(&tilemap(), compression);
+        let read = TilemapRef::<u16>::read_binary(&bytes[..]).unwrap();
+        assert_eq!(read.tile_size, tilemap().tile_size, "{compression:?}");
+        assert_eq!(read.data, tilemap().data, "{compression:?}");
+    }
+    // Run-length encoding stores the 5 runs rather than the 12 tiles.
+    assert_eq!(
+        write(&tilemap(), MapCompression::RunLength).len(),
+        20 + 5 * 6
+    );
+}
+
+#[test]
+fn stacks_round_trip() {
+    let mut stack = TilemapStack::new_zeroed(Vec2::new(4, 3), 1);
+    stack.push_layer(&tilemap());
+    let mut bytes = Vec::new();
+    stack
+        .as_ref()
+        .write_binary(&mut bytes, MapCompression::RunLength)
+        .unwrap();
+    let read = TilemapRef::<u16>::read_binary(&bytes[..]).unwrap();
+    assert_eq!(read.tile_size, Vec2::new(4, 3));
+    assert_eq!(read.data.len(), 24);
+    assert_eq!(read.data[12..], tilemap().data[..]);
+}
+
+/// Read `bytes` as a tilemap of `u16` tiles, for the tests of corrupted data.
+fn read(bytes: &[u8]) -> Result<TilemapRef<'static, u16>, BinaryMapError> {
+    TilemapRef::read_binary(bytes)
+}
+
+#[test]
+fn corrupted_headers_are_rejected() {
+    let bytes = write(&tilemap(), MapCompression::RunLength);
+    let with = |offset: usize, patch: &[u8]| {
+        let mut bytes = bytes.clone();
+        bytes[offset..offset + patch.len()].copy_from_slice(patch);
+        bytes
+    };
+    assert!(matches!(
+        read(&with(0, b"WTMX")),
+        Err(BinaryMapError::NotATilemap)
+    ));
+    for version in [0, 2, u16::MAX] {
+        assert!(
+            matches!(
+                read(&with(4, &version.to_le_bytes())),
+                Err(BinaryMapError::UnsupportedVersion(found)) if found == version
+            ),
+            "version {version}"
+        );
+    }
+    assert!(matches!(
+        read(&with(6, &[0])),
+        Err(BinaryMapError::FormatMismatch {
+            expected: TileFormat::R16,
+            found: TileFormat::R8,
+        })
+    ));
+    assert!(matches!(
+        read(&with(6, &[9])),
+        Err(BinaryMapError::InvalidData(_))
+    ));
+    assert!(matches!(
+        read(&with(7, &[2])),
+        Err(BinaryMapError::InvalidData(_))
+    ));
+    assert!(matches!(read(&bytes[..10]), Err(BinaryMapError::Io(_))));
+}
+
+#[test]
+fn corrupted_tiles_are_rejected() {
+    let bytes = write(&tilemap(), MapCompression::RunLength);
+    // The tiles end before the map does.
+    assert!(matches!(
+        read(&bytes[..bytes.len() - 1]),
+        Err(BinaryMapError::Io(_))
+    ));
+    // The first run is longer than the whole map.
+    let mut long_run = bytes.clone();
+    long_run[20..24].copy_from_slice(&13u32.to_le_bytes());
+    assert!(matches!(
+        read(&long_run),
+        Err(BinaryMapError::InvalidData(_))
+    ));
+    // A map too large to have its tiles in the data fails when the tiles run out, rather than
+    // allocating them all up front.
+    let mut huge = bytes.clone();
+    huge[8..16].copy_from_slice(&[0xff; 8]);
+    assert!(matches!(read(&huge), Err(BinaryMapError::Io(_))));
+}
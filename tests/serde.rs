@@ -0,0 +1,76 @@
+//! Serialization of the map data types. Run with `cargo test --features serde`.
+use serde_json::json;
+use std::borrow::Cow;
+use vek::Vec2;
+use wgpu_tilemap::{AlphaMode, CheckerboardTile, TilemapRef, TilesetRef};
+
+#[test]
+fn tilemaps_round_trip() {
+    let tilemap = TilemapRef::<u16> {
+        tile_size: Vec2::new(3, 2),
+        data: Cow::Owned((0..12).map(|tile| tile * 1000).collect()),
+    };
+    let json = serde_json::to_string(&tilemap).unwrap();
+    let parsed: TilemapRef<u16> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.tile_size, tilemap.tile_size);
+    assert_eq!(parsed.data, tilemap.data);
+}
+
+#[test]
+fn tilesets_round_trip() {
+    let mut tileset = TilesetRef::new(
+        Vec2::new(4, 2),
+        Vec2::new(2, 2),
+        (0..8)
+            .map(|pixel| pixel * 0x0101_0101)
+            .collect::<Vec<u32>>(),
+    );
+    tileset.alpha_mode = AlphaMode::Premultiplied;
+    tileset.uv_inset = 0.5;
+    tileset.checkerboard = vec![CheckerboardTile {
+        tile: 0,
+        alternate: 1,
+    }]
+    .into();
+    let json = serde_json::to_string(&tileset).unwrap();
+    let parsed: TilesetRef = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.pixel_size, tileset.pixel_size);
+    assert_eq!(parsed.size_of_tile, tileset.size_of_tile);
+    assert_eq!(parsed.data, tileset.data);
+    assert_eq!(parsed.alpha_mode, tileset.alpha_mode);
+    assert_eq!(parsed.uv_inset, tileset.uv_inset);
+    assert_eq!(parsed.checkerboard, tileset.checkerboard);
+}
+
+#[test]
+fn tilemaps_with_the_wrong_number_of_tiles_are_rejected() {
+    // Four u8 tiles, which isn't a whole number of 3x1 layers.
+    let tilemap = json!({ "tile_size": [3, 1], "format": "R8", "data": "AAECAw==" });
+    let err = serde_json::from_value::<TilemapRef<u8>>(tilemap).unwrap_err();
+    assert!(err.to_string().contains("invalid length 4"), "{err}");
+    // Two whole layers are fine.
+    let stack = json!({ "tile_size": [2, 1], "format": "R8", "data": "AAECAw==" });
+    assert_eq!(
+        serde_json::from_value::<TilemapRef<u8>>(stack)
+            .unwrap()
+            .data
+            .len(),
+        4
+    );
+    let empty = json!({ "tile_size": [0, 2], "format": "R8", "data": "AA==" });
+    assert!(serde_json::from_value::<TilemapRef<u8>>(empty).is_err());
+}
+
+#[test]
+fn tilesets_with_the_wrong_number_of_pixels_are_rejected() {
+    // Two pixels for a 2x2 tileset.
+    let tileset = json!({
+        "pixel_size": [2, 2],
+        "size_of_tile": [1, 1],
+        "data": "AAAAAAAAAAA=",
+        "alpha_mode": "Straight",
+        "uv_inset": 0.0,
+    });
+    let err = serde_json::from_value::<TilesetRef>(tileset).unwrap_err();
+    assert!(err.to_string().contains("invalid length 2"), "{err}");
+}
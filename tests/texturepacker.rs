@@ -0,0 +1,106 @@
+//! Parsing of TexturePacker atlas descriptions. Run with `cargo test --features texturepacker`.
+use vek::Vec2;
+use wgpu_tilemap::texturepacker::{parse_texturepacker, TexturePackerError};
+
+/// An 8x4 atlas image whose red and green channels are the position of each pixel.
+fn image() -> image::RgbaImage {
+    image::RgbaImage::from_fn(8, 4, |x, y| image::Rgba([x as u8, y as u8, 0, 255]))
+}
+
+fn pixel(x: u32, y: u32) -> u32 {
+    u32::from_le_bytes([x as u8, y as u8, 0, 255])
+}
+
+#[test]
+fn hash_format_with_trimmed_regions() {
+    let json = r#"{
+        "frames": {
+            "b.png": {
+                "frame": {"x": 2, "y": 0, "w": 1, "h": 2},
+                "trimmed": true,
+                "spriteSourceSize": {"x": 1, "y": 0, "w": 1, "h": 2},
+                "sourceSize": {"w": 2, "h": 2}
+            },
+            "a.png": {
+                "frame": {"x": 0, "y": 0, "w": 2, "h": 2},
+                "trimmed": false
+            }
+        },
+        "meta": {"image": "atlas.png"}
+    }"#;
+    let atlas = parse_texturepacker(json, &image(), 256).unwrap();
+    assert_eq!(atlas.tile("a.png"), Some(0));
+    assert_eq!(atlas.tile("b.png"), Some(1));
+    assert_eq!(atlas.tile("c.png"), None);
+    let tileset = &atlas.tileset;
+    assert_eq!(tileset.size_of_tile, Vec2::new(2, 2));
+    assert_eq!(tileset.pixel_size, Vec2::new(4, 2));
+    assert_eq!(
+        &tileset.data[..],
+        &[
+            pixel(0, 0),
+            pixel(1, 0),
+            pixel(0, 1),
+            pixel(1, 1),
+            // The trimmed region is placed at its offset, with the rest left transparent.
+            0,
+            pixel(2, 0),
+            0,
+            pixel(2, 1),
+        ]
+    );
+}
+
+#[test]
+fn array_format_with_rotated_regions() {
+    let json = r#"{
+        "frames": [
+            {
+                "filename": "rotated",
+                "frame": {"x": 4, "y": 0, "w": 3, "h": 2},
+                "rotated": true
+            }
+        ]
+    }"#;
+    let atlas = parse_texturepacker(json, &image(), 256).unwrap();
+    assert_eq!(atlas.tile("rotated"), Some(0));
+    let tileset = &atlas.tileset;
+    assert_eq!(tileset.size_of_tile, Vec2::new(3, 2));
+    // The region is stored 2 pixels wide and 3 tall, turned clockwise.
+    let expected: Vec<u32> = (0..2)
+        .flat_map(|y| (0..3).map(move |x| pixel(5 - y, x)))
+        .collect();
+    assert_eq!(&tileset.data[..], &expected[..]);
+}
+
+#[test]
+fn rejects_invalid_atlases() {
+    let past_edge = r#"{"frames": {"a": {"frame": {"x": 6, "y": 0, "w": 4, "h": 4}}}}"#;
+    assert!(matches!(
+        parse_texturepacker(past_edge, &image(), 256),
+        Err(TexturePackerError::InvalidData(_))
+    ));
+    let missing_frame = r#"{"frames": {"a": {"rotated": false}}}"#;
+    assert!(matches!(
+        parse_texturepacker(missing_frame, &image(), 256),
+        Err(TexturePackerError::InvalidData(_))
+    ));
+    assert!(matches!(
+        parse_texturepacker("{", &image(), 256),
+        Err(TexturePackerError::Json(_))
+    ));
+}
+
+#[test]
+fn rejects_atlases_with_more_tiles_than_the_limit() {
+    let frames: Vec<String> = (0..17)
+        .map(|i| format!(r#""{i}": {{"frame": {{"x": 0, "y": 0, "w": 1, "h": 1}}}}"#))
+        .collect();
+    let json = format!(r#"{{"frames": {{{}}}}}"#, frames.join(","));
+    // 17 regions take two rows of 16 tiles.
+    assert!(parse_texturepacker(&json, &image(), 32).is_ok());
+    assert!(matches!(
+        parse_texturepacker(&json, &image(), 31),
+        Err(TexturePackerError::TooManyTiles { tiles: 32, max: 31 })
+    ));
+}
@@ -0,0 +1,193 @@
+//! Importing and exporting Tiled maps. Run with `cargo test --features tiled`.
+use std::{io, time::Duration};
+use vek::{Mat4, Vec2};
+use wgpu_tilemap::{
+    tiled::{export_tmx, parse_tmx, parse_tmx_with, TiledError},
+    AnyTilemapRef, MapObject, ObjectLayer, Tilemap, TilemapBlendMode, TilemapDrawData,
+    TilemapLayer, TilemapWorld, TilesetRef, WorldLayer,
+};
+
+/// A 3x1 map with an embedded tileset, whose first tile is animated.
+const ANIMATED_TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="1" tilewidth="8" tileheight="8" infinite="0">
+ <tileset firstgid="1" name="water" tilewidth="8" tileheight="8" tilecount="3" columns="3">
+  <tile id="0">
+   <animation>
+    <frame tileid="0" duration="100"/>
+    <frame tileid="2" duration="300"/>
+   </animation>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="1">
+  <data encoding="csv">1,2,1</data>
+ </layer>
+</map>"#;
+
+/// The tiles drawn by the single draw of a map.
+fn drawn_tiles(draws: &[TilemapDrawData]) -> Vec<u8> {
+    let [draw] = draws else {
+        panic!("expected a single draw, got {}", draws.len());
+    };
+    let AnyTilemapRef::U8(tilemap) = &draw.tilemap else {
+        panic!("expected u8 tiles");
+    };
+    tilemap.data.to_vec()
+}
+
+#[test]
+fn animations_are_drawn() {
+    let map = parse_tmx(ANIMATED_TMX).unwrap();
+    assert_eq!(map.world.animations.len(), 1);
+    assert_eq!(map.world.animations[0], map.tilesets[0].animations);
+    let transform = Mat4::identity();
+    let at = |millis| {
+        drawn_tiles(
+            &map.world
+                .draw_list_at(transform, Duration::from_millis(millis)),
+        )
+    };
+    assert_eq!(at(0), [0, 1, 0]);
+    assert_eq!(at(150), [2, 1, 2]);
+    // The animation loops after 400ms.
+    assert_eq!(at(450), [0, 1, 0]);
+    // The layer itself keeps the animated tile.
+    let WorldLayer::Tiles(layer) = &map.world.layers[0] else {
+        panic!("expected a tile layer");
+    };
+    assert_eq!(layer.tilemap.data, [0, 1, 0]);
+    assert_eq!(layer.tilemap.tile_size, Vec2::new(3, 1));
+}
+
+/// A map with an object made from `thing.tx`, whose tileset `water.tsx` starts at gid 3.
+const TEMPLATE_TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="8" tileheight="8" infinite="0">
+ <tileset firstgid="1" name="grass" tilewidth="8" tileheight="8" tilecount="2" columns="2">
+ </tileset>
+ <tileset firstgid="3" source="water.tsx"/>
+ <objectgroup id="2" name="things">
+  <object id="1" template="thing.tx" x="0" y="8"/>
+ </objectgroup>
+</map>"#;
+
+/// Import `TEMPLATE_TMX` with `thing.tx` being a tile object of `gid` from its own copy of
+/// `water.tsx`, which starts at gid 5.
+fn import_template(gid: u32) -> Result<Option<u32>, TiledError> {
+    let template = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<template>
+ <tileset firstgid="5" source="water.tsx"/>
+ <object gid="{gid}" width="8" height="8"/>
+</template>"#
+    );
+    let map = parse_tmx_with(TEMPLATE_TMX, |path: &str| match path {
+        "thing.tx" => Ok(template.clone().into_bytes()),
+        _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+    })?;
+    Ok(map.world.objects.layers[0].objects[0].gid)
+}
+
+#[test]
+fn template_gids_are_remapped() {
+    assert_eq!(import_template(6).unwrap(), Some(4));
+    // Flip flags are kept.
+    assert_eq!(import_template(0x8000_0006).unwrap(), Some(0x8000_0004));
+}
+
+#[test]
+fn template_gids_before_their_tileset_are_rejected() {
+    let result = import_template(2);
+    assert!(
+        matches!(result, Err(TiledError::InvalidData(_))),
+        "{result:?}"
+    );
+}
+
+#[test]
+fn exported_layers_and_objects_keep_their_tiles() {
+    let tileset = |tiles: u32| {
+        TilesetRef::new(
+            Vec2::new(8 * tiles, 8),
+            Vec2::new(8, 8),
+            vec![0; 64 * tiles as usize],
+        )
+    };
+    let mut world = TilemapWorld {
+        tile_size: Vec2::new(3, 2),
+        size_of_tile: Vec2::new(8, 8),
+        tilesets: vec![tileset(2), tileset(3)],
+        // Imported from a map whose second tileset started at 10.
+        firstgids: vec![1, 10],
+        ..TilemapWorld::default()
+    };
+    world.layers.push(WorldLayer::Tiles(TilemapLayer {
+        name: "small".to_owned(),
+        tilemap: Tilemap::new_filled(Vec2::new(2, 1), 1),
+        tileset: 1,
+        transform: Mat4::identity(),
+        visible: true,
+        blend_mode: TilemapBlendMode::Alpha,
+        opacity: 1.0,
+        parallax: Vec2::one(),
+    }));
+    world.objects.layers.push(ObjectLayer {
+        name: "things".to_owned(),
+        visible: true,
+        objects: vec![MapObject {
+            id: 1,
+            gid: Some(0x8000_000b),
+            visible: true,
+            ..MapObject::default()
+        }],
+        ..ObjectLayer::default()
+    });
+    let tmx = export_tmx(&world);
+    assert!(
+        tmx.contains(r#"name="small" width="2" height="1""#),
+        "{tmx}"
+    );
+    // The second tileset is exported after the 2 tiles of the first, and the object keeps its
+    // flip flag.
+    assert!(
+        tmx.contains(r#"<tileset firstgid="3" source="tileset1.tsx"/>"#),
+        "{tmx}"
+    );
+    assert!(
+        tmx.contains(&format!(r#"gid="{}""#, 0x8000_0004u32)),
+        "{tmx}"
+    );
+    let map = parse_tmx(&tmx).unwrap();
+    assert_eq!(
+        map.world.objects.layers[0].objects[0].gid,
+        Some(0x8000_0004)
+    );
+    let WorldLayer::Tiles(layer) = &map.world.layers[0] else {
+        panic!("expected a tile layer");
+    };
+    assert_eq!(layer.tilemap.tile_size, Vec2::new(2, 1));
+    assert_eq!(layer.tilemap.data, [1, 1]);
+}
+
+#[test]
+fn empty_chunks_are_rejected() {
+    for (width, height) in [(0, 2), (2, 0)] {
+        let tmx = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="8" tileheight="8" infinite="1">
+ <tileset firstgid="1" name="grass" tilewidth="8" tileheight="8" tilecount="2" columns="2">
+ </tileset>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+   <chunk x="0" y="0" width="{width}" height="{height}"></chunk>
+   <chunk x="2" y="0" width="2" height="2">1,1,1,1</chunk>
+  </data>
+ </layer>
+</map>"#
+        );
+        let result = parse_tmx(&tmx);
+        assert!(
+            matches!(result, Err(TiledError::InvalidAttribute { .. })),
+            "{width}x{height}: {:?}",
+            result.map(|_| ())
+        );
+    }
+}